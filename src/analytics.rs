@@ -0,0 +1,185 @@
+use anyhow::{Result, Context};
+use log::warn;
+use rusqlite::{params, Connection};
+use std::path::Path;
+
+use crate::config::AnalyticsSettings;
+
+/// One finished recording, as stored in `[analytics].db_path`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordingStats {
+    /// RFC 3339 local time at which the recording finished
+    pub timestamp: String,
+    pub duration_secs: f64,
+    pub word_count: usize,
+    pub language: Option<String>,
+    pub endpoint: String,
+    /// Time from end of recording to final text
+    pub latency_ms: u64,
+}
+
+/// Per-day totals shown by `rusty-scribe stats`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DailyTotals {
+    pub day: String,
+    pub recordings: u64,
+    pub words: u64,
+    pub audio_secs: f64,
+    pub avg_latency_ms: f64,
+}
+
+pub fn count_words(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+/// Local SQLite store for per-recording analytics
+pub struct AnalyticsStore {
+    conn: Connection,
+}
+
+impl AnalyticsStore {
+    /// Opens (creating if needed) the database and its schema
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open analytics database at {}", path.display()))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS recordings (
+                id            INTEGER PRIMARY KEY,
+                timestamp     TEXT NOT NULL,
+                duration_secs REAL NOT NULL,
+                word_count    INTEGER NOT NULL,
+                language      TEXT,
+                endpoint      TEXT NOT NULL,
+                latency_ms    INTEGER NOT NULL
+            );",
+        )
+        .context("Failed to create analytics schema")?;
+        Ok(AnalyticsStore { conn })
+    }
+
+    pub fn insert(&self, stats: &RecordingStats) -> Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO recordings (timestamp, duration_secs, word_count, language, endpoint, latency_ms)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    stats.timestamp,
+                    stats.duration_secs,
+                    stats.word_count as i64,
+                    stats.language,
+                    stats.endpoint,
+                    stats.latency_ms as i64,
+                ],
+            )
+            .context("Failed to record analytics")?;
+        Ok(())
+    }
+
+    /// Totals grouped by the date part of the timestamp, newest day first
+    pub fn daily_totals(&self) -> Result<Vec<DailyTotals>> {
+        let mut statement = self.conn.prepare(
+            "SELECT substr(timestamp, 1, 10) AS day, COUNT(*), SUM(word_count), SUM(duration_secs), AVG(latency_ms)
+             FROM recordings GROUP BY day ORDER BY day DESC",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok(DailyTotals {
+                day: row.get(0)?,
+                recordings: row.get::<_, i64>(1)? as u64,
+                words: row.get::<_, i64>(2)? as u64,
+                audio_secs: row.get(3)?,
+                avg_latency_ms: row.get(4)?,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read analytics")
+    }
+}
+
+/// Adds a row when `analytics.db_path` is set. Failures are logged only, so
+/// they never keep the text from being delivered.
+pub fn record_analytics(settings: &AnalyticsSettings, stats: &RecordingStats) {
+    let Some(path) = &settings.db_path else {
+        return;
+    };
+    if let Err(e) = AnalyticsStore::open(path).and_then(|store| store.insert(stats)) {
+        warn!("Failed to record analytics: {:?}", e);
+    }
+}
+
+/// Prints the `stats` command's per-day summary
+pub fn print_stats(db_path: &str) -> Result<()> {
+    let totals = AnalyticsStore::open(db_path)?.daily_totals()?;
+    if totals.is_empty() {
+        println!("No recordings recorded yet.");
+        return Ok(());
+    }
+    println!("{:<12}{:>12}{:>10}{:>12}{:>14}", "Day", "Recordings", "Words", "Audio (s)", "Latency (ms)");
+    for day in totals {
+        println!(
+            "{:<12}{:>12}{:>10}{:>12.1}{:>14.0}",
+            day.day, day.recordings, day.words, day.audio_secs, day.avg_latency_ms
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn stats(timestamp: &str, text: &str, duration_secs: f64, latency_ms: u64) -> RecordingStats {
+        RecordingStats {
+            timestamp: timestamp.to_string(),
+            duration_secs,
+            word_count: count_words(text),
+            language: Some("en".to_string()),
+            endpoint: "local".to_string(),
+            latency_ms,
+        }
+    }
+
+    #[test]
+    fn test_insert_and_query_daily_totals() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let store = AnalyticsStore::open(dir.path().join("analytics.db")).unwrap();
+
+        store.insert(&stats("2024-09-01T09:00:00+02:00", "hello there world", 2.5, 400)).unwrap();
+        store.insert(&stats("2024-09-01T17:30:00+02:00", "one two", 1.5, 600)).unwrap();
+        store.insert(&stats("2024-09-02T08:00:00+02:00", "next day", 3.0, 300)).unwrap();
+
+        let totals = store.daily_totals().unwrap();
+        assert_eq!(
+            totals,
+            vec![
+                DailyTotals {
+                    day: "2024-09-02".to_string(),
+                    recordings: 1,
+                    words: 2,
+                    audio_secs: 3.0,
+                    avg_latency_ms: 300.0,
+                },
+                DailyTotals {
+                    day: "2024-09-01".to_string(),
+                    recordings: 2,
+                    words: 5,
+                    audio_secs: 4.0,
+                    avg_latency_ms: 500.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_store_reopens_existing_database() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("analytics.db");
+
+        AnalyticsStore::open(&path)
+            .unwrap()
+            .insert(&stats("2024-09-01T09:00:00+02:00", "persisted", 1.0, 100))
+            .unwrap();
+        assert_eq!(AnalyticsStore::open(&path).unwrap().daily_totals().unwrap().len(), 1);
+    }
+}
@@ -1,11 +1,230 @@
 use anyhow::{Result, Context};
-use reqwest::blocking::{Client, multipart};
+use log::{info, warn};
+use reqwest::blocking::multipart;
 use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
-use serde::Deserialize;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::BufReader;
+
+use crate::config::{Endpoints, LLMSettings, ResponseFormat, TimestampGranularity};
+use crate::http::{build_client, build_probe_client, log_response_body, send_request, space_request};
+use crate::llm::{complete_template, join_prompt, LlmProvider};
+use crate::streaming::{read_text_stream, read_transcript_events};
+
+/// Model name sent with every Whisper request
+pub const WHISPER_MODEL: &str = "whisper-1";
+
+/// File name given to in-memory uploads; servers use its extension to detect the format
+pub const RECORDING_FILE_NAME: &str = "recording.wav";
+
+/// Non-success HTTP status returned by one of the remote services
+#[derive(Debug)]
+pub struct ApiError {
+    pub service: &'static str,
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} API error {}: {}", self.service, self.status, self.body)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ApiError {
+    pub(crate) fn from_response(service: &'static str, response: reqwest::blocking::Response) -> Self {
+        let status = response.status();
+        let body = response.text().unwrap_or_default();
+        log_response_body(service, &body);
+        ApiError { service, status, body }
+    }
+}
+
+/// `{"error": ...}` payload; the detail is either an OpenAI-style object or a
+/// bare message
+#[derive(Deserialize, Debug)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ErrorDetail {
+    Object {
+        message: String,
+        #[serde(default, rename = "type")]
+        kind: Option<String>,
+    },
+    Message(String),
+}
+
+/// Some gateways answer 200 with an `{"error": ...}` payload instead of the
+/// expected shape; this turns such a body into an `ApiError` carrying the
+/// gateway's message
+fn embedded_error(service: &'static str, status: StatusCode, body: &str) -> Option<ApiError> {
+    let ErrorBody { error } = serde_json::from_str(body).ok()?;
+    let message = match error {
+        ErrorDetail::Object { message, kind: Some(kind) } => format!("{} ({})", message, kind),
+        ErrorDetail::Object { message, kind: None } | ErrorDetail::Message(message) => message,
+    };
+    Some(ApiError { service, status, body: message })
+}
+
+/// Parses the JSON body of a successful response, checking for an embedded
+/// error payload first
+fn parse_success_body<T: DeserializeOwned>(
+    service: &'static str,
+    response: reqwest::blocking::Response,
+    what: &str,
+) -> Result<T> {
+    let status = response.status();
+    let body = response.text().with_context(|| format!("Failed to read {}", what))?;
+    log_response_body(service, &body);
+    if let Some(error) = embedded_error(service, status, &body) {
+        return Err(error.into());
+    }
+    serde_json::from_str(&body).with_context(|| format!("Failed to parse {}", what))
+}
+
+/// Whether an error came from an endpoint answering 429 Too Many Requests
+pub fn is_rate_limited(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<ApiError>()
+        .map(|e| e.status == StatusCode::TOO_MANY_REQUESTS)
+        .unwrap_or(false)
+}
 
 #[derive(Deserialize, Debug, PartialEq)]
 pub struct WhisperResponse {
     pub text: String,
+    /// Spoken language; only `verbose_json` responses carry it
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+/// Whisper's `verbose_json` names the language ("english") where the config
+/// uses codes ("en"). Names not listed here, and codes from servers that
+/// already report them, are passed through lowercased.
+const WHISPER_LANGUAGE_CODES: &[(&str, &str)] = &[
+    ("arabic", "ar"),
+    ("chinese", "zh"),
+    ("czech", "cs"),
+    ("danish", "da"),
+    ("dutch", "nl"),
+    ("english", "en"),
+    ("finnish", "fi"),
+    ("french", "fr"),
+    ("german", "de"),
+    ("greek", "el"),
+    ("hebrew", "he"),
+    ("hindi", "hi"),
+    ("hungarian", "hu"),
+    ("indonesian", "id"),
+    ("italian", "it"),
+    ("japanese", "ja"),
+    ("korean", "ko"),
+    ("norwegian", "no"),
+    ("polish", "pl"),
+    ("portuguese", "pt"),
+    ("romanian", "ro"),
+    ("russian", "ru"),
+    ("spanish", "es"),
+    ("swedish", "sv"),
+    ("thai", "th"),
+    ("turkish", "tr"),
+    ("ukrainian", "uk"),
+    ("vietnamese", "vi"),
+];
+
+/// Language code for a language reported by Whisper
+pub fn language_code(language: &str) -> String {
+    let language = language.trim().to_lowercase();
+    WHISPER_LANGUAGE_CODES
+        .iter()
+        .find(|(name, _)| *name == language)
+        .map(|(_, code)| code.to_string())
+        .unwrap_or(language)
+}
+
+/// A timed span of the transcription, in seconds from the start of the audio
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Word {
+    pub word: String,
+    pub start: f64,
+    pub end: f64,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Segment {
+    pub text: String,
+    pub start: f64,
+    pub end: f64,
+    /// Speaker label from a diarizing endpoint, e.g. "A" or "SPEAKER_00"
+    #[serde(default)]
+    pub speaker: Option<String>,
+}
+
+/// `verbose_json` Whisper response; `words` is only present when word
+/// granularity was requested and is supported by the server
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct VerboseWhisperResponse {
+    pub text: String,
+    #[serde(default)]
+    pub words: Option<Vec<Word>>,
+    #[serde(default)]
+    pub segments: Option<Vec<Segment>>,
+}
+
+/// Transcription with per-word timings (or per-segment timings as a fallback)
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TimedTranscription {
+    pub text: String,
+    pub word_level: bool,
+    pub words: Vec<Word>,
+}
+
+impl TimedTranscription {
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).context("Failed to serialize word timestamps")
+    }
+}
+
+/// Which Whisper endpoint produced a transcription
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EndpointKind {
+    Local,
+    Hosted,
+}
+
+impl fmt::Display for EndpointKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EndpointKind::Local => write!(f, "local"),
+            EndpointKind::Hosted => write!(f, "hosted"),
+        }
+    }
+}
+
+/// Transcribed text along with the endpoint and model that actually produced it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transcription {
+    pub text: String,
+    pub endpoint_used: EndpointKind,
+    pub model_used: String,
+    /// Language code detected by a `verbose_json` transcription
+    pub language: Option<String>,
+}
+
+/// Results of the LLM stage: cleaned-up text plus an optional summary
+#[derive(Debug, Clone, PartialEq)]
+pub struct LlmOutput {
+    pub text: String,
+    pub post_processed: bool,
+    pub summary: Option<String>,
 }
 
 #[derive(Deserialize, Debug, PartialEq)]
@@ -18,9 +237,25 @@ pub struct LLMResponse {
     pub choices: Vec<LLMChoice>,
 }
 
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ChatMessage {
+    pub content: String,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ChatChoice {
+    pub message: ChatMessage,
+}
+
+/// `/chat/completions` response
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ChatResponse {
+    pub choices: Vec<ChatChoice>,
+}
+
 /// Determines whether the local Whisper endpoint is available
 pub fn is_local_endpoint_available(url: &str) -> bool {
-    let client = Client::new();
+    let client = build_probe_client();
     match client.get(url).send() {
         Ok(response) => response.status().is_success(),
         Err(_) => false,
@@ -33,28 +268,340 @@ pub fn transcribe_audio(
     api_key: &str,
     audio_path: &str,
 ) -> Result<String> {
-    let client = Client::new();
+    transcribe_audio_with_prompt(whisper_url, api_key, audio_path, None)
+}
+
+/// Like `transcribe_audio`, passing `prompt` as the Whisper `prompt` field to
+/// bias recognition toward the given vocabulary
+pub fn transcribe_audio_with_prompt(
+    whisper_url: &str,
+    api_key: &str,
+    audio_path: &str,
+    prompt: Option<&str>,
+) -> Result<String> {
+    transcribe_audio_formatted(whisper_url, api_key, audio_path, prompt, ResponseFormat::Json)
+}
+
+/// Requests the given `response_format`. JSON formats return the transcribed
+/// text; "text" returns the body as is, and "srt"/"vtt" the raw subtitles.
+pub fn transcribe_audio_formatted(
+    whisper_url: &str,
+    api_key: &str,
+    audio_path: &str,
+    prompt: Option<&str>,
+    format: ResponseFormat,
+) -> Result<String> {
+    Ok(transcribe_file_response(whisper_url, api_key, audio_path, prompt, format)?.text)
+}
+
+fn transcribe_file_response(
+    whisper_url: &str,
+    api_key: &str,
+    audio_path: &str,
+    prompt: Option<&str>,
+    format: ResponseFormat,
+) -> Result<WhisperResponse> {
+    let form = multipart::Form::new()
+        .file("file", audio_path)
+        .with_context(|| format!("Failed to attach audio file at {}", audio_path))?;
+    send_transcription(whisper_url, api_key, form, prompt, format)
+}
+
+/// Multipart `file` field carrying an in-memory WAV
+fn wav_form(wav: Vec<u8>, filename: &str) -> Result<multipart::Form> {
+    let part = multipart::Part::bytes(wav)
+        .file_name(filename.to_string())
+        .mime_str("audio/wav")?;
+    Ok(multipart::Form::new().part("file", part))
+}
+
+/// Uploads an in-memory WAV without it ever touching the disk
+pub fn transcribe_audio_bytes(
+    whisper_url: &str,
+    api_key: &str,
+    wav: Vec<u8>,
+    filename: &str,
+    prompt: Option<&str>,
+    format: ResponseFormat,
+) -> Result<String> {
+    Ok(send_transcription(whisper_url, api_key, wav_form(wav, filename)?, prompt, format)?.text)
+}
+
+/// Sends the audio file to Whisper's translation route (`/audio/translations`),
+/// which returns English text whatever language was spoken
+pub fn translate_audio(translation_url: &str, api_key: &str, audio_path: &str) -> Result<String> {
+    transcribe_audio_formatted(translation_url, api_key, audio_path, None, ResponseFormat::Json)
+}
+
+/// `translate_audio` for an in-memory WAV named `filename`
+pub fn translate_audio_bytes(translation_url: &str, api_key: &str, wav: Vec<u8>, filename: &str) -> Result<String> {
+    transcribe_audio_bytes(translation_url, api_key, wav, filename, None, ResponseFormat::Json)
+}
+
+fn send_transcription(
+    whisper_url: &str,
+    api_key: &str,
+    form: multipart::Form,
+    prompt: Option<&str>,
+    format: ResponseFormat,
+) -> Result<WhisperResponse> {
+    let client = build_client();
+
+    let mut form = form
+        .text("model", WHISPER_MODEL)
+        .text("response_format", format.as_str());
+    if let Some(prompt) = prompt {
+        form = form.text("prompt", prompt.to_string());
+    }
+
+    space_request(whisper_url);
+    let request = client
+        .post(whisper_url)
+        .multipart(form)
+        .header(AUTHORIZATION, format!("Bearer {}", api_key));
+    let response = send_request(&client, request, "Whisper endpoint")?;
+
+    if !response.status().is_success() {
+        return Err(ApiError::from_response("Whisper", response).into());
+    }
+
+    match format {
+        ResponseFormat::Json | ResponseFormat::VerboseJson => parse_success_body("Whisper", response, "Whisper response"),
+        ResponseFormat::Text | ResponseFormat::Srt | ResponseFormat::Vtt => {
+            let status = response.status();
+            let body = response.text().context("Failed to read Whisper response")?;
+            log_response_body("Whisper", &body);
+            if let Some(error) = embedded_error("Whisper", status, &body) {
+                return Err(error.into());
+            }
+            let text = match format {
+                ResponseFormat::Text => body.trim().to_string(),
+                _ => body,
+            };
+            Ok(WhisperResponse { text, language: None })
+        }
+    }
+}
+
+/// Requests the transcription as Server-Sent Events (`stream=true`) and calls
+/// `on_chunk` with each piece of text as it arrives. Servers that stream
+/// chunked plain text instead are read through `read_text_stream`. A server
+/// that answers with a plain JSON body is handled like `transcribe_audio`, its
+/// whole text passed to `on_chunk` once. Returns the full transcription.
+pub fn transcribe_audio_streaming(
+    whisper_url: &str,
+    api_key: &str,
+    audio_path: &str,
+    mut on_chunk: impl FnMut(&str),
+) -> Result<String> {
+    let client = build_client();
 
     let form = multipart::Form::new()
         .file("file", audio_path)
         .with_context(|| format!("Failed to attach audio file at {}", audio_path))?
-        .text("model", "whisper-1");
+        .text("model", WHISPER_MODEL)
+        .text("response_format", "json")
+        .text("stream", "true");
 
-    let response = client
+    space_request(whisper_url);
+    let request = client
         .post(whisper_url)
         .multipart(form)
-        .header(AUTHORIZATION, format!("Bearer {}", api_key))
-        .send()
-        .context("Failed to send request to Whisper endpoint")?;
+        .header(AUTHORIZATION, format!("Bearer {}", api_key));
+    let response = send_request(&client, request, "Whisper endpoint")?;
 
-    if response.status().is_success() {
-        let whisper_resp: WhisperResponse = response.json()
-            .context("Failed to parse Whisper response")?;
-        Ok(whisper_resp.text)
-    } else {
-        let status = response.status();
-        let text = response.text().unwrap_or_default();
-        Err(anyhow::anyhow!("Whisper API error {}: {}", status, text))
+    if !response.status().is_success() {
+        return Err(ApiError::from_response("Whisper", response).into());
+    }
+
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    if content_type.starts_with("text/event-stream") {
+        return read_transcript_events(BufReader::new(response), on_chunk);
+    }
+    if content_type.starts_with("text/plain") {
+        return read_text_stream(response, on_chunk);
+    }
+    let whisper_resp: WhisperResponse = parse_success_body("Whisper", response, "Whisper response")?;
+    on_chunk(&whisper_resp.text);
+    Ok(whisper_resp.text)
+}
+
+/// Requests word-level timestamps from the Whisper endpoint. Servers that ignore
+/// `timestamp_granularities[]=word` get their segment timings used instead.
+pub fn transcribe_audio_words(
+    whisper_url: &str,
+    api_key: &str,
+    audio_path: &str,
+) -> Result<TimedTranscription> {
+    let form = multipart::Form::new()
+        .file("file", audio_path)
+        .with_context(|| format!("Failed to attach audio file at {}", audio_path))?;
+    send_timed_transcription(whisper_url, api_key, form, &[TimestampGranularity::Word])
+}
+
+/// Like `transcribe_audio_words` for an in-memory WAV, requesting the given
+/// `timestamp_granularities[]`
+pub fn transcribe_audio_bytes_timed(
+    whisper_url: &str,
+    api_key: &str,
+    wav: Vec<u8>,
+    granularities: &[TimestampGranularity],
+) -> Result<TimedTranscription> {
+    send_timed_transcription(whisper_url, api_key, wav_form(wav, RECORDING_FILE_NAME)?, granularities)
+}
+
+fn send_timed_transcription(
+    whisper_url: &str,
+    api_key: &str,
+    form: multipart::Form,
+    granularities: &[TimestampGranularity],
+) -> Result<TimedTranscription> {
+    let client = build_client();
+
+    let mut form = form
+        .text("model", WHISPER_MODEL)
+        .text("response_format", "verbose_json");
+    for granularity in granularities {
+        form = form.text("timestamp_granularities[]", granularity.as_str());
+    }
+
+    space_request(whisper_url);
+    let request = client
+        .post(whisper_url)
+        .multipart(form)
+        .header(AUTHORIZATION, format!("Bearer {}", api_key));
+    let response = send_request(&client, request, "Whisper endpoint")?;
+
+    if !response.status().is_success() {
+        return Err(ApiError::from_response("Whisper", response).into());
+    }
+
+    let verbose: VerboseWhisperResponse =
+        parse_success_body("Whisper", response, "Whisper verbose_json response")?;
+    Ok(timed_from_verbose(verbose))
+}
+
+/// Requests speaker-labelled segments (`response_format=diarized_json`) from a
+/// diarizing endpoint
+pub fn transcribe_audio_diarized(
+    url: &str,
+    api_key: &str,
+    audio_path: &str,
+) -> Result<VerboseWhisperResponse> {
+    let client = build_client();
+
+    let form = multipart::Form::new()
+        .file("file", audio_path)
+        .with_context(|| format!("Failed to attach audio file at {}", audio_path))?
+        .text("model", WHISPER_MODEL)
+        .text("response_format", "diarized_json");
+
+    space_request(url);
+    let request = client
+        .post(url)
+        .multipart(form)
+        .header(AUTHORIZATION, format!("Bearer {}", api_key));
+    let response = send_request(&client, request, "diarization endpoint")?;
+
+    if !response.status().is_success() {
+        return Err(ApiError::from_response("Diarization", response).into());
+    }
+
+    parse_success_body("Diarization", response, "diarization response")
+}
+
+fn timed_from_verbose(verbose: VerboseWhisperResponse) -> TimedTranscription {
+    match verbose.words {
+        Some(words) if !words.is_empty() => TimedTranscription {
+            text: verbose.text,
+            word_level: true,
+            words,
+        },
+        _ => {
+            warn!("Whisper endpoint returned no word timestamps; using segment timings");
+            let words = verbose
+                .segments
+                .unwrap_or_default()
+                .into_iter()
+                .map(|segment| Word {
+                    word: segment.text.trim().to_string(),
+                    start: segment.start,
+                    end: segment.end,
+                })
+                .collect();
+            TimedTranscription {
+                text: verbose.text,
+                word_level: false,
+                words,
+            }
+        }
+    }
+}
+
+/// Endpoint selection honoring a one-shot override from the hotkeys. A forced
+/// endpoint is used on its own: no availability probe and no fallback.
+pub fn transcribe_with_override(
+    endpoints: &Endpoints,
+    api_key: &str,
+    audio_path: &str,
+    prompt: Option<&str>,
+    forced: Option<EndpointKind>,
+    format: ResponseFormat,
+) -> Result<Transcription> {
+    with_endpoint_override(endpoints, forced, |url| {
+        transcribe_file_response(url, api_key, audio_path, prompt, format)
+    })
+}
+
+/// `transcribe_with_override` for an in-memory WAV named `filename`
+pub fn transcribe_bytes_with_override(
+    endpoints: &Endpoints,
+    api_key: &str,
+    wav: &[u8],
+    filename: &str,
+    prompt: Option<&str>,
+    forced: Option<EndpointKind>,
+    format: ResponseFormat,
+) -> Result<Transcription> {
+    with_endpoint_override(endpoints, forced, |url| {
+        send_transcription(url, api_key, wav_form(wav.to_vec(), filename)?, prompt, format)
+    })
+}
+
+/// Runs `transcribe` against the forced endpoint, or the local one when it
+/// answers, falling back to hosted if that fails
+fn with_endpoint_override<F>(endpoints: &Endpoints, forced: Option<EndpointKind>, transcribe: F) -> Result<Transcription>
+where
+    F: Fn(&str) -> Result<WhisperResponse>,
+{
+    match forced {
+        Some(EndpointKind::Hosted) => return Ok(used(transcribe(&endpoints.hosted_whisper)?, EndpointKind::Hosted)),
+        Some(EndpointKind::Local) => return Ok(used(transcribe(&endpoints.local_whisper)?, EndpointKind::Local)),
+        None => {}
+    }
+
+    if is_local_endpoint_available(&endpoints.local_whisper) {
+        match transcribe(&endpoints.local_whisper) {
+            Ok(response) => return Ok(used(response, EndpointKind::Local)),
+            Err(e) => warn!("Local transcription failed, falling back to hosted: {:?}", e),
+        }
+    }
+
+    Ok(used(transcribe(&endpoints.hosted_whisper)?, EndpointKind::Hosted))
+}
+
+fn used(response: WhisperResponse, endpoint_used: EndpointKind) -> Transcription {
+    info!("Transcribed via {} endpoint ({})", endpoint_used, WHISPER_MODEL);
+    Transcription {
+        text: response.text,
+        endpoint_used,
+        model_used: WHISPER_MODEL.to_string(),
+        language: response.language.as_deref().map(language_code),
     }
 }
 
@@ -64,41 +611,127 @@ pub fn post_process_text(
     api_key: &str,
     prompt: &str,
     text: &str,
+    max_tokens: u32,
+    temperature: f64,
 ) -> Result<String> {
-    let client = Client::new();
+    let client = build_client();
 
     let payload = serde_json::json!({
-        "prompt": format!("{} {}", prompt, text),
-        "max_tokens": 150,
-        "temperature": 0.7,
+        "prompt": join_prompt(prompt, text),
+        "max_tokens": max_tokens,
+        "temperature": temperature,
     });
 
-    let response = client
+    space_request(llm_url);
+    let request = client
         .post(llm_url)
         .header(AUTHORIZATION, format!("Bearer {}", api_key))
         .header(CONTENT_TYPE, "application/json")
-        .json(&payload)
-        .send()
-        .context("Failed to send request to LLM endpoint")?;
+        .json(&payload);
+    let response = send_request(&client, request, "LLM endpoint")?;
 
     if response.status().is_success() {
-        let llm_resp: LLMResponse = response.json()
-            .context("Failed to parse LLM response")?;
+        let llm_resp: LLMResponse = parse_success_body("LLM", response, "LLM response")?;
         if let Some(choice) = llm_resp.choices.into_iter().next() {
             Ok(choice.text.trim().to_string())
         } else {
             Err(anyhow::anyhow!("No choices found in LLM response"))
         }
     } else {
-        let status = response.status();
-        let text = response.text().unwrap_or_default();
-        Err(anyhow::anyhow!("LLM API error {}: {}", status, text))
+        Err(ApiError::from_response("LLM", response).into())
     }
 }
 
+/// Post-processes through a `/chat/completions` endpoint, sending the prompt as
+/// the system message and the transcription as the user message
+pub fn post_process_chat(
+    llm_url: &str,
+    api_key: &str,
+    model: &str,
+    prompt: &str,
+    text: &str,
+    max_tokens: u32,
+    temperature: f64,
+) -> Result<String> {
+    let client = build_client();
+
+    let mut messages = Vec::new();
+    if !prompt.is_empty() {
+        messages.push(serde_json::json!({ "role": "system", "content": prompt }));
+    }
+    messages.push(serde_json::json!({ "role": "user", "content": text }));
+    let payload = serde_json::json!({
+        "model": model,
+        "messages": messages,
+        "max_tokens": max_tokens,
+        "temperature": temperature,
+    });
+
+    space_request(llm_url);
+    let request = client
+        .post(llm_url)
+        .header(AUTHORIZATION, format!("Bearer {}", api_key))
+        .header(CONTENT_TYPE, "application/json")
+        .json(&payload);
+    let response = send_request(&client, request, "LLM endpoint")?;
+
+    if response.status().is_success() {
+        let chat_resp: ChatResponse = parse_success_body("LLM", response, "LLM response")?;
+        if let Some(choice) = chat_resp.choices.into_iter().next() {
+            Ok(choice.message.content.trim().to_string())
+        } else {
+            Err(anyhow::anyhow!("No choices found in LLM response"))
+        }
+    } else {
+        Err(ApiError::from_response("LLM", response).into())
+    }
+}
+
+/// Runs the cleanup pass (when requested) with the prompt for `language`, or
+/// with a spoken `instruction` in its place, and, if `llm.summarize` is set,
+/// a second summary pass over the resulting text. A failed summary is logged
+/// and dropped so it never costs the user the cleaned-up transcription. Both
+/// prompts may be templates, see `render_prompt`.
+pub fn run_llm_passes(
+    provider: &dyn LlmProvider,
+    llm: &LLMSettings,
+    text: &str,
+    language: Option<&str>,
+    instruction: Option<&str>,
+    clean_up: bool,
+) -> Result<LlmOutput> {
+    let lang = language.or(llm.default_prompt_language.as_deref());
+    let cleaned = if clean_up {
+        let prompt = instruction.unwrap_or_else(|| llm.prompt_for(language));
+        complete_template(provider, prompt, text, lang)?
+    } else {
+        text.to_string()
+    };
+
+    let summary = if llm.summarize {
+        match complete_template(provider, &llm.summary_prompt, &cleaned, lang) {
+            Ok(summary) => Some(summary),
+            Err(e) => {
+                warn!("Summary pass failed: {:?}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    Ok(LlmOutput {
+        text: cleaned,
+        post_processed: clean_up,
+        summary,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{LlmApiStyle, LlmProviderKind};
+    use crate::llm::OpenAiCompletions;
     use mockito::{mock, Matcher};
     use serde_json::json;
     use tempfile::NamedTempFile;
@@ -146,6 +779,134 @@ mod tests {
         assert_eq!(transcription, "Transcribed text.");
     }
 
+    #[test]
+    fn test_translate_audio_success() {
+        let _m = mock("POST", "/v1/audio/translations")
+            .match_header("authorization", "Bearer test_api_key")
+            .match_header("content-type", Matcher::Regex("multipart/form-data.*".to_string()))
+            .match_body(Matcher::Regex(format!("name=\"model\"\r\n\r\n{}", WHISPER_MODEL)))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "Translated text."}"#)
+            .create();
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "dummy audio data").expect("Failed to write to temp file");
+        let audio_path = temp_file.path().to_str().unwrap();
+        let translation_url = &format!("{}/v1/audio/translations", &mockito::server_url());
+        let result = translate_audio(translation_url, "test_api_key", audio_path);
+        assert_eq!(result.unwrap(), "Translated text.");
+    }
+
+    #[test]
+    fn test_translate_audio_failure() {
+        let _m = mock("POST", "/translate")
+            .with_status(400)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"error": "Bad Request"}"#)
+            .create();
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "dummy audio data").expect("Failed to write to temp file");
+        let audio_path = temp_file.path().to_str().unwrap();
+
+        let translation_url = &format!("{}/translate", &mockito::server_url());
+        let err = translate_audio(translation_url, "test_api_key", audio_path).unwrap_err();
+        assert_eq!(err.to_string(), "Whisper API error 400 Bad Request: {\"error\": \"Bad Request\"}");
+    }
+
+    #[test]
+    fn test_translate_audio_bytes_uploads_wav_part() {
+        let _m = mock("POST", "/translate-in-memory")
+            .match_body(Matcher::Regex(
+                "filename=\"recording.wav\"\r\nContent-Type: audio/wav".to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "Translated from memory."}"#)
+            .create();
+
+        let url = format!("{}/translate-in-memory", &mockito::server_url());
+        let result = translate_audio_bytes(&url, "test_api_key", b"RIFF".to_vec(), RECORDING_FILE_NAME);
+        assert_eq!(result.unwrap(), "Translated from memory.");
+    }
+
+    #[test]
+    fn test_transcribe_audio_streaming_reports_each_delta() {
+        let _m = mock("POST", "/v1/audio/transcriptions/stream")
+            .match_header("authorization", "Bearer test_api_key")
+            .match_body(Matcher::Regex("name=\"stream\"\\r\n\\r\ntrue".to_string()))
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_body_from_fn(|body| {
+                // One event per chunk, the last split across two
+                body.write_all(b"data: {\"type\":\"transcript.text.delta\",\"delta\":\"Hello\"}\n\n")?;
+                body.write_all(b"data: {\"type\":\"transcript.text.delta\",\"delta\":\" there,\"}\n\n")?;
+                body.write_all(b"data: {\"type\":\"transcript.text.delta\",")?;
+                body.write_all(b"\"delta\":\" world.\"}\n\n")?;
+                body.write_all(b"data: {\"type\":\"transcript.text.done\",\"text\":\"Hello there, world.\"}\n\n")
+            })
+            .create();
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "dummy audio data").expect("Failed to write to temp file");
+        let audio_path = temp_file.path().to_str().unwrap();
+        let whisper_url = &format!("{}/v1/audio/transcriptions/stream", &mockito::server_url());
+
+        let mut chunks = Vec::new();
+        let text = transcribe_audio_streaming(whisper_url, "test_api_key", audio_path, |chunk| {
+            chunks.push(chunk.to_string())
+        })
+        .unwrap();
+        assert_eq!(chunks, vec!["Hello", " there,", " world."]);
+        assert_eq!(text, chunks.concat());
+    }
+
+    #[test]
+    fn test_transcribe_audio_streaming_reads_chunked_text() {
+        let _m = mock("POST", "/text-stream/transcriptions")
+            .with_status(200)
+            .with_header("content-type", "text/plain; charset=utf-8")
+            .with_body_from_fn(|body| {
+                body.write_all(b"Gr\xC3")?;
+                body.write_all(b"\xBC\xC3\x9Fe")
+            })
+            .create();
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "dummy audio data").expect("Failed to write to temp file");
+        let audio_path = temp_file.path().to_str().unwrap();
+        let whisper_url = &format!("{}/text-stream/transcriptions", &mockito::server_url());
+
+        let mut chunks = Vec::new();
+        let text =
+            transcribe_audio_streaming(whisper_url, "test_api_key", audio_path, |chunk| chunks.push(chunk.to_string()))
+                .unwrap();
+        assert_eq!(text, "Grüße");
+        assert_eq!(chunks.concat(), "Grüße");
+    }
+
+    #[test]
+    fn test_transcribe_audio_streaming_falls_back_to_json() {
+        let _m = mock("POST", "/no-stream/transcriptions")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "Transcribed text."}"#)
+            .create();
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "dummy audio data").expect("Failed to write to temp file");
+        let audio_path = temp_file.path().to_str().unwrap();
+        let whisper_url = &format!("{}/no-stream/transcriptions", &mockito::server_url());
+
+        let mut chunks = Vec::new();
+        let text =
+            transcribe_audio_streaming(whisper_url, "test_api_key", audio_path, |chunk| chunks.push(chunk.to_string()))
+                .unwrap();
+        assert_eq!(text, "Transcribed text.");
+        assert_eq!(chunks, vec!["Transcribed text."]);
+    }
+
     #[test]
     fn test_transcribe_audio_failure() {
         let _m = mock("POST", "/transcribe")
@@ -169,6 +930,65 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_transcribe_audio_error_body_with_200_status() {
+        let _m = mock("POST", "/gateway/transcriptions")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"error": {"message": "Upstream quota exhausted", "type": "insufficient_quota"}}"#)
+            .create();
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "dummy audio data").expect("Failed to write to temp file");
+        let whisper_url = format!("{}/gateway/transcriptions", &mockito::server_url());
+
+        let err = transcribe_audio(&whisper_url, "test_api_key", temp_file.path().to_str().unwrap()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Whisper API error 200 OK: Upstream quota exhausted (insufficient_quota)"
+        );
+        assert!(err.downcast_ref::<ApiError>().is_some());
+    }
+
+    #[test]
+    fn test_transcribe_audio_text_format_reads_body() {
+        let _m = mock("POST", "/format/text")
+            .match_body(Matcher::Regex("name=\"response_format\"\r\n\r\ntext\r\n".to_string()))
+            .with_status(200)
+            .with_header("content-type", "text/plain")
+            .with_body("Plain transcript.\n")
+            .create();
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "dummy audio data").expect("Failed to write to temp file");
+        let url = format!("{}/format/text", &mockito::server_url());
+
+        let audio_path = temp_file.path().to_str().unwrap();
+        let text = transcribe_audio_formatted(&url, "test_api_key", audio_path, None, ResponseFormat::Text)
+            .expect("Text transcription failed");
+        assert_eq!(text, "Plain transcript.");
+    }
+
+    #[test]
+    fn test_transcribe_audio_srt_format_returns_raw_subtitles() {
+        let srt = "1\n00:00:00,000 --> 00:00:01,500\nHello there.\n\n2\n00:00:01,500 --> 00:00:03,000\nGeneral Kenobi.\n\n";
+        let _m = mock("POST", "/format/srt")
+            .match_body(Matcher::Regex("name=\"response_format\"\r\n\r\nsrt\r\n".to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/x-subrip")
+            .with_body(srt)
+            .create();
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "dummy audio data").expect("Failed to write to temp file");
+        let url = format!("{}/format/srt", &mockito::server_url());
+
+        let audio_path = temp_file.path().to_str().unwrap();
+        let subtitles = transcribe_audio_formatted(&url, "test_api_key", audio_path, None, ResponseFormat::Srt)
+            .expect("SRT transcription failed");
+        assert_eq!(subtitles, srt);
+    }
+
     #[test]
     fn test_post_process_text_success() {
         let _m = mock("POST", "/llm")
@@ -193,10 +1013,46 @@ mod tests {
         let prompt = "Please clean up and format the following text:";
         let text = "Transcribed text.";
 
-        let processed_text = post_process_text(llm_url, api_key, prompt, text).expect("Post-processing failed");
+        let processed_text = post_process_text(llm_url, api_key, prompt, text, 150, 0.7).expect("Post-processing failed");
         assert_eq!(processed_text, "Cleaned up and formatted text.");
     }
 
+    #[test]
+    fn test_post_process_chat_success() {
+        let _m = mock("POST", "/v1/chat/completions")
+            .match_header("authorization", "Bearer test_api_key")
+            .match_body(Matcher::Json(json!({
+                "model": "gpt-4o-mini",
+                "messages": [
+                    { "role": "system", "content": "Please clean up and format the following text:" },
+                    { "role": "user", "content": "Transcribed text." }
+                ],
+                "max_tokens": 150,
+                "temperature": 0.7
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "choices": [
+                    { "index": 0, "message": { "role": "assistant", "content": " Cleaned up chat text. " } }
+                ]
+            }"#)
+            .create();
+
+        let llm_url = &format!("{}/v1/chat/completions", &mockito::server_url());
+        let processed_text = post_process_chat(
+            llm_url,
+            "test_api_key",
+            "gpt-4o-mini",
+            "Please clean up and format the following text:",
+            "Transcribed text.",
+            150,
+            0.7,
+        )
+        .expect("Chat post-processing failed");
+        assert_eq!(processed_text, "Cleaned up chat text.");
+    }
+
     #[test]
     fn test_post_process_text_no_choices() {
         let _m = mock("POST", "/llm")
@@ -212,7 +1068,7 @@ mod tests {
         let prompt = "Please clean up and format the following text:";
         let text = "Transcribed text.";
 
-        let result = post_process_text(llm_url, api_key, prompt, text);
+        let result = post_process_text(llm_url, api_key, prompt, text, 150, 0.7);
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err().to_string(),
@@ -233,11 +1089,269 @@ mod tests {
         let prompt = "Please clean up and format the following text:";
         let text = "Transcribed text.";
 
-        let result = post_process_text(llm_url, api_key, prompt, text);
+        let result = post_process_text(llm_url, api_key, prompt, text, 150, 0.7);
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err().to_string(),
             "LLM API error 500 Internal Server Error: {\"error\": \"Internal Server Error\"}"
         );
     }
+
+    #[test]
+    fn test_rate_limited_error_is_detected() {
+        let _m = mock("POST", "/transcribe-busy")
+            .with_status(429)
+            .with_body(r#"{"error": "Rate limit reached"}"#)
+            .create();
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "dummy audio data").expect("Failed to write to temp file");
+        let audio_path = temp_file.path().to_str().unwrap();
+
+        let whisper_url = &format!("{}/transcribe-busy", &mockito::server_url());
+        let err = transcribe_audio(whisper_url, "test_api_key", audio_path).unwrap_err();
+        assert!(is_rate_limited(&err));
+        assert!(!is_rate_limited(&anyhow::anyhow!("connection refused")));
+    }
+
+    #[test]
+    fn test_transcribe_audio_bytes_uploads_wav_part() {
+        let _m = mock("POST", "/in-memory")
+            .match_body(Matcher::Regex(
+                "filename=\"recording.wav\"\r\nContent-Type: audio/wav".to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "From memory."}"#)
+            .create();
+
+        let url = format!("{}/in-memory", &mockito::server_url());
+        let result = transcribe_audio_bytes(
+            &url,
+            "test_api_key",
+            b"RIFF....WAVE".to_vec(),
+            RECORDING_FILE_NAME,
+            None,
+            ResponseFormat::Json,
+        );
+        assert_eq!(result.unwrap(), "From memory.");
+    }
+
+    fn fallback_endpoints(local_path: &str) -> Endpoints {
+        Endpoints {
+            local_whisper: format!("{}{}", &mockito::server_url(), local_path),
+            hosted_whisper: format!("{}/hosted/v1/audio/transcriptions", &mockito::server_url()),
+            llm_endpoint: format!("{}/llm", &mockito::server_url()),
+            translation: format!("{}/v1/audio/translations", &mockito::server_url()),
+        }
+    }
+
+    #[test]
+    fn test_unforced_transcription_records_hosted_after_local_failure() {
+        let _probe = mock("GET", "/local-broken").with_status(200).create();
+        let _local = mock("POST", "/local-broken")
+            .with_status(500)
+            .with_body("model crashed")
+            .create();
+        let _hosted = mock("POST", "/hosted/v1/audio/transcriptions")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "Hosted text."}"#)
+            .create();
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "dummy audio data").expect("Failed to write to temp file");
+        let audio_path = temp_file.path().to_str().unwrap();
+
+        let endpoints = fallback_endpoints("/local-broken");
+        let result = transcribe_with_override(&endpoints, "test_api_key", audio_path, None, None, ResponseFormat::Json)
+            .expect("Fallback transcription failed");
+        assert_eq!(result.text, "Hosted text.");
+        assert_eq!(result.endpoint_used, EndpointKind::Hosted);
+        assert_eq!(result.model_used, "whisper-1");
+    }
+
+    #[test]
+    fn test_unforced_transcription_prefers_local() {
+        let _probe = mock("GET", "/local-ok").with_status(200).create();
+        let _local = mock("POST", "/local-ok")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "Local text."}"#)
+            .create();
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "dummy audio data").expect("Failed to write to temp file");
+        let audio_path = temp_file.path().to_str().unwrap();
+
+        let endpoints = fallback_endpoints("/local-ok");
+        let result = transcribe_with_override(&endpoints, "test_api_key", audio_path, None, None, ResponseFormat::Json)
+            .expect("Local transcription failed");
+        assert_eq!(result.text, "Local text.");
+        assert_eq!(result.endpoint_used, EndpointKind::Local);
+    }
+
+    #[test]
+    fn test_forced_endpoint_skips_automatic_selection() {
+        let _probe = mock("GET", "/local-forced").with_status(200).create();
+        let _local = mock("POST", "/local-forced")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "Local text."}"#)
+            .create();
+        let _hosted = mock("POST", "/hosted/v1/audio/transcriptions")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "Hosted text."}"#)
+            .create();
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "dummy audio data").expect("Failed to write to temp file");
+        let audio_path = temp_file.path().to_str().unwrap();
+        let endpoints = fallback_endpoints("/local-forced");
+
+        let forced = transcribe_with_override(&endpoints, "test_api_key", audio_path, None, Some(EndpointKind::Hosted), ResponseFormat::Json)
+            .expect("Forced hosted transcription failed");
+        assert_eq!(forced.endpoint_used, EndpointKind::Hosted);
+
+        let automatic = transcribe_with_override(&endpoints, "test_api_key", audio_path, None, None, ResponseFormat::Json)
+            .expect("Automatic transcription failed");
+        assert_eq!(automatic.endpoint_used, EndpointKind::Local);
+    }
+
+    #[test]
+    fn test_verbose_json_reports_the_language_as_a_code() {
+        let _probe = mock("GET", "/local-verbose").with_status(200).create();
+        let _local = mock("POST", "/local-verbose")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "Hallo.", "language": "german", "duration": 1.0}"#)
+            .create();
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "dummy audio data").expect("Failed to write to temp file");
+        let audio_path = temp_file.path().to_str().unwrap();
+
+        let result = transcribe_with_override(
+            &fallback_endpoints("/local-verbose"),
+            "test_api_key",
+            audio_path,
+            None,
+            None,
+            ResponseFormat::VerboseJson,
+        )
+        .expect("Verbose transcription failed");
+        assert_eq!(result.text, "Hallo.");
+        assert_eq!(result.language.as_deref(), Some("de"));
+        assert_eq!(language_code("pt"), "pt");
+    }
+
+    #[test]
+    fn test_run_llm_passes_requests_cleanup_and_summary() {
+        let cleanup = mock("POST", "/llm-two-pass")
+            .match_body(Matcher::PartialJson(json!({
+                "prompt": "Please clean up and format the following text: um so the meeting went well"
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"choices": [{ "text": "The meeting went well." }]}"#)
+            .expect(1)
+            .create();
+        let summary = mock("POST", "/llm-two-pass")
+            .match_body(Matcher::PartialJson(json!({
+                "prompt": "Summarize: The meeting went well."
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"choices": [{ "text": "Good meeting." }]}"#)
+            .expect(1)
+            .create();
+
+        let llm = LLMSettings {
+            post_processing_prompt: "Please clean up and format the following text:".to_string(),
+            always_post_process: true,
+            skip_post_processing_languages: Vec::new(),
+            summarize: true,
+            summary_prompt: "Summarize:".to_string(),
+            provider: LlmProviderKind::OpenAi,
+            model: None,
+            max_tokens: 150,
+            temperature: 0.7,
+            max_calls_per_session: 0,
+            record_diff: false,
+            notify_diff: false,
+            api_style: LlmApiStyle::Completions,
+            prompts_by_language: std::collections::HashMap::new(),
+            default_prompt_language: None,
+        };
+        let provider = OpenAiCompletions {
+            url: format!("{}/llm-two-pass", &mockito::server_url()),
+            api_key: "test_api_key".to_string(),
+            max_tokens: 150,
+            temperature: 0.7,
+        };
+
+        let output = run_llm_passes(&provider, &llm, "um so the meeting went well", None, None, true)
+            .expect("LLM passes failed");
+
+        cleanup.assert();
+        summary.assert();
+        assert_eq!(output.text, "The meeting went well.");
+        assert!(output.post_processed);
+        assert_eq!(output.summary, Some("Good meeting.".to_string()));
+    }
+
+    #[test]
+    fn test_transcribe_audio_words_parses_word_timestamps() {
+        let _m = mock("POST", "/v1/audio/transcriptions-words")
+            .match_body(Matcher::Regex("timestamp_granularities\\[\\]".to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{
+                "text": "Hello world",
+                "words": [
+                    { "word": "Hello", "start": 0.0, "end": 0.42 },
+                    { "word": "world", "start": 0.5, "end": 0.98 }
+                ]
+            }"#)
+            .create();
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "dummy audio data").expect("Failed to write to temp file");
+        let audio_path = temp_file.path().to_str().unwrap();
+
+        let whisper_url = &format!("{}/v1/audio/transcriptions-words", &mockito::server_url());
+        let timed = transcribe_audio_words(whisper_url, "test_api_key", audio_path)
+            .expect("Word transcription failed");
+
+        assert!(timed.word_level);
+        assert_eq!(
+            timed.words,
+            vec![
+                Word { word: "Hello".to_string(), start: 0.0, end: 0.42 },
+                Word { word: "world".to_string(), start: 0.5, end: 0.98 },
+            ]
+        );
+        assert_eq!(
+            timed.to_json().unwrap(),
+            r#"{"text":"Hello world","word_level":true,"words":[{"word":"Hello","start":0.0,"end":0.42},{"word":"world","start":0.5,"end":0.98}]}"#
+        );
+    }
+
+    #[test]
+    fn test_word_timestamps_fall_back_to_segments() {
+        let verbose: VerboseWhisperResponse = serde_json::from_str(r#"{
+            "text": "Hello world. Bye.",
+            "segments": [
+                { "id": 0, "text": " Hello world.", "start": 0.0, "end": 1.2 },
+                { "id": 1, "text": " Bye.", "start": 1.2, "end": 1.8 }
+            ]
+        }"#).unwrap();
+
+        let timed = timed_from_verbose(verbose);
+        assert!(!timed.word_level);
+        assert_eq!(timed.words.len(), 2);
+        assert_eq!(timed.words[0].word, "Hello world.");
+        assert_eq!(timed.words[1].end, 1.8);
+    }
 }
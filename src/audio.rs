@@ -1,40 +1,134 @@
 use anyhow::{Result, Context};
-use bytemuck::NoUninit;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::SizedSample;
 use hound::{WavWriter, WavSpec, SampleFormat};
+use std::io::{BufWriter, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::{self, Sender};
-use std::time::Duration;
-use log::{info, error};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use log::{debug, info, error, warn};
 
+use crate::api::EndpointKind;
+use crate::config::{AudioSettings, AudioStep, DeviceProfile};
+
+/// Captures shorter than this are treated as a device glitch rather than speech
+pub const MIN_CAPTURED_SAMPLES: usize = 64;
+
+/// Sample rate Whisper works at internally; uploading more only costs bandwidth
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+/// How often a recording loop checks its stop signal
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long a push-to-talk recording may wait for the device's first samples
+/// after the hotkey was released
+const FIRST_SAMPLE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Attempts to re-open a device that disappeared mid-recording before giving up
+const RECONNECT_ATTEMPTS: u32 = 3;
+
+/// Pause before each reconnect attempt, giving a replugged device time to settle
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// Prints the input devices with their default format, marking the OS default
 pub fn list_audio_devices() -> Result<()> {
     let host = cpal::default_host();
+    let default = host.default_input_device().and_then(|d| d.name().ok());
 
-    println!("Available input audio devices:");
+    println!("Available input audio devices (* = default):");
     for device in host.input_devices().context("Failed to get input devices")? {
-        println!("{}", device.name().unwrap_or_else(|_| "Unknown".to_string()));
+        let name = device.name().unwrap_or_else(|_| "Unknown".to_string());
+        let marker = if default.as_deref() == Some(name.as_str()) { '*' } else { ' ' };
+        let format = match device.default_input_config() {
+            Ok(config) => format!("{}, {} ch, {} Hz", config.sample_format(), config.channels(), config.sample_rate().0),
+            Err(_) => "no default format".to_string(),
+        };
+        println!("{} {} ({})", marker, name, format);
     }
     Ok(())
 }
 
-/// Records audio from the specified device for the given duration in seconds
-pub fn record_audio(device_name: &str, duration_secs: u64, tx: mpsc::Sender<i16>) -> Result<()> {
-    let device = get_device_from_name( device_name)?;
+/// Opens the input stream for `device_name`, applying the first device profile
+/// whose name matches the selected device. A non-empty `channel_selection` mixes
+/// just those channels down to mono. Returns the stream (not yet playing) and the
+/// format of the samples it sends; `captured` counts samples sent to `tx` and
+/// stream errors are sent to `errors`.
+fn open_input_stream(
+    device_name: &str,
+    fallback_to_default: bool,
+    profiles: &[DeviceProfile],
+    channel_selection: &[u16],
+    captured: Arc<AtomicUsize>,
+    tx: mpsc::Sender<i16>,
+    errors: mpsc::Sender<cpal::StreamError>,
+) -> Result<(cpal::Stream, WavSpec)> {
+    let device = get_device_from_name(device_name, fallback_to_default)?;
+    let resolved_name = device.name()?;
+
+    info!("Using audio device: {}", resolved_name);
 
-    info!("Using audio device: {}", device.name()?);
+    let profile = find_device_profile(profiles, &resolved_name).cloned();
+    if let Some(profile) = &profile {
+        info!("Applying device profile '{}'", profile.name);
+    }
 
     let config = device.default_input_config().context("Failed to get default input config")?;
 
     let sample_format = config.sample_format();
-    let config: cpal::StreamConfig = config.into();
+    let mut config: cpal::StreamConfig = config.into();
+    if let Some(channels) = profile.as_ref().and_then(|p| p.channels) {
+        config.channels = channels;
+    }
+    validate_channel_selection(channel_selection, config.channels)?;
+    let selection = channel_selection.to_vec();
+    let spec = WavSpec {
+        channels: captured_channels(config.channels, &selection),
+        sample_rate: config.sample_rate.0,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
 
-    // Build and run the stream
     let stream = match sample_format {
-        cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, tx.clone())?,
-        cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, tx.clone())?,
-        cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, tx.clone())?,
+        cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, profile, selection, captured, tx, errors)?,
+        cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, profile, selection, captured, tx, errors)?,
+        cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, profile, selection, captured, tx, errors)?,
         _ => return Err(anyhow::anyhow!("Unsupported sample format")),
     };
+    Ok((stream, spec))
+}
+
+/// What a finished capture delivered to its receiver
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureSummary {
+    /// Samples sent, counting every channel
+    pub samples: usize,
+    /// Format of those samples
+    pub spec: WavSpec,
+}
+
+impl CaptureSummary {
+    /// Exact length of the captured audio
+    pub fn duration(&self) -> Duration {
+        let frames = self.samples / usize::from(self.spec.channels.max(1));
+        Duration::from_secs_f64(frames as f64 / f64::from(self.spec.sample_rate.max(1)))
+    }
+}
+
+/// Records audio from the specified device for the given duration in seconds
+pub fn record_audio(
+    device_name: &str,
+    profiles: &[DeviceProfile],
+    channel_selection: &[u16],
+    duration_secs: u64,
+    tx: mpsc::Sender<i16>,
+) -> Result<CaptureSummary> {
+    let captured = Arc::new(AtomicUsize::new(0));
+    // A fixed-length capture has nothing to reconnect, so errors are only logged
+    let (errors, _) = mpsc::channel();
+    let (stream, spec) =
+        open_input_stream(device_name, false, profiles, channel_selection, Arc::clone(&captured), tx, errors)?;
 
     stream.play().context("Failed to start audio stream")?;
 
@@ -44,34 +138,635 @@ pub fn record_audio(device_name: &str, duration_secs: u64, tx: mpsc::Sender<i16>
 
     drop(stream);
 
-    info!("Audio recording completed");
-    Ok(())
+    let summary = CaptureSummary {
+        samples: captured.load(Ordering::SeqCst),
+        spec,
+    };
+    info!(
+        "Audio recording completed ({} samples, {:.3} s)",
+        summary.samples,
+        summary.duration().as_secs_f64()
+    );
+    Ok(summary)
 }
 
-pub fn get_device_from_name(device_name: &str) -> Result<cpal::Device> {
-    let host = cpal::default_host();
+/// Push-to-talk recording: captures until `stop_signal` is raised (plus
+/// `release_grace`), then tears the stream down. Even a very short press keeps
+/// the stream open until at least one sample has arrived. With
+/// `audio.silence_timeout_ms` set, trailing silence after speech raises
+/// `stop_signal` itself. If the device disappears, the stream is re-opened up
+/// to `RECONNECT_ATTEMPTS` times before the recording is aborted. Returns how
+/// much audio was sent to `tx`, and its format.
+pub fn record_audio_until(
+    device_name: &str,
+    audio: &AudioSettings,
+    profiles: &[DeviceProfile],
+    release_grace: Duration,
+    stop_signal: Arc<AtomicBool>,
+    tx: mpsc::Sender<i16>,
+) -> Result<CaptureSummary> {
+    let captured = Arc::new(AtomicUsize::new(0));
+    let (stream_tx, stream_rx) = mpsc::channel();
+    let (error_tx, error_rx) = mpsc::channel();
+    // Owns the stream's sender, so dropping it lets the forwarder finish
+    let stream_captured = Arc::clone(&captured);
+    let open = move || {
+        open_input_stream(
+            device_name,
+            audio.fallback_to_default,
+            profiles,
+            &audio.channel_selection,
+            Arc::clone(&stream_captured),
+            stream_tx.clone(),
+            error_tx.clone(),
+        )
+    };
+    let (mut stream, spec) = open()?;
+
+    // Forwards samples to the caller, watching for trailing silence on the way
+    let mut detector = SilenceDetector::from_settings(audio, spec);
+    let silence_stop = Arc::clone(&stop_signal);
+    let forwarder = std::thread::spawn(move || {
+        for sample in stream_rx {
+            if tx.send(sample).is_err() {
+                break;
+            }
+            if let Some(detector) = detector.as_mut() {
+                if detector.push(&[sample]) && !silence_stop.swap(true, Ordering::SeqCst) {
+                    info!("Stopping recording after trailing silence");
+                }
+            }
+        }
+    });
+
+    stream.play().context("Failed to start audio stream")?;
+    info!("Recording until the hotkey is released...");
+
+    let limit = (audio.max_recording_secs > 0).then(|| Duration::from_secs(audio.max_recording_secs));
+    let started = Instant::now();
+    while wait_for_stop_or_device_loss(&stop_signal, release_grace, limit, started, Some(&error_rx)) {
+        drop(stream);
+        stream = match reconnect(&open, spec) {
+            Ok(stream) => stream,
+            Err(e) => {
+                drop(open);
+                let _ = forwarder.join();
+                return Err(e);
+            }
+        };
+    }
+    let got_audio = wait_for_first_sample(&captured, FIRST_SAMPLE_TIMEOUT);
+    drop(stream);
+    drop(open);
+    let _ = forwarder.join();
+
+    if !got_audio {
+        return Err(anyhow::anyhow!(
+            "No audio arrived from the recording device within {} ms",
+            FIRST_SAMPLE_TIMEOUT.as_millis()
+        ));
+    }
+    let summary = CaptureSummary {
+        samples: captured.load(Ordering::SeqCst),
+        spec,
+    };
+    info!(
+        "Audio recording completed ({} samples, {:.3} s)",
+        summary.samples,
+        summary.duration().as_secs_f64()
+    );
+    Ok(summary)
+}
+
+/// Re-opens the recording device after it disappeared, trying
+/// `RECONNECT_ATTEMPTS` times. The new stream must deliver the same format as
+/// the lost one so the samples captured so far still fit.
+fn reconnect<F>(open: F, spec: WavSpec) -> Result<cpal::Stream>
+where
+    F: Fn() -> Result<(cpal::Stream, WavSpec)>,
+{
+    warn!("The recording device was disconnected; trying to reconnect");
+    for attempt in 1..=RECONNECT_ATTEMPTS {
+        std::thread::sleep(RECONNECT_DELAY);
+        match open() {
+            Ok((stream, new_spec)) if new_spec == spec => {
+                stream.play().context("Failed to restart audio stream")?;
+                info!("Recording device reconnected (attempt {})", attempt);
+                return Ok(stream);
+            }
+            Ok(_) => {
+                return Err(anyhow::anyhow!(
+                    "The recording device came back with a different audio format; aborting the recording"
+                ))
+            }
+            Err(e) => warn!("Reconnect attempt {}/{} failed: {:?}", attempt, RECONNECT_ATTEMPTS, e),
+        }
+    }
+    Err(anyhow::anyhow!(
+        "The recording device was disconnected and could not be re-opened after {} attempts; the recording was aborted",
+        RECONNECT_ATTEMPTS
+    ))
+}
+
+/// An open-ended capture for continuous use such as live captions. Samples
+/// are sent to `tx` as they arrive; dropping the capture stops the stream.
+pub struct LiveCapture {
+    _stream: cpal::Stream,
+    /// Format of the samples sent
+    pub spec: WavSpec,
+}
+
+impl LiveCapture {
+    pub fn start(
+        device_name: &str,
+        audio: &AudioSettings,
+        profiles: &[DeviceProfile],
+        tx: mpsc::Sender<i16>,
+    ) -> Result<Self> {
+        let captured = Arc::new(AtomicUsize::new(0));
+        let (errors, _) = mpsc::channel();
+        let (stream, spec) = open_input_stream(
+            device_name,
+            audio.fallback_to_default,
+            profiles,
+            &audio.channel_selection,
+            captured,
+            tx,
+            errors,
+        )?;
+        stream.play().context("Failed to start audio stream")?;
+        info!("Capturing continuously from {}", device_name);
+        Ok(LiveCapture { _stream: stream, spec })
+    }
+}
+
+/// Length of the windows the silence detector measures RMS over
+const SILENCE_WINDOW: Duration = Duration::from_millis(20);
+
+/// Voice activity check for hands-free recording: reports when the RMS level has
+/// stayed below a threshold for a timeout, but only after speech was heard, so
+/// the silence before the user starts talking never ends a recording
+#[derive(Debug)]
+pub struct SilenceDetector {
+    threshold: f64,
+    window_samples: usize,
+    timeout_samples: usize,
+    window_sum_squares: f64,
+    window_len: usize,
+    heard_speech: bool,
+    silent_samples: usize,
+}
+
+impl SilenceDetector {
+    pub fn new(threshold: f32, timeout: Duration, sample_rate: u32, channels: u16) -> Self {
+        let samples_per_sec = sample_rate as f64 * channels.max(1) as f64;
+        SilenceDetector {
+            threshold: threshold.clamp(0.0, 1.0) as f64 * i16::MAX as f64,
+            window_samples: ((samples_per_sec * SILENCE_WINDOW.as_secs_f64()) as usize).max(1),
+            timeout_samples: (samples_per_sec * timeout.as_secs_f64()) as usize,
+            window_sum_squares: 0.0,
+            window_len: 0,
+            heard_speech: false,
+            silent_samples: 0,
+        }
+    }
+
+    /// Detector for `audio.silence_*`, or `None` when `silence_timeout_ms` is 0
+    pub fn from_settings(audio: &AudioSettings, spec: WavSpec) -> Option<Self> {
+        (audio.silence_timeout_ms > 0).then(|| {
+            SilenceDetector::new(
+                audio.silence_threshold,
+                Duration::from_millis(audio.silence_timeout_ms),
+                spec.sample_rate,
+                spec.channels,
+            )
+        })
+    }
+
+    /// Feeds captured samples; returns true once the trailing silence is long enough
+    pub fn push(&mut self, samples: &[i16]) -> bool {
+        for &sample in samples {
+            self.window_sum_squares += (sample as f64).powi(2);
+            self.window_len += 1;
+            if self.window_len < self.window_samples {
+                continue;
+            }
+
+            let rms = (self.window_sum_squares / self.window_len as f64).sqrt();
+            if rms >= self.threshold {
+                self.heard_speech = true;
+                self.silent_samples = 0;
+            } else if self.heard_speech {
+                self.silent_samples += self.window_len;
+            }
+            self.window_sum_squares = 0.0;
+            self.window_len = 0;
+        }
+        self.heard_speech && self.silent_samples >= self.timeout_samples
+    }
+}
+
+/// Waits until the stream has delivered at least one sample, giving up after
+/// `timeout`. Returns whether any sample arrived.
+pub fn wait_for_first_sample(captured: &AtomicUsize, timeout: Duration) -> bool {
+    let started = Instant::now();
+    while captured.load(Ordering::SeqCst) == 0 {
+        if started.elapsed() >= timeout {
+            return false;
+        }
+        std::thread::sleep(STOP_POLL_INTERVAL);
+    }
+    true
+}
+
+/// Blocks until `stop` is raised, then keeps waiting for `grace` so the stream
+/// still captures the tail of the last word spoken as the hotkey was released.
+/// After `limit` the recording stops on its own: `stop` is raised and the
+/// grace period skipped.
+pub fn wait_for_stop(stop: &AtomicBool, grace: Duration, limit: Option<Duration>) {
+    wait_for_stop_or_device_loss(stop, grace, limit, Instant::now(), None);
+}
+
+/// `wait_for_stop` for a recording started at `started` that also watches the
+/// stream's `errors`. Returns true, without stopping, as soon as the device is
+/// reported lost; other stream errors were already logged and are ignored.
+fn wait_for_stop_or_device_loss(
+    stop: &AtomicBool,
+    grace: Duration,
+    limit: Option<Duration>,
+    started: Instant,
+    errors: Option<&mpsc::Receiver<cpal::StreamError>>,
+) -> bool {
+    while !stop.load(Ordering::SeqCst) {
+        if errors
+            .into_iter()
+            .flat_map(|errors| errors.try_iter())
+            .any(|err| matches!(err, cpal::StreamError::DeviceNotAvailable))
+        {
+            return true;
+        }
+        if let Some(limit) = limit.filter(|&limit| started.elapsed() >= limit) {
+            warn!(
+                "Recording reached audio.max_recording_secs ({} s); stopping and transcribing what was captured",
+                limit.as_secs()
+            );
+            stop.store(true, Ordering::SeqCst);
+            return false;
+        }
+        std::thread::sleep(STOP_POLL_INTERVAL);
+    }
+    if !grace.is_zero() {
+        debug!("Stop requested; capturing for another {} ms", grace.as_millis());
+        std::thread::sleep(grace);
+    }
+    false
+}
+
+/// Runs a capture and, if it yields (almost) no samples, either re-records once
+/// or aborts so that an empty WAV is never uploaded
+pub fn record_with_empty_retry<F>(retry_empty_recording: bool, mut record: F) -> Result<Vec<i16>>
+where
+    F: FnMut() -> Result<Vec<i16>>,
+{
+    let samples = record()?;
+    if samples.len() >= MIN_CAPTURED_SAMPLES {
+        return Ok(samples);
+    }
+
+    if retry_empty_recording {
+        warn!("Recording captured only {} samples; recording again", samples.len());
+        let samples = record()?;
+        if samples.len() >= MIN_CAPTURED_SAMPLES {
+            return Ok(samples);
+        }
+    }
+
+    Err(anyhow::anyhow!(
+        "No audio was captured from the recording device. Check that the microphone is connected and not muted."
+    ))
+}
+
+/// Finds the profile for a device, preferring an exact name over a substring match
+pub fn find_device_profile<'a>(profiles: &'a [DeviceProfile], device_name: &str) -> Option<&'a DeviceProfile> {
+    let device_lower = device_name.to_lowercase();
+    profiles
+        .iter()
+        .find(|p| p.name == device_name)
+        .or_else(|| {
+            profiles
+                .iter()
+                .find(|p| !p.name.is_empty() && device_lower.contains(&p.name.to_lowercase()))
+        })
+}
+
+/// Applies a device profile's noise gate and gain to captured samples in place
+pub fn apply_device_profile(samples: &mut [i16], profile: &DeviceProfile) {
+    if let Some(threshold) = profile.noise_gate_threshold {
+        apply_noise_gate(samples, threshold);
+    }
+    if let Some(gain) = profile.gain {
+        apply_gain(samples, gain);
+    }
+}
+
+fn full_scale_fraction(fraction: f32) -> i32 {
+    (fraction.clamp(0.0, 1.0) * i16::MAX as f32) as i32
+}
+
+/// Multiplies every sample by `gain`, clipping at full scale
+pub fn apply_gain(samples: &mut [i16], gain: f32) {
+    if gain == 1.0 {
+        return;
+    }
+    for sample in samples.iter_mut() {
+        let scaled = (*sample as f32 * gain).round();
+        *sample = scaled.clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+    }
+}
+
+/// Silences samples quieter than `threshold` (a fraction of full scale)
+pub fn apply_noise_gate(samples: &mut [i16], threshold: f32) {
+    let gate = full_scale_fraction(threshold);
+    for sample in samples.iter_mut() {
+        if (*sample as i32).abs() < gate {
+            *sample = 0;
+        }
+    }
+}
+
+/// Drops leading and trailing frames in which every channel is quieter than
+/// `threshold`
+pub fn trim_silence(samples: &[i16], channels: u16, threshold: f32) -> Vec<i16> {
+    let gate = full_scale_fraction(threshold);
+    let frames: Vec<&[i16]> = samples.chunks(channels.max(1) as usize).collect();
+    let loud = |frame: &&[i16]| frame.iter().any(|&s| (s as i32).abs() >= gate);
+    match (frames.iter().position(loud), frames.iter().rposition(loud)) {
+        (Some(first), Some(last)) => frames[first..=last].concat(),
+        _ => Vec::new(),
+    }
+}
+
+/// Averages interleaved channels into a single mono channel
+pub fn downmix_to_mono(samples: &[i16], channels: u16) -> Vec<i16> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+    samples
+        .chunks_exact(channels as usize)
+        .map(|frame| (frame.iter().map(|&s| s as i32).sum::<i32>() / channels as i32) as i16)
+        .collect()
+}
+
+/// Scales the recording so its loudest sample reaches `peak` (a fraction of
+/// full scale). Silent recordings are left alone.
+pub fn normalize_peak(samples: &mut [i16], peak: f32) {
+    let loudest = samples.iter().map(|&s| (s as i32).abs()).max().unwrap_or(0);
+    if loudest == 0 {
+        return;
+    }
+    apply_gain(samples, full_scale_fraction(peak) as f32 / loudest as f32);
+}
+
+/// Peak level `audio.normalize` brings recordings to, in dBFS
+pub const NORMALIZE_TARGET_DBFS: f32 = -3.0;
+
+/// Recordings whose peak stays below this (dBFS) hold only noise, which
+/// normalizing would amplify
+const NORMALIZE_SILENCE_FLOOR_DBFS: f32 = -50.0;
+
+fn dbfs_to_fraction(dbfs: f32) -> f32 {
+    10f32.powf(dbfs / 20.0)
+}
+
+/// Auto-gain for quiet microphones: scales the recording uniformly so its
+/// peak reaches `target_peak` dBFS, clipping at full scale. Recordings that
+/// are effectively silent are left untouched.
+pub fn normalize_samples(samples: &mut [i16], target_peak: f32) {
+    let loudest = samples.iter().map(|&s| (s as i32).abs()).max().unwrap_or(0);
+    if (loudest as f32) < dbfs_to_fraction(NORMALIZE_SILENCE_FLOOR_DBFS) * i16::MAX as f32 {
+        debug!("Peak of {} is below the noise floor; not normalizing", loudest);
+        return;
+    }
+    normalize_peak(samples, dbfs_to_fraction(target_peak));
+}
+
+/// Configurable chain of DSP steps (`audio.preprocessing`) run over a whole
+/// recording in order, usable on live captures and on loaded files alike
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AudioPipeline {
+    steps: Vec<AudioStep>,
+}
+
+impl AudioPipeline {
+    pub fn new(steps: Vec<AudioStep>) -> Self {
+        AudioPipeline { steps }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Runs every step, returning the processed samples and their new format
+    pub fn apply(&self, mut samples: Vec<i16>, mut spec: WavSpec) -> (Vec<i16>, WavSpec) {
+        for step in &self.steps {
+            match *step {
+                AudioStep::Gain { factor } => apply_gain(&mut samples, factor),
+                AudioStep::NoiseGate { threshold } => apply_noise_gate(&mut samples, threshold),
+                AudioStep::TrimSilence { threshold } => {
+                    samples = trim_silence(&samples, spec.channels, threshold);
+                }
+                AudioStep::Resample { rate } => {
+                    samples = resample_linear(&samples, spec.channels, spec.sample_rate, rate);
+                    spec.sample_rate = rate;
+                }
+                AudioStep::Downmix => {
+                    samples = downmix_to_mono(&samples, spec.channels);
+                    spec.channels = 1;
+                }
+                AudioStep::Normalize { peak } => normalize_peak(&mut samples, peak),
+            }
+            debug!("Audio step {:?} left {} samples", step, samples.len());
+        }
+        (samples, spec)
+    }
+}
+
+/// Checks that every selected channel exists on a device with `device_channels` inputs
+pub fn validate_channel_selection(selection: &[u16], device_channels: u16) -> Result<()> {
+    match selection.iter().find(|&&channel| channel >= device_channels) {
+        Some(channel) => Err(anyhow::anyhow!(
+            "audio.channel_selection includes channel {}, but the device only has {} channels (0-{})",
+            channel,
+            device_channels,
+            device_channels.saturating_sub(1)
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Number of channels written to the WAV for a given device and selection
+pub fn captured_channels(device_channels: u16, selection: &[u16]) -> u16 {
+    if selection.is_empty() {
+        device_channels
+    } else {
+        1
+    }
+}
+
+/// Extracts the selected channels from interleaved frames and averages them to
+/// mono. An empty selection returns the samples unchanged; a trailing partial
+/// frame is dropped.
+pub fn select_channels(interleaved: &[i16], device_channels: u16, selection: &[u16]) -> Vec<i16> {
+    if selection.is_empty() || device_channels == 0 {
+        return interleaved.to_vec();
+    }
+    interleaved
+        .chunks_exact(device_channels as usize)
+        .map(|frame| {
+            let sum: i32 = selection.iter().map(|&channel| frame[channel as usize] as i32).sum();
+            (sum / selection.len() as i32) as i16
+        })
+        .collect()
+}
+
+/// Source of the OS input devices, abstracted so selection can be tested
+pub trait InputDeviceSource {
+    fn default_input_name(&self) -> Option<String>;
+    fn input_names(&self) -> Vec<String>;
+}
+
+/// The real cpal host
+pub struct CpalDevices;
+
+impl InputDeviceSource for CpalDevices {
+    fn default_input_name(&self) -> Option<String> {
+        cpal::default_host()
+            .default_input_device()
+            .and_then(|d| d.name().ok())
+    }
+
+    fn input_names(&self) -> Vec<String> {
+        cpal::default_host()
+            .input_devices()
+            .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Resolves `audio.recording_device` to a concrete device name before each recording
+pub struct DeviceSelector {
+    follow_default: bool,
+    last_default: Option<String>,
+}
+
+impl DeviceSelector {
+    pub fn new(follow_default: bool) -> Self {
+        DeviceSelector {
+            follow_default,
+            last_default: None,
+        }
+    }
+
+    /// Explicit device names are used as-is. "default" is re-resolved on every call
+    /// when following the OS default, otherwise pinned to the first device found.
+    pub fn select(&mut self, configured: &str, source: &dyn InputDeviceSource) -> Result<String> {
+        if configured.to_lowercase() != "default" {
+            return Ok(configured.to_string());
+        }
+        if !self.follow_default {
+            if let Some(pinned) = &self.last_default {
+                return Ok(pinned.clone());
+            }
+        }
+
+        let current = source
+            .default_input_name()
+            .context("No default input device available")?;
+        if let Some(previous) = &self.last_default {
+            if previous != &current {
+                info!("Default input device changed from '{}' to '{}'", previous, current);
+            }
+        }
+        self.last_default = Some(current.clone());
+        Ok(current)
+    }
+}
+
+/// Device `get_device_from_name` opens
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceChoice {
+    Named(String),
+    Default,
+}
+
+/// Picks the device for `device_name`: the OS default for "default", else the
+/// input device of that name. A missing device is an error unless
+/// `fallback_to_default` is set and there is a default to use instead.
+pub fn choose_input_device(
+    device_name: &str,
+    source: &dyn InputDeviceSource,
+    fallback_to_default: bool,
+) -> Result<DeviceChoice> {
     if device_name.to_lowercase() == "default" {
-        host.default_input_device().context("No default input device available")
+        return Ok(DeviceChoice::Default);
+    }
+    let available = source.input_names();
+    if available.iter().any(|name| name == device_name) {
+        return Ok(DeviceChoice::Named(device_name.to_string()));
+    }
+    if fallback_to_default {
+        if let Some(default) = source.default_input_name() {
+            warn!("Recording device '{}' not found; falling back to the default '{}'", device_name, default);
+            return Ok(DeviceChoice::Default);
+        }
+    }
+    let available = if available.is_empty() {
+        "none".to_string()
     } else {
-        host.input_devices()
+        available.iter().map(|name| format!("'{}'", name)).collect::<Vec<_>>().join(", ")
+    };
+    Err(anyhow::anyhow!(
+        "Recording device '{}' not found; available input devices: {} (see `rusty-scribe devices`)",
+        device_name,
+        available
+    ))
+}
+
+pub fn get_device_from_name(device_name: &str, fallback_to_default: bool) -> Result<cpal::Device> {
+    let host = cpal::default_host();
+    match choose_input_device(device_name, &CpalDevices, fallback_to_default)? {
+        DeviceChoice::Default => host.default_input_device().context("No default input device available"),
+        DeviceChoice::Named(name) => host
+            .input_devices()
             .context("Failed to get input devices")?
-            .find(|d| d.name().map(|n| n == device_name).unwrap_or(false))
-            .context("Specified recording device not found")
+            .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+            .context("Specified recording device not found"),
     }
 }
 
-pub fn save_audio_to_wav(rx: mpsc::Receiver<i16>, file_path: &str, config: &cpal::StreamConfig) -> Result<()> {
-    // Setup WAV writer
-    let spec = WavSpec {
-        channels: config.channels,
-        sample_rate: config.sample_rate.0,
-        bits_per_sample: 16,
-        sample_format: SampleFormat::Int,
-    };
+/// Drains the capture channel into a WAV file, converted for Whisper at
+/// `target_sample_rate` (0 keeps the stream's own format)
+pub fn save_audio_to_wav(
+    rx: mpsc::Receiver<i16>,
+    file_path: &str,
+    config: &cpal::StreamConfig,
+    target_sample_rate: u32,
+) -> Result<()> {
+    let captured: Vec<i16> = rx.iter().collect();
+    let (samples, spec) = convert_for_upload(
+        &captured,
+        WavSpec {
+            channels: config.channels,
+            sample_rate: config.sample_rate.0,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        },
+        target_sample_rate,
+    );
+
     let mut writer = WavWriter::create(file_path, spec)
         .with_context(|| format!("Failed to create WAV file at {}", file_path))?;
-
-    while let Ok(sample) = rx.recv() {
+    for sample in samples {
         writer.write_sample(sample)
             .context("Failed to write audio sample to WAV")?;
     }
@@ -81,28 +776,237 @@ pub fn save_audio_to_wav(rx: mpsc::Receiver<i16>, file_path: &str, config: &cpal
     info!("Audio recording saved to {}", file_path);
     Ok(())
 }
+
+/// Downmixes to mono and linearly resamples to Whisper's 16 kHz
+pub fn resample_to_whisper(samples: &[i16], src_rate: u32, src_channels: u16) -> Vec<i16> {
+    resample_linear(&downmix_to_mono(samples, src_channels), 1, src_rate, WHISPER_SAMPLE_RATE)
+}
+
+/// Converts a recording to mono at `target_sample_rate` for upload. A target of
+/// 0 leaves the samples untouched.
+pub fn convert_for_upload(samples: &[i16], spec: WavSpec, target_sample_rate: u32) -> (Vec<i16>, WavSpec) {
+    if target_sample_rate == 0 {
+        return (samples.to_vec(), spec);
+    }
+    let mono = downmix_to_mono(samples, spec.channels);
+    let resampled = resample_linear(&mono, 1, spec.sample_rate, target_sample_rate);
+    (
+        resampled,
+        WavSpec {
+            channels: 1,
+            sample_rate: target_sample_rate,
+            ..spec
+        },
+    )
+}
+/// Linearly resamples interleaved samples from `from_rate` to `to_rate`
+pub fn resample_linear(samples: &[i16], channels: u16, from_rate: u32, to_rate: u32) -> Vec<i16> {
+    let channels = channels.max(1) as usize;
+    if from_rate == to_rate || samples.len() < channels {
+        return samples.to_vec();
+    }
+
+    let in_frames = samples.len() / channels;
+    let out_frames = ((in_frames as u64 * to_rate as u64) / from_rate as u64).max(1) as usize;
+    let step = from_rate as f64 / to_rate as f64;
+
+    let mut out = Vec::with_capacity(out_frames * channels);
+    for frame in 0..out_frames {
+        let position = frame as f64 * step;
+        let index = (position as usize).min(in_frames - 1);
+        let next = (index + 1).min(in_frames - 1);
+        let fraction = position - index as f64;
+        for channel in 0..channels {
+            let a = samples[index * channels + channel] as f64;
+            let b = samples[next * channels + channel] as f64;
+            out.push((a + (b - a) * fraction).round() as i16);
+        }
+    }
+    out
+}
+
+/// Decides the upload format once the endpoint is known. With
+/// `transcription.resample_for_hosted_only`, hosted uploads are downsampled to
+/// 16 kHz to save bandwidth while the local endpoint keeps native quality.
+pub fn prepare_for_endpoint(
+    samples: &[i16],
+    spec: WavSpec,
+    endpoint: EndpointKind,
+    resample_for_hosted_only: bool,
+) -> (Vec<i16>, WavSpec) {
+    let downsample = resample_for_hosted_only
+        && endpoint == EndpointKind::Hosted
+        && spec.sample_rate > WHISPER_SAMPLE_RATE;
+    if !downsample {
+        return (samples.to_vec(), spec);
+    }
+
+    info!("Resampling {} Hz audio to {} Hz for the hosted endpoint", spec.sample_rate, WHISPER_SAMPLE_RATE);
+    let resampled = resample_linear(samples, spec.channels, spec.sample_rate, WHISPER_SAMPLE_RATE);
+    (
+        resampled,
+        WavSpec {
+            sample_rate: WHISPER_SAMPLE_RATE,
+            ..spec
+        },
+    )
+}
+
+/// A finished recording, ready to upload
+#[derive(Debug)]
+pub enum CapturedAudio {
+    /// Temporary WAV file; the caller removes it after upload
+    File(PathBuf),
+    /// WAV bytes that never touched the disk, the default
+    Memory(Vec<u8>),
+}
+
+/// Encodes samples as a complete WAV file held in memory
+pub fn encode_wav_to_memory(samples: &[i16], spec: WavSpec) -> Result<Vec<u8>> {
+    let mut buffer = Cursor::new(Vec::new());
+    let mut writer = WavWriter::new(&mut buffer, spec).context("Failed to start in-memory WAV")?;
+    for &sample in samples {
+        writer.write_sample(sample).context("Failed to write audio sample to WAV")?;
+    }
+    writer.finalize().context("Failed to finalize in-memory WAV")?;
+    Ok(buffer.into_inner())
+}
+
+/// Temporary WAVs written by `store_recording` that have not been removed yet
+#[derive(Debug, Default)]
+pub struct TempRecordings {
+    paths: Mutex<Vec<PathBuf>>,
+}
+
+/// Every temporary recording of this process, so a shutdown can remove the
+/// ones still waiting for upload
+pub static TEMP_RECORDINGS: TempRecordings = TempRecordings::new();
+
+impl TempRecordings {
+    pub const fn new() -> Self {
+        TempRecordings {
+            paths: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn register(&self, path: PathBuf) {
+        self.paths.lock().unwrap().push(path);
+    }
+
+    /// Deletes a recording once it is no longer needed. Failures are only logged.
+    pub fn remove(&self, path: &Path) {
+        self.paths.lock().unwrap().retain(|p| p != path);
+        if let Err(e) = std::fs::remove_file(path) {
+            warn!("Failed to remove temporary recording {}: {}", path.display(), e);
+        }
+    }
+
+    /// Deletes every recording still registered and returns how many there were
+    pub fn remove_all(&self) -> usize {
+        let paths = std::mem::take(&mut *self.paths.lock().unwrap());
+        for path in &paths {
+            if let Err(e) = std::fs::remove_file(path) {
+                warn!("Failed to remove temporary recording {}: {}", path.display(), e);
+            }
+        }
+        paths.len()
+    }
+}
+
+/// Stores a recording for upload: in memory when `in_memory` is set,
+/// otherwise as a uniquely named WAV in `temp_dir`, registered with
+/// `TEMP_RECORDINGS`
+pub fn store_recording(
+    samples: &[i16],
+    spec: WavSpec,
+    in_memory: bool,
+    temp_dir: &Path,
+) -> Result<CapturedAudio> {
+    if in_memory {
+        return Ok(CapturedAudio::Memory(encode_wav_to_memory(samples, spec)?));
+    }
+
+    let (file, path) = tempfile::Builder::new()
+        .prefix("rusty_scribe_")
+        .suffix(".wav")
+        .tempfile_in(temp_dir)
+        .with_context(|| format!("Failed to create WAV file in {}", temp_dir.display()))?
+        .keep()
+        .context("Failed to keep temporary WAV file")?;
+    TEMP_RECORDINGS.register(path.clone());
+    let mut writer = WavWriter::new(BufWriter::new(file), spec)
+        .with_context(|| format!("Failed to create WAV file at {}", path.display()))?;
+    for &sample in samples {
+        writer.write_sample(sample).context("Failed to write audio sample to WAV")?;
+    }
+    writer.finalize().context("Failed to finalize WAV file")?;
+    Ok(CapturedAudio::File(path))
+}
+
+/// Conversion of a device sample to the 16-bit PCM written to the WAV
+pub trait ToI16Sample: Copy {
+    fn to_i16_sample(self) -> i16;
+}
+
+impl ToI16Sample for i16 {
+    fn to_i16_sample(self) -> i16 {
+        self
+    }
+}
+
+impl ToI16Sample for f32 {
+    /// Full scale is [-1.0, 1.0]; anything louder is clipped
+    fn to_i16_sample(self) -> i16 {
+        (self.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+    }
+}
+
+impl ToI16Sample for u16 {
+    /// Unsigned samples are centred on 32768
+    fn to_i16_sample(self) -> i16 {
+        (self as i32 - 32768) as i16
+    }
+}
+
+/// Converts a buffer of device samples to 16-bit PCM
+pub fn convert_samples<T: ToI16Sample>(data: &[T]) -> Vec<i16> {
+    data.iter().map(|&sample| sample.to_i16_sample()).collect()
+}
+
 /// Helper function to build an input stream
 fn build_stream<T>(
     device: &cpal::Device,
     config: &cpal::StreamConfig,
+    profile: Option<DeviceProfile>,
+    channel_selection: Vec<u16>,
+    captured: Arc<AtomicUsize>,
     tx: Sender<i16>,
+    errors: Sender<cpal::StreamError>,
 ) -> Result<cpal::Stream>
 where
-    T: cpal::Sample + NoUninit + SizedSample
+    T: cpal::Sample + SizedSample + ToI16Sample
 {
+    let channels = config.channels;
     device.build_input_stream(
         config,
         move |data: &[T], _: &cpal::InputCallbackInfo| {
-            let sample_i16: &[i16] = bytemuck::cast_slice::<T, i16>(data);
+            let raw = convert_samples(data);
+            let mut sample_i16 = select_channels(&raw, channels, &channel_selection);
+            if let Some(profile) = &profile {
+                apply_device_profile(&mut sample_i16, profile);
+            }
             for &sample in sample_i16.iter() {
                 if tx.send(sample).is_err() {
                     // Receiver disconnected
                     break;
                 }
+                captured.fetch_add(1, Ordering::SeqCst);
             }
         },
         move |err| {
             error!("An error occurred on the input stream: {}", err);
+            // Nobody listens once the recording is over
+            let _ = errors.send(err);
         },
         None,
     ).context("Failed to build input stream")
@@ -111,8 +1015,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempfile::tempdir;
-    use std::fs;
+    use std::cell::Cell;
 
     #[test]
     fn test_parse_audio_devices() {
@@ -131,23 +1034,577 @@ mod tests {
         let (sender, receiver) = std::sync::mpsc::channel::<i16>();
 
         // Increase the duration to ensure we get a complete number of samples
-        let result = record_audio("default", 2, sender);
+        let result = record_audio("default", &[], &[], 2, sender);
         if let Err(e) = &result {
             eprintln!("Error recording audio: {:?}", e);
         }
         assert!(result.is_ok());
 
-        // Check that we received some data
+        // Check that we received some data, exactly as much as was reported
         let received: Vec<i16> = receiver.iter().collect();
         assert!(!received.is_empty());
+        let summary = result.unwrap();
+        assert_eq!(summary.samples, received.len());
+        let frames = received.len() / summary.spec.channels as usize;
+        assert_eq!(
+            summary.duration(),
+            Duration::from_secs_f64(frames as f64 / summary.spec.sample_rate as f64)
+        );
 
         // No need for cleanup as we're using in-memory buffer
     }
 
+    #[test]
+    fn test_capture_summary_duration_counts_frames() {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let summary = CaptureSummary { samples: 48000, spec };
+        assert_eq!(summary.duration(), Duration::from_millis(1500));
+        // A trailing partial frame is not audio yet
+        assert_eq!(CaptureSummary { samples: 48001, spec }.duration(), Duration::from_millis(1500));
+    }
+
     #[test]
     fn test_record_audio_invalid_device() {
         let (sender, _) = std::sync::mpsc::channel::<i16>();
-        let result = record_audio("InvalidDeviceName", 1, sender);
+        let result = record_audio("InvalidDeviceName", &[], &[], 1, sender);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_empty_capture_aborts_without_retry() {
+        let attempts = Cell::new(0);
+        let result = record_with_empty_retry(false, || {
+            attempts.set(attempts.get() + 1);
+            Ok(Vec::new())
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[test]
+    fn test_empty_capture_retries_once() {
+        let attempts = Cell::new(0);
+        let result = record_with_empty_retry(true, || {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() == 1 {
+                Ok(Vec::new())
+            } else {
+                Ok(vec![1; MIN_CAPTURED_SAMPLES])
+            }
+        });
+        assert_eq!(result.unwrap().len(), MIN_CAPTURED_SAMPLES);
+        assert_eq!(attempts.get(), 2);
+    }
+
+    #[test]
+    fn test_repeated_empty_capture_errors_after_retry() {
+        let attempts = Cell::new(0);
+        let result = record_with_empty_retry(true, || {
+            attempts.set(attempts.get() + 1);
+            Ok(vec![0; 3])
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 2);
+    }
+
+    fn profile(name: &str) -> DeviceProfile {
+        DeviceProfile {
+            name: name.to_string(),
+            gain: None,
+            noise_gate_threshold: None,
+            channels: None,
+        }
+    }
+
+    #[test]
+    fn test_find_device_profile_prefers_exact_match() {
+        let profiles = vec![profile("USB"), profile("Blue Yeti USB")];
+
+        let found = find_device_profile(&profiles, "Blue Yeti USB").unwrap();
+        assert_eq!(found.name, "Blue Yeti USB");
+
+        let found = find_device_profile(&profiles, "Generic usb Microphone").unwrap();
+        assert_eq!(found.name, "USB");
+
+        assert!(find_device_profile(&profiles, "Built-in Microphone").is_none());
+    }
+
+    #[test]
+    fn test_apply_device_profile_gain_and_gate() {
+        let mut laptop = profile("Built-in");
+        laptop.gain = Some(2.0);
+        laptop.noise_gate_threshold = Some(0.01);
+
+        let mut samples = vec![100, -200, 1000, -1000, 20000];
+        apply_device_profile(&mut samples, &laptop);
+
+        // 100 and -200 fall under the ~327 gate, 20000 clips at i16::MAX
+        assert_eq!(samples, vec![0, 0, 2000, -2000, i16::MAX]);
+    }
+
+    #[test]
+    fn test_wait_for_stop_keeps_capturing_for_grace_window() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel::<Instant>();
+
+        // Stand-in for the stream callback: keeps delivering until the channel closes
+        let capture = std::thread::spawn(move || {
+            while tx.send(Instant::now()).is_ok() {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        });
+
+        let stop_clone = Arc::clone(&stop);
+        let signaller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(30));
+            let stopped_at = Instant::now();
+            stop_clone.store(true, Ordering::SeqCst);
+            stopped_at
+        });
+
+        let grace = Duration::from_millis(100);
+        wait_for_stop(&stop, grace, None);
+        let stopped_at = signaller.join().unwrap();
+        let captured: Vec<Instant> = rx.try_iter().collect();
+        drop(rx);
+        capture.join().unwrap();
+
+        assert!(stopped_at.elapsed() >= grace);
+        let after_stop = captured.iter().filter(|&&t| t > stopped_at).count();
+        assert!(after_stop > 0, "no samples captured after the stop signal");
+    }
+
+    #[test]
+    fn test_wait_for_stop_without_grace_returns_promptly() {
+        let stop = AtomicBool::new(true);
+        let start = Instant::now();
+        wait_for_stop(&stop, Duration::ZERO, None);
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_wait_for_stop_ends_at_limit() {
+        // Nothing ever raises the signal, as with a stuck hotkey
+        let stop = AtomicBool::new(false);
+        let start = Instant::now();
+        wait_for_stop(&stop, Duration::from_millis(500), Some(Duration::from_millis(30)));
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(30));
+        assert!(elapsed < Duration::from_millis(200), "stopped after {:?}", elapsed);
+        assert!(stop.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_short_press_waits_for_first_sample() {
+        // Hotkey already released before the device delivered anything
+        let stop = AtomicBool::new(true);
+        let captured = Arc::new(AtomicUsize::new(0));
+        let device = Arc::clone(&captured);
+        let start = Instant::now();
+        let delivery = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(40));
+            device.fetch_add(1, Ordering::SeqCst);
+        });
+
+        wait_for_stop(&stop, Duration::ZERO, None);
+        assert!(wait_for_first_sample(&captured, FIRST_SAMPLE_TIMEOUT));
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        delivery.join().unwrap();
+
+        // A single sample still makes a valid WAV
+        let wav = encode_wav_to_memory(&[1234], mono_spec()).unwrap();
+        let reader = hound::WavReader::new(Cursor::new(wav)).unwrap();
+        assert_eq!(reader.len(), 1);
+    }
+
+    #[test]
+    fn test_encode_wav_to_memory_writes_a_valid_header() {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let wav = encode_wav_to_memory(&[1, -1, 2, -2, 3, -3], spec).unwrap();
+
+        let u16_at = |offset: usize| u16::from_le_bytes([wav[offset], wav[offset + 1]]);
+        let u32_at = |offset: usize| u32::from_le_bytes(wav[offset..offset + 4].try_into().unwrap());
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32_at(4) as usize, wav.len() - 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u16_at(20), 1, "PCM");
+        assert_eq!(u16_at(22), 2);
+        assert_eq!(u32_at(24), 44100);
+        assert_eq!(u32_at(28), 44100 * 2 * 2, "byte rate");
+        assert_eq!(u16_at(34), 16);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(u32_at(40), 12);
+        assert_eq!(wav.len(), 44 + 12);
+    }
+
+    #[test]
+    fn test_wait_for_first_sample_gives_up() {
+        let captured = AtomicUsize::new(0);
+        assert!(!wait_for_first_sample(&captured, Duration::from_millis(30)));
+    }
+
+    #[test]
+    fn test_normalize_samples_reaches_target_peak() {
+        let mut samples = vec![0, 1000, -2000, 500];
+        normalize_samples(&mut samples, NORMALIZE_TARGET_DBFS);
+
+        // -3 dBFS is about 70.8% of full scale
+        let peak = samples.iter().map(|&s| (s as i32).abs()).max().unwrap();
+        assert!((23190..=23200).contains(&peak), "peak {}", peak);
+        assert_eq!(samples[2], -peak as i16);
+        assert!((samples[1] as i32 - peak / 2).abs() <= 1);
+    }
+
+    #[test]
+    fn test_normalize_samples_leaves_silence_untouched() {
+        let mut silence = vec![0i16; 64];
+        normalize_samples(&mut silence, NORMALIZE_TARGET_DBFS);
+        assert!(silence.iter().all(|&s| s == 0));
+
+        // Faint hiss below the noise floor is not amplified either
+        let mut hiss = vec![3, -5, 4, -2];
+        normalize_samples(&mut hiss, NORMALIZE_TARGET_DBFS);
+        assert_eq!(hiss, vec![3, -5, 4, -2]);
+    }
+
+    #[test]
+    fn test_pipeline_order_gate_then_normalize() {
+        let samples = vec![1000, 100, -2000, 50];
+
+        // Gating first removes the quiet samples before they are amplified
+        let gate_first = AudioPipeline::new(vec![
+            AudioStep::NoiseGate { threshold: 0.01 },
+            AudioStep::Normalize { peak: 1.0 },
+        ]);
+        let (gated, _) = gate_first.apply(samples.clone(), mono_spec());
+        assert_eq!(gated, vec![16383, 0, -32767, 0]);
+
+        // Normalizing first lifts them above the gate
+        let normalize_first = AudioPipeline::new(vec![
+            AudioStep::Normalize { peak: 1.0 },
+            AudioStep::NoiseGate { threshold: 0.01 },
+        ]);
+        let (normalized, _) = normalize_first.apply(samples, mono_spec());
+        assert_eq!(normalized, vec![16383, 1638, -32767, 819]);
+    }
+
+    #[test]
+    fn test_pipeline_trim_downmix_and_resample_update_spec() {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 32000,
+            ..mono_spec()
+        };
+        let pipeline = AudioPipeline::new(vec![
+            AudioStep::TrimSilence { threshold: 0.01 },
+            AudioStep::Downmix,
+            AudioStep::Resample { rate: 16000 },
+        ]);
+        // Two silent frames on each side of four loud ones
+        let samples = vec![0, 0, 0, 0, 1000, 3000, 2000, 2000, 1000, 1000, 4000, 0, 0, 0, 0, 0];
+
+        let (out, out_spec) = pipeline.apply(samples, spec);
+        assert_eq!(out_spec.channels, 1);
+        assert_eq!(out_spec.sample_rate, 16000);
+        assert_eq!(out, vec![2000, 1000]);
+    }
+
+    #[test]
+    fn test_resample_to_whisper_48k_stereo_length() {
+        // One second of 48 kHz stereo: 48000 frames of two samples
+        let samples: Vec<i16> = (0..96_000).map(|i| (i % 200) as i16).collect();
+        let resampled = resample_to_whisper(&samples, 48_000, 2);
+        assert_eq!(resampled.len(), 16_000);
+
+        let quarter_second = &samples[..24_000];
+        assert_eq!(resample_to_whisper(quarter_second, 48_000, 2).len(), 4_000);
+    }
+
+    #[test]
+    fn test_convert_for_upload_respects_target_rate() {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 44_100,
+            ..mono_spec()
+        };
+        let samples = vec![100i16; 88_200];
+
+        let (converted, converted_spec) = convert_for_upload(&samples, spec, 16_000);
+        assert_eq!(converted.len(), 16_000);
+        assert_eq!((converted_spec.channels, converted_spec.sample_rate), (1, 16_000));
+
+        let (kept, kept_spec) = convert_for_upload(&samples, spec, 0);
+        assert_eq!((kept.len(), kept_spec), (88_200, spec));
+    }
+
+    #[test]
+    fn test_silence_detector_stops_after_trailing_silence() {
+        // 1 kHz mono: 20-sample windows, 100 ms timeout = 100 samples
+        let mut detector = SilenceDetector::new(0.01, Duration::from_millis(100), 1000, 1);
+        let silence = vec![0i16; 20];
+        let speech: Vec<i16> = (0..20).map(|i| if i % 2 == 0 { 3000 } else { -3000 }).collect();
+
+        // A long lead-in of silence never triggers
+        for _ in 0..50 {
+            assert!(!detector.push(&silence));
+        }
+        assert!(!detector.push(&speech));
+        for _ in 0..4 {
+            assert!(!detector.push(&silence));
+        }
+        // Speech again resets the countdown
+        assert!(!detector.push(&speech));
+        for _ in 0..4 {
+            assert!(!detector.push(&silence));
+        }
+        assert!(detector.push(&silence));
+    }
+
+    #[test]
+    fn test_silence_detector_disabled_with_zero_timeout() {
+        let audio: AudioSettings = toml::from_str("recording_device = \"default\"").unwrap();
+        assert!(SilenceDetector::from_settings(&audio, mono_spec()).is_none());
+
+        let audio = AudioSettings {
+            silence_timeout_ms: 800,
+            ..audio
+        };
+        assert!(SilenceDetector::from_settings(&audio, mono_spec()).is_some());
+    }
+
+    #[test]
+    fn test_convert_f32_samples() {
+        assert_eq!(
+            convert_samples(&[0.0f32, 1.0, -1.0, 0.5, -0.25, 1.5, -2.0]),
+            vec![0, 32767, -32767, 16383, -8191, 32767, -32767]
+        );
+    }
+
+    #[test]
+    fn test_convert_u16_samples() {
+        assert_eq!(
+            convert_samples(&[32768u16, 0, 65535, 49152, 16384]),
+            vec![0, -32768, 32767, 16384, -16384]
+        );
+    }
+
+    #[test]
+    fn test_convert_i16_samples_is_a_copy() {
+        let samples = [0i16, i16::MIN, i16::MAX, -5, 5];
+        assert_eq!(convert_samples(&samples), samples.to_vec());
+    }
+
+    #[test]
+    fn test_select_channels_from_interleaved_frames() {
+        // Four-channel frames: [ch0, ch1, ch2, ch3]
+        let interleaved = vec![
+            100, 9000, -50, 7,
+            200, 9000, -60, 7,
+            300, 9000, -70, 7,
+        ];
+
+        assert_eq!(select_channels(&interleaved, 4, &[0]), vec![100, 200, 300]);
+        assert_eq!(select_channels(&interleaved, 4, &[0, 2]), vec![25, 70, 115]);
+        assert_eq!(select_channels(&interleaved, 4, &[]), interleaved);
+        assert_eq!(captured_channels(4, &[0, 2]), 1);
+        assert_eq!(captured_channels(4, &[]), 4);
+    }
+
+    #[test]
+    fn test_channel_selection_out_of_range() {
+        assert!(validate_channel_selection(&[0, 7], 8).is_ok());
+        let err = validate_channel_selection(&[2], 2).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "audio.channel_selection includes channel 2, but the device only has 2 channels (0-1)"
+        );
+    }
+
+    fn mono_spec() -> WavSpec {
+        WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        }
+    }
+
+    #[test]
+    fn test_memory_only_recording_creates_no_temp_file() {
+        let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let samples: Vec<i16> = (0..1600).map(|i| (i % 100) as i16).collect();
+
+        let captured = store_recording(&samples, mono_spec(), true, temp_dir.path()).unwrap();
+
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+        let wav = match captured {
+            CapturedAudio::Memory(wav) => wav,
+            CapturedAudio::File(path) => panic!("memory_only wrote {}", path.display()),
+        };
+        let reader = hound::WavReader::new(Cursor::new(wav)).expect("Invalid in-memory WAV");
+        assert_eq!(reader.spec(), mono_spec());
+        let decoded: Vec<i16> = reader.into_samples().map(|s| s.unwrap()).collect();
+        assert_eq!(decoded, samples);
+    }
+
+    #[test]
+    fn test_disk_recording_writes_temp_wav() {
+        let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let captured = store_recording(&[1, 2, 3], mono_spec(), false, temp_dir.path()).unwrap();
+
+        match captured {
+            CapturedAudio::File(path) => assert!(path.starts_with(temp_dir.path()) && path.exists()),
+            CapturedAudio::Memory(_) => panic!("expected a temp file"),
+        }
+    }
+
+    #[test]
+    fn test_disk_recordings_get_unique_names() {
+        let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let first = store_recording(&[1], mono_spec(), false, temp_dir.path()).unwrap();
+        let second = store_recording(&[1], mono_spec(), false, temp_dir.path()).unwrap();
+        match (first, second) {
+            (CapturedAudio::File(first), CapturedAudio::File(second)) => {
+                assert_ne!(first, second);
+                TEMP_RECORDINGS.remove(&first);
+                TEMP_RECORDINGS.remove(&second);
+            }
+            _ => panic!("expected temp files"),
+        }
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_remove_all_deletes_pending_recordings() {
+        let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let recordings = TempRecordings::new();
+        let paths: Vec<PathBuf> = (0..3).map(|i| temp_dir.path().join(format!("{}.wav", i))).collect();
+        for path in &paths {
+            std::fs::write(path, b"RIFF").unwrap();
+            recordings.register(path.clone());
+        }
+
+        recordings.remove(&paths[0]);
+        assert!(!paths[0].exists());
+        assert_eq!(recordings.remove_all(), 2);
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+        assert_eq!(recordings.remove_all(), 0);
+    }
+
+    #[test]
+    fn test_hosted_selection_resamples_and_local_keeps_native() {
+        let spec = WavSpec {
+            sample_rate: 48000,
+            ..mono_spec()
+        };
+        let samples: Vec<i16> = (0..4800).map(|i| (i % 300) as i16).collect();
+
+        let (hosted, hosted_spec) = prepare_for_endpoint(&samples, spec, EndpointKind::Hosted, true);
+        assert_eq!(hosted_spec.sample_rate, WHISPER_SAMPLE_RATE);
+        assert_eq!(hosted.len(), 1600);
+
+        let (local, local_spec) = prepare_for_endpoint(&samples, spec, EndpointKind::Local, true);
+        assert_eq!(local_spec, spec);
+        assert_eq!(local, samples);
+
+        // Without the option nothing is resampled
+        let (unchanged, unchanged_spec) = prepare_for_endpoint(&samples, spec, EndpointKind::Hosted, false);
+        assert_eq!((unchanged.len(), unchanged_spec), (samples.len(), spec));
+    }
+
+    #[test]
+    fn test_resample_linear_interpolates_interleaved_channels() {
+        // Stereo ramp at 4 Hz: left rises, right falls
+        let samples = vec![0, 300, 100, 200, 200, 100, 300, 0];
+        assert_eq!(resample_linear(&samples, 2, 4, 2), vec![0, 300, 200, 100]);
+        assert_eq!(
+            resample_linear(&samples, 2, 4, 8),
+            vec![0, 300, 50, 250, 100, 200, 150, 150, 200, 100, 250, 50, 300, 0, 300, 0]
+        );
+    }
+
+    struct SwitchingDefault {
+        names: Vec<&'static str>,
+        calls: Cell<usize>,
+    }
+
+    impl InputDeviceSource for SwitchingDefault {
+        fn default_input_name(&self) -> Option<String> {
+            let index = self.calls.get().min(self.names.len() - 1);
+            self.calls.set(self.calls.get() + 1);
+            Some(self.names[index].to_string())
+        }
+
+        fn input_names(&self) -> Vec<String> {
+            self.names.iter().map(|name| name.to_string()).collect()
+        }
+    }
+
+    #[test]
+    fn test_device_selector_follows_default_change() {
+        let source = SwitchingDefault {
+            names: vec!["Built-in Microphone", "USB Headset"],
+            calls: Cell::new(0),
+        };
+        let mut selector = DeviceSelector::new(true);
+
+        assert_eq!(selector.select("default", &source).unwrap(), "Built-in Microphone");
+        assert_eq!(selector.select("default", &source).unwrap(), "USB Headset");
+    }
+
+    #[test]
+    fn test_device_selector_can_pin_startup_default() {
+        let source = SwitchingDefault {
+            names: vec!["Built-in Microphone", "USB Headset"],
+            calls: Cell::new(0),
+        };
+        let mut selector = DeviceSelector::new(false);
+
+        assert_eq!(selector.select("default", &source).unwrap(), "Built-in Microphone");
+        assert_eq!(selector.select("Default", &source).unwrap(), "Built-in Microphone");
+        assert_eq!(selector.select("Studio Mic", &source).unwrap(), "Studio Mic");
+        assert_eq!(source.calls.get(), 1);
+    }
+
+    #[test]
+    fn test_missing_device_falls_back_to_default_only_when_enabled() {
+        let source = SwitchingDefault {
+            names: vec!["Built-in Microphone"],
+            calls: Cell::new(0),
+        };
+
+        assert_eq!(
+            choose_input_device("Built-in Microphone", &source, false).unwrap(),
+            DeviceChoice::Named("Built-in Microphone".to_string())
+        );
+        assert_eq!(choose_input_device("Default", &source, false).unwrap(), DeviceChoice::Default);
+        assert!(choose_input_device("USB Microphone", &source, false).is_err());
+        assert_eq!(
+            choose_input_device("USB Microphone", &source, true).unwrap(),
+            DeviceChoice::Default
+        );
+    }
+
+    #[test]
+    fn test_unknown_device_error_lists_available_devices() {
+        let source = SwitchingDefault {
+            names: vec!["Built-in Microphone", "USB Headset"],
+            calls: Cell::new(0),
+        };
+
+        let message = choose_input_device("Studio Mic", &source, false).unwrap_err().to_string();
+        assert!(message.contains("'Studio Mic' not found"), "{}", message);
+        assert!(message.contains("'Built-in Microphone', 'USB Headset'"), "{}", message);
+    }
 }
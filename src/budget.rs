@@ -0,0 +1,151 @@
+use anyhow::Result;
+use log::warn;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::llm::LlmProvider;
+use crate::notify::Notifier;
+
+/// Caps the number of paid API calls made in one run of the daemon. Counts live
+/// in atomics so the budget can be shared across threads; they reset on restart.
+#[derive(Debug)]
+pub struct CallBudget {
+    service: &'static str,
+    limit: u64,
+    used: AtomicU64,
+    notified: AtomicBool,
+}
+
+impl CallBudget {
+    /// `limit` of 0 means unlimited
+    pub fn new(service: &'static str, limit: u64) -> Self {
+        CallBudget {
+            service,
+            limit,
+            used: AtomicU64::new(0),
+            notified: AtomicBool::new(false),
+        }
+    }
+
+    /// Reserves one call. Once the cap is reached this returns false, and the
+    /// first refusal notifies the user.
+    pub fn try_acquire(&self, notifier: &dyn Notifier) -> bool {
+        if self.limit == 0 {
+            self.used.fetch_add(1, Ordering::SeqCst);
+            return true;
+        }
+        let acquired = self
+            .used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| (used < self.limit).then_some(used + 1))
+            .is_ok();
+        if !acquired && !self.notified.swap(true, Ordering::SeqCst) {
+            warn!("{} call budget of {} per session is used up", self.service, self.limit);
+            notifier.notify(
+                "API call limit reached",
+                &format!(
+                    "{} has been called {} times this session. Further calls are skipped until rusty-scribe restarts.",
+                    self.service, self.limit
+                ),
+            );
+        }
+        acquired
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::SeqCst)
+    }
+}
+
+/// Per-session budgets from `llm.max_calls_per_session` and
+/// `transcription.max_calls_per_session`
+#[derive(Debug)]
+pub struct SessionBudgets {
+    pub llm: CallBudget,
+    pub transcription: CallBudget,
+}
+
+impl SessionBudgets {
+    pub fn new(llm_limit: u64, transcription_limit: u64) -> Self {
+        SessionBudgets {
+            llm: CallBudget::new("LLM", llm_limit),
+            transcription: CallBudget::new("Whisper", transcription_limit),
+        }
+    }
+}
+
+/// LLM provider that stops forwarding calls once the budget is spent. The
+/// error makes the pipeline fall back to the raw transcription.
+pub struct BudgetedProvider<'a> {
+    pub inner: &'a dyn LlmProvider,
+    pub budget: &'a CallBudget,
+    pub notifier: &'a dyn Notifier,
+}
+
+impl LlmProvider for BudgetedProvider<'_> {
+    fn complete(&self, prompt: &str, text: &str) -> Result<String> {
+        if !self.budget.try_acquire(self.notifier) {
+            return Err(anyhow::anyhow!("LLM call limit for this session reached"));
+        }
+        self.inner.complete(prompt, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Default)]
+    struct RecordingNotifier {
+        sent: RefCell<Vec<String>>,
+    }
+
+    impl Notifier for RecordingNotifier {
+        fn notify(&self, title: &str, _body: &str) {
+            self.sent.borrow_mut().push(title.to_string());
+        }
+    }
+
+    #[derive(Default)]
+    struct CountingProvider {
+        calls: Cell<u32>,
+    }
+
+    impl LlmProvider for CountingProvider {
+        fn complete(&self, _prompt: &str, text: &str) -> Result<String> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(text.to_uppercase())
+        }
+    }
+
+    #[test]
+    fn test_exceeding_cap_blocks_calls_and_notifies_once() {
+        let notifier = RecordingNotifier::default();
+        let budgets = SessionBudgets::new(2, 0);
+        let inner = CountingProvider::default();
+        let provider = BudgetedProvider {
+            inner: &inner,
+            budget: &budgets.llm,
+            notifier: &notifier,
+        };
+
+        assert_eq!(provider.complete("Clean up:", "one").unwrap(), "ONE");
+        assert_eq!(provider.complete("Clean up:", "two").unwrap(), "TWO");
+        assert!(provider.complete("Clean up:", "three").is_err());
+        assert!(provider.complete("Clean up:", "four").is_err());
+
+        assert_eq!(inner.calls.get(), 2);
+        assert_eq!(*notifier.sent.borrow(), vec!["API call limit reached".to_string()]);
+    }
+
+    #[test]
+    fn test_unlimited_budget_only_counts() {
+        let notifier = RecordingNotifier::default();
+        let budget = CallBudget::new("Whisper", 0);
+
+        for _ in 0..100 {
+            assert!(budget.try_acquire(&notifier));
+        }
+        assert_eq!(budget.used(), 100);
+        assert!(notifier.sent.borrow().is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+use log::{error, info, warn};
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use crate::audio::{record_audio, resample_to_whisper, WHISPER_SAMPLE_RATE};
+use crate::config::Config;
+use crate::device_check::{is_silent, SILENT_DEVICE_FLOOR};
+
+/// Peak normalized correlation above which the microphone is considered to
+/// pick up the speakers
+pub const ECHO_CORRELATION_THRESHOLD: f32 = 0.3;
+
+/// The reference tone sweeps this range so it correlates at one lag only
+const TONE_START_HZ: f32 = 500.0;
+const TONE_END_HZ: f32 = 3000.0;
+const TONE_DURATION: Duration = Duration::from_millis(500);
+const TONE_AMPLITUDE: f32 = 0.5;
+
+/// Recording starts this long before the tone and outlasts it, leaving room
+/// for output and input latency
+const TONE_LEAD_IN: Duration = Duration::from_millis(300);
+const ECHO_RECORDING_SECS: u64 = 2;
+
+/// Linear sweep from `TONE_START_HZ` to `TONE_END_HZ` at `sample_rate`
+pub fn reference_tone(sample_rate: u32) -> Vec<f32> {
+    let len = (TONE_DURATION.as_secs_f32() * sample_rate as f32) as usize;
+    let duration = TONE_DURATION.as_secs_f32();
+    let sweep_rate = (TONE_END_HZ - TONE_START_HZ) / duration;
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / sample_rate as f32;
+            let phase = 2.0 * PI * (TONE_START_HZ * t + 0.5 * sweep_rate * t * t);
+            TONE_AMPLITUDE * phase.sin()
+        })
+        .collect()
+}
+
+/// Largest normalized cross-correlation (0.0 to 1.0) between `reference` and
+/// any equally long window of `captured`. Scaling and a delay do not lower it;
+/// unrelated sound keeps it near zero.
+pub fn peak_correlation(reference: &[f32], captured: &[f32]) -> f32 {
+    if reference.is_empty() || captured.len() < reference.len() {
+        return 0.0;
+    }
+    let reference_energy: f32 = reference.iter().map(|s| s * s).sum();
+    if reference_energy == 0.0 {
+        return 0.0;
+    }
+
+    let mut window_energy: f32 = captured[..reference.len()].iter().map(|s| s * s).sum();
+    let mut peak: f32 = 0.0;
+    for lag in 0..=captured.len() - reference.len() {
+        if lag > 0 {
+            let left = captured[lag - 1];
+            let entered = captured[lag + reference.len() - 1];
+            window_energy = (window_energy - left * left + entered * entered).max(0.0);
+        }
+        if window_energy <= f32::EPSILON {
+            continue;
+        }
+        let window = &captured[lag..lag + reference.len()];
+        let dot: f32 = reference.iter().zip(window).map(|(r, c)| r * c).sum();
+        peak = peak.max(dot.abs() / (reference_energy * window_energy).sqrt());
+    }
+    peak.min(1.0)
+}
+
+/// Whether the microphone captured the reference tone
+pub fn is_echo(correlation: f32) -> bool {
+    correlation >= ECHO_CORRELATION_THRESHOLD
+}
+
+/// Plays `TONE_DURATION` of the reference sweep on the default output device
+fn play_reference_tone() -> Result<()> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .context("No default output device available")?;
+    let config = device.default_output_config().context("Failed to get default output config")?;
+    let sample_format = config.sample_format();
+    let config: cpal::StreamConfig = config.into();
+
+    let tone = Arc::new(reference_tone(config.sample_rate.0));
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => build_tone_stream::<f32>(&device, &config, tone)?,
+        cpal::SampleFormat::I16 => build_tone_stream::<i16>(&device, &config, tone)?,
+        cpal::SampleFormat::U16 => build_tone_stream::<u16>(&device, &config, tone)?,
+        _ => return Err(anyhow::anyhow!("Unsupported output sample format")),
+    };
+    stream.play().context("Failed to start output stream")?;
+    std::thread::sleep(TONE_DURATION + Duration::from_millis(100));
+    Ok(())
+}
+
+fn build_tone_stream<T>(device: &cpal::Device, config: &cpal::StreamConfig, tone: Arc<Vec<f32>>) -> Result<cpal::Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    let position = AtomicUsize::new(0);
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                for frame in data.chunks_mut(channels) {
+                    let index = position.fetch_add(1, Ordering::Relaxed);
+                    let value = tone.get(index).copied().unwrap_or(0.0);
+                    frame.fill(T::from_sample(value));
+                }
+            },
+            move |err| error!("An error occurred on the output stream: {}", err),
+            None,
+        )
+        .context("Failed to build output stream")
+}
+
+/// Records while playing the reference tone and returns the peak correlation
+/// between the two
+pub fn measure_echo(device_name: &str) -> Result<f32> {
+    let (tx, rx) = mpsc::channel();
+    let device_name = device_name.to_string();
+    let recorder = std::thread::spawn(move || record_audio(&device_name, &[], &[], ECHO_RECORDING_SECS, tx));
+
+    std::thread::sleep(TONE_LEAD_IN);
+    play_reference_tone()?;
+
+    let summary = recorder
+        .join()
+        .map_err(|_| anyhow::anyhow!("Recording thread panicked"))??;
+    let samples: Vec<i16> = rx.try_iter().collect();
+    let captured: Vec<f32> = resample_to_whisper(&samples, summary.spec.sample_rate, summary.spec.channels)
+        .into_iter()
+        .map(|s| s as f32 / i16::MAX as f32)
+        .collect();
+    Ok(peak_correlation(&reference_tone(WHISPER_SAMPLE_RATE), &captured))
+}
+
+/// `rusty-scribe calibrate`: checks that the recording device hears something
+/// and, with `echo_test`, that it does not hear the speakers
+pub fn calibrate(config: &Config, echo_test: bool) -> Result<()> {
+    let device = &config.audio.recording_device;
+    let (tx, rx) = mpsc::channel();
+    let summary = record_audio(device, &[], &[], 1, tx)?;
+    let samples: Vec<i16> = rx.try_iter().collect();
+    let peak = samples.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0) as f32 / i16::MAX as f32;
+    println!(
+        "Recorded {:.2} s from '{}', peak level {:.1}%",
+        summary.duration().as_secs_f64(),
+        device,
+        peak * 100.0
+    );
+    if is_silent(&samples, SILENT_DEVICE_FLOOR) {
+        warn!("The recording device produced only silence; it may be muted or misrouted");
+    }
+
+    if echo_test {
+        println!("Playing a test tone on the default output...");
+        let correlation = measure_echo(device)?;
+        if is_echo(correlation) {
+            warn!(
+                "The microphone picks up the speakers (correlation {:.2}). Use headphones or disable loopback/monitoring, or meeting audio will end up in transcriptions.",
+                correlation
+            );
+        } else {
+            info!("No echo from the speakers detected (correlation {:.2})", correlation);
+        }
+        println!("Echo correlation: {:.2}", correlation);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic noise in -1.0..1.0
+    fn noise(len: usize, seed: u32) -> Vec<f32> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_delayed_attenuated_tone_correlates() {
+        let reference = reference_tone(WHISPER_SAMPLE_RATE);
+        let delay = 2400;
+        let mut captured: Vec<f32> = noise(reference.len() + 8000, 7).iter().map(|n| n * 0.05).collect();
+        for (i, sample) in reference.iter().enumerate() {
+            captured[delay + i] += sample * 0.2;
+        }
+
+        let correlation = peak_correlation(&reference, &captured);
+        assert!(correlation > 0.8, "correlation {}", correlation);
+        assert!(is_echo(correlation));
+    }
+
+    #[test]
+    fn test_unrelated_sound_does_not_correlate() {
+        let reference = reference_tone(WHISPER_SAMPLE_RATE);
+        let captured = noise(reference.len() + 8000, 42);
+
+        let correlation = peak_correlation(&reference, &captured);
+        assert!(correlation < 0.1, "correlation {}", correlation);
+        assert!(!is_echo(correlation));
+        assert_eq!(peak_correlation(&reference, &vec![0.0; reference.len() * 2]), 0.0);
+    }
+}
@@ -0,0 +1,302 @@
+use anyhow::{Context, Result};
+use hound::{SampleFormat, WavSpec};
+use log::{info, warn};
+use std::fs;
+use std::io::Write;
+use std::sync::mpsc;
+
+use crate::api::{is_local_endpoint_available, transcribe_audio_bytes, RECORDING_FILE_NAME};
+use crate::audio::{encode_wav_to_memory, resample_to_whisper, LiveCapture, WHISPER_SAMPLE_RATE};
+use crate::config::{CaptionSettings, Config, ResponseFormat};
+use crate::text::discard_hallucination;
+
+/// Longest run of words compared when looking for the overlap between windows
+const MAX_OVERLAP_WORDS: usize = 30;
+
+/// A window may start in the middle of a word, which Whisper then transcribes
+/// as a fragment; up to this many leading words may be skipped to find the overlap
+const MAX_SKIPPED_WORDS: usize = 2;
+
+/// Cuts a continuous stream of interleaved samples into windows of
+/// `window_secs`, starting a new one every `step_secs`
+#[derive(Debug)]
+pub struct SlidingWindows {
+    window: usize,
+    step: usize,
+    buffer: Vec<i16>,
+}
+
+impl SlidingWindows {
+    pub fn new(spec: WavSpec, settings: &CaptionSettings) -> Result<Self> {
+        if settings.window_secs == 0 || settings.step_secs == 0 || settings.step_secs > settings.window_secs {
+            return Err(anyhow::anyhow!(
+                "captions.step_secs ({}) must be between 1 and captions.window_secs ({})",
+                settings.step_secs,
+                settings.window_secs
+            ));
+        }
+        let frame = spec.sample_rate as usize * spec.channels.max(1) as usize;
+        Ok(SlidingWindows {
+            window: settings.window_secs as usize * frame,
+            step: settings.step_secs as usize * frame,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Adds captured samples and returns every window completed by them
+    pub fn push(&mut self, samples: &[i16]) -> Vec<Vec<i16>> {
+        self.buffer.extend_from_slice(samples);
+        let mut windows = Vec::new();
+        while self.buffer.len() >= self.window {
+            windows.push(self.buffer[..self.window].to_vec());
+            self.buffer.drain(..self.step);
+        }
+        windows
+    }
+}
+
+/// Case and punctuation differ between windows, so words are compared by this
+fn word_key(word: &str) -> String {
+    word.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// Words of `next` that `previous` does not already end with. The overlap is
+/// the longest run of trailing words of `previous` that `next` starts with,
+/// allowing for a few cut-off fragments in front of it.
+pub fn new_words<'a>(previous: &[String], next: &'a [&'a str]) -> &'a [&'a str] {
+    let previous: Vec<String> = previous.iter().map(|w| word_key(w)).collect();
+    let next_keys: Vec<String> = next.iter().map(|w| word_key(w)).collect();
+
+    let mut best: Option<(usize, usize)> = None;
+    for skip in 0..=MAX_SKIPPED_WORDS.min(next.len()) {
+        let longest = previous.len().min(next.len() - skip).min(MAX_OVERLAP_WORDS);
+        // A single word after a skipped fragment matches by chance too easily
+        let shortest = if skip == 0 { 1 } else { 2 };
+        let found = (shortest..=longest)
+            .rev()
+            .find(|&len| previous[previous.len() - len..] == next_keys[skip..skip + len]);
+        if let Some(len) = found {
+            if best.is_none_or(|(_, best_len)| len > best_len) {
+                best = Some((skip, len));
+            }
+        }
+    }
+    match best {
+        Some((skip, len)) => &next[skip + len..],
+        None => next,
+    }
+}
+
+/// Transcript assembled from overlapping windows, shown as a caption of its
+/// last few words
+#[derive(Debug)]
+pub struct RollingCaption {
+    words: Vec<String>,
+    max_chars: usize,
+}
+
+impl RollingCaption {
+    pub fn new(max_chars: usize) -> Self {
+        RollingCaption {
+            words: Vec::new(),
+            max_chars,
+        }
+    }
+
+    /// Adds a window's transcription, dropping the part that repeats the end
+    /// of the transcript. Returns whether anything new was added.
+    pub fn update(&mut self, window_text: &str) -> bool {
+        let words: Vec<&str> = window_text.split_whitespace().collect();
+        let added = new_words(&self.words, &words);
+        self.words.extend(added.iter().map(|w| w.to_string()));
+        !added.is_empty()
+    }
+
+    /// Everything transcribed so far
+    pub fn transcript(&self) -> String {
+        self.words.join(" ")
+    }
+
+    /// The trailing words that fit in `max_chars`; the last word is always shown
+    pub fn caption(&self) -> String {
+        let mut len = 0;
+        let mut start = self.words.len();
+        for (i, word) in self.words.iter().enumerate().rev() {
+            let with_word = len + word.chars().count() + usize::from(len > 0);
+            if with_word > self.max_chars && start < self.words.len() {
+                break;
+            }
+            len = with_word;
+            start = i;
+        }
+        self.words[start..].join(" ")
+    }
+}
+
+/// Feeds blocks of captured samples through `windows`, transcribes each
+/// completed window and emits the caption whenever it changes. A failed window
+/// is logged and skipped so captions keep running.
+pub fn caption_stream<I, T, E>(
+    blocks: I,
+    windows: &mut SlidingWindows,
+    caption: &mut RollingCaption,
+    mut transcribe: T,
+    mut emit: E,
+) where
+    I: IntoIterator<Item = Vec<i16>>,
+    T: FnMut(&[i16]) -> Result<String>,
+    E: FnMut(&str),
+{
+    for block in blocks {
+        for window in windows.push(&block) {
+            match transcribe(&window) {
+                Ok(text) => {
+                    if caption.update(&text) {
+                        emit(&caption.caption());
+                    }
+                }
+                Err(e) => warn!("Failed to transcribe caption window: {:?}", e),
+            }
+        }
+    }
+}
+
+/// Shows the caption on the terminal, redrawing one line, and writes it to
+/// `captions.output_file` if set
+fn show_caption(settings: &CaptionSettings, caption: &str) {
+    print!("\r\x1b[2K{}", caption);
+    let _ = std::io::stdout().flush();
+    if let Some(path) = &settings.output_file {
+        if let Err(e) = fs::write(path, caption) {
+            warn!("Failed to write caption to {}: {}", path, e);
+        }
+    }
+}
+
+/// `rusty-scribe captions`: transcribes the recording device continuously
+/// until the process is stopped
+pub fn run_live_captions(config: &Config) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let capture = LiveCapture::start(&config.audio.recording_device, &config.audio, &config.device_profiles, tx)?;
+    let spec = capture.spec;
+    let mut windows = SlidingWindows::new(spec, &config.captions)?;
+    let mut caption = RollingCaption::new(config.captions.max_chars);
+
+    let url = if is_local_endpoint_available(&config.endpoints.local_whisper) {
+        &config.endpoints.local_whisper
+    } else {
+        &config.endpoints.hosted_whisper
+    };
+    info!("Live captions from {} via {}", config.audio.recording_device, url);
+
+    let upload_spec = WavSpec {
+        channels: 1,
+        sample_rate: WHISPER_SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let transcribe = |window: &[i16]| -> Result<String> {
+        let samples = resample_to_whisper(window, spec.sample_rate, spec.channels);
+        let wav = encode_wav_to_memory(&samples, upload_spec).context("Failed to encode caption window")?;
+        let text = transcribe_audio_bytes(url, &config.api_keys.openai, wav, RECORDING_FILE_NAME, None, ResponseFormat::Json)?;
+        Ok(discard_hallucination(&text, &config.transcription.hallucination_phrases).unwrap_or_default())
+    };
+
+    // Whatever arrived while the previous window was transcribing forms the next block
+    let blocks = std::iter::from_fn(|| {
+        let first = rx.recv().ok()?;
+        let mut block = vec![first];
+        block.extend(rx.try_iter());
+        Some(block)
+    });
+    caption_stream(blocks, &mut windows, &mut caption, transcribe, |text| {
+        show_caption(&config.captions, text)
+    });
+    drop(capture);
+    Err(anyhow::anyhow!("The recording device stopped delivering audio"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: [&str; 9] = ["The", "quick", "brown", "fox", "jumps", "over", "the", "lazy", "dog"];
+    const RATE: u32 = 10;
+
+    /// One second of audio per word, every sample holding the word's number;
+    /// the trailing second is silence
+    fn synthetic_stream() -> Vec<i16> {
+        (1..=WORDS.len() as i16 + 1)
+            .map(|n| if n as usize > WORDS.len() { 0 } else { n })
+            .flat_map(|n| std::iter::repeat_n(n, RATE as usize))
+            .collect()
+    }
+
+    /// Stands in for Whisper: each second of the window becomes its word, and
+    /// like Whisper it capitalizes and punctuates every window as a sentence
+    fn fake_transcribe(window: &[i16]) -> Result<String> {
+        let words: Vec<&str> = window
+            .chunks(RATE as usize)
+            .filter(|second| second[0] > 0)
+            .map(|second| WORDS[second[0] as usize - 1])
+            .collect();
+        let mut text = words.join(" ");
+        if let Some(first) = text.get_mut(..1) {
+            first.make_ascii_uppercase();
+        }
+        Ok(format!("{}.", text))
+    }
+
+    fn spec() -> WavSpec {
+        WavSpec {
+            channels: 1,
+            sample_rate: RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        }
+    }
+
+    #[test]
+    fn test_rolling_caption_does_not_repeat_overlap() {
+        let settings = CaptionSettings {
+            window_secs: 4,
+            step_secs: 2,
+            max_chars: 20,
+            output_file: None,
+        };
+        let mut windows = SlidingWindows::new(spec(), &settings).unwrap();
+        let mut caption = RollingCaption::new(settings.max_chars);
+        // Uneven blocks, as the capture delivers them
+        let blocks: Vec<Vec<i16>> = synthetic_stream().chunks(7).map(<[i16]>::to_vec).collect();
+
+        let mut shown = Vec::new();
+        caption_stream(blocks, &mut windows, &mut caption, fake_transcribe, |text| {
+            shown.push(text.to_string())
+        });
+
+        assert_eq!(
+            shown,
+            vec!["The quick brown fox.", "fox. jumps over.", "over. the lazy.", "over. the lazy. dog."]
+        );
+        assert_eq!(caption.transcript(), "The quick brown fox. jumps over. the lazy. dog.");
+    }
+
+    #[test]
+    fn test_overlap_found_after_cut_off_fragment() {
+        let previous: Vec<String> = "we should meet tomorrow at noon".split(' ').map(String::from).collect();
+        let next = ["row", "at", "noon,", "to", "discuss"];
+        assert_eq!(new_words(&previous, &next), ["to", "discuss"]);
+        assert_eq!(new_words(&previous, &["lunch", "then"]), ["lunch", "then"]);
+        assert!(new_words(&previous, &["At", "noon."]).is_empty());
+    }
+
+    #[test]
+    fn test_step_longer_than_window_is_rejected() {
+        let settings = CaptionSettings {
+            window_secs: 2,
+            step_secs: 3,
+            ..CaptionSettings::default()
+        };
+        assert!(SlidingWindows::new(spec(), &settings).is_err());
+    }
+}
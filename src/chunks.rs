@@ -0,0 +1,113 @@
+use anyhow::Result;
+use log::debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Splits interleaved samples into chunks of `chunk_secs`, never cutting a frame
+/// in half. A `chunk_secs` of 0 returns the whole recording as one chunk.
+pub fn split_into_chunks(samples: &[i16], channels: u16, sample_rate: u32, chunk_secs: u64) -> Vec<Vec<i16>> {
+    let chunk_len = chunk_secs as usize * sample_rate as usize * channels.max(1) as usize;
+    if chunk_len == 0 || samples.len() <= chunk_len {
+        return vec![samples.to_vec()];
+    }
+    samples.chunks(chunk_len).map(<[i16]>::to_vec).collect()
+}
+
+/// Joins chunk transcriptions in order, dropping empty chunks
+pub fn stitch(parts: &[String]) -> String {
+    parts
+        .iter()
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Transcribes chunks on up to `max_parallel` threads. Results are stored by
+/// chunk index, so the returned transcriptions follow chunk order whatever
+/// order the requests finish in. The first error is returned once all workers stop.
+pub fn transcribe_chunks_parallel<T, R, F>(chunks: &[T], max_parallel: usize, transcribe: F) -> Result<Vec<R>>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(usize, &T) -> Result<R> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<R>>>> = Mutex::new((0..chunks.len()).map(|_| None).collect());
+    let workers = max_parallel.clamp(1, chunks.len().max(1));
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(chunk) = chunks.get(index) else {
+                    break;
+                };
+                let result = transcribe(index, chunk);
+                debug!("Chunk {} of {} finished", index + 1, chunks.len());
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every chunk index is claimed by a worker"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Condvar;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parallel_chunks_reassemble_in_order() {
+        let chunks = ["first", "second", "third"];
+        let finished = Mutex::new(Vec::new());
+        let chunk_done = Condvar::new();
+
+        // Each chunk waits for the one after it, so they complete in reverse
+        let texts = transcribe_chunks_parallel(&chunks, 3, |index, chunk| {
+            let waiting = |done: &mut Vec<usize>| index + 1 < chunks.len() && !done.contains(&(index + 1));
+            let (mut done, wait) = chunk_done
+                .wait_timeout_while(finished.lock().unwrap(), Duration::from_secs(5), waiting)
+                .unwrap();
+            assert!(!wait.timed_out(), "chunk {} never saw the next one finish", index);
+            done.push(index);
+            chunk_done.notify_all();
+            Ok(chunk.to_string())
+        })
+        .unwrap();
+
+        assert_eq!(*finished.lock().unwrap(), vec![2, 1, 0]);
+        assert_eq!(texts, vec!["first", "second", "third"]);
+        assert_eq!(stitch(&texts), "first second third");
+    }
+
+    #[test]
+    fn test_chunk_failure_is_reported() {
+        let chunks = [1, 2, 3];
+        let result = transcribe_chunks_parallel(&chunks, 2, |_, &chunk| {
+            if chunk == 2 {
+                Err(anyhow::anyhow!("chunk {} failed", chunk))
+            } else {
+                Ok(chunk.to_string())
+            }
+        });
+        assert_eq!(result.unwrap_err().to_string(), "chunk 2 failed");
+    }
+
+    #[test]
+    fn test_split_into_chunks_keeps_frames_whole() {
+        let samples: Vec<i16> = (0..10).collect();
+        // 2 channels at 2 Hz: one second is 4 samples
+        let chunks = split_into_chunks(&samples, 2, 2, 1);
+        assert_eq!(chunks, vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9]]);
+        assert_eq!(split_into_chunks(&samples, 2, 2, 0).len(), 1);
+    }
+}
@@ -1,14 +1,102 @@
 use anyhow::Result;
 use clipboard::ClipboardContext;
 use clipboard::ClipboardProvider;
-use log::info;
+use log::{info, warn};
+use rdev::{simulate, EventType, Key};
+use std::time::Duration;
+
+/// Time for the clipboard owner to publish new contents before the target app
+/// is asked to read them
+const PASTE_SETTLE_DELAY: Duration = Duration::from_millis(30);
+
+/// Gap between synthesized events; macOS drops events sent back to back
+const KEY_EVENT_DELAY: Duration = Duration::from_millis(10);
 
 pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    copy_to_clipboard_with_retries(text, 0, Duration::ZERO)
+}
+
+/// Copies to the system clipboard, retrying `retries` more times with `delay`
+/// in between when a clipboard manager briefly holds the clipboard
+pub fn copy_to_clipboard_with_retries(text: &str, retries: u32, delay: Duration) -> Result<()> {
     let mut ctx: ClipboardContext = ClipboardProvider::new()
         .map_err(|e| anyhow::anyhow!("Failed to initialize clipboard context: {}", e))?;
-    ctx.set_contents(text.to_owned())
-        .map_err(|e| anyhow::anyhow!("Failed to set clipboard contents: {}", e))?;
-    info!("Text copied to clipboard.");
+    set_contents_with_retries(&mut ctx, text, retries, delay)
+}
+
+/// Empties the system clipboard, e.g. after pasting sensitive dictation
+pub fn clear_clipboard() -> Result<()> {
+    let mut ctx: ClipboardContext = ClipboardProvider::new()
+        .map_err(|e| anyhow::anyhow!("Failed to initialize clipboard context: {}", e))?;
+    clear_contents(&mut ctx)
+}
+
+/// Clears `ctx`; an already empty clipboard is left untouched
+pub fn clear_contents<C: ClipboardProvider>(ctx: &mut C) -> Result<()> {
+    if ctx.get_contents().map(|contents| contents.is_empty()).unwrap_or(false) {
+        return Ok(());
+    }
+    ctx.set_contents(String::new())
+        .map_err(|e| anyhow::anyhow!("Failed to clear clipboard: {}", e))?;
+    info!("Clipboard cleared.");
+    Ok(())
+}
+
+/// Retry loop behind `copy_to_clipboard_with_retries`, generic over the provider
+pub fn set_contents_with_retries<C: ClipboardProvider>(
+    ctx: &mut C,
+    text: &str,
+    retries: u32,
+    delay: Duration,
+) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        match ctx.set_contents(text.to_owned()) {
+            Ok(()) => {
+                info!("Text copied to clipboard.");
+                return Ok(());
+            }
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                warn!("Clipboard busy ({}); retry {} of {}", e, attempt, retries);
+                std::thread::sleep(delay);
+            }
+            Err(e) => return Err(anyhow::anyhow!("Failed to set clipboard contents: {}", e)),
+        }
+    }
+}
+
+/// Modifier of the platform's paste shortcut
+pub fn paste_modifier() -> Key {
+    if cfg!(target_os = "macos") {
+        Key::MetaLeft
+    } else {
+        Key::ControlLeft
+    }
+}
+
+/// Sends modifier+V through `send`. Once the modifier is down it is always
+/// released again, even if a later event fails, so it never stays stuck.
+pub fn send_paste_chord<F>(modifier: Key, mut send: F) -> Result<()>
+where
+    F: FnMut(&EventType) -> Result<()>,
+{
+    send(&EventType::KeyPress(modifier))?;
+    let chord = send(&EventType::KeyPress(Key::KeyV)).and_then(|()| send(&EventType::KeyRelease(Key::KeyV)));
+    let release = send(&EventType::KeyRelease(modifier));
+    chord.and(release)
+}
+
+/// Pastes the clipboard into the focused window (`clipboard.auto_paste`).
+/// Call right after copying; waits briefly so the new contents are visible.
+pub fn auto_paste() -> Result<()> {
+    std::thread::sleep(PASTE_SETTLE_DELAY);
+    send_paste_chord(paste_modifier(), |event| {
+        let sent = simulate(event).map_err(|e| anyhow::anyhow!("Failed to simulate {:?}: {:?}", event, e));
+        std::thread::sleep(KEY_EVENT_DELAY);
+        sent
+    })?;
+    info!("Pasted into the focused window.");
     Ok(())
 }
 
@@ -18,6 +106,112 @@ mod tests {
     use clipboard::ClipboardProvider;
     use clipboard::ClipboardContext;
     use anyhow::Result;
+    use std::error::Error;
+
+    /// Clipboard that reports contention for the first `failures` writes
+    struct FlakyClipboard {
+        failures: u32,
+        attempts: u32,
+        contents: String,
+    }
+
+    impl ClipboardProvider for FlakyClipboard {
+        fn new() -> std::result::Result<Self, Box<dyn Error>> {
+            Ok(FlakyClipboard {
+                failures: 0,
+                attempts: 0,
+                contents: String::new(),
+            })
+        }
+
+        fn get_contents(&mut self) -> std::result::Result<String, Box<dyn Error>> {
+            Ok(self.contents.clone())
+        }
+
+        fn set_contents(&mut self, contents: String) -> std::result::Result<(), Box<dyn Error>> {
+            self.attempts += 1;
+            if self.attempts <= self.failures {
+                return Err("clipboard is locked by another application".into());
+            }
+            self.contents = contents;
+            Ok(())
+        }
+    }
+
+    fn flaky(failures: u32) -> FlakyClipboard {
+        FlakyClipboard {
+            failures,
+            ..FlakyClipboard::new().unwrap()
+        }
+    }
+
+    #[test]
+    fn test_clipboard_retries_until_success() {
+        let mut clipboard = flaky(2);
+        set_contents_with_retries(&mut clipboard, "dictated text", 3, Duration::from_millis(1))
+            .expect("Copy should succeed on the third attempt");
+
+        assert_eq!(clipboard.attempts, 3);
+        assert_eq!(clipboard.get_contents().unwrap(), "dictated text");
+    }
+
+    #[test]
+    fn test_clipboard_gives_up_after_retries() {
+        let mut clipboard = flaky(5);
+        let err = set_contents_with_retries(&mut clipboard, "lost", 2, Duration::ZERO).unwrap_err();
+
+        assert_eq!(clipboard.attempts, 3);
+        assert_eq!(
+            err.to_string(),
+            "Failed to set clipboard contents: clipboard is locked by another application"
+        );
+    }
+
+    #[test]
+    fn test_clear_contents_empties_clipboard() {
+        let mut clipboard = flaky(0);
+        clipboard.set_contents("account number 1234".to_string()).unwrap();
+
+        clear_contents(&mut clipboard).unwrap();
+        assert_eq!(clipboard.get_contents().unwrap(), "");
+
+        // Already empty: nothing is written
+        clear_contents(&mut clipboard).unwrap();
+        assert_eq!(clipboard.attempts, 2);
+    }
+
+    #[test]
+    fn test_paste_chord_order() {
+        let mut sent = Vec::new();
+        send_paste_chord(Key::ControlLeft, |event| {
+            sent.push(*event);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            sent,
+            vec![
+                EventType::KeyPress(Key::ControlLeft),
+                EventType::KeyPress(Key::KeyV),
+                EventType::KeyRelease(Key::KeyV),
+                EventType::KeyRelease(Key::ControlLeft),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_paste_chord_releases_modifier_after_failure() {
+        let mut sent = Vec::new();
+        let result = send_paste_chord(Key::MetaLeft, |event| {
+            sent.push(*event);
+            match event {
+                EventType::KeyPress(Key::KeyV) => Err(anyhow::anyhow!("simulate failed")),
+                _ => Ok(()),
+            }
+        });
+        assert!(result.is_err());
+        assert_eq!(sent.last(), Some(&EventType::KeyRelease(Key::MetaLeft)));
+    }
 
     #[test]
     fn test_copy_to_clipboard_success() -> Result<()> {
@@ -0,0 +1,133 @@
+use anyhow::{Result, Context};
+use log::{info, warn};
+use std::process::Command;
+
+use crate::api::{EndpointKind, Transcription};
+use crate::config::{CommandFallback, TranscriptionSettings};
+
+const AUDIO_PLACEHOLDER: &str = "{{audio}}";
+
+/// Runs `transcription.command` on a WAV file and returns its trimmed stdout.
+/// Without an "{{audio}}" argument the path is appended as the last argument.
+pub fn transcribe_with_command(command: &[String], audio_path: &str) -> Result<String> {
+    let (program, args) = command
+        .split_first()
+        .context("transcription.command is empty")?;
+    let mut args: Vec<String> = args.iter().map(|arg| arg.replace(AUDIO_PLACEHOLDER, audio_path)).collect();
+    if !command.iter().any(|arg| arg.contains(AUDIO_PLACEHOLDER)) {
+        args.push(audio_path.to_string());
+    }
+
+    let output = Command::new(program)
+        .args(&args)
+        .output()
+        .with_context(|| format!("Failed to start transcription command '{}'", program))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Transcription command '{}' exited with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Transcribes with the command backend when one is configured. If it is missing
+/// or fails and `command_fallback = "http"`, `http` is called instead, which is
+/// expected to apply the usual endpoint selection and consent rules.
+pub fn transcribe_command_or_http<F>(
+    settings: &TranscriptionSettings,
+    audio_path: &str,
+    http: F,
+) -> Result<Transcription>
+where
+    F: FnOnce() -> Result<Transcription>,
+{
+    let Some(command) = &settings.command else {
+        return http();
+    };
+
+    match transcribe_with_command(command, audio_path) {
+        Ok(text) => {
+            info!("Transcribed via command '{}'", command[0]);
+            Ok(Transcription {
+                text,
+                endpoint_used: EndpointKind::Local,
+                model_used: command[0].clone(),
+                language: None,
+            })
+        }
+        Err(e) => match settings.command_fallback {
+            CommandFallback::Http => {
+                warn!("Transcription command failed, falling back to HTTP: {:?}", e);
+                http()
+            }
+            CommandFallback::Error => Err(e),
+        },
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::api::transcribe_audio_with_prompt;
+    use mockito::mock;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn settings(command: &[&str], fallback: CommandFallback) -> TranscriptionSettings {
+        TranscriptionSettings {
+            command: Some(command.iter().map(|s| s.to_string()).collect()),
+            command_fallback: fallback,
+            ..TranscriptionSettings::default()
+        }
+    }
+
+    fn audio_file() -> NamedTempFile {
+        let mut audio = NamedTempFile::new().expect("Failed to create temp file");
+        write!(audio, "dummy audio data").expect("Failed to write to temp file");
+        audio
+    }
+
+    #[test]
+    fn test_command_output_is_transcript() {
+        let audio = audio_file();
+        let path = audio.path().to_str().unwrap();
+        let settings = settings(&["sh", "-c", "test -f \"$0\" && echo ' local words '", "{{audio}}"], CommandFallback::Error);
+
+        let transcription = transcribe_command_or_http(&settings, path, || panic!("HTTP must not be used")).unwrap();
+        assert_eq!(transcription.text, "local words");
+        assert_eq!(transcription.endpoint_used, EndpointKind::Local);
+    }
+
+    #[test]
+    fn test_failed_command_falls_back_to_http() {
+        let _m = mock("POST", "/command-fallback/transcriptions")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "from the server"}"#)
+            .create();
+        let url = format!("{}/command-fallback/transcriptions", &mockito::server_url());
+        let audio = audio_file();
+        let path = audio.path().to_str().unwrap();
+
+        let http = || {
+            let text = transcribe_audio_with_prompt(&url, "test_api_key", path, None)?;
+            Ok(Transcription {
+                text,
+                endpoint_used: EndpointKind::Hosted,
+                model_used: "whisper-1".to_string(),
+                language: None,
+            })
+        };
+        let transcription =
+            transcribe_command_or_http(&settings(&["sh", "-c", "exit 3"], CommandFallback::Http), path, http).unwrap();
+        assert_eq!(transcription.text, "from the server");
+        assert_eq!(transcription.endpoint_used, EndpointKind::Hosted);
+
+        let missing = settings(&["/nonexistent/whisper-cli"], CommandFallback::Error);
+        let err = transcribe_command_or_http(&missing, path, || panic!("HTTP must not be used")).unwrap_err();
+        assert!(err.to_string().contains("Failed to start transcription command"));
+    }
+}
@@ -1,14 +1,126 @@
 use serde::Deserialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::fs;
+use std::path::PathBuf;
 use anyhow::{Result, Context};
+use log::{info, warn};
+
+use crate::hotkeys::parse_hotkey;
+use crate::migrate::CURRENT_CONFIG_VERSION;
+use crate::text::{correct_homophones, instruction_regex};
 
 #[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Config {
+    /// Schema version, see `migrate::CURRENT_CONFIG_VERSION`; absent in files
+    /// written before versioning
+    #[serde(default)]
+    pub config_version: Option<u32>,
     pub endpoints: Endpoints,
     pub hotkeys: Hotkeys,
     pub audio: AudioSettings,
     pub llm: LLMSettings,
+    #[serde(default)]
     pub api_keys: ApiKeys,
+    #[serde(default)]
+    pub output: OutputSettings,
+    #[serde(default)]
+    pub webhook: Option<WebhookSettings>,
+    #[serde(default, rename = "device_profile")]
+    pub device_profiles: Vec<DeviceProfile>,
+    #[serde(default)]
+    pub network: NetworkSettings,
+    #[serde(default)]
+    pub transcription: TranscriptionSettings,
+    #[serde(default)]
+    pub general: GeneralSettings,
+    #[serde(default)]
+    pub ui: UiSettings,
+    #[serde(default)]
+    pub privacy: PrivacySettings,
+    #[serde(default)]
+    pub analytics: AnalyticsSettings,
+    #[serde(default)]
+    pub timeouts: TimeoutSettings,
+    #[serde(default)]
+    pub session: SessionSettings,
+    #[serde(default)]
+    pub clipboard: ClipboardSettings,
+    #[serde(default)]
+    pub debug: DebugSettings,
+    #[serde(default)]
+    pub captions: CaptionSettings,
+    #[serde(default)]
+    pub history: HistorySettings,
+    #[serde(default)]
+    pub app: AppSettings,
+    #[serde(default)]
+    pub log: LogSettings,
+}
+
+impl Config {
+    /// Checks what parsing cannot: endpoints are http(s) URLs, the recording
+    /// hotkey names only keys `parse_hotkey` knows, always-on post-processing
+    /// has a prompt to work with, the LLM temperature is in range,
+    /// `transcription.instruction_prefix_regex` and the homophone guards
+    /// compile and nothing asks `privacy.memory_only` to write audio to disk
+    pub fn validate(&self) -> Result<()> {
+        let endpoints = [
+            ("endpoints.local_whisper", &self.endpoints.local_whisper),
+            ("endpoints.hosted_whisper", &self.endpoints.hosted_whisper),
+            ("endpoints.llm_endpoint", &self.endpoints.llm_endpoint),
+        ];
+        for (name, url) in endpoints {
+            let parsed = reqwest::Url::parse(url)
+                .with_context(|| format!("{} = '{}' is not a valid URL", name, url))?;
+            if !matches!(parsed.scheme(), "http" | "https") {
+                return Err(anyhow::anyhow!(
+                    "{} = '{}' must be an http:// or https:// URL",
+                    name,
+                    url
+                ));
+            }
+        }
+
+        let recording = parse_hotkey(&self.hotkeys.recording)
+            .with_context(|| format!("hotkeys.recording = '{}' is not a valid hotkey", self.hotkeys.recording))?;
+        if recording.is_empty() {
+            return Err(anyhow::anyhow!(
+                "hotkeys.recording = '{}' contains no recognized keys",
+                self.hotkeys.recording
+            ));
+        }
+
+        if self.llm.always_post_process && self.llm.post_processing_prompt.trim().is_empty() {
+            return Err(anyhow::anyhow!(
+                "llm.post_processing_prompt is empty but llm.always_post_process = true; set a prompt or turn always_post_process off"
+            ));
+        }
+        if !(0.0..=2.0).contains(&self.llm.temperature) {
+            return Err(anyhow::anyhow!(
+                "llm.temperature = {} is out of range; use a value from 0.0 to 2.0",
+                self.llm.temperature
+            ));
+        }
+        if let Some(pattern) = &self.transcription.instruction_prefix_regex {
+            instruction_regex(pattern)?;
+        }
+        // Compiles every guard without correcting anything
+        correct_homophones("", &self.transcription.homophones)?;
+        if self.privacy.memory_only {
+            let disk_features = [
+                ("transcription.command", self.transcription.command.is_some()),
+                ("transcription.diarize", self.transcription.diarize),
+            ];
+            if let Some((name, _)) = disk_features.iter().find(|(_, enabled)| *enabled) {
+                return Err(anyhow::anyhow!(
+                    "privacy.memory_only = true keeps audio off disk, but {} needs recordings written to a file; turn one of them off",
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Deserialize, Debug, Clone, PartialEq)]
@@ -16,35 +128,1097 @@ pub struct Endpoints {
     pub local_whisper: String,
     pub hosted_whisper: String,
     pub llm_endpoint: String,
+    /// Whisper translation route, used instead of transcription with `audio.translate`
+    #[serde(default = "default_translation_endpoint")]
+    pub translation: String,
+}
+
+fn default_translation_endpoint() -> String {
+    "https://api.openai.com/v1/audio/translations".to_string()
 }
 
 #[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Hotkeys {
     pub recording: String,
     pub post_processing_modifier: String,
+    /// Captures the focused window as the destination for typed/pasted output
+    #[serde(default)]
+    pub set_target_window: Option<String>,
+    /// Keep capturing this long after the recording hotkey is released
+    #[serde(default)]
+    pub release_grace_ms: u64,
+    /// A release followed by a re-press within this window counts as one continuous hold
+    #[serde(default)]
+    pub debounce_ms: u64,
+    /// In hybrid mode, presses of the recording hotkey shorter than this toggle
+    /// recording; longer ones are push-to-talk. Each decision is logged at debug
+    /// level for tuning.
+    #[serde(default = "default_tap_threshold_ms")]
+    pub tap_threshold_ms: u64,
+    /// Copies the current selection and uses it as LLM context for the next dictation
+    #[serde(default)]
+    pub capture_selection: Option<String>,
+    /// Sends the next recording to the hosted endpoint even if local is up
+    #[serde(default)]
+    pub force_hosted: Option<String>,
+    /// Sends the next recording to the local endpoint only, without hosted fallback
+    #[serde(default)]
+    pub force_local: Option<String>,
+    /// Ignore the recording hotkey while the focused window is fullscreen (games, slides)
+    #[serde(default)]
+    pub disable_in_fullscreen: bool,
+    /// Empties the clipboard immediately, e.g. after pasting sensitive dictation
+    #[serde(default)]
+    pub clear_clipboard: Option<String>,
+    /// Turns post-processing on for every recording until pressed again
+    #[serde(default)]
+    pub toggle_post_processing: Option<String>,
+    /// Tap the recording hotkey to start and stop, or hold it to record while held;
+    /// "hold" and "toggle" keep only one of the two
+    #[serde(default)]
+    pub mode: HotkeyMode,
+}
+
+/// How the recording hotkey controls recording
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HotkeyMode {
+    /// A tap of the combo starts or stops recording, a longer press records while held
+    #[default]
+    Hybrid,
+    /// Record while the combo is held
+    Hold,
+    /// Each full press of the combo starts or stops recording
+    Toggle,
+}
+
+fn default_tap_threshold_ms() -> u64 {
+    250
 }
 
 #[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct AudioSettings {
     pub recording_device: String,
+    /// Re-record once when a capture yields no samples instead of aborting
+    #[serde(default)]
+    pub retry_empty_recording: bool,
+    /// With recording_device = "default", pick up a new OS default before each
+    /// recording instead of sticking with the one found at startup
+    #[serde(default = "default_true")]
+    pub follow_default_device: bool,
+    /// Record from the OS default when the configured device is missing,
+    /// e.g. an unplugged USB microphone, instead of failing
+    #[serde(default)]
+    pub fallback_to_default: bool,
+    /// Send recordings to `endpoints.translation`, which answers in English
+    /// whatever language was spoken
+    #[serde(default)]
+    pub translate: bool,
+    /// Zero-based input channels to capture and mix to mono, e.g. [0] for the
+    /// first input of a multi-channel interface. Empty keeps every channel.
+    #[serde(default)]
+    pub channel_selection: Vec<u16>,
+    /// Record a short probe at startup and periodically, warning when the device
+    /// only produces silence (usually a muted or misrouted input)
+    #[serde(default = "default_true")]
+    pub silent_device_check: bool,
+    /// Minutes between probes after the startup one (0 = startup only)
+    #[serde(default = "default_silent_device_check_interval_mins")]
+    pub silent_device_check_interval_mins: u64,
+    /// Ordered DSP steps applied to each recording before upload, from
+    /// `[[audio.preprocessing]]` tables
+    #[serde(default)]
+    pub preprocessing: Vec<AudioStep>,
+    /// Recordings are downmixed to mono and resampled to this rate before upload,
+    /// which is what Whisper works with internally (0 keeps the device format)
+    #[serde(default = "default_target_sample_rate")]
+    pub target_sample_rate: u32,
+    /// RMS level (fraction of full scale) below which audio counts as silence
+    #[serde(default = "default_silence_threshold")]
+    pub silence_threshold: f32,
+    /// Stop recording after this much silence following speech (0 = never)
+    #[serde(default)]
+    pub silence_timeout_ms: u64,
+    /// Stop any recording after this long, e.g. when a hotkey gets stuck, and
+    /// transcribe what was captured (0 = no limit)
+    #[serde(default = "default_max_recording_secs")]
+    pub max_recording_secs: u64,
+    /// Bring each recording's peak to -3 dBFS before upload; near-silent
+    /// recordings are left alone
+    #[serde(default = "default_true")]
+    pub normalize: bool,
+    /// Timings requested for subtitles (`once --srt`); servers without word
+    /// timings fall back to segments
+    #[serde(default = "default_timestamp_granularities")]
+    pub timestamp_granularities: Vec<TimestampGranularity>,
+    /// Whisper `response_format`; "srt" and "vtt" return subtitles instead of text
+    #[serde(default)]
+    pub response_format: ResponseFormat,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    VerboseJson,
+    Text,
+    Srt,
+    Vtt,
+}
+
+/// Timing detail requested with `verbose_json` (`timestamp_granularities[]`)
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampGranularity {
+    Word,
+    Segment,
+}
+
+impl TimestampGranularity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TimestampGranularity::Word => "word",
+            TimestampGranularity::Segment => "segment",
+        }
+    }
+}
+
+fn default_timestamp_granularities() -> Vec<TimestampGranularity> {
+    vec![TimestampGranularity::Word]
+}
+
+impl ResponseFormat {
+    /// Value of the `response_format` form field
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ResponseFormat::Json => "json",
+            ResponseFormat::VerboseJson => "verbose_json",
+            ResponseFormat::Text => "text",
+            ResponseFormat::Srt => "srt",
+            ResponseFormat::Vtt => "vtt",
+        }
+    }
+}
+
+fn default_silence_threshold() -> f32 {
+    0.01
+}
+
+fn default_max_recording_secs() -> u64 {
+    300
+}
+
+fn default_target_sample_rate() -> u32 {
+    16000
+}
+
+/// One step of the audio preprocessing chain, selected by its `step` key
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum AudioStep {
+    /// Multiply every sample by `factor`, clipping at full scale
+    Gain { factor: f32 },
+    /// Silence samples quieter than `threshold` (fraction of full scale)
+    NoiseGate { threshold: f32 },
+    /// Drop leading and trailing frames quieter than `threshold`
+    TrimSilence { threshold: f32 },
+    /// Linearly resample to `rate` Hz
+    Resample { rate: u32 },
+    /// Average all channels into one
+    Downmix,
+    /// Scale so the loudest sample reaches `peak` (fraction of full scale)
+    Normalize { peak: f32 },
+}
+
+fn default_silent_device_check_interval_mins() -> u64 {
+    30
 }
 
 #[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct LLMSettings {
     pub post_processing_prompt: String,
     pub always_post_process: bool,
+    /// Language codes (e.g. "de", "ja") whose transcriptions are never post-processed
+    #[serde(default)]
+    pub skip_post_processing_languages: Vec<String>,
+    /// Run a second LLM pass that produces a short summary
+    #[serde(default)]
+    pub summarize: bool,
+    #[serde(default = "default_summary_prompt")]
+    pub summary_prompt: String,
+    /// API shape spoken by `endpoints.llm_endpoint`
+    #[serde(default)]
+    pub provider: LlmProviderKind,
+    /// Model name sent to providers that need one (e.g. "llama3" for Ollama,
+    /// "gpt-4o-mini" for the chat API)
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Reply length limit for OpenAI-compatible requests
+    #[serde(default = "default_llm_max_tokens")]
+    pub max_tokens: u32,
+    /// Sampling temperature for OpenAI-compatible requests, 0.0 to 2.0
+    #[serde(default = "default_llm_temperature")]
+    pub temperature: f64,
+    /// Stop calling the LLM after this many requests until restart (0 = unlimited)
+    #[serde(default)]
+    pub max_calls_per_session: u64,
+    /// Log a word diff between the raw and post-processed text and keep it in the result
+    #[serde(default)]
+    pub record_diff: bool,
+    /// Also show the number of changed words as a notification
+    #[serde(default)]
+    pub notify_diff: bool,
+    /// Request shape for OpenAI-compatible servers: legacy `/completions` or `/chat/completions`
+    #[serde(default)]
+    pub api_style: LlmApiStyle,
+    /// Cleanup prompts keyed by language code, from `[llm.prompts_by_language]`;
+    /// languages without an entry use `post_processing_prompt`
+    #[serde(default)]
+    pub prompts_by_language: HashMap<String, String>,
+    /// Language whose prompt is used when no language was detected, e.g. with
+    /// the plain `json` response format
+    #[serde(default)]
+    pub default_prompt_language: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone, PartialEq)]
+/// Payload and response schema used with an OpenAI-compatible LLM endpoint
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmApiStyle {
+    /// `{"prompt"}` in, `choices[].text` out
+    #[default]
+    Completions,
+    /// `{"messages"}` in, `choices[].message.content` out
+    Chat,
+}
+
+/// LLM backend used for post-processing
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmProviderKind {
+    /// OpenAI-compatible `/completions`
+    #[default]
+    OpenAi,
+    /// Ollama's native `/api/generate`
+    Ollama,
+    /// Anthropic's `/v1/messages`, authenticated with `api_keys.anthropic`
+    Anthropic,
+}
+
+fn default_llm_max_tokens() -> u32 {
+    150
+}
+
+fn default_llm_temperature() -> f64 {
+    0.7
+}
+
+fn default_summary_prompt() -> String {
+    "Summarize the following transcript in a few sentences:".to_string()
+}
+
+impl LLMSettings {
+    /// Decides whether a transcription goes through the LLM. A language listed in
+    /// `skip_post_processing_languages` wins over both the modifier and `always_post_process`.
+    pub fn should_post_process(&self, modifier_held: bool, language: Option<&str>) -> bool {
+        if let Some(language) = language {
+            let primary = language.split(['-', '_']).next().unwrap_or(language);
+            let skipped = self
+                .skip_post_processing_languages
+                .iter()
+                .any(|code| code.eq_ignore_ascii_case(language) || code.eq_ignore_ascii_case(primary));
+            if skipped {
+                return false;
+            }
+        }
+        self.always_post_process || modifier_held
+    }
+
+    /// Whether any setting depends on the detected language, which only a
+    /// `verbose_json` transcription reports
+    pub fn needs_language(&self) -> bool {
+        !self.skip_post_processing_languages.is_empty() || !self.prompts_by_language.is_empty()
+    }
+
+    /// Cleanup prompt for the detected `language`, falling back to
+    /// `default_prompt_language` when detection gave nothing and to
+    /// `post_processing_prompt` when no language-specific prompt matches
+    pub fn prompt_for(&self, language: Option<&str>) -> &str {
+        let Some(language) = language.or(self.default_prompt_language.as_deref()) else {
+            return &self.post_processing_prompt;
+        };
+        let primary = language.split(['-', '_']).next().unwrap_or(language);
+        [language, primary]
+            .iter()
+            .find_map(|code| {
+                self.prompts_by_language
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(code))
+                    .map(|(_, prompt)| prompt.as_str())
+            })
+            .unwrap_or(&self.post_processing_prompt)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct ApiKeys {
+    /// May be left empty to take the key from `OPENAI_API_KEY` or the OS keyring
+    #[serde(default)]
     pub openai: String,
+    /// Key for `llm.provider = "anthropic"`; may be left empty to use `ANTHROPIC_API_KEY`
+    #[serde(default)]
+    pub anthropic: String,
+}
+
+/// Environment variable consulted when `api_keys.openai` is empty
+pub const OPENAI_API_KEY_ENV: &str = "OPENAI_API_KEY";
+
+/// Environment variable consulted when `api_keys.anthropic` is empty
+pub const ANTHROPIC_API_KEY_ENV: &str = "ANTHROPIC_API_KEY";
+
+/// OS keyring entry (service, user) holding the OpenAI key
+pub const KEYRING_SERVICE: &str = "rusty-scribe";
+pub const KEYRING_OPENAI_USER: &str = "openai";
+
+/// Picks the OpenAI key from, in order, the config value, the environment
+/// variable and the keyring. Blank values count as missing.
+pub fn resolve_api_key_from<F>(configured: &str, env: Option<String>, keyring: F) -> Result<String>
+where
+    F: FnOnce() -> Option<String>,
+{
+    let present = |key: &str| !key.trim().is_empty();
+    if present(configured) {
+        return Ok(configured.trim().to_string());
+    }
+    if let Some(key) = env.filter(|key| present(key)) {
+        return Ok(key.trim().to_string());
+    }
+    if let Some(key) = keyring().filter(|key| present(key)) {
+        return Ok(key.trim().to_string());
+    }
+    Err(anyhow::anyhow!(
+        "No OpenAI API key found. Set api_keys.openai in config.toml, the {} environment variable, or store it in the OS keyring under service \"{}\", user \"{}\".",
+        OPENAI_API_KEY_ENV,
+        KEYRING_SERVICE,
+        KEYRING_OPENAI_USER
+    ))
+}
+
+/// The OpenAI key for `config`, see `resolve_api_key_from`
+pub fn resolve_api_key(config: &Config) -> Result<String> {
+    resolve_api_key_from(&config.api_keys.openai, std::env::var(OPENAI_API_KEY_ENV).ok(), || {
+        keyring::Entry::new(KEYRING_SERVICE, KEYRING_OPENAI_USER)
+            .and_then(|entry| entry.get_password())
+            .ok()
+    })
+}
+
+/// Per-microphone capture tweaks, selected by matching `name` against the device name
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct DeviceProfile {
+    /// Exact device name or a case-insensitive substring of it
+    pub name: String,
+    /// Linear gain applied to every captured sample
+    #[serde(default)]
+    pub gain: Option<f32>,
+    /// Samples quieter than this fraction of full scale (0.0–1.0) are silenced
+    #[serde(default)]
+    pub noise_gate_threshold: Option<f32>,
+    /// Channel count to request from the device instead of its default
+    #[serde(default)]
+    pub channels: Option<u16>,
+}
+
+/// How the final text is delivered to the user
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    #[default]
+    Clipboard,
+    Paste,
+    Type,
+}
+
+/// Line endings used in the final text
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    /// CRLF on Windows, LF elsewhere
+    #[default]
+    Platform,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct OutputSettings {
+    #[serde(default)]
+    pub mode: OutputMode,
+    /// Use clipboard-only output when synthetic input is unavailable instead of failing
+    #[serde(default = "default_true")]
+    pub fallback_to_clipboard: bool,
+    /// Deliver JSON with per-word start/end times in place of the text.
+    /// Post-processing is skipped since it would no longer match the timings.
+    #[serde(default)]
+    pub word_timestamps_json: bool,
+    /// Wrap text in bracketed-paste sequences when the target window is a terminal
+    #[serde(default)]
+    pub bracketed_paste: bool,
+    /// Window class substrings (case-insensitive) that identify terminal emulators
+    #[serde(default = "default_terminal_window_classes")]
+    pub terminal_window_classes: Vec<String>,
+    /// Markup tags (e.g. "speak", "prosody") removed from the final text
+    #[serde(default)]
+    pub strip_tags: Vec<String>,
+    #[serde(default)]
+    pub line_ending: LineEnding,
+    /// Command (program and arguments) that inserts the text itself, e.g. an
+    /// osascript or xdotool call. "{{text}}" in an argument is replaced by the
+    /// text; without it the text is written to stdin.
+    #[serde(default)]
+    pub insert_command: Option<Vec<String>>,
+    #[serde(default = "default_insert_command_timeout_ms")]
+    pub insert_command_timeout_ms: u64,
+    /// Extra attempts when another app is holding the clipboard
+    #[serde(default = "default_clipboard_retries")]
+    pub clipboard_retries: u32,
+    #[serde(default = "default_clipboard_retry_delay_ms")]
+    pub clipboard_retry_delay_ms: u64,
+    /// How parts split by `transcription.segment_delimiter` are delivered
+    #[serde(default)]
+    pub segment_output: SegmentOutput,
+    /// In type mode, type `placeholder_text` as soon as recording ends and replace
+    /// it with the transcription once it is ready
+    #[serde(default)]
+    pub placeholder_while_processing: bool,
+    /// Typed with key events, so characters without a key on a US layout go
+    /// through the clipboard
+    #[serde(default = "default_placeholder_text")]
+    pub placeholder_text: String,
+    /// Pause between characters in type mode; some apps drop keys sent faster
+    #[serde(default = "default_typing_delay_ms")]
+    pub typing_delay_ms: u64,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ClipboardSettings {
+    /// After copying, send Ctrl+V (Cmd+V on macOS) to paste into the focused window
+    #[serde(default)]
+    pub auto_paste: bool,
+}
+
+/// Delivery of a dictation split into segments
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentOutput {
+    /// One text with a Tab between segments, moving to the next form field
+    #[default]
+    Tabbed,
+    /// Each segment goes on the clipboard in turn, one per paste
+    Sequential,
+}
+
+fn default_typing_delay_ms() -> u64 {
+    5
+}
+
+fn default_placeholder_text() -> String {
+    "[transcribing...]".to_string()
+}
+
+fn default_clipboard_retries() -> u32 {
+    3
+}
+
+fn default_clipboard_retry_delay_ms() -> u64 {
+    50
+}
+
+fn default_insert_command_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_terminal_window_classes() -> Vec<String> {
+    [
+        "terminal", "konsole", "alacritty", "kitty", "xterm", "wezterm", "tilix", "terminator",
+        "foot", "iterm",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        OutputSettings {
+            mode: OutputMode::default(),
+            fallback_to_clipboard: true,
+            word_timestamps_json: false,
+            bracketed_paste: false,
+            terminal_window_classes: default_terminal_window_classes(),
+            strip_tags: Vec::new(),
+            line_ending: LineEnding::default(),
+            insert_command: None,
+            insert_command_timeout_ms: default_insert_command_timeout_ms(),
+            clipboard_retries: default_clipboard_retries(),
+            clipboard_retry_delay_ms: default_clipboard_retry_delay_ms(),
+            segment_output: SegmentOutput::default(),
+            placeholder_while_processing: false,
+            placeholder_text: default_placeholder_text(),
+            typing_delay_ms: default_typing_delay_ms(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WebhookSettings {
+    pub url: String,
+    /// Sent verbatim as the Authorization header, e.g. "Bearer <token>"
+    #[serde(default)]
+    pub auth_header: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct UiSettings {
+    /// Show a notification with actionable guidance when a pipeline stage fails
+    #[serde(default = "default_true")]
+    pub error_notifications: bool,
+    /// Desktop notifications when recording starts, transcription begins, the
+    /// text is delivered or the dictation fails. Off by default for headless use.
+    #[serde(default)]
+    pub notifications: bool,
+    /// Show a tray icon with the recording state and a menu; needs a build
+    /// with the `tray-icon` feature
+    #[serde(default)]
+    pub tray: bool,
+}
+
+impl Default for UiSettings {
+    fn default() -> Self {
+        UiSettings {
+            error_notifications: true,
+            notifications: false,
+            tray: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct GeneralSettings {
+    /// Exit after this many seconds without a recording (0 = never)
+    #[serde(default)]
+    pub idle_exit_secs: u64,
+    /// Soft limit on audio held in memory while waiting for transcription (0 = unlimited)
+    #[serde(default)]
+    pub max_memory_mb: u64,
+    /// Free space required where recordings are written before a recording starts (0 = no check)
+    #[serde(default)]
+    pub min_free_disk_mb: u64,
+    #[serde(default)]
+    pub low_disk_action: LowDiskAction,
+}
+
+/// What to do when less than `general.min_free_disk_mb` is free
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LowDiskAction {
+    /// Record anyway, keeping the audio in memory instead of writing it
+    #[default]
+    MemoryOnly,
+    /// Refuse to start the recording
+    Abort,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TranscriptionSettings {
+    /// Regex matched at the start of a transcription, e.g. `(?i)^as an? ([^:]+):`.
+    /// The match (or its first capture group) becomes the LLM instruction.
+    #[serde(default)]
+    pub instruction_prefix_regex: Option<String>,
+    /// Word list (one term per line) sent as the Whisper prompt to bias
+    /// recognition of names and jargon. Reloaded when the file changes.
+    #[serde(default)]
+    pub vocabulary_file: Option<String>,
+    /// Formats for spoken digit sequences, `#` standing for a digit, e.g.
+    /// "###-###-####" for US phone numbers. A sequence is only regrouped when its
+    /// digit count matches a pattern exactly.
+    #[serde(default)]
+    pub digit_grouping: Vec<String>,
+    /// Boilerplate Whisper invents on near-silent audio. A transcription made up
+    /// only of these phrases is discarded.
+    #[serde(default = "default_hallucination_phrases")]
+    pub hallucination_phrases: Vec<String>,
+    /// Deterministic word fixes for known misrecognitions, from `[[transcription.homophone]]`
+    #[serde(default, rename = "homophone")]
+    pub homophones: Vec<HomophoneRule>,
+    /// Downsample to 16 kHz only for the hosted endpoint; local gets native-rate audio
+    #[serde(default)]
+    pub resample_for_hosted_only: bool,
+    /// Spoken phrase (e.g. "next field") that splits a dictation into separate parts
+    #[serde(default)]
+    pub segment_delimiter: Option<String>,
+    /// Stop calling Whisper after this many requests until restart (0 = unlimited)
+    #[serde(default)]
+    pub max_calls_per_session: u64,
+    /// Recordings longer than this are split into chunks of this many seconds and
+    /// transcribed separately (0 = never split). Translation, word timestamps,
+    /// a transcription command and diarization always send the whole recording.
+    #[serde(default)]
+    pub chunk_secs: u64,
+    /// How many chunks may be in flight at once
+    #[serde(default = "default_max_parallel_chunks")]
+    pub max_parallel_chunks: usize,
+    /// Local transcription command (e.g. whisper.cpp) used instead of the local
+    /// HTTP endpoint. "{{audio}}" in an argument is replaced by the WAV path and
+    /// the transcript is read from stdout.
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    /// What to do when `command` is missing or fails
+    #[serde(default)]
+    pub command_fallback: CommandFallback,
+    /// Label output by speaker ("Speaker 1: ...") when the endpoint can tell voices apart
+    #[serde(default)]
+    pub diarize: bool,
+    /// Separate service returning speaker-labelled segments; defaults to the hosted endpoint
+    #[serde(default)]
+    pub diarization_endpoint: Option<String>,
+}
+
+/// Behaviour when the transcription command cannot produce a transcript
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandFallback {
+    /// Retry through the configured HTTP endpoints
+    #[default]
+    Http,
+    /// Report the failure and keep the recording untranscribed
+    Error,
+}
+
+fn default_max_parallel_chunks() -> usize {
+    3
+}
+
+/// Replaces the whole word `from` (case-insensitively) with `to`. The optional
+/// `before`/`after` regexes must match the text directly before/after the word.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct HomophoneRule {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub before: Option<String>,
+    #[serde(default)]
+    pub after: Option<String>,
+}
+
+impl Default for TranscriptionSettings {
+    fn default() -> Self {
+        TranscriptionSettings {
+            instruction_prefix_regex: None,
+            vocabulary_file: None,
+            digit_grouping: Vec::new(),
+            hallucination_phrases: default_hallucination_phrases(),
+            homophones: Vec::new(),
+            resample_for_hosted_only: false,
+            segment_delimiter: None,
+            max_calls_per_session: 0,
+            chunk_secs: 0,
+            max_parallel_chunks: default_max_parallel_chunks(),
+            command: None,
+            command_fallback: CommandFallback::default(),
+            diarize: false,
+            diarization_endpoint: None,
+        }
+    }
+}
+
+fn default_hallucination_phrases() -> Vec<String> {
+    [
+        "thanks for watching",
+        "thank you for watching",
+        "thank you",
+        "please subscribe",
+        "like and subscribe",
+        "subtitles by the amara.org community",
+        "subtitles by",
+        "see you in the next video",
+        "you",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct NetworkSettings {
+    /// Minimum minutes between "rate limited" notifications
+    #[serde(default = "default_rate_limit_notify_interval_mins")]
+    pub rate_limit_notify_interval_mins: u64,
+    /// Ping the local Whisper endpoint this often so it keeps the model loaded (0 = off)
+    #[serde(default)]
+    pub keepalive_interval_secs: u64,
+    /// Keep pinging while the machine runs on battery
+    #[serde(default)]
+    pub keepalive_on_battery: bool,
+    /// Minimum time between requests to hosted endpoints; requests that come
+    /// sooner wait (0 = no spacing). The local Whisper endpoint is exempt.
+    #[serde(default)]
+    pub min_request_interval_ms: u64,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        NetworkSettings {
+            rate_limit_notify_interval_mins: default_rate_limit_notify_interval_mins(),
+            keepalive_interval_secs: 0,
+            keepalive_on_battery: false,
+            min_request_interval_ms: 0,
+        }
+    }
+}
+
+/// Limits for every HTTP request so a hung server cannot block the app
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TimeoutSettings {
+    /// Time allowed to establish a connection
+    #[serde(default = "default_connect_ms")]
+    pub connect_ms: u64,
+    /// Time allowed for a whole request, including upload and response
+    #[serde(default = "default_request_ms")]
+    pub request_ms: u64,
+    /// Total time for the local endpoint availability probe
+    #[serde(default = "default_probe_ms")]
+    pub probe_ms: u64,
+}
+
+impl Default for TimeoutSettings {
+    fn default() -> Self {
+        TimeoutSettings {
+            connect_ms: default_connect_ms(),
+            request_ms: default_request_ms(),
+            probe_ms: default_probe_ms(),
+        }
+    }
+}
+
+fn default_connect_ms() -> u64 {
+    5000
+}
+
+fn default_request_ms() -> u64 {
+    60_000
+}
+
+fn default_probe_ms() -> u64 {
+    500
+}
+
+/// Continuous dictation: with either autosave on, the text delivered since
+/// start-up is kept as one session and saved for recovery after a crash
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionSettings {
+    /// Save the session text to `recovery_file` at most this often; 0 disables
+    /// time-based saves
+    #[serde(default = "default_autosave_secs")]
+    pub autosave_secs: u64,
+    /// Also save once this many characters were added since the last save;
+    /// 0 disables size-based saves
+    #[serde(default = "default_autosave_chars")]
+    pub autosave_chars: usize,
+    /// Restored on the next start when a session did not end cleanly
+    #[serde(default = "default_recovery_file")]
+    pub recovery_file: String,
+}
+
+impl SessionSettings {
+    /// Whether a dictation session is kept at all
+    pub fn autosave_enabled(&self) -> bool {
+        self.autosave_secs > 0 || self.autosave_chars > 0
+    }
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        SessionSettings {
+            autosave_secs: default_autosave_secs(),
+            autosave_chars: default_autosave_chars(),
+            recovery_file: default_recovery_file(),
+        }
+    }
+}
+
+fn default_autosave_secs() -> u64 {
+    0
+}
+
+fn default_autosave_chars() -> usize {
+    0
+}
+
+/// `session_recovery.txt` in the platform config dir, or the working directory
+/// on platforms without one
+fn default_recovery_file() -> String {
+    dirs::config_dir()
+        .map(|dir| dir.join("rusty-scribe").join("session_recovery.txt"))
+        .unwrap_or_else(|| PathBuf::from("session_recovery.txt"))
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct CaptionSettings {
+    /// Length of audio sent to Whisper for each caption update
+    #[serde(default = "default_caption_window_secs")]
+    pub window_secs: u64,
+    /// A new window starts this often; the rest of each window overlaps the
+    /// previous one so words cut at a boundary are heard whole once
+    #[serde(default = "default_caption_step_secs")]
+    pub step_secs: u64,
+    /// The caption shows at most this many trailing characters of the transcript
+    #[serde(default = "default_caption_max_chars")]
+    pub max_chars: usize,
+    /// Rewritten with the current caption on every update, for overlays to display
+    #[serde(default)]
+    pub output_file: Option<String>,
+}
+
+impl Default for CaptionSettings {
+    fn default() -> Self {
+        CaptionSettings {
+            window_secs: default_caption_window_secs(),
+            step_secs: default_caption_step_secs(),
+            max_chars: default_caption_max_chars(),
+            output_file: None,
+        }
+    }
+}
+
+fn default_caption_window_secs() -> u64 {
+    6
+}
+
+fn default_caption_step_secs() -> u64 {
+    4
+}
+
+fn default_caption_max_chars() -> usize {
+    120
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct DebugSettings {
+    /// Log every request (credentials redacted, audio omitted) and its response,
+    /// for diagnosing self-hosted backends
+    #[serde(default)]
+    pub log_http: bool,
+}
+
+/// How log lines are written to stderr
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// env_logger's usual human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line: `ts`, `level`, `target`, `msg`
+    Json,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct LogSettings {
+    #[serde(default)]
+    pub format: LogFormat,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AppSettings {
+    /// Record and deliver as usual, but answer with canned text instead of
+    /// calling Whisper or the LLM (also `--dry-run`)
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct HistorySettings {
+    /// Append every delivered transcription to the history log
+    #[serde(default)]
+    pub enabled: bool,
+    /// JSONL file of the log; unset uses history.jsonl in the platform config dir
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AnalyticsSettings {
+    /// SQLite database recording per-transcription stats; unset disables analytics
+    #[serde(default)]
+    pub db_path: Option<String>,
 }
 
-pub fn load_config() -> Result<Config> {
-    let config_content = fs::read_to_string("config.toml")
-        .context("Unable to read config.toml. Ensure the file exists in the project root.")?;
-    let config: Config = toml::from_str(&config_content)
-        .context("Error parsing config.toml. Please check the file's syntax.")?;
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct PrivacySettings {
+    /// Ask before sending audio or text to an endpoint that is not trusted yet
+    #[serde(default)]
+    pub confirm_before_hosted: bool,
+    /// Hosts that never need confirmation, e.g. "localhost" or "whisper.internal"
+    #[serde(default)]
+    pub trusted_hosts: Vec<String>,
+    /// Where endpoints accepted at the prompt are remembered across sessions
+    #[serde(default = "default_consent_file")]
+    pub consent_file: String,
+    /// Keep recordings in memory only; audio is never written to disk, not even
+    /// as a temporary WAV
+    #[serde(default)]
+    pub memory_only: bool,
+}
+
+impl Default for PrivacySettings {
+    fn default() -> Self {
+        PrivacySettings {
+            confirm_before_hosted: false,
+            trusted_hosts: Vec::new(),
+            consent_file: default_consent_file(),
+            memory_only: false,
+        }
+    }
+}
+
+/// `hosted_consent.json` in the platform config dir, next to the history log,
+/// or the working directory on platforms without one
+fn default_consent_file() -> String {
+    dirs::config_dir()
+        .map(|dir| dir.join("rusty-scribe").join("hosted_consent.json"))
+        .unwrap_or_else(|| PathBuf::from("hosted_consent.json"))
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn default_rate_limit_notify_interval_mins() -> u64 {
+    10
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Environment variable naming the config file when `--config` is not given
+pub const CONFIG_PATH_ENV: &str = "RUSTY_SCRIBE_CONFIG";
+
+/// Picks the config file: `explicit` (from `--config`), then `env`, then
+/// `platform` if that file exists, and finally `config.toml` in the current
+/// directory
+pub fn resolve_config_path(explicit: Option<PathBuf>, env: Option<OsString>, platform: Option<PathBuf>) -> PathBuf {
+    explicit
+        .or_else(|| env.filter(|path| !path.is_empty()).map(PathBuf::from))
+        .or_else(|| platform.filter(|path| path.is_file()))
+        .unwrap_or_else(|| PathBuf::from("config.toml"))
+}
+
+/// `rusty-scribe/config.toml` in the platform config directory, e.g.
+/// `~/.config/rusty-scribe/config.toml` on Linux
+pub fn platform_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("rusty-scribe").join("config.toml"))
+}
+
+/// Config file to use, see `resolve_config_path`
+pub fn config_path(explicit: Option<PathBuf>) -> PathBuf {
+    resolve_config_path(explicit, std::env::var_os(CONFIG_PATH_ENV), platform_config_path())
+}
+
+/// Sections a `[profiles.<name>]` table may override
+const PROFILE_SECTIONS: [&str; 2] = ["llm", "audio"];
+
+/// Copies the fields of a profile's `llm`/`audio` tables over the base
+/// sections; fields the profile leaves out keep their base values
+fn apply_profile(root: &mut toml::value::Table, name: &str, profile: &toml::Value) -> Result<()> {
+    let profile = profile
+        .as_table()
+        .with_context(|| format!("profiles.{} must be a table", name))?;
+    for (section, overrides) in profile {
+        if !PROFILE_SECTIONS.contains(&section.as_str()) {
+            return Err(anyhow::anyhow!(
+                "profiles.{}.{} is not supported; profiles override only [llm] and [audio]",
+                name,
+                section
+            ));
+        }
+        let overrides = overrides
+            .as_table()
+            .with_context(|| format!("profiles.{}.{} must be a table", name, section))?;
+        let base = root
+            .entry(section.clone())
+            .or_insert_with(|| toml::Value::Table(toml::value::Table::new()))
+            .as_table_mut()
+            .with_context(|| format!("{} must be a table", section))?;
+        for (key, value) in overrides {
+            base.insert(key.clone(), value.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Parses a config file, merging the selected `[profiles.<name>]` over the
+/// base `[llm]` and `[audio]` sections. `profile` (from `--profile`) wins over
+/// the file's `default_profile`; naming a profile that is not defined is an error.
+pub fn parse_config(content: &str, profile: Option<&str>) -> Result<Config> {
+    let mut value: toml::Value = toml::from_str(content).context("Please check the file's syntax")?;
+    let root = value.as_table_mut().context("The config file must be a table")?;
+    let profiles = root.remove("profiles");
+    let default_profile = root.remove("default_profile");
+    let selected = match profile {
+        Some(name) => Some(name.to_string()),
+        None => default_profile
+            .map(|name| name.as_str().map(str::to_string).context("default_profile must be a string"))
+            .transpose()?,
+    };
+
+    if let Some(name) = selected {
+        let defined: Vec<String> = profiles
+            .as_ref()
+            .and_then(toml::Value::as_table)
+            .map(|table| table.keys().cloned().collect())
+            .unwrap_or_default();
+        let chosen = profiles.as_ref().and_then(|profiles| profiles.get(&name)).with_context(|| {
+            let defined = if defined.is_empty() { "none".to_string() } else { defined.join(", ") };
+            format!("Profile '{}' is not defined; available profiles: {}", name, defined)
+        })?;
+        apply_profile(root, &name, chosen)?;
+        info!("Using profile '{}'", name);
+    }
+    value.try_into().context("Please check the file's syntax")
+}
+
+pub fn load_config_from(path: Option<PathBuf>, profile: Option<&str>) -> Result<Config> {
+    let path = config_path(path);
+    let config_content = fs::read_to_string(&path).with_context(|| {
+        format!(
+            "Unable to read {}. Create it, or point --config or {} at your config file.",
+            path.display(),
+            CONFIG_PATH_ENV
+        )
+    })?;
+    let mut config =
+        parse_config(&config_content, profile).with_context(|| format!("Error parsing {}", path.display()))?;
+    config
+        .validate()
+        .with_context(|| format!("Invalid setting in {}", path.display()))?;
+    // Only the hosted endpoints need a key, so a missing one is not fatal here
+    match resolve_api_key(&config) {
+        Ok(key) => config.api_keys.openai = key,
+        Err(e) => warn!("{}", e),
+    }
+    if config.api_keys.anthropic.trim().is_empty() {
+        if let Ok(key) = std::env::var(ANTHROPIC_API_KEY_ENV) {
+            config.api_keys.anthropic = key.trim().to_string();
+        }
+    }
+    if config.config_version.unwrap_or(1) < CURRENT_CONFIG_VERSION {
+        warn!(
+            "{} uses an older format; run `rusty-scribe migrate-config` to upgrade it",
+            path.display()
+        );
+    }
     Ok(config)
 }
 
@@ -77,6 +1251,7 @@ mod tests {
 
             [api_keys]
             openai = "test_openai_api_key"
+            anthropic = "test_anthropic_api_key"
         "#;
         write!(temp_file, "{}", config_content).expect("Failed to write to temp file");
 
@@ -94,29 +1269,88 @@ mod tests {
         fs::copy(&temp_path, original_config).expect("Failed to copy temp config to config.toml");
 
         // Load config
-        let loaded_config = load_config().expect("Failed to load config");
+        let loaded_config = load_config_from(Some(PathBuf::from(original_config)), None).expect("Failed to load config");
 
         // Define expected config
         let expected_config = Config {
+            config_version: None,
             endpoints: Endpoints {
                 local_whisper: "http://localhost:5000/transcribe".to_string(),
                 hosted_whisper: "https://api.openai.com/v1/audio/transcriptions".to_string(),
                 llm_endpoint: "https://api.openai.com/v1/engines/davinci/completions".to_string(),
+                translation: "https://api.openai.com/v1/audio/translations".to_string(),
             },
             hotkeys: Hotkeys {
                 recording: "Shift+Space".to_string(),
                 post_processing_modifier: "Control".to_string(),
+                set_target_window: None,
+                release_grace_ms: 0,
+                debounce_ms: 0,
+                tap_threshold_ms: 250,
+                capture_selection: None,
+                force_hosted: None,
+                force_local: None,
+                disable_in_fullscreen: false,
+                clear_clipboard: None,
+                toggle_post_processing: None,
+                mode: HotkeyMode::Hybrid,
             },
             audio: AudioSettings {
                 recording_device: "default".to_string(),
+                retry_empty_recording: false,
+                follow_default_device: true,
+                fallback_to_default: false,
+                translate: false,
+                channel_selection: Vec::new(),
+                silent_device_check: true,
+                silent_device_check_interval_mins: 30,
+                preprocessing: Vec::new(),
+                target_sample_rate: 16000,
+                silence_threshold: 0.01,
+                silence_timeout_ms: 0,
+                max_recording_secs: 300,
+                normalize: true,
+                timestamp_granularities: vec![TimestampGranularity::Word],
+                response_format: ResponseFormat::Json,
             },
             llm: LLMSettings {
                 post_processing_prompt: "Please clean up and format the following text:".to_string(),
                 always_post_process: false,
+                skip_post_processing_languages: Vec::new(),
+                summarize: false,
+                summary_prompt: default_summary_prompt(),
+                provider: LlmProviderKind::OpenAi,
+                model: None,
+                max_tokens: 150,
+                temperature: 0.7,
+                max_calls_per_session: 0,
+                record_diff: false,
+                notify_diff: false,
+                api_style: LlmApiStyle::Completions,
+                prompts_by_language: HashMap::new(),
+                default_prompt_language: None,
             },
             api_keys: ApiKeys {
                 openai: "test_openai_api_key".to_string(),
+                anthropic: "test_anthropic_api_key".to_string(),
             },
+            output: OutputSettings::default(),
+            webhook: None,
+            device_profiles: Vec::new(),
+            network: NetworkSettings::default(),
+            transcription: TranscriptionSettings::default(),
+            general: GeneralSettings::default(),
+            ui: UiSettings::default(),
+            privacy: PrivacySettings::default(),
+            analytics: AnalyticsSettings::default(),
+            timeouts: TimeoutSettings::default(),
+            session: SessionSettings::default(),
+            clipboard: ClipboardSettings::default(),
+            debug: DebugSettings::default(),
+            captions: CaptionSettings::default(),
+            history: HistorySettings::default(),
+            app: AppSettings::default(),
+            log: LogSettings::default(),
         };
 
         assert_eq!(loaded_config, expected_config);
@@ -128,4 +1362,280 @@ mod tests {
             fs::remove_file(original_config).expect("Failed to remove temp config.toml");
         }
     }
+
+    fn llm_settings(always_post_process: bool) -> LLMSettings {
+        LLMSettings {
+            post_processing_prompt: "Clean up:".to_string(),
+            always_post_process,
+            skip_post_processing_languages: Vec::new(),
+            summarize: false,
+            summary_prompt: default_summary_prompt(),
+            provider: LlmProviderKind::default(),
+            model: None,
+            max_tokens: default_llm_max_tokens(),
+            temperature: default_llm_temperature(),
+            max_calls_per_session: 0,
+            record_diff: false,
+            notify_diff: false,
+            api_style: LlmApiStyle::default(),
+            prompts_by_language: HashMap::new(),
+            default_prompt_language: None,
+        }
+    }
+
+    #[test]
+    fn test_audio_preprocessing_steps_parse_in_order() {
+        let audio: AudioSettings = toml::from_str(
+            r#"
+            recording_device = "default"
+
+            [[preprocessing]]
+            step = "noise_gate"
+            threshold = 0.02
+
+            [[preprocessing]]
+            step = "downmix"
+
+            [[preprocessing]]
+            step = "normalize"
+            peak = 0.9
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            audio.preprocessing,
+            vec![
+                AudioStep::NoiseGate { threshold: 0.02 },
+                AudioStep::Downmix,
+                AudioStep::Normalize { peak: 0.9 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_post_process_skips_listed_languages() {
+        let mut llm = llm_settings(true);
+        llm.skip_post_processing_languages = vec!["de".to_string(), "ja".to_string()];
+
+        assert!(!llm.should_post_process(false, Some("de")));
+        assert!(!llm.should_post_process(true, Some("DE-at")));
+        assert!(llm.should_post_process(false, Some("en")));
+        assert!(llm.should_post_process(false, None));
+    }
+
+    #[test]
+    fn test_prompt_for_falls_back_to_default_language() {
+        let mut llm = llm_settings(true);
+        llm.prompts_by_language = HashMap::from([
+            ("de".to_string(), "Bereinige:".to_string()),
+            ("fr".to_string(), "Nettoie :".to_string()),
+        ]);
+
+        assert_eq!(llm.prompt_for(Some("fr-CA")), "Nettoie :");
+        assert_eq!(llm.prompt_for(None), "Clean up:");
+
+        llm.default_prompt_language = Some("de".to_string());
+        assert_eq!(llm.prompt_for(None), "Bereinige:");
+        // A detected language still wins, and one without a prompt gets the generic one
+        assert_eq!(llm.prompt_for(Some("fr")), "Nettoie :");
+        assert_eq!(llm.prompt_for(Some("en")), "Clean up:");
+    }
+
+    #[test]
+    fn test_config_path_precedence() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let platform = dir.path().join("rusty-scribe").join("config.toml");
+        let explicit = || Some(PathBuf::from("/etc/scribe.toml"));
+        let env = || Some(OsString::from("/srv/scribe.toml"));
+
+        assert_eq!(resolve_config_path(explicit(), env(), Some(platform.clone())), PathBuf::from("/etc/scribe.toml"));
+        assert_eq!(resolve_config_path(None, env(), Some(platform.clone())), PathBuf::from("/srv/scribe.toml"));
+        // The platform location only counts once the file exists
+        assert_eq!(resolve_config_path(None, None, Some(platform.clone())), PathBuf::from("config.toml"));
+        fs::create_dir_all(platform.parent().unwrap()).unwrap();
+        fs::write(&platform, "").unwrap();
+        assert_eq!(resolve_config_path(None, Some(OsString::new()), Some(platform.clone())), platform);
+    }
+
+    #[test]
+    fn test_missing_config_error_names_path() {
+        let err = load_config_from(Some(PathBuf::from("/nonexistent/scribe.toml")), None).unwrap_err();
+        assert!(err.to_string().starts_with("Unable to read /nonexistent/scribe.toml."));
+    }
+
+    #[test]
+    fn test_api_key_falls_back_from_config_to_env_to_keyring() {
+        let env = || Some("sk-env".to_string());
+        let keyring = || Some("sk-keyring".to_string());
+
+        assert_eq!(resolve_api_key_from("sk-config", env(), keyring).unwrap(), "sk-config");
+        assert_eq!(resolve_api_key_from("", env(), keyring).unwrap(), "sk-env");
+        assert_eq!(resolve_api_key_from("  ", Some(String::new()), keyring).unwrap(), "sk-keyring");
+        // The keyring is only asked when nothing earlier had a key
+        assert_eq!(
+            resolve_api_key_from("sk-config", None, || panic!("keyring consulted")).unwrap(),
+            "sk-config"
+        );
+    }
+
+    #[test]
+    fn test_missing_api_key_names_every_source() {
+        let api_keys: ApiKeys = toml::from_str("openai = \"\"").unwrap();
+        let err = resolve_api_key_from(&api_keys.openai, None, || None).unwrap_err().to_string();
+        assert!(err.contains("api_keys.openai"));
+        assert!(err.contains(OPENAI_API_KEY_ENV));
+        assert!(err.contains(KEYRING_SERVICE));
+    }
+
+    fn template_config() -> Config {
+        toml::from_str(crate::init::CONFIG_TEMPLATE).unwrap()
+    }
+
+    const PROFILES: &str = r#"
+        default_profile = "email"
+
+        [profiles.code.llm]
+        post_processing_prompt = "Terse:"
+        always_post_process = false
+
+        [profiles.email.llm]
+        post_processing_prompt = "Write this up as a polite email:"
+        always_post_process = true
+        temperature = 0.9
+
+        [profiles.email.audio]
+        silence_timeout_ms = 1500
+    "#;
+
+    #[test]
+    fn test_profile_fields_override_and_others_are_inherited() {
+        let content = format!("{}\n{}", PROFILES, crate::init::CONFIG_TEMPLATE);
+        let base = template_config();
+
+        let email = parse_config(&content, None).unwrap();
+        assert_eq!(email.llm.post_processing_prompt, "Write this up as a polite email:");
+        assert!(email.llm.always_post_process);
+        assert_eq!(email.llm.temperature, 0.9);
+        assert_eq!(email.audio.silence_timeout_ms, 1500);
+        assert_eq!(email.llm.max_tokens, base.llm.max_tokens);
+        assert_eq!(email.audio.recording_device, base.audio.recording_device);
+        assert_eq!(email.hotkeys, base.hotkeys);
+
+        // --profile wins over default_profile
+        let code = parse_config(&content, Some("code")).unwrap();
+        assert_eq!(code.llm.post_processing_prompt, "Terse:");
+        assert!(!code.llm.always_post_process);
+        assert_eq!(code.llm.temperature, base.llm.temperature);
+        assert_eq!(code.audio, base.audio);
+    }
+
+    #[test]
+    fn test_unknown_profile_is_rejected() {
+        let content = format!("{}\n{}", PROFILES, crate::init::CONFIG_TEMPLATE);
+        let err = parse_config(&content, Some("chat")).unwrap_err().to_string();
+        assert_eq!(err, "Profile 'chat' is not defined; available profiles: code, email");
+
+        let err = parse_config(crate::init::CONFIG_TEMPLATE, Some("code")).unwrap_err().to_string();
+        assert_eq!(err, "Profile 'code' is not defined; available profiles: none");
+        assert_eq!(parse_config(crate::init::CONFIG_TEMPLATE, None).unwrap(), template_config());
+    }
+
+    #[test]
+    fn test_validate_accepts_template() {
+        template_config().validate().unwrap();
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_endpoint() {
+        let mut config = template_config();
+        config.endpoints.hosted_whisper = "api.openai.com/v1/audio".to_string();
+        let err = config.validate().unwrap_err().to_string();
+        assert_eq!(err, "endpoints.hosted_whisper = 'api.openai.com/v1/audio' is not a valid URL");
+
+        config.endpoints.hosted_whisper = "ftp://example.com/whisper".to_string();
+        let err = config.validate().unwrap_err().to_string();
+        assert_eq!(err, "endpoints.hosted_whisper = 'ftp://example.com/whisper' must be an http:// or https:// URL");
+    }
+
+    #[test]
+    fn test_validate_rejects_unrecognized_hotkey() {
+        let mut config = template_config();
+        config.hotkeys.recording = "".to_string();
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "hotkeys.recording = '' contains no recognized keys"
+        );
+
+        config.hotkeys.recording = "Control+Foo".to_string();
+        let err = config.validate().unwrap_err();
+        assert_eq!(err.to_string(), "hotkeys.recording = 'Control+Foo' is not a valid hotkey");
+        assert!(err.root_cause().to_string().starts_with("Unknown key 'Foo'"));
+    }
+
+    #[test]
+    fn test_validate_requires_prompt_for_always_post_process() {
+        let mut config = template_config();
+        config.llm.post_processing_prompt = "  ".to_string();
+        config.validate().unwrap();
+
+        config.llm.always_post_process = true;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.starts_with("llm.post_processing_prompt is empty"));
+    }
+
+    #[test]
+    fn test_validate_rejects_disk_features_in_memory_only_mode() {
+        let mut config = template_config();
+        config.privacy.memory_only = true;
+        config.validate().unwrap();
+
+        let assert_conflict = |mut conflicting: Config, name: &str| {
+            let err = conflicting.validate().unwrap_err().to_string();
+            assert!(err.contains(name), "{}: {}", name, err);
+            conflicting.privacy.memory_only = false;
+            conflicting.validate().unwrap();
+        };
+
+        let mut command = config.clone();
+        command.transcription.command = Some(vec!["whisper-cli".to_string()]);
+        assert_conflict(command, "transcription.command");
+
+        let mut diarize = config;
+        diarize.transcription.diarize = true;
+        assert_conflict(diarize, "transcription.diarize");
+    }
+
+    #[test]
+    fn test_validate_rejects_invalid_homophone_guard() {
+        let mut config = template_config();
+        config.transcription.homophones = vec![HomophoneRule {
+            from: "there".to_string(),
+            to: "their".to_string(),
+            before: Some("(unclosed".to_string()),
+            after: None,
+        }];
+        let err = config.validate().unwrap_err().to_string();
+        assert_eq!(err, "Invalid 'before' guard for homophone 'there'");
+    }
+
+    #[test]
+    fn test_validate_rejects_out_of_range_temperature() {
+        let mut config = template_config();
+        config.llm.temperature = 2.0;
+        config.validate().unwrap();
+
+        config.llm.temperature = 2.5;
+        let err = config.validate().unwrap_err().to_string();
+        assert_eq!(err, "llm.temperature = 2.5 is out of range; use a value from 0.0 to 2.0");
+        config.llm.temperature = -0.1;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_should_post_process_without_skip_list() {
+        let llm = llm_settings(false);
+
+        assert!(!llm.should_post_process(false, Some("en")));
+        assert!(llm.should_post_process(true, Some("en")));
+    }
 }
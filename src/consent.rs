@@ -0,0 +1,175 @@
+use anyhow::{Result, Context};
+use dialoguer::Confirm;
+use log::info;
+use reqwest::Url;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::PrivacySettings;
+
+/// Endpoints the user has already agreed to send data to, persisted as a JSON list
+#[derive(Debug)]
+pub struct ConsentStore {
+    path: PathBuf,
+    accepted: BTreeSet<String>,
+}
+
+impl ConsentStore {
+    /// Loads the store, treating a missing file as "nothing accepted yet"
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let accepted = if path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read consent file at {}", path.display()))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse consent file at {}", path.display()))?
+        } else {
+            BTreeSet::new()
+        };
+        Ok(ConsentStore { path, accepted })
+    }
+
+    pub fn is_accepted(&self, endpoint: &str) -> bool {
+        self.accepted.contains(endpoint)
+    }
+
+    /// Records the endpoint and writes the store back to disk, creating its
+    /// directory on first use
+    pub fn accept(&mut self, endpoint: &str) -> Result<()> {
+        self.accepted.insert(endpoint.to_string());
+        let content = serde_json::to_string_pretty(&self.accepted)?;
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create consent directory {}", dir.display()))?;
+        }
+        fs::write(&self.path, content)
+            .with_context(|| format!("Failed to write consent file at {}", self.path.display()))
+    }
+}
+
+/// The user chose not to send data to `endpoint`. A recording that fails with
+/// this is dropped quietly instead of being kept for retry or reported.
+#[derive(Debug)]
+pub struct Declined {
+    pub endpoint: String,
+}
+
+impl fmt::Display for Declined {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Sending data to {} was declined", self.endpoint)
+    }
+}
+
+impl std::error::Error for Declined {}
+
+/// Whether `err` comes from a declined confirmation
+pub fn is_declined(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| cause.is::<Declined>())
+}
+
+fn is_trusted_host(endpoint: &str, trusted_hosts: &[String]) -> bool {
+    Url::parse(endpoint)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase))
+        .map(|host| trusted_hosts.iter().any(|trusted| trusted.eq_ignore_ascii_case(&host)))
+        .unwrap_or(false)
+}
+
+/// Returns whether data may be sent to `endpoint`. Trusted hosts and endpoints
+/// accepted in an earlier session pass without prompting; otherwise `prompt` is
+/// asked once and a "yes" is remembered.
+pub fn confirm_endpoint<F>(
+    endpoint: &str,
+    settings: &PrivacySettings,
+    store: &mut ConsentStore,
+    prompt: F,
+) -> Result<bool>
+where
+    F: FnOnce(&str) -> Result<bool>,
+{
+    if !settings.confirm_before_hosted
+        || is_trusted_host(endpoint, &settings.trusted_hosts)
+        || store.is_accepted(endpoint)
+    {
+        return Ok(true);
+    }
+
+    if prompt(endpoint)? {
+        store.accept(endpoint)?;
+        info!("Remembered consent for {}", endpoint);
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Asks on the terminal whether recordings may be sent to `endpoint`
+pub fn prompt_terminal(endpoint: &str) -> Result<bool> {
+    Confirm::new()
+        .with_prompt(format!(
+            "Recordings will be sent to {}. Allow this endpoint from now on?",
+            endpoint
+        ))
+        .default(false)
+        .interact()
+        .context("Failed to read confirmation")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const HOSTED: &str = "https://api.openai.com/v1/audio/transcriptions";
+
+    fn settings(trusted_hosts: &[&str]) -> PrivacySettings {
+        PrivacySettings {
+            confirm_before_hosted: true,
+            trusted_hosts: trusted_hosts.iter().map(|h| h.to_string()).collect(),
+            ..PrivacySettings::default()
+        }
+    }
+
+    #[test]
+    fn test_accepted_endpoint_skips_prompt_on_fresh_run() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("consent.json");
+        let settings = settings(&[]);
+
+        let mut store = ConsentStore::load(&path).unwrap();
+        assert!(confirm_endpoint(HOSTED, &settings, &mut store, |_| Ok(true)).unwrap());
+
+        // A new session reloads the store from disk
+        let mut store = ConsentStore::load(&path).unwrap();
+        let allowed = confirm_endpoint(HOSTED, &settings, &mut store, |_| {
+            panic!("should not prompt for an accepted endpoint")
+        });
+        assert!(allowed.unwrap());
+    }
+
+    #[test]
+    fn test_declined_endpoint_is_not_remembered() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("consent.json");
+        let settings = settings(&[]);
+
+        let mut store = ConsentStore::load(&path).unwrap();
+        assert!(!confirm_endpoint(HOSTED, &settings, &mut store, |_| Ok(false)).unwrap());
+        assert!(!ConsentStore::load(&path).unwrap().is_accepted(HOSTED));
+    }
+
+    #[test]
+    fn test_trusted_host_and_disabled_setting_skip_prompt() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let mut store = ConsentStore::load(dir.path().join("consent.json")).unwrap();
+        let never = |_: &str| -> Result<bool> { panic!("should not prompt") };
+
+        let trusted = settings(&["API.openai.com"]);
+        assert!(confirm_endpoint(HOSTED, &trusted, &mut store, never).unwrap());
+
+        let disabled = PrivacySettings::default();
+        assert!(confirm_endpoint(HOSTED, &disabled, &mut store, never).unwrap());
+    }
+}
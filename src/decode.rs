@@ -0,0 +1,217 @@
+use anyhow::{Result, Context};
+use log::{info, warn};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Container/codec family of an audio file, as detected from its contents or extension
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioFormat {
+    Wav,
+    Mp3,
+    Flac,
+    Ogg,
+    M4a,
+    Unknown(String),
+}
+
+impl AudioFormat {
+    fn extension(&self) -> Option<&str> {
+        match self {
+            AudioFormat::Wav => Some("wav"),
+            AudioFormat::Mp3 => Some("mp3"),
+            AudioFormat::Flac => Some("flac"),
+            AudioFormat::Ogg => Some("ogg"),
+            AudioFormat::M4a => Some("m4a"),
+            AudioFormat::Unknown(_) => None,
+        }
+    }
+}
+
+/// Interleaved 16-bit samples decoded from an audio file
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedAudio {
+    pub samples: Vec<i16>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// Detects the audio format from the file's magic bytes, falling back to its extension
+pub fn detect_audio_format(path: &Path, header: &[u8]) -> AudioFormat {
+    if header.len() >= 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"WAVE" {
+        return AudioFormat::Wav;
+    }
+    if header.starts_with(b"fLaC") {
+        return AudioFormat::Flac;
+    }
+    if header.starts_with(b"OggS") {
+        return AudioFormat::Ogg;
+    }
+    if header.len() >= 8 && &header[4..8] == b"ftyp" {
+        return AudioFormat::M4a;
+    }
+    if header.starts_with(b"ID3") || (header.len() >= 2 && header[0] == 0xFF && header[1] & 0xE0 == 0xE0) {
+        return AudioFormat::Mp3;
+    }
+
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "wav" | "wave" => AudioFormat::Wav,
+        "mp3" => AudioFormat::Mp3,
+        "flac" => AudioFormat::Flac,
+        "ogg" | "oga" => AudioFormat::Ogg,
+        "m4a" | "mp4" | "aac" => AudioFormat::M4a,
+        _ => AudioFormat::Unknown(extension),
+    }
+}
+
+/// Decodes a WAV, MP3, FLAC, Ogg Vorbis or M4A (AAC) file into interleaved i16 samples
+pub fn decode_audio_file(path: &Path) -> Result<DecodedAudio> {
+    let mut header = [0u8; 12];
+    let header_len = File::open(path)
+        .and_then(|mut f| f.read(&mut header))
+        .with_context(|| format!("Failed to read audio file at {}", path.display()))?;
+    let format = detect_audio_format(path, &header[..header_len]);
+    info!("Detected {:?} audio in {}", format, path.display());
+
+    let unsupported = |reason: String| {
+        anyhow::anyhow!(
+            "Unsupported audio format {:?} in {}: {}",
+            format,
+            path.display(),
+            reason
+        )
+    };
+
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open audio file at {}", path.display()))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = format.extension() {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| unsupported(e.to_string()))?;
+    let mut reader = probed.format;
+
+    let track = reader
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| unsupported("no decodable audio track".to_string()))?;
+    let track_id = track.id;
+    let mut sample_rate = track.codec_params.sample_rate.unwrap_or(0);
+    let mut channels = track.codec_params.channels.map(|c| c.count() as u16).unwrap_or(0);
+
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| unsupported(e.to_string()))?;
+
+    let mut samples = Vec::new();
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(anyhow::anyhow!("Failed to read audio packet: {}", e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let spec = *decoded.spec();
+                sample_rate = spec.rate;
+                channels = spec.channels.count() as u16;
+                let mut buffer = SampleBuffer::<i16>::new(decoded.capacity() as u64, spec);
+                buffer.copy_interleaved_ref(decoded);
+                samples.extend_from_slice(buffer.samples());
+            }
+            Err(SymphoniaError::DecodeError(e)) => {
+                warn!("Skipping undecodable audio packet: {}", e);
+            }
+            Err(e) => return Err(anyhow::anyhow!("Failed to decode audio: {}", e)),
+        }
+    }
+
+    Ok(DecodedAudio {
+        samples,
+        sample_rate,
+        channels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use tempfile::NamedTempFile;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+    }
+
+    #[test]
+    fn test_detect_audio_format_by_magic_bytes() {
+        let path = Path::new("recording.bin");
+        assert_eq!(detect_audio_format(path, b"RIFF\0\0\0\0WAVEfmt "), AudioFormat::Wav);
+        assert_eq!(detect_audio_format(path, b"fLaC\0\0\0\x22"), AudioFormat::Flac);
+        assert_eq!(detect_audio_format(path, b"OggS\0\x02"), AudioFormat::Ogg);
+        assert_eq!(detect_audio_format(path, b"\0\0\0\x20ftypM4A "), AudioFormat::M4a);
+        assert_eq!(detect_audio_format(path, b"ID3\x04\0"), AudioFormat::Mp3);
+        assert_eq!(detect_audio_format(path, &[0xFF, 0xFB, 0x90, 0xC0]), AudioFormat::Mp3);
+    }
+
+    #[test]
+    fn test_detect_audio_format_falls_back_to_extension() {
+        assert_eq!(detect_audio_format(Path::new("memo.M4A"), b""), AudioFormat::M4a);
+        assert_eq!(
+            detect_audio_format(Path::new("memo.xyz"), b"garbage"),
+            AudioFormat::Unknown("xyz".to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_flac_fixture() {
+        let decoded = decode_audio_file(&fixture("ramp.flac")).expect("Failed to decode FLAC");
+
+        assert_eq!(decoded.sample_rate, 8000);
+        assert_eq!(decoded.channels, 1);
+        let expected: Vec<i16> = (0..160).map(|i| (i * 200 - 16000) as i16).collect();
+        assert_eq!(decoded.samples, expected);
+    }
+
+    #[test]
+    fn test_decode_mp3_fixture() {
+        let decoded = decode_audio_file(&fixture("silence.mp3")).expect("Failed to decode MP3");
+
+        assert_eq!(decoded.sample_rate, 44100);
+        assert_eq!(decoded.channels, 1);
+        assert!(!decoded.samples.is_empty());
+        assert!(decoded.samples.iter().all(|&s| s == 0));
+    }
+
+    #[test]
+    fn test_decode_unsupported_file_names_format() {
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(temp_file, "definitely not audio").expect("Failed to write to temp file");
+
+        let err = decode_audio_file(temp_file.path()).unwrap_err();
+        assert!(err.to_string().starts_with("Unsupported audio format Unknown"), "{}", err);
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::Result;
+use log::{info, warn};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::audio::record_audio;
+use crate::notify::Notifier;
+
+/// Peak level (fraction of full scale) below which a probe counts as silent.
+/// Even a quiet room puts a live microphone well above this.
+pub const SILENT_DEVICE_FLOOR: f32 = 0.0005;
+
+pub const SILENT_DEVICE_TITLE: &str = "Microphone appears silent";
+pub const SILENT_DEVICE_BODY: &str =
+    "The recording device is only producing silence. Check that it is not muted and that the system routes the right input.";
+
+/// Whether every sample stays below `floor` (a fraction of full scale)
+pub fn is_silent(samples: &[i16], floor: f32) -> bool {
+    let threshold = (floor.clamp(0.0, 1.0) * i16::MAX as f32) as i32;
+    samples.iter().all(|&s| (s as i32).abs() <= threshold)
+}
+
+/// Startup and periodic check for a device that records nothing but silence
+pub struct SilentDeviceGuard {
+    interval: Option<Duration>,
+    last_check: Option<Instant>,
+}
+
+impl SilentDeviceGuard {
+    /// `interval_mins` of 0 checks only once, at startup
+    pub fn new(interval_mins: u64) -> Self {
+        SilentDeviceGuard {
+            interval: (interval_mins > 0).then(|| Duration::from_secs(interval_mins * 60)),
+            last_check: None,
+        }
+    }
+
+    /// Whether a probe should be recorded at `now`
+    pub fn is_due(&self, now: Instant) -> bool {
+        match (self.last_check, self.interval) {
+            (None, _) => true,
+            (Some(last), Some(interval)) => now.saturating_duration_since(last) >= interval,
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Evaluates a probe recording, warning and notifying when it is all silence.
+    /// Returns whether the device looked silent.
+    pub fn check(&mut self, samples: &[i16], now: Instant, notifier: &dyn Notifier) -> bool {
+        self.last_check = Some(now);
+        if is_silent(samples, SILENT_DEVICE_FLOOR) {
+            warn!(
+                "Recording device produced only silence over {} samples; it may be muted or misrouted",
+                samples.len()
+            );
+            notifier.notify(SILENT_DEVICE_TITLE, SILENT_DEVICE_BODY);
+            true
+        } else {
+            info!("Recording device check passed");
+            false
+        }
+    }
+}
+
+/// Records a one-second probe from the device for `SilentDeviceGuard::check`
+pub fn record_probe(device_name: &str) -> Result<Vec<i16>> {
+    let (tx, rx) = mpsc::channel();
+    record_audio(device_name, &[], &[], 1, tx)?;
+    Ok(rx.try_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct RecordingNotifier {
+        sent: RefCell<Vec<String>>,
+    }
+
+    impl Notifier for RecordingNotifier {
+        fn notify(&self, title: &str, _body: &str) {
+            self.sent.borrow_mut().push(title.to_string());
+        }
+    }
+
+    #[test]
+    fn test_all_silence_probe_triggers_warning() {
+        let notifier = RecordingNotifier::default();
+        let mut guard = SilentDeviceGuard::new(30);
+
+        assert!(guard.check(&[0, 1, -2, 0, 3], Instant::now(), &notifier));
+        assert_eq!(*notifier.sent.borrow(), vec![SILENT_DEVICE_TITLE.to_string()]);
+    }
+
+    #[test]
+    fn test_live_probe_passes() {
+        let notifier = RecordingNotifier::default();
+        let mut guard = SilentDeviceGuard::new(30);
+
+        let room_noise: Vec<i16> = (0..1000).map(|i| ((i % 40) - 20) * 5).collect();
+        assert!(!guard.check(&room_noise, Instant::now(), &notifier));
+        assert!(notifier.sent.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_guard_schedule() {
+        let start = Instant::now();
+        let notifier = RecordingNotifier::default();
+
+        let mut periodic = SilentDeviceGuard::new(30);
+        assert!(periodic.is_due(start));
+        periodic.check(&[500], start, &notifier);
+        assert!(!periodic.is_due(start + Duration::from_secs(29 * 60)));
+        assert!(periodic.is_due(start + Duration::from_secs(30 * 60)));
+
+        let mut startup_only = SilentDeviceGuard::new(0);
+        startup_only.check(&[500], start, &notifier);
+        assert!(!startup_only.is_due(start + Duration::from_secs(24 * 3600)));
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::Result;
+use log::warn;
+use std::collections::HashMap;
+
+use crate::api::{transcribe_audio_diarized, Segment};
+
+/// Formats speaker-tagged segments as one "Speaker N: ..." line per turn.
+/// Speakers are numbered in order of first appearance, and consecutive segments
+/// from the same speaker are merged. Returns `None` unless every segment has a
+/// speaker label, so callers can fall back to the unlabeled text.
+pub fn format_speaker_segments(segments: &[Segment]) -> Option<String> {
+    if segments.is_empty() {
+        return None;
+    }
+
+    let mut numbers: HashMap<&str, usize> = HashMap::new();
+    let mut turns: Vec<(usize, Vec<&str>)> = Vec::new();
+    for segment in segments {
+        let label = segment.speaker.as_deref()?;
+        let next = numbers.len() + 1;
+        let number = *numbers.entry(label).or_insert(next);
+        let text = segment.text.trim();
+        match turns.last_mut() {
+            Some((speaker, texts)) if *speaker == number => texts.push(text),
+            _ => turns.push((number, vec![text])),
+        }
+    }
+
+    Some(
+        turns
+            .iter()
+            .map(|(speaker, texts)| format!("Speaker {}: {}", speaker, texts.join(" ")))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+/// Asks the diarization endpoint for labelled output, falling back to `unlabeled`
+/// when the request fails or the response carries no speaker labels
+pub fn diarized_text(url: &str, api_key: &str, audio_path: &str, unlabeled: &str) -> String {
+    let labeled: Result<Option<String>> = transcribe_audio_diarized(url, api_key, audio_path)
+        .map(|response| format_speaker_segments(&response.segments.unwrap_or_default()));
+    match labeled {
+        Ok(Some(text)) => text,
+        Ok(None) => {
+            warn!("Diarization endpoint returned no speaker labels; using unlabeled text");
+            unlabeled.to_string()
+        }
+        Err(e) => {
+            warn!("Diarization failed, using unlabeled text: {:?}", e);
+            unlabeled.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(speaker: Option<&str>, text: &str) -> Segment {
+        Segment {
+            text: text.to_string(),
+            start: 0.0,
+            end: 0.0,
+            speaker: speaker.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_format_speaker_segments() {
+        let segments = vec![
+            segment(Some("SPEAKER_01"), " Shall we start?"),
+            segment(Some("SPEAKER_00"), " Yes."),
+            segment(Some("SPEAKER_00"), " The numbers are in."),
+            segment(Some("SPEAKER_01"), " Great."),
+        ];
+        assert_eq!(
+            format_speaker_segments(&segments).unwrap(),
+            "Speaker 1: Shall we start?\nSpeaker 2: Yes. The numbers are in.\nSpeaker 1: Great."
+        );
+    }
+
+    #[test]
+    fn test_unlabeled_segments_fall_back() {
+        let segments = vec![segment(Some("A"), "Hello."), segment(None, "Hi.")];
+        assert_eq!(format_speaker_segments(&segments), None);
+        assert_eq!(format_speaker_segments(&[]), None);
+    }
+}
@@ -0,0 +1,100 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+use crate::config::LLMSettings;
+use crate::notify::Notifier;
+
+/// One edit made by post-processing, at word granularity
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum WordChange {
+    Inserted { words: String },
+    Removed { words: String },
+    Changed { from: String, to: String },
+}
+
+/// Word-level differences between the raw and post-processed text
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct WordDiff {
+    pub changes: Vec<WordChange>,
+}
+
+impl WordDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Number of separate edits, as shown in the notification
+    pub fn change_count(&self) -> usize {
+        self.changes.len()
+    }
+}
+
+/// Diffs two texts word by word, ignoring how the words are spaced
+pub fn word_diff(raw: &str, processed: &str) -> WordDiff {
+    let old: Vec<&str> = raw.split_whitespace().collect();
+    let new: Vec<&str> = processed.split_whitespace().collect();
+    let changes = capture_diff_slices(Algorithm::Myers, &old, &new)
+        .into_iter()
+        .filter_map(|op| match op {
+            DiffOp::Equal { .. } => None,
+            DiffOp::Delete { old_index, old_len, .. } => Some(WordChange::Removed {
+                words: old[old_index..old_index + old_len].join(" "),
+            }),
+            DiffOp::Insert { new_index, new_len, .. } => Some(WordChange::Inserted {
+                words: new[new_index..new_index + new_len].join(" "),
+            }),
+            DiffOp::Replace { old_index, old_len, new_index, new_len } => Some(WordChange::Changed {
+                from: old[old_index..old_index + old_len].join(" "),
+                to: new[new_index..new_index + new_len].join(" "),
+            }),
+        })
+        .collect();
+    WordDiff { changes }
+}
+
+/// Computes and reports the diff according to `llm.record_diff` / `notify_diff`.
+/// Returns the diff to store in the result, if recording is enabled.
+pub fn report_diff(raw: &str, processed: &str, llm: &LLMSettings, notifier: &dyn Notifier) -> Option<WordDiff> {
+    if !llm.record_diff && !llm.notify_diff {
+        return None;
+    }
+    let diff = word_diff(raw, processed);
+    info!("Post-processing made {} change(s): {:?}", diff.change_count(), diff.changes);
+    if llm.notify_diff {
+        notifier.notify(
+            "Post-processing",
+            &format!("{} change(s) to the transcription", diff.change_count()),
+        );
+    }
+    llm.record_diff.then_some(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_diff_finds_inserted_removed_and_changed_words() {
+        let diff = word_diff(
+            "um so the meeting is on tuesday at three",
+            "So the meeting is on Tuesday at three pm.",
+        );
+        assert_eq!(
+            diff.changes,
+            vec![
+                WordChange::Changed { from: "um so".to_string(), to: "So".to_string() },
+                WordChange::Changed { from: "tuesday".to_string(), to: "Tuesday".to_string() },
+                WordChange::Inserted { words: "pm.".to_string() },
+            ]
+        );
+
+        let diff = word_diff("send the  report now", "send the report");
+        assert_eq!(diff.changes, vec![WordChange::Removed { words: "now".to_string() }]);
+
+        let diff = word_diff("call me", "please call me");
+        assert_eq!(diff.changes, vec![WordChange::Inserted { words: "please".to_string() }]);
+        assert!(word_diff("same words", "same   words").is_empty());
+    }
+}
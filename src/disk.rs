@@ -0,0 +1,141 @@
+use anyhow::Result;
+use log::warn;
+use std::path::Path;
+
+use crate::config::{GeneralSettings, LowDiskAction};
+use crate::notify::Notifier;
+
+pub const LOW_DISK_TITLE: &str = "Low disk space";
+
+/// Where the next recording should be stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StoragePlan {
+    Disk,
+    Memory,
+}
+
+/// Free space in MiB on the filesystem holding `path`, if it can be determined
+#[cfg(unix)]
+pub fn free_disk_mb(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is NUL-terminated and stats is a valid, writable statvfs
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    Some(stats.f_bavail as u64 * stats.f_frsize as u64 / (1024 * 1024))
+}
+
+#[cfg(not(unix))]
+pub fn free_disk_mb(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Checks `general.min_free_disk_mb` before recording. On a low disk the user is
+/// notified and the recording either stays in memory or is refused up front,
+/// rather than failing halfway through the WAV write.
+pub fn plan_storage(
+    free_mb: Option<u64>,
+    settings: &GeneralSettings,
+    notifier: &dyn Notifier,
+) -> Result<StoragePlan> {
+    let free_mb = match free_mb {
+        Some(free_mb) if settings.min_free_disk_mb > 0 && free_mb < settings.min_free_disk_mb => free_mb,
+        _ => return Ok(StoragePlan::Disk),
+    };
+
+    warn!(
+        "Only {} MB free, below general.min_free_disk_mb = {}",
+        free_mb, settings.min_free_disk_mb
+    );
+    match settings.low_disk_action {
+        LowDiskAction::MemoryOnly => {
+            notifier.notify(
+                LOW_DISK_TITLE,
+                "The disk is almost full, so this recording is kept in memory only.",
+            );
+            Ok(StoragePlan::Memory)
+        }
+        LowDiskAction::Abort => {
+            notifier.notify(
+                LOW_DISK_TITLE,
+                "The disk is almost full, so recording was not started. Free up some space and try again.",
+            );
+            Err(anyhow::anyhow!(
+                "Not enough free disk space to record ({} MB free, {} MB required)",
+                free_mb,
+                settings.min_free_disk_mb
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct RecordingNotifier {
+        sent: RefCell<Vec<String>>,
+    }
+
+    impl Notifier for RecordingNotifier {
+        fn notify(&self, title: &str, _body: &str) {
+            self.sent.borrow_mut().push(title.to_string());
+        }
+    }
+
+    fn settings(min_free_disk_mb: u64, low_disk_action: LowDiskAction) -> GeneralSettings {
+        GeneralSettings {
+            min_free_disk_mb,
+            low_disk_action,
+            ..GeneralSettings::default()
+        }
+    }
+
+    #[test]
+    fn test_low_disk_switches_to_memory() {
+        let notifier = RecordingNotifier::default();
+        let plan = plan_storage(Some(50), &settings(200, LowDiskAction::MemoryOnly), &notifier);
+
+        assert_eq!(plan.unwrap(), StoragePlan::Memory);
+        assert_eq!(*notifier.sent.borrow(), vec![LOW_DISK_TITLE.to_string()]);
+    }
+
+    #[test]
+    fn test_low_disk_aborts_when_configured() {
+        let notifier = RecordingNotifier::default();
+        let err = plan_storage(Some(50), &settings(200, LowDiskAction::Abort), &notifier).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Not enough free disk space to record (50 MB free, 200 MB required)"
+        );
+        assert_eq!(notifier.sent.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_enough_disk_or_unknown_or_disabled_uses_disk() {
+        let notifier = RecordingNotifier::default();
+        let abort = settings(200, LowDiskAction::Abort);
+
+        assert_eq!(plan_storage(Some(500), &abort, &notifier).unwrap(), StoragePlan::Disk);
+        assert_eq!(plan_storage(None, &abort, &notifier).unwrap(), StoragePlan::Disk);
+        assert_eq!(
+            plan_storage(Some(0), &settings(0, LowDiskAction::Abort), &notifier).unwrap(),
+            StoragePlan::Disk
+        );
+        assert!(notifier.sent.borrow().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_free_disk_mb_reads_temp_dir() {
+        assert!(free_disk_mb(&std::env::temp_dir()).is_some());
+        assert!(free_disk_mb(Path::new("/definitely/not/a/mount")).is_none());
+    }
+}
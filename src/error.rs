@@ -0,0 +1,187 @@
+use reqwest::StatusCode;
+use std::fmt;
+
+use crate::api::ApiError;
+use crate::notify::Notifier;
+
+/// Failure of one stage of the recording pipeline
+#[derive(Debug)]
+pub enum ScribeError {
+    Device(anyhow::Error),
+    Capture(anyhow::Error),
+    Transcribe(anyhow::Error),
+    PostProcess(anyhow::Error),
+    Clipboard(anyhow::Error),
+}
+
+impl fmt::Display for ScribeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScribeError::Device(e) => write!(f, "Recording device error: {:#}", e),
+            ScribeError::Capture(e) => write!(f, "Audio capture error: {:#}", e),
+            ScribeError::Transcribe(e) => write!(f, "Transcription error: {:#}", e),
+            ScribeError::PostProcess(e) => write!(f, "Post-processing error: {:#}", e),
+            ScribeError::Clipboard(e) => write!(f, "Clipboard error: {:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScribeError {}
+
+fn is_auth_failure(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<ApiError>())
+        .any(|e| e.status == StatusCode::UNAUTHORIZED || e.status == StatusCode::FORBIDDEN)
+}
+
+fn is_unreachable(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .any(|e| e.is_connect() || e.is_timeout())
+}
+
+impl ScribeError {
+    /// Notification title and actionable body for this failure
+    pub fn notification(&self) -> (&'static str, &'static str) {
+        match self {
+            ScribeError::Device(_) => (
+                "Microphone unavailable",
+                "Check that the recording device is connected and that microphone access is allowed.",
+            ),
+            ScribeError::Capture(_) => (
+                "Recording failed",
+                "Check microphone permissions and that no other app is holding the device.",
+            ),
+            ScribeError::Transcribe(e) if is_auth_failure(e) => (
+                "Transcription failed",
+                "The API key was rejected. Check api_keys.openai in config.toml.",
+            ),
+            ScribeError::Transcribe(e) if is_unreachable(e) => (
+                "Transcription failed",
+                "The Whisper endpoint is unreachable. Is the local server running, or should the hosted endpoint be used?",
+            ),
+            ScribeError::Transcribe(_) => (
+                "Transcription failed",
+                "The Whisper endpoint returned an error. See the log for details.",
+            ),
+            ScribeError::PostProcess(e) if is_auth_failure(e) => (
+                "Post-processing failed",
+                "The LLM API key was rejected. The raw transcription was used instead.",
+            ),
+            ScribeError::PostProcess(_) => (
+                "Post-processing failed",
+                "The LLM endpoint returned an error. The raw transcription was used instead.",
+            ),
+            ScribeError::Clipboard(_) => (
+                "Copy to clipboard failed",
+                "Another app may be holding the clipboard. Try again.",
+            ),
+        }
+    }
+}
+
+/// Shows the stage-specific notification for a failure when enabled in `[ui]`
+pub fn notify_error(err: &ScribeError, enabled: bool, notifier: &dyn Notifier) {
+    if enabled {
+        let (title, body) = err.notification();
+        notifier.notify(title, body);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct RecordingNotifier {
+        sent: RefCell<Vec<(String, String)>>,
+    }
+
+    impl Notifier for RecordingNotifier {
+        fn notify(&self, title: &str, body: &str) {
+            self.sent.borrow_mut().push((title.to_string(), body.to_string()));
+        }
+    }
+
+    fn api_error(service: &'static str, status: StatusCode) -> anyhow::Error {
+        ApiError {
+            service,
+            status,
+            body: String::new(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_each_stage_maps_to_actionable_notification() {
+        let cases = vec![
+            (
+                ScribeError::Device(anyhow::anyhow!("Specified recording device not found")),
+                "Microphone unavailable",
+                "Check that the recording device is connected and that microphone access is allowed.",
+            ),
+            (
+                ScribeError::Capture(anyhow::anyhow!("stream error")),
+                "Recording failed",
+                "Check microphone permissions and that no other app is holding the device.",
+            ),
+            (
+                ScribeError::Transcribe(api_error("Whisper", StatusCode::UNAUTHORIZED)),
+                "Transcription failed",
+                "The API key was rejected. Check api_keys.openai in config.toml.",
+            ),
+            (
+                ScribeError::Transcribe(api_error("Whisper", StatusCode::BAD_REQUEST)),
+                "Transcription failed",
+                "The Whisper endpoint returned an error. See the log for details.",
+            ),
+            (
+                ScribeError::PostProcess(api_error("LLM", StatusCode::FORBIDDEN)),
+                "Post-processing failed",
+                "The LLM API key was rejected. The raw transcription was used instead.",
+            ),
+            (
+                ScribeError::PostProcess(anyhow::anyhow!("No choices found in LLM response")),
+                "Post-processing failed",
+                "The LLM endpoint returned an error. The raw transcription was used instead.",
+            ),
+            (
+                ScribeError::Clipboard(anyhow::anyhow!("Failed to set clipboard contents")),
+                "Copy to clipboard failed",
+                "Another app may be holding the clipboard. Try again.",
+            ),
+        ];
+
+        for (err, title, body) in cases {
+            assert_eq!(err.notification(), (title, body), "for {}", err);
+        }
+    }
+
+    #[test]
+    fn test_unreachable_endpoint_is_reported() {
+        // Nothing listens on port 9 (discard), so the connection is refused
+        let err = reqwest::blocking::Client::new()
+            .get("http://127.0.0.1:9/transcribe")
+            .send()
+            .unwrap_err();
+        let err = ScribeError::Transcribe(anyhow::Error::new(err).context("Failed to send request"));
+
+        assert_eq!(
+            err.notification().1,
+            "The Whisper endpoint is unreachable. Is the local server running, or should the hosted endpoint be used?"
+        );
+    }
+
+    #[test]
+    fn test_notify_error_respects_setting() {
+        let notifier = RecordingNotifier::default();
+        let err = ScribeError::Clipboard(anyhow::anyhow!("busy"));
+
+        notify_error(&err, false, &notifier);
+        assert!(notifier.sent.borrow().is_empty());
+
+        notify_error(&err, true, &notifier);
+        assert_eq!(notifier.sent.borrow()[0].0, "Copy to clipboard failed");
+    }
+}
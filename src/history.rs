@@ -0,0 +1,123 @@
+use anyhow::{Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::HistorySettings;
+use crate::diff::WordDiff;
+
+/// One line of the history log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub timestamp: String,
+    pub duration_secs: f64,
+    pub model: String,
+    pub used_local: bool,
+    pub post_processed: bool,
+    pub text: String,
+    /// What post-processing changed, when `llm.record_diff` is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<WordDiff>,
+}
+
+/// `history.path`, or `history.jsonl` next to the platform config file
+pub fn history_path(settings: &HistorySettings) -> Result<PathBuf> {
+    match &settings.path {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => dirs::config_dir()
+            .map(|dir| dir.join("rusty-scribe").join("history.jsonl"))
+            .context("No config directory on this platform; set history.path"),
+    }
+}
+
+/// Appends `entry` as one JSON line and syncs it to disk, creating the file
+/// and its parent directories on first use. The line is written in a single
+/// call so concurrent writers never interleave within an entry.
+pub fn append_entry(path: &Path, entry: &HistoryEntry) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let mut line = serde_json::to_string(entry).context("Failed to serialize history entry")?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open history file {}", path.display()))?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Failed to write to history file {}", path.display()))?;
+    file.sync_all()
+        .with_context(|| format!("Failed to sync history file {}", path.display()))
+}
+
+/// Last `count` entries, oldest first. A missing file has no entries; lines
+/// that do not parse, e.g. one cut short by a crash, are skipped.
+pub fn read_last_entries(path: &Path, count: usize) -> Result<Vec<HistoryEntry>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read history file {}", path.display())),
+    };
+    let entries: Vec<HistoryEntry> = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("Skipping unreadable history line: {}", e);
+                None
+            }
+        })
+        .collect();
+    let skip = entries.len().saturating_sub(count);
+    Ok(entries.into_iter().skip(skip).collect())
+}
+
+/// Appends to the history when `history.enabled`. Failures are logged only, so
+/// they never keep the text from being delivered.
+pub fn record_history(settings: &HistorySettings, entry: &HistoryEntry) {
+    if !settings.enabled {
+        return;
+    }
+    if let Err(e) = history_path(settings).and_then(|path| append_entry(&path, entry)) {
+        warn!("Failed to record history: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(text: &str, used_local: bool) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: "2024-09-01T12:00:00+00:00".to_string(),
+            duration_secs: 2.5,
+            model: "whisper-1".to_string(),
+            used_local,
+            post_processed: !used_local,
+            text: text.to_string(),
+            diff: None,
+        }
+    }
+
+    #[test]
+    fn test_entries_are_appended_and_read_back() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("nested").join("history.jsonl");
+
+        append_entry(&path, &entry("First note.", true)).unwrap();
+        append_entry(&path, &entry("Second note.", false)).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+        assert_eq!(
+            read_last_entries(&path, 10).unwrap(),
+            vec![entry("First note.", true), entry("Second note.", false)]
+        );
+        assert_eq!(read_last_entries(&path, 1).unwrap(), vec![entry("Second note.", false)]);
+        assert!(read_last_entries(&dir.path().join("missing.jsonl"), 5).unwrap().is_empty());
+    }
+}
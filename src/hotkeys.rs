@@ -1,13 +1,47 @@
 use rdev::{Event, EventType, Key, listen};
 use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
 use std::collections::HashSet;
-use anyhow::Result;
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result};
+use clipboard::{ClipboardContext, ClipboardProvider};
+use log::{debug, error, info, warn};
+use std::fmt;
+use crate::api::EndpointKind;
+use crate::clipboard::paste_modifier;
+use crate::config::{HotkeyMode, Hotkeys};
+use crate::output::advance_segments;
+use crate::selection::{capture_selection, simulate_copy, COPY_SETTLE};
+use crate::window::{SystemWindowHelper, TargetWindow, WindowHelper};
 
 /// Represents the application state related to hotkeys
 #[derive(Debug, Clone, PartialEq)]
 pub struct HotkeyState {
     pub is_recording: bool,
     pub is_post_processing: bool,
+    pub target_window: TargetWindow,
+    /// Debounced view of the recording combo, see `HoldDebouncer::is_held`
+    pub recording_hold: HoldDebouncer,
+    /// Switch flipped by presses of the recording combo in toggle mode
+    pub recording_toggle: RecordingToggle,
+    /// Taps and holds of the recording combo in hybrid mode
+    pub recording_press: HybridPress,
+    /// Selected text captured by `hotkeys.capture_selection`, consumed by the next dictation
+    pub selection_context: Option<String>,
+    /// One-shot endpoint choice from `hotkeys.force_hosted` / `force_local`
+    pub endpoint_override: Option<EndpointKind>,
+    /// Escape was pressed during the current recording
+    pub is_cancelled: bool,
+    /// Sticky post-processing switch flipped by `hotkeys.toggle_post_processing`
+    pub post_processing_enabled: bool,
+    /// Finished recordings still being transcribed or delivered
+    pub pending_transcriptions: usize,
+    /// `hotkeys.mode`
+    pub mode: HotkeyMode,
+    /// `hotkeys.debounce_ms`
+    pub debounce: Duration,
+    /// `hotkeys.tap_threshold_ms`
+    pub tap_threshold: Duration,
 }
 
 impl HotkeyState {
@@ -15,83 +49,695 @@ impl HotkeyState {
         HotkeyState {
             is_recording: false,
             is_post_processing: false,
+            target_window: TargetWindow::Unset,
+            recording_hold: HoldDebouncer::default(),
+            recording_toggle: RecordingToggle::default(),
+            recording_press: HybridPress::default(),
+            selection_context: None,
+            endpoint_override: None,
+            is_cancelled: false,
+            post_processing_enabled: false,
+            pending_transcriptions: 0,
+            mode: HotkeyMode::default(),
+            debounce: Duration::ZERO,
+            tap_threshold: Duration::from_millis(250),
         }
     }
+
+    /// State for the configured recording mode, debounce and tap threshold
+    pub fn for_hotkeys(hotkeys: &Hotkeys) -> Self {
+        HotkeyState {
+            mode: hotkeys.mode,
+            debounce: Duration::from_millis(hotkeys.debounce_ms),
+            tap_threshold: Duration::from_millis(hotkeys.tap_threshold_ms),
+            ..Self::new()
+        }
+    }
+
+    /// In hold and hybrid mode, re-derives `is_recording` from the debounced
+    /// combo and, in hold mode, the tray's switch. The listener calls this on
+    /// every event and the main loop on every poll, since a release that
+    /// outlasts the debounce produces no event of its own.
+    pub fn refresh_recording(&mut self, now: Instant) {
+        let held = self.recording_hold.is_held(now, self.debounce);
+        match self.mode {
+            HotkeyMode::Hold => self.is_recording = self.recording_toggle.is_on() || held,
+            HotkeyMode::Hybrid => self.is_recording = self.recording_press.on_combo(held, now, self.tap_threshold),
+            HotkeyMode::Toggle => {}
+        }
+    }
+
+    /// Feeds the raw state of the recording combo seen by the listener at `now`
+    pub fn on_recording_combo(&mut self, active: bool, now: Instant) {
+        self.recording_hold.on_combo(active, now);
+        match self.mode {
+            HotkeyMode::Hold | HotkeyMode::Hybrid => self.refresh_recording(now),
+            HotkeyMode::Toggle => self.is_recording = self.recording_toggle.on_combo(active),
+        }
+    }
+
+    /// Starts or stops recording from outside the hotkey, e.g. the tray, in
+    /// any mode. Returns whether it is recording now.
+    pub fn toggle_recording(&mut self, now: Instant) -> bool {
+        match self.mode {
+            HotkeyMode::Hold => {
+                self.recording_toggle.flip();
+                self.refresh_recording(now);
+            }
+            HotkeyMode::Toggle => {
+                self.recording_toggle.flip();
+                self.is_recording = self.recording_toggle.is_on();
+            }
+            HotkeyMode::Hybrid => {
+                self.recording_press.flip();
+                self.is_recording = self.recording_press.is_on();
+            }
+        }
+        self.is_recording
+    }
+
+    /// Ends the current recording without waiting for the hotkey, e.g. on
+    /// Escape or after trailing silence. In toggle and hybrid mode the next
+    /// press starts a new recording; in hold mode a held combo still has to
+    /// be released first.
+    pub fn stop_recording(&mut self) {
+        match self.mode {
+            HotkeyMode::Hold => self.recording_toggle.stop(),
+            HotkeyMode::Toggle => {
+                self.recording_toggle.stop();
+                self.is_recording = false;
+            }
+            HotkeyMode::Hybrid => {
+                self.recording_press.stop();
+                self.is_recording = false;
+            }
+        }
+    }
+
+    /// Flips the sticky post-processing switch and returns its new value
+    pub fn toggle_post_processing(&mut self) -> bool {
+        self.post_processing_enabled = !self.post_processing_enabled;
+        info!(
+            "Post-processing {} for all recordings",
+            if self.post_processing_enabled { "enabled" } else { "disabled" }
+        );
+        self.post_processing_enabled
+    }
+
+    /// Consumes a pending cancel so it applies to exactly one recording
+    pub fn take_cancel(&mut self) -> bool {
+        std::mem::take(&mut self.is_cancelled)
+    }
+
+    /// Forces the next recording onto `endpoint`, replacing any pending override
+    pub fn force_endpoint(&mut self, endpoint: EndpointKind) {
+        info!("Next recording will use the {} endpoint", endpoint);
+        self.endpoint_override = Some(endpoint);
+    }
+
+    /// Consumes the override so it applies to exactly one recording
+    pub fn take_endpoint_override(&mut self) -> Option<EndpointKind> {
+        self.endpoint_override.take()
+    }
+}
+
+/// Whether a press of the recording hotkey was a quick tap or a hold
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PressKind {
+    Tap,
+    Hold,
 }
 
-/// Parses a hotkey string like "Shift+Space" into a set of Keys
-pub fn parse_hotkey(hotkey: &str) -> HashSet<Key> {
+impl fmt::Display for PressKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PressKind::Tap => write!(f, "tap"),
+            PressKind::Hold => write!(f, "hold"),
+        }
+    }
+}
+
+/// Classifies a completed press against `hotkeys.tap_threshold_ms`, logging the
+/// measured duration so users can calibrate the threshold from debug logs
+pub fn classify_press(held_for: Duration, tap_threshold: Duration) -> PressKind {
+    let kind = if held_for < tap_threshold {
+        PressKind::Tap
+    } else {
+        PressKind::Hold
+    };
+    debug!(
+        "Recording hotkey press classified as {} ({} ms, tap_threshold_ms = {})",
+        kind,
+        held_for.as_millis(),
+        tap_threshold.as_millis()
+    );
+    kind
+}
+
+/// Smooths over bouncy keyboards that report a brief key-up/key-down in the
+/// middle of a hold. A release only counts once it has lasted `hotkeys.debounce_ms`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HoldDebouncer {
+    raw_active: bool,
+    released_at: Option<Instant>,
+}
+
+impl HoldDebouncer {
+    /// Records the raw state of the combo as seen by the listener at `now`
+    pub fn on_combo(&mut self, active: bool, now: Instant) {
+        if active {
+            self.released_at = None;
+        } else if self.raw_active {
+            self.released_at = Some(now);
+        }
+        self.raw_active = active;
+    }
+
+    /// Whether the combo counts as held at `now`, treating releases shorter
+    /// than `debounce` as part of a continuous hold
+    pub fn is_held(&self, now: Instant, debounce: Duration) -> bool {
+        self.raw_active
+            || self
+                .released_at
+                .map(|released| now.saturating_duration_since(released) < debounce)
+                .unwrap_or(false)
+    }
+}
+
+/// Toggle-mode recording state, driven by edges of the recording combo
+/// rather than its level: each released-to-pressed transition flips
+/// recording, and releases in between are ignored
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RecordingToggle {
+    was_active: bool,
+    recording: bool,
+}
+
+impl RecordingToggle {
+    /// Feeds the current state of the combo and returns whether to record
+    pub fn on_combo(&mut self, active: bool) -> bool {
+        if active && !self.was_active {
+            self.recording = !self.recording;
+        }
+        self.was_active = active;
+        self.recording
+    }
+
+    /// Ends the current recording without waiting for the next press
+    pub fn stop(&mut self) {
+        self.recording = false;
+    }
+
+    /// Flips recording as a full press would
+    pub fn flip(&mut self) {
+        self.recording = !self.recording;
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.recording
+    }
+}
+
+/// Hybrid mode's view of the recording combo, which starts recording on every
+/// press. A press shorter than `hotkeys.tap_threshold_ms` keeps recording
+/// after its release until the next press; a longer one is push-to-talk and
+/// stops on release.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HybridPress {
+    pressed_at: Option<Instant>,
+    /// The current press ends a tapped recording, so its release starts nothing
+    stopping: bool,
+    tapped: bool,
+}
+
+impl HybridPress {
+    /// Feeds the state of the combo at `now` and returns whether to record
+    pub fn on_combo(&mut self, active: bool, now: Instant, tap_threshold: Duration) -> bool {
+        match (active, self.pressed_at) {
+            (true, None) => {
+                self.pressed_at = Some(now);
+                self.stopping = std::mem::take(&mut self.tapped);
+            }
+            (false, Some(pressed_at)) => {
+                self.pressed_at = None;
+                let stopping = std::mem::take(&mut self.stopping);
+                let kind = classify_press(now - pressed_at, tap_threshold);
+                self.tapped = kind == PressKind::Tap && !stopping;
+            }
+            _ => {}
+        }
+        self.is_on()
+    }
+
+    /// Ends the current recording; a press still held starts nothing on release
+    pub fn stop(&mut self) {
+        self.tapped = false;
+        self.stopping = self.pressed_at.is_some();
+    }
+
+    /// Starts recording as a tap would, or stops it
+    pub fn flip(&mut self) {
+        if self.is_on() {
+            self.stop();
+        } else {
+            self.tapped = true;
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.tapped || (self.pressed_at.is_some() && !self.stopping)
+    }
+}
+
+/// Records a key event in `pressed`. Returns false for a press of a key that
+/// is already down, i.e. auto-repeat, which changes nothing and is skipped.
+pub fn track_key(pressed: &mut HashSet<Key>, event: &EventType) -> bool {
+    match *event {
+        EventType::KeyPress(key) => pressed.insert(key),
+        EventType::KeyRelease(key) => {
+            pressed.remove(&key);
+            true
+        }
+        _ => true,
+    }
+}
+
+impl Default for HotkeyState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Config names of the keys hotkeys can use, in the order
+/// `keys_to_hotkey_string` writes them: modifiers first
+const KEY_NAMES: [(&str, Key); 54] = [
+    ("Control", Key::ControlLeft),
+    ("Shift", Key::ShiftLeft),
+    ("Alt", Key::Alt),
+    ("Space", Key::Space),
+    ("Enter", Key::Return),
+    ("Escape", Key::Escape),
+    ("A", Key::KeyA),
+    ("B", Key::KeyB),
+    ("C", Key::KeyC),
+    ("D", Key::KeyD),
+    ("E", Key::KeyE),
+    ("F", Key::KeyF),
+    ("G", Key::KeyG),
+    ("H", Key::KeyH),
+    ("I", Key::KeyI),
+    ("J", Key::KeyJ),
+    ("K", Key::KeyK),
+    ("L", Key::KeyL),
+    ("M", Key::KeyM),
+    ("N", Key::KeyN),
+    ("O", Key::KeyO),
+    ("P", Key::KeyP),
+    ("Q", Key::KeyQ),
+    ("R", Key::KeyR),
+    ("S", Key::KeyS),
+    ("T", Key::KeyT),
+    ("U", Key::KeyU),
+    ("V", Key::KeyV),
+    ("W", Key::KeyW),
+    ("X", Key::KeyX),
+    ("Y", Key::KeyY),
+    ("Z", Key::KeyZ),
+    ("0", Key::Num0),
+    ("1", Key::Num1),
+    ("2", Key::Num2),
+    ("3", Key::Num3),
+    ("4", Key::Num4),
+    ("5", Key::Num5),
+    ("6", Key::Num6),
+    ("7", Key::Num7),
+    ("8", Key::Num8),
+    ("9", Key::Num9),
+    ("F1", Key::F1),
+    ("F2", Key::F2),
+    ("F3", Key::F3),
+    ("F4", Key::F4),
+    ("F5", Key::F5),
+    ("F6", Key::F6),
+    ("F7", Key::F7),
+    ("F8", Key::F8),
+    ("F9", Key::F9),
+    ("F10", Key::F10),
+    ("F11", Key::F11),
+    ("F12", Key::F12),
+];
+
+/// Parses a hotkey string like "Control+Alt+R" into a set of Keys. Names are
+/// case-insensitive and "Ctrl" is accepted for "Control". A blank string gives
+/// an empty set; any other unknown token is an error.
+pub fn parse_hotkey(hotkey: &str) -> Result<HashSet<Key>> {
+    if hotkey.trim().is_empty() {
+        return Ok(HashSet::new());
+    }
     hotkey
         .split('+')
-        .filter_map(|part| match part.trim().to_lowercase().as_str() {
-            "shift" => Some(Key::ShiftLeft), // or Key::ShiftRight
-            "control" | "ctrl" => Some(Key::ControlLeft), // or Key::ControlRight
-            "alt" => Some(Key::Alt),
-            "space" => Some(Key::Space),
-            "enter" => Some(Key::Return),
-            "escape" => Some(Key::Escape),
-            // Add more keys as needed
-            _ => None,
+        .map(|part| {
+            let token = part.trim();
+            let name = if token.eq_ignore_ascii_case("ctrl") { "Control" } else { token };
+            KEY_NAMES
+                .iter()
+                .find(|(known, _)| known.eq_ignore_ascii_case(name))
+                .map(|&(_, key)| key)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Unknown key '{}' in hotkey '{}'. Use modifiers (Control, Shift, Alt), Space, Enter, Escape, A-Z, 0-9 or F1-F12.",
+                        token,
+                        hotkey
+                    )
+                })
         })
         .collect()
 }
 
-/// Starts listening to global keyboard events and updates the shared state accordingly
-pub async fn start_hotkey_listener(
-    config_recording: &str,
-    config_modifier: &str,
-    state: Arc<Mutex<HotkeyState>>,
-) -> Result<()> {
-    let recording_keys = parse_hotkey(config_recording);
-    let modifier_keys = parse_hotkey(config_modifier);
+/// Config name for a key, the inverse of `parse_hotkey`. Left and right
+/// variants of a modifier share one name.
+fn key_name(key: &Key) -> Option<&'static str> {
+    let key = match key {
+        Key::ControlRight => &Key::ControlLeft,
+        Key::ShiftRight => &Key::ShiftLeft,
+        Key::AltGr => &Key::Alt,
+        other => other,
+    };
+    KEY_NAMES.iter().find(|(_, known)| known == key).map(|&(name, _)| name)
+}
+
+/// Formats a key set as a config hotkey string such as "Control+Shift+Space".
+/// Modifiers come first in a fixed order; keys `parse_hotkey` cannot read are skipped.
+pub fn keys_to_hotkey_string(keys: &HashSet<Key>) -> String {
+    let names: HashSet<&str> = keys.iter().filter_map(key_name).collect();
+    KEY_NAMES
+        .iter()
+        .map(|&(name, _)| name)
+        .filter(|name| names.contains(name))
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// One key of a hotkey as matched against the keys currently pressed.
+/// Modifiers match on either side of the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyMatcher {
+    Exact(Key),
+    Either(Key, Key),
+}
+
+impl KeyMatcher {
+    /// Matcher for a key from `parse_hotkey`
+    pub fn for_key(key: Key) -> Self {
+        match key {
+            Key::ControlLeft | Key::ControlRight => KeyMatcher::Either(Key::ControlLeft, Key::ControlRight),
+            Key::ShiftLeft | Key::ShiftRight => KeyMatcher::Either(Key::ShiftLeft, Key::ShiftRight),
+            // The right Alt key reports as AltGr on most layouts
+            Key::Alt | Key::AltGr => KeyMatcher::Either(Key::Alt, Key::AltGr),
+            key => KeyMatcher::Exact(key),
+        }
+    }
+
+    pub fn is_pressed(&self, pressed: &HashSet<Key>) -> bool {
+        match self {
+            KeyMatcher::Exact(key) => pressed.contains(key),
+            KeyMatcher::Either(a, b) => pressed.contains(a) || pressed.contains(b),
+        }
+    }
+}
+
+/// Parses a hotkey string into matchers, see `parse_hotkey`
+pub fn parse_hotkey_matchers(hotkey: &str) -> Result<Vec<KeyMatcher>> {
+    Ok(parse_hotkey(hotkey)?.into_iter().map(KeyMatcher::for_key).collect())
+}
+
+/// Whether every key of a non-empty hotkey is pressed
+pub fn hotkey_active(matchers: &[KeyMatcher], pressed: &HashSet<Key>) -> bool {
+    !matchers.is_empty() && matchers.iter().all(|m| m.is_pressed(pressed))
+}
+
+/// Blocks until the user presses and releases a key chord, returning every key
+/// that was held at once. Fails if the chord contains keys hotkeys cannot use.
+pub fn capture_chord() -> Result<HashSet<Key>> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut held = HashSet::new();
+        let mut chord = HashSet::new();
+        let result = listen(move |event: Event| match event.event_type {
+            EventType::KeyPress(key) => {
+                held.insert(key);
+                chord.insert(key);
+            }
+            EventType::KeyRelease(key) => {
+                held.remove(&key);
+                if held.is_empty() && !chord.is_empty() {
+                    let _ = tx.send(std::mem::take(&mut chord));
+                }
+            }
+            _ => {}
+        });
+        if let Err(e) = result {
+            warn!("Keyboard listener failed while capturing a chord: {:?}", e);
+        }
+    });
+
+    let chord = rx
+        .recv()
+        .map_err(|_| anyhow::anyhow!("Keyboard listener stopped before a chord was pressed"))?;
+    if let Some(key) = chord.iter().find(|k| key_name(k).is_none()) {
+        return Err(anyhow::anyhow!("{:?} cannot be used in a hotkey", key));
+    }
+    Ok(chord)
+}
+
+/// Whether a new press of the recording hotkey should be ignored because
+/// `hotkeys.disable_in_fullscreen` is set and the focused window is fullscreen
+pub fn suppressed_by_fullscreen(disable_in_fullscreen: bool, windows: &dyn WindowHelper) -> bool {
+    disable_in_fullscreen
+        && windows
+            .focused_window()
+            .is_some_and(|id| windows.is_fullscreen(id))
+}
+
+fn capture_selection_into(state: &Mutex<HotkeyState>) {
+    let captured = ClipboardContext::new()
+        .map_err(|e| anyhow::anyhow!("Failed to initialize clipboard context: {}", e))
+        .and_then(|mut clipboard| capture_selection(&mut clipboard, simulate_copy, COPY_SETTLE));
+    match captured {
+        Ok(selection) => state.lock().unwrap().selection_context = selection,
+        Err(e) => warn!("Failed to capture selected text: {:?}", e),
+    }
+}
+
+/// An optional single-action hotkey that fires once per press
+struct ActionCombo {
+    keys: Vec<KeyMatcher>,
+    was_active: bool,
+}
+
+impl ActionCombo {
+    fn new(hotkey: Option<&str>) -> Result<Self> {
+        Ok(ActionCombo {
+            keys: hotkey.map(parse_hotkey_matchers).transpose()?.unwrap_or_default(),
+            was_active: false,
+        })
+    }
+
+    /// Whether the combo went from released to fully pressed with this event
+    fn pressed(&mut self, pressed: &HashSet<Key>) -> bool {
+        let active = hotkey_active(&self.keys, pressed);
+        let edge = active && !self.was_active;
+        self.was_active = active;
+        edge
+    }
+}
+
+/// Starts listening to global keyboard events on a dedicated OS thread, which
+/// updates the shared state for the rest of the process. Invalid hotkeys are
+/// reported here; a listener that cannot hook the keyboard logs the error and
+/// ends its thread.
+pub fn start_hotkey_listener(hotkeys: &Hotkeys, state: Arc<Mutex<HotkeyState>>) -> Result<JoinHandle<()>> {
+    let recording_keys = parse_hotkey_matchers(&hotkeys.recording).context("Invalid hotkeys.recording")?;
+    let modifier_keys =
+        parse_hotkey_matchers(&hotkeys.post_processing_modifier).context("Invalid hotkeys.post_processing_modifier")?;
+    let mut set_target =
+        ActionCombo::new(hotkeys.set_target_window.as_deref()).context("Invalid hotkeys.set_target_window")?;
+    let mut capture_selection =
+        ActionCombo::new(hotkeys.capture_selection.as_deref()).context("Invalid hotkeys.capture_selection")?;
+    let mut force_hosted =
+        ActionCombo::new(hotkeys.force_hosted.as_deref()).context("Invalid hotkeys.force_hosted")?;
+    let mut force_local =
+        ActionCombo::new(hotkeys.force_local.as_deref()).context("Invalid hotkeys.force_local")?;
+    let mut clear_clipboard =
+        ActionCombo::new(hotkeys.clear_clipboard.as_deref()).context("Invalid hotkeys.clear_clipboard")?;
+    let mut toggle_post_processing = ActionCombo::new(hotkeys.toggle_post_processing.as_deref())
+        .context("Invalid hotkeys.toggle_post_processing")?;
+    // The platform paste shortcut moves sequential segments along
+    let mut paste = ActionCombo {
+        keys: vec![KeyMatcher::for_key(paste_modifier()), KeyMatcher::Exact(Key::KeyV)],
+        was_active: false,
+    };
+    let disable_in_fullscreen = hotkeys.disable_in_fullscreen;
+    // Escape cannot cancel a recording it is part of
+    let recording_uses_escape = recording_keys.contains(&KeyMatcher::Exact(Key::Escape));
+    let mut recording_was_active = false;
+    let mut recording_suppressed = false;
 
     let pressed_keys = Arc::new(Mutex::new(HashSet::new()));
 
-    tokio::task::spawn(async move {
+    let listener = std::thread::Builder::new().name("hotkeys".to_string()).spawn(move || {
+        // `listen` blocks for as long as the hook runs
         if let Err(error) = listen(move |event: Event| {
             let mut pressed = pressed_keys.lock().unwrap();
+            if !track_key(&mut pressed, &event.event_type) {
+                return;
+            }
 
-            match event.event_type {
-                EventType::KeyPress(key) => {
-                    pressed.insert(key);
-                }
-                EventType::KeyRelease(key) => {
-                    pressed.remove(&key);
+            let combo_held = hotkey_active(&recording_keys, &pressed);
+            if !combo_held {
+                recording_suppressed = false;
+            } else if !recording_was_active && !recording_suppressed {
+                // Checked once per press so xprop is not run on every key event
+                recording_suppressed = suppressed_by_fullscreen(disable_in_fullscreen, &SystemWindowHelper);
+                if recording_suppressed {
+                    info!("Recording hotkey ignored: focused window is fullscreen");
                 }
-                _ => {}
             }
+            let recording_active = combo_held && !recording_suppressed;
+            let modifier_active = hotkey_active(&modifier_keys, &pressed);
 
-            let recording_active = recording_keys.iter().all(|k| pressed.contains(k));
-            let modifier_active = modifier_keys.iter().all(|k| pressed.contains(k));
+            recording_was_active = recording_active;
 
+            let now = Instant::now();
             let mut state_lock = state.lock().unwrap();
-            state_lock.is_recording = recording_active;
+            let escape_pressed = matches!(event.event_type, EventType::KeyPress(Key::Escape));
+            if escape_pressed && state_lock.is_recording && !recording_uses_escape {
+                info!("Escape pressed, cancelling the recording");
+                state_lock.is_cancelled = true;
+                state_lock.stop_recording();
+            }
+            state_lock.on_recording_combo(recording_active, now);
             state_lock.is_post_processing = modifier_active;
+            if set_target.pressed(&pressed) {
+                state_lock.target_window.capture(&SystemWindowHelper);
+            }
+            if capture_selection.pressed(&pressed) {
+                // Simulating the copy from inside the listen callback can stall the
+                // event hook, so the capture runs on its own thread
+                let state = Arc::clone(&state);
+                std::thread::spawn(move || capture_selection_into(&state));
+            }
+            if force_hosted.pressed(&pressed) {
+                state_lock.force_endpoint(EndpointKind::Hosted);
+            }
+            if force_local.pressed(&pressed) {
+                state_lock.force_endpoint(EndpointKind::Local);
+            }
+            if toggle_post_processing.pressed(&pressed) {
+                state_lock.toggle_post_processing();
+            }
+            if paste.pressed(&pressed) {
+                std::thread::spawn(move || advance_segments(now));
+            }
+            if clear_clipboard.pressed(&pressed) {
+                std::thread::spawn(|| {
+                    if let Err(e) = crate::clipboard::clear_clipboard() {
+                        warn!("Failed to clear clipboard: {:?}", e);
+                    }
+                });
+            }
         }) {
-            println!("Error in hotkey listener: {:?}", error);
+            error!("Error in hotkey listener: {:?}", error);
         }
-    })
-    .await?;
-
-    Ok(())
+    });
+    listener.context("Failed to start the hotkey listener thread")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::{Arc, Mutex};
+    use log::{Level, Log, Metadata, Record};
+    use crate::window::WindowId;
+    use std::sync::{Arc, Mutex, Once};
+
+    static CAPTURED_LOGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    static INIT_LOGGER: Once = Once::new();
+
+    struct CaptureLogger;
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= Level::Debug
+        }
+
+        fn log(&self, record: &Record) {
+            CAPTURED_LOGS.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn capture_logs() {
+        INIT_LOGGER.call_once(|| {
+            log::set_logger(&CaptureLogger).expect("Failed to install test logger");
+            log::set_max_level(log::LevelFilter::Debug);
+        });
+    }
+
+    struct FullscreenWindow(bool);
+
+    impl WindowHelper for FullscreenWindow {
+        fn focused_window(&self) -> Option<WindowId> {
+            Some(7)
+        }
+
+        fn window_exists(&self, _id: WindowId) -> bool {
+            true
+        }
+
+        fn activate(&self, _id: WindowId) -> Result<()> {
+            Ok(())
+        }
+
+        fn window_class(&self, _id: WindowId) -> Option<String> {
+            None
+        }
+
+        fn is_fullscreen(&self, _id: WindowId) -> bool {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_fullscreen_suppresses_recording_only_when_enabled() {
+        assert!(suppressed_by_fullscreen(true, &FullscreenWindow(true)));
+        assert!(!suppressed_by_fullscreen(true, &FullscreenWindow(false)));
+        assert!(!suppressed_by_fullscreen(false, &FullscreenWindow(true)));
+    }
+
+    #[test]
+    fn test_keys_to_hotkey_string_round_trips() {
+        for hotkey in ["Shift+Space", "Control+Alt+Enter", "Control+Shift+Alt+Escape", "Space", "Control+Alt+R+F9"] {
+            let keys = parse_hotkey(hotkey).unwrap();
+            assert_eq!(keys_to_hotkey_string(&keys), hotkey);
+            assert_eq!(parse_hotkey(&keys_to_hotkey_string(&keys)).unwrap(), keys);
+        }
+
+        let right_hand: HashSet<Key> = [Key::ControlRight, Key::AltGr, Key::Return].into_iter().collect();
+        assert_eq!(keys_to_hotkey_string(&right_hand), "Control+Alt+Enter");
+    }
 
     #[test]
     fn test_parse_hotkey() {
         let hotkey = "Shift+Space";
-        let parsed = parse_hotkey(hotkey);
+        let parsed = parse_hotkey(hotkey).unwrap();
         let mut expected = HashSet::new();
         expected.insert(Key::ShiftLeft);
         expected.insert(Key::Space);
         assert_eq!(parsed, expected);
 
         let hotkey = "Control+Alt+Enter";
-        let parsed = parse_hotkey(hotkey);
+        let parsed = parse_hotkey(hotkey).unwrap();
         let mut expected = HashSet::new();
         expected.insert(Key::ControlLeft);
         expected.insert(Key::Alt);
@@ -99,7 +745,7 @@ mod tests {
         assert_eq!(parsed, expected);
 
         let hotkey = "Ctrl + Shift + Escape";
-        let parsed = parse_hotkey(hotkey);
+        let parsed = parse_hotkey(hotkey).unwrap();
         let mut expected = HashSet::new();
         expected.insert(Key::ControlLeft);
         expected.insert(Key::ShiftLeft);
@@ -107,6 +753,47 @@ mod tests {
         assert_eq!(parsed, expected);
     }
 
+    #[test]
+    fn test_parse_hotkey_letters_digits_and_function_keys() {
+        let expected: HashSet<Key> = [Key::ControlLeft, Key::Alt, Key::KeyR].into_iter().collect();
+        assert_eq!(parse_hotkey("Control+Alt+R").unwrap(), expected);
+        assert_eq!(parse_hotkey("ctrl+alt+r").unwrap(), expected);
+
+        let expected: HashSet<Key> = [Key::F9].into_iter().collect();
+        assert_eq!(parse_hotkey("F9").unwrap(), expected);
+        let expected: HashSet<Key> = [Key::ShiftLeft, Key::Num7, Key::F12].into_iter().collect();
+        assert_eq!(parse_hotkey("Shift+7+f12").unwrap(), expected);
+    }
+
+    #[test]
+    fn test_right_side_modifiers_activate_hotkey() {
+        let combo = parse_hotkey_matchers("Control+Shift+Alt+R").unwrap();
+        let pressed = |keys: &[Key]| keys.iter().copied().collect::<HashSet<Key>>();
+
+        assert!(hotkey_active(&combo, &pressed(&[Key::ControlLeft, Key::ShiftLeft, Key::Alt, Key::KeyR])));
+        assert!(hotkey_active(&combo, &pressed(&[Key::ControlRight, Key::ShiftRight, Key::AltGr, Key::KeyR])));
+        assert!(hotkey_active(&combo, &pressed(&[Key::ControlRight, Key::ShiftLeft, Key::Alt, Key::KeyR])));
+        assert!(!hotkey_active(&combo, &pressed(&[Key::ControlRight, Key::ShiftRight, Key::KeyR])));
+    }
+
+    #[test]
+    fn test_non_modifiers_match_exactly() {
+        assert_eq!(KeyMatcher::for_key(Key::Space), KeyMatcher::Exact(Key::Space));
+        let combo = parse_hotkey_matchers("Shift+Space").unwrap();
+        let pressed: HashSet<Key> = [Key::ShiftRight, Key::Return].into_iter().collect();
+        assert!(!hotkey_active(&combo, &pressed));
+        assert!(!hotkey_active(&[], &pressed));
+    }
+
+    #[test]
+    fn test_parse_hotkey_rejects_unknown_token() {
+        let err = parse_hotkey("Control+Sapce").unwrap_err().to_string();
+        assert!(err.starts_with("Unknown key 'Sapce' in hotkey 'Control+Sapce'"), "{}", err);
+        assert!(parse_hotkey("F13").is_err());
+        assert!(parse_hotkey("Shift+").is_err());
+        assert!(parse_hotkey("  ").unwrap().is_empty());
+    }
+
     #[test]
     fn test_hotkey_listener_updates_state() {
         // Note: Testing the actual hotkey listener would require simulating key events,
@@ -136,7 +823,19 @@ mod tests {
                 *state_lock,
                 HotkeyState {
                     is_recording: true,
-                    is_post_processing: false
+                    is_post_processing: false,
+                    target_window: TargetWindow::Unset,
+                    recording_hold: HoldDebouncer::default(),
+                    recording_toggle: RecordingToggle::default(),
+                    recording_press: HybridPress::default(),
+                    selection_context: None,
+                    endpoint_override: None,
+                    is_cancelled: false,
+                    post_processing_enabled: false,
+                    pending_transcriptions: 0,
+                    mode: HotkeyMode::Hybrid,
+                    debounce: Duration::ZERO,
+                    tap_threshold: Duration::from_millis(250),
                 }
             );
         }
@@ -152,9 +851,296 @@ mod tests {
                 *state_lock,
                 HotkeyState {
                     is_recording: true,
-                    is_post_processing: true
+                    is_post_processing: true,
+                    target_window: TargetWindow::Unset,
+                    recording_hold: HoldDebouncer::default(),
+                    recording_toggle: RecordingToggle::default(),
+                    recording_press: HybridPress::default(),
+                    selection_context: None,
+                    endpoint_override: None,
+                    is_cancelled: false,
+                    post_processing_enabled: false,
+                    pending_transcriptions: 0,
+                    mode: HotkeyMode::Hybrid,
+                    debounce: Duration::ZERO,
+                    tap_threshold: Duration::from_millis(250),
                 }
             );
         }
     }
+
+    /// Replays (millisecond offset, combo active) events and samples the debounced
+    /// state every millisecond, returning how many distinct holds were seen
+    fn count_holds(events: &[(u64, bool)], debounce_ms: u64, until_ms: u64) -> usize {
+        let start = Instant::now();
+        let debounce = Duration::from_millis(debounce_ms);
+        let mut debouncer = HoldDebouncer::default();
+        let mut events = events.iter().peekable();
+        let mut holds = 0;
+        let mut was_held = false;
+
+        for ms in 0..=until_ms {
+            let now = start + Duration::from_millis(ms);
+            while let Some(&&(at, active)) = events.peek() {
+                if at > ms {
+                    break;
+                }
+                debouncer.on_combo(active, start + Duration::from_millis(at));
+                events.next();
+            }
+            let held = debouncer.is_held(now, debounce);
+            if held && !was_held {
+                holds += 1;
+            }
+            was_held = held;
+        }
+        holds
+    }
+
+    #[test]
+    fn test_debounce_merges_sub_threshold_bounces() {
+        // Held from 0 to 1000 ms with two 10 ms bounces in between
+        let events = [(0, true), (300, false), (310, true), (600, false), (610, true), (1000, false)];
+
+        assert_eq!(count_holds(&events, 30, 1100), 1);
+        assert_eq!(count_holds(&events, 0, 1100), 3);
+    }
+
+    #[test]
+    fn test_debounce_keeps_separate_presses_apart() {
+        // Two deliberate presses 200 ms apart
+        let events = [(0, true), (100, false), (300, true), (400, false)];
+        assert_eq!(count_holds(&events, 30, 500), 2);
+    }
+
+    /// Replays key events for a Shift+Space recording combo through the toggle
+    /// and returns the recording state after each event
+    fn replay_toggle(events: &[EventType]) -> Vec<bool> {
+        let combo = parse_hotkey_matchers("Shift+Space").unwrap();
+        let mut pressed = HashSet::new();
+        let mut toggle = RecordingToggle::default();
+        events
+            .iter()
+            .map(|event| {
+                track_key(&mut pressed, event);
+                toggle.on_combo(hotkey_active(&combo, &pressed))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_toggle_flips_on_full_presses_only() {
+        use EventType::{KeyPress, KeyRelease};
+        let states = replay_toggle(&[
+            KeyPress(Key::ShiftLeft),
+            KeyPress(Key::Space), // full press: start
+            KeyRelease(Key::Space),
+            KeyRelease(Key::ShiftLeft), // releases are ignored
+            KeyPress(Key::KeyA),
+            KeyRelease(Key::KeyA),
+            KeyPress(Key::Space),
+            KeyPress(Key::ShiftLeft), // full press in the other order: stop
+            KeyRelease(Key::ShiftLeft),
+            KeyRelease(Key::Space),
+        ]);
+        assert_eq!(states, vec![false, true, true, true, true, true, true, false, false, false]);
+    }
+
+    #[test]
+    fn test_toggle_ignores_repeats_while_held() {
+        use EventType::{KeyPress, KeyRelease};
+        // Auto-repeat sends more presses of a key that is already down
+        let states = replay_toggle(&[
+            KeyPress(Key::ShiftLeft),
+            KeyPress(Key::Space),
+            KeyPress(Key::Space),
+            KeyPress(Key::Space),
+            KeyRelease(Key::Space),
+            KeyPress(Key::Space), // Shift still held: a new full press
+        ]);
+        assert_eq!(states, vec![false, true, true, true, true, false]);
+    }
+
+    #[test]
+    fn test_autorepeat_burst_is_one_activation() {
+        use EventType::{KeyPress, KeyRelease};
+        let combo = parse_hotkey_matchers("Shift+Space").unwrap();
+        let mut pressed = HashSet::new();
+        let mut toggle = RecordingToggle::default();
+        let mut events = vec![KeyPress(Key::ShiftLeft)];
+        events.extend(std::iter::repeat_n(KeyPress(Key::Space), 30));
+        events.extend(std::iter::repeat_n(KeyPress(Key::ShiftLeft), 10));
+        events.push(KeyRelease(Key::Space));
+
+        let mut handled = Vec::new();
+        for event in &events {
+            if track_key(&mut pressed, event) {
+                handled.push(toggle.on_combo(hotkey_active(&combo, &pressed)));
+            }
+        }
+
+        // Shift, the first Space and the release; every repeat was skipped
+        assert_eq!(handled, vec![false, true, true]);
+        assert_eq!(pressed, HashSet::from([Key::ShiftLeft]));
+    }
+
+    #[test]
+    fn test_debounced_release_ends_after_threshold() {
+        let start = Instant::now();
+        let debounce = Duration::from_millis(30);
+        let mut debouncer = HoldDebouncer::default();
+
+        debouncer.on_combo(true, start);
+        debouncer.on_combo(false, start + Duration::from_millis(100));
+
+        assert!(debouncer.is_held(start + Duration::from_millis(129), debounce));
+        assert!(!debouncer.is_held(start + Duration::from_millis(130), debounce));
+    }
+
+    #[test]
+    fn test_refresh_ends_a_debounced_hold_without_another_event() {
+        let hotkeys: Hotkeys = toml::from_str(
+            "recording = \"Shift+Space\"\npost_processing_modifier = \"Control\"\ndebounce_ms = 30\nmode = \"hold\"",
+        )
+        .unwrap();
+        let mut state = HotkeyState::for_hotkeys(&hotkeys);
+        let start = Instant::now();
+
+        state.recording_hold.on_combo(true, start);
+        state.refresh_recording(start);
+        assert!(state.is_recording);
+
+        // A bounce shorter than the debounce keeps recording
+        let released = start + Duration::from_millis(100);
+        state.recording_hold.on_combo(false, released);
+        state.refresh_recording(released);
+        assert!(state.is_recording);
+
+        // The main loop's next poll after the debounce ends it
+        state.refresh_recording(released + Duration::from_millis(30));
+        assert!(!state.is_recording);
+    }
+
+    #[test]
+    fn test_stopping_switches_the_toggle_off() {
+        let hotkeys: Hotkeys =
+            toml::from_str("recording = \"Shift+Space\"\npost_processing_modifier = \"Control\"\nmode = \"toggle\"")
+                .unwrap();
+        let mut state = HotkeyState::for_hotkeys(&hotkeys);
+        let start = Instant::now();
+
+        state.on_recording_combo(true, start);
+        state.on_recording_combo(false, start);
+        assert!(state.is_recording);
+
+        // Trailing silence ended the recording; the next press starts another
+        state.stop_recording();
+        assert!(!state.is_recording);
+        state.on_recording_combo(true, start);
+        assert!(state.is_recording);
+    }
+
+    /// Replays key events for a Shift+Space recording combo through `state`
+    /// and returns whether it is recording afterwards
+    fn replay_state(state: &mut HotkeyState, pressed: &mut HashSet<Key>, events: &[EventType]) -> bool {
+        let combo = parse_hotkey_matchers("Shift+Space").unwrap();
+        for event in events {
+            track_key(pressed, event);
+            state.on_recording_combo(hotkey_active(&combo, pressed), Instant::now());
+        }
+        state.is_recording
+    }
+
+    #[test]
+    fn test_one_press_records_again_after_an_auto_stop() {
+        use EventType::{KeyPress, KeyRelease};
+        let press = [
+            KeyPress(Key::ShiftLeft),
+            KeyPress(Key::Space),
+            KeyRelease(Key::Space),
+            KeyRelease(Key::ShiftLeft),
+        ];
+
+        let mut state = HotkeyState {
+            mode: HotkeyMode::Toggle,
+            ..HotkeyState::new()
+        };
+        let mut pressed = HashSet::new();
+        assert!(replay_state(&mut state, &mut pressed, &press));
+        // audio.max_recording_secs or trailing silence stopped the recording
+        state.stop_recording();
+        assert!(!state.is_recording);
+        assert!(replay_state(&mut state, &mut pressed, &press));
+
+        // In hold mode a stuck combo keeps "recording" until it is released
+        let mut state = HotkeyState {
+            mode: HotkeyMode::Hold,
+            ..HotkeyState::new()
+        };
+        let mut pressed = HashSet::new();
+        assert!(replay_state(&mut state, &mut pressed, &press[..2]));
+        state.stop_recording();
+        assert!(state.is_recording);
+        assert!(!replay_state(&mut state, &mut pressed, &press[2..]));
+        assert!(replay_state(&mut state, &mut pressed, &press[..2]));
+    }
+
+    #[test]
+    fn test_hybrid_tap_toggles_and_hold_is_push_to_talk() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut state = HotkeyState {
+            mode: HotkeyMode::Hybrid,
+            ..HotkeyState::new()
+        };
+
+        // A tap keeps recording until the next press, whose release starts nothing
+        state.on_recording_combo(true, at(0));
+        assert!(state.is_recording);
+        state.on_recording_combo(false, at(100));
+        assert!(state.is_recording);
+        state.on_recording_combo(true, at(2000));
+        assert!(!state.is_recording);
+        state.on_recording_combo(false, at(2100));
+        assert!(!state.is_recording);
+
+        // A hold records only while held
+        state.on_recording_combo(true, at(3000));
+        assert!(state.is_recording);
+        state.on_recording_combo(false, at(4000));
+        assert!(!state.is_recording);
+
+        // An auto-stop during a press is not undone by its release
+        state.on_recording_combo(true, at(5000));
+        state.stop_recording();
+        state.on_recording_combo(false, at(5100));
+        assert!(!state.is_recording);
+    }
+
+    #[test]
+    fn test_classify_press_logs_duration() {
+        capture_logs();
+        let threshold = Duration::from_millis(250);
+
+        assert_eq!(classify_press(Duration::from_millis(120), threshold), PressKind::Tap);
+        assert_eq!(classify_press(Duration::from_millis(250), threshold), PressKind::Hold);
+
+        let logs = CAPTURED_LOGS.lock().unwrap();
+        assert!(logs.contains(
+            &"Recording hotkey press classified as tap (120 ms, tap_threshold_ms = 250)".to_string()
+        ));
+        assert!(logs.contains(
+            &"Recording hotkey press classified as hold (250 ms, tap_threshold_ms = 250)".to_string()
+        ));
+    }
+
+    #[test]
+    fn test_endpoint_override_applies_to_one_recording() {
+        let mut state = HotkeyState::new();
+        state.force_endpoint(EndpointKind::Local);
+        state.force_endpoint(EndpointKind::Hosted);
+
+        assert_eq!(state.take_endpoint_override(), Some(EndpointKind::Hosted));
+        assert_eq!(state.take_endpoint_override(), None);
+    }
 }
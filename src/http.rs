@@ -0,0 +1,306 @@
+use anyhow::Result;
+use log::info;
+use reqwest::blocking::{Client, Request, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use reqwest::Url;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::config::{DebugSettings, NetworkSettings, TimeoutSettings};
+
+static TIMEOUTS: OnceLock<TimeoutSettings> = OnceLock::new();
+static SPACER: OnceLock<RequestSpacer> = OnceLock::new();
+static LOG_HTTP: AtomicBool = AtomicBool::new(false);
+
+/// Headers whose values are never logged
+const SECRET_HEADERS: [&str; 4] = ["authorization", "proxy-authorization", "x-api-key", "api-key"];
+
+/// Longest body excerpt written to the log
+const LOGGED_BODY_CHARS: usize = 2000;
+
+/// Installs the `[timeouts]` settings used by every client built afterwards.
+/// Only the first call takes effect; until then the defaults apply.
+pub fn configure_timeouts(timeouts: &TimeoutSettings) {
+    let _ = TIMEOUTS.set(timeouts.clone());
+}
+
+fn timeouts() -> TimeoutSettings {
+    TIMEOUTS.get().cloned().unwrap_or_default()
+}
+
+/// HTTP client with the configured connect and request timeouts
+pub fn build_client() -> Client {
+    build_client_with(&timeouts())
+}
+
+pub fn build_client_with(timeouts: &TimeoutSettings) -> Client {
+    Client::builder()
+        .connect_timeout(Duration::from_millis(timeouts.connect_ms))
+        .timeout(Duration::from_millis(timeouts.request_ms))
+        .build()
+        .expect("HTTP client with timeouts only")
+}
+
+/// Client for the availability probe, which must answer quickly so a dead
+/// local server does not delay startup or recordings
+pub fn build_probe_client() -> Client {
+    build_probe_client_with(&timeouts())
+}
+
+pub fn build_probe_client_with(timeouts: &TimeoutSettings) -> Client {
+    let probe = Duration::from_millis(timeouts.probe_ms);
+    Client::builder()
+        .connect_timeout(probe)
+        .timeout(probe)
+        .build()
+        .expect("HTTP client with timeouts only")
+}
+
+/// Wraps a failed send, naming the timeout when that was the cause. The
+/// reqwest error stays in the chain for callers that inspect it.
+pub fn send_error(err: reqwest::Error, target: &str) -> anyhow::Error {
+    let message = if err.is_timeout() {
+        let limit = if err.is_connect() {
+            timeouts().connect_ms
+        } else {
+            timeouts().request_ms
+        };
+        format!("Request to {} timed out after {} ms", target, limit)
+    } else {
+        format!("Failed to send request to {}", target)
+    };
+    anyhow::Error::new(err).context(message)
+}
+
+/// Keeps requests to hosted endpoints at least `min_interval` apart, so bursts
+/// from rapid dictation stay under strict rate limits instead of running into
+/// 429s. Loopback hosts and `exempt_hosts` are never delayed.
+pub struct RequestSpacer {
+    min_interval: Duration,
+    exempt_hosts: Vec<String>,
+    last_request: Mutex<Option<Instant>>,
+}
+
+impl RequestSpacer {
+    pub fn new(min_interval: Duration, exempt_hosts: Vec<String>) -> Self {
+        RequestSpacer {
+            min_interval,
+            exempt_hosts,
+            last_request: Mutex::new(None),
+        }
+    }
+
+    fn is_exempt(&self, url: &str) -> bool {
+        let Some(host) = Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_string)) else {
+            return false;
+        };
+        matches!(host.as_str(), "localhost" | "127.0.0.1" | "[::1]")
+            || self.exempt_hosts.iter().any(|exempt| exempt.eq_ignore_ascii_case(&host))
+    }
+
+    /// Blocks until `min_interval` has passed since the previous hosted request,
+    /// then records this one
+    pub fn wait(&self, url: &str) {
+        if self.min_interval.is_zero() || self.is_exempt(url) {
+            return;
+        }
+        // Held while sleeping so concurrent requests queue up one interval apart
+        let mut last_request = self.last_request.lock().unwrap();
+        if let Some(previous) = *last_request {
+            let ready = previous + self.min_interval;
+            let now = Instant::now();
+            if ready > now {
+                std::thread::sleep(ready - now);
+            }
+        }
+        *last_request = Some(Instant::now());
+    }
+}
+
+/// Installs `network.min_request_interval_ms`, exempting the host of the local
+/// Whisper endpoint. Only the first call takes effect.
+pub fn configure_request_spacing(network: &NetworkSettings, local_whisper: &str) {
+    let exempt = Url::parse(local_whisper)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .into_iter()
+        .collect();
+    let _ = SPACER.set(RequestSpacer::new(
+        Duration::from_millis(network.min_request_interval_ms),
+        exempt,
+    ));
+}
+
+/// Waits out the configured spacing before a request to `url`
+pub fn space_request(url: &str) {
+    if let Some(spacer) = SPACER.get() {
+        spacer.wait(url);
+    }
+}
+
+/// Turns `[debug].log_http` on or off
+pub fn configure_http_logging(debug: &DebugSettings) {
+    LOG_HTTP.store(debug.log_http, Ordering::Relaxed);
+}
+
+fn log_http_enabled() -> bool {
+    LOG_HTTP.load(Ordering::Relaxed)
+}
+
+fn describe_headers(out: &mut String, headers: &HeaderMap) {
+    for (name, value) in headers {
+        let value = if SECRET_HEADERS.contains(&name.as_str()) {
+            "<redacted>"
+        } else {
+            value.to_str().unwrap_or("<binary>")
+        };
+        let _ = writeln!(out, "  {}: {}", name, value);
+    }
+}
+
+fn excerpt(body: &str) -> String {
+    match body.char_indices().nth(LOGGED_BODY_CHARS) {
+        Some((end, _)) => format!("{}… ({} bytes total)", &body[..end], body.len()),
+        None => body.to_string(),
+    }
+}
+
+/// Log line for an outgoing request. Credentials are redacted, and only JSON
+/// bodies are shown; anything else (multipart audio uploads) is reduced to
+/// its size so audio never ends up in the log.
+pub fn describe_request(request: &Request) -> String {
+    let mut out = format!("HTTP request: {} {}\n", request.method(), request.url());
+    describe_headers(&mut out, request.headers());
+    let is_json = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    match request.body().map(|body| body.as_bytes()) {
+        None => {}
+        Some(Some(bytes)) if is_json => {
+            let _ = write!(out, "  body: {}", excerpt(&String::from_utf8_lossy(bytes)));
+        }
+        Some(Some(bytes)) => {
+            let _ = write!(out, "  body: <{} bytes omitted>", bytes.len());
+        }
+        Some(None) => out.push_str("  body: <streamed body omitted>"),
+    }
+    out.trim_end().to_string()
+}
+
+/// Log line for the status and headers of a response
+pub fn describe_response(response: &Response) -> String {
+    let mut out = format!("HTTP response: {} from {}\n", response.status(), response.url());
+    describe_headers(&mut out, response.headers());
+    out.trim_end().to_string()
+}
+
+/// Logs a response body once a caller has read it, with `[debug].log_http` on
+pub fn log_response_body(service: &str, body: &str) {
+    if log_http_enabled() {
+        info!("HTTP response body from {}: {}", service, excerpt(body));
+    }
+}
+
+/// Sends `request` through `client`, logging both sides when
+/// `[debug].log_http` is on. Errors are wrapped as in `send_error`.
+pub fn send_request(client: &Client, request: RequestBuilder, target: &str) -> Result<Response> {
+    let request = request.build().map_err(|e| send_error(e, target))?;
+    if log_http_enabled() {
+        info!("{}", describe_request(&request));
+    }
+    let response = client.execute(request).map_err(|e| send_error(e, target))?;
+    if log_http_enabled() {
+        info!("{}", describe_response(&response));
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    fn short_timeouts() -> TimeoutSettings {
+        TimeoutSettings {
+            connect_ms: 200,
+            request_ms: 200,
+            probe_ms: 100,
+        }
+    }
+
+    #[test]
+    fn test_hung_server_times_out() {
+        // Accepts connections but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/transcriptions", listener.local_addr().unwrap());
+
+        let start = Instant::now();
+        let err = build_client_with(&short_timeouts()).post(&url).send().unwrap_err();
+        assert!(err.is_timeout());
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        let wrapped = send_error(err, "Whisper endpoint");
+        assert!(wrapped.to_string().starts_with("Request to Whisper endpoint timed out after"));
+        assert!(wrapped.chain().any(|cause| cause.downcast_ref::<reqwest::Error>().is_some()));
+
+        let start = Instant::now();
+        assert!(build_probe_client_with(&short_timeouts()).get(&url).send().is_err());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_logged_request_redacts_key_and_omits_audio() {
+        use reqwest::blocking::multipart;
+        use reqwest::header::AUTHORIZATION;
+
+        let client = Client::new();
+        let audio = multipart::Part::bytes(b"RIFF-secret-audio-samples".to_vec()).file_name("recording.wav");
+        let form = multipart::Form::new().part("file", audio).text("model", "whisper-1");
+        let upload = client
+            .post("https://api.example.com/v1/audio/transcriptions")
+            .multipart(form)
+            .header(AUTHORIZATION, "Bearer sk-live-123")
+            .build()
+            .unwrap();
+
+        let logged = describe_request(&upload);
+        assert!(logged.starts_with("HTTP request: POST https://api.example.com/v1/audio/transcriptions"));
+        assert!(logged.contains("authorization: <redacted>"));
+        assert!(!logged.contains("sk-live-123"));
+        assert!(!logged.contains("secret-audio"));
+        assert!(logged.contains("body: <streamed body omitted>"));
+
+        let completion = client
+            .post("https://api.example.com/v1/completions")
+            .header(AUTHORIZATION, "Bearer sk-live-123")
+            .json(&serde_json::json!({ "prompt": "Clean up: hi" }))
+            .build()
+            .unwrap();
+        let logged = describe_request(&completion);
+        assert!(logged.contains(r#"body: {"prompt":"Clean up: hi"}"#));
+        assert!(!logged.contains("sk-live-123"));
+    }
+
+    #[test]
+    fn test_hosted_requests_are_spaced() {
+        let interval = Duration::from_millis(150);
+        let spacer = RequestSpacer::new(interval, vec!["whisper.lan".to_string()]);
+        let hosted = "https://api.openai.com/v1/audio/transcriptions";
+
+        let start = Instant::now();
+        spacer.wait(hosted);
+        spacer.wait(hosted);
+        assert!(start.elapsed() >= interval);
+
+        // Local endpoints go straight through
+        let start = Instant::now();
+        spacer.wait("http://localhost:5000/transcribe");
+        spacer.wait("http://whisper.lan:9000/transcribe");
+        assert!(start.elapsed() < interval);
+    }
+}
@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+/// Whether the daemon has been idle long enough to exit. A zero timeout never expires.
+pub fn idle_timeout_elapsed(last_activity: Instant, now: Instant, idle_exit_secs: u64) -> bool {
+    idle_exit_secs > 0 && now.saturating_duration_since(last_activity) >= Duration::from_secs(idle_exit_secs)
+}
+
+/// Tracks time since the last recording for `[general].idle_exit_secs`
+pub struct IdleTimer {
+    idle_exit_secs: u64,
+    last_activity: Instant,
+}
+
+impl IdleTimer {
+    pub fn new(idle_exit_secs: u64) -> Self {
+        IdleTimer {
+            idle_exit_secs,
+            last_activity: Instant::now(),
+        }
+    }
+
+    /// Resets the timer; call while a recording is active and when it finishes
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    pub fn is_expired(&self) -> bool {
+        idle_timeout_elapsed(self.last_activity, Instant::now(), self.idle_exit_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_timeout_elapsed() {
+        let start = Instant::now();
+
+        assert!(!idle_timeout_elapsed(start, start + Duration::from_secs(59), 60));
+        assert!(idle_timeout_elapsed(start, start + Duration::from_secs(60), 60));
+        assert!(idle_timeout_elapsed(start, start + Duration::from_secs(3600), 60));
+    }
+
+    #[test]
+    fn test_zero_idle_timeout_never_expires() {
+        let start = Instant::now();
+        assert!(!idle_timeout_elapsed(start, start + Duration::from_secs(86_400), 0));
+    }
+
+    #[test]
+    fn test_activity_resets_idle_timer() {
+        let start = Instant::now();
+        let recording_at = start + Duration::from_secs(50);
+
+        assert!(!idle_timeout_elapsed(recording_at, start + Duration::from_secs(100), 60));
+        assert!(idle_timeout_elapsed(recording_at, start + Duration::from_secs(110), 60));
+    }
+}
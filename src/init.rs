@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{platform_config_path, CONFIG_PATH_ENV};
+
+/// Commented config with every setting at its default
+pub const CONFIG_TEMPLATE: &str = include_str!("config_template.toml");
+
+/// Where `init` writes: `explicit` (from `--config`), then `env`, then the
+/// platform location whether or not it exists yet, and finally `config.toml`
+/// in the current directory
+pub fn resolve_init_path(explicit: Option<PathBuf>, env: Option<OsString>, platform: Option<PathBuf>) -> PathBuf {
+    explicit
+        .or_else(|| env.filter(|path| !path.is_empty()).map(PathBuf::from))
+        .or(platform)
+        .unwrap_or_else(|| PathBuf::from("config.toml"))
+}
+
+/// Config file `init` creates, see `resolve_init_path`
+pub fn init_path(explicit: Option<PathBuf>) -> PathBuf {
+    resolve_init_path(explicit, std::env::var_os(CONFIG_PATH_ENV), platform_config_path())
+}
+
+/// `rusty-scribe init`: writes `CONFIG_TEMPLATE` to `path`, creating its
+/// directory. An existing file is only replaced with `force`.
+pub fn write_default_config(path: &Path, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        return Err(anyhow::anyhow!(
+            "{} already exists; pass --force to overwrite it",
+            path.display()
+        ));
+    }
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("Unable to create {}", dir.display()))?;
+    }
+    fs::write(path, CONFIG_TEMPLATE).with_context(|| format!("Unable to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::*;
+    use crate::migrate::CURRENT_CONFIG_VERSION;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_template_deserializes_to_defaults() {
+        let config: Config = toml::from_str(CONFIG_TEMPLATE).expect("template must deserialize");
+
+        assert_eq!(config.config_version, Some(CURRENT_CONFIG_VERSION));
+        assert_eq!(config.api_keys, ApiKeys::default());
+        assert_eq!(config.output, OutputSettings::default());
+        assert_eq!(config.clipboard, ClipboardSettings::default());
+        assert_eq!(config.transcription, TranscriptionSettings::default());
+        assert_eq!(config.network, NetworkSettings::default());
+        assert_eq!(config.timeouts, TimeoutSettings::default());
+        assert_eq!(config.general, GeneralSettings::default());
+        assert_eq!(config.ui, UiSettings::default());
+        assert_eq!(config.privacy, PrivacySettings::default());
+        assert_eq!(config.analytics, AnalyticsSettings::default());
+        assert_eq!(config.session, SessionSettings::default());
+        assert_eq!(config.captions, CaptionSettings::default());
+        assert_eq!(config.debug, DebugSettings::default());
+        assert_eq!(config.history, HistorySettings::default());
+        assert_eq!(config.app, AppSettings::default());
+        assert_eq!(config.webhook, None);
+        assert!(config.device_profiles.is_empty());
+    }
+
+    #[test]
+    fn test_init_refuses_to_overwrite_without_force() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("rusty-scribe").join("config.toml");
+
+        write_default_config(&path, false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), CONFIG_TEMPLATE);
+
+        fs::write(&path, "# mine").unwrap();
+        let err = write_default_config(&path, false).unwrap_err();
+        assert!(err.to_string().ends_with("already exists; pass --force to overwrite it"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# mine");
+
+        write_default_config(&path, true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), CONFIG_TEMPLATE);
+    }
+}
@@ -0,0 +1,139 @@
+use log::debug;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::api::is_local_endpoint_available;
+use crate::config::NetworkSettings;
+
+/// How often the keepalive thread wakes to check whether a ping is due
+const TICK: Duration = Duration::from_millis(10);
+
+/// Whether any battery reports that it is discharging (Linux sysfs only)
+pub fn on_battery_power() -> bool {
+    let Ok(supplies) = fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    supplies.flatten().any(|supply| {
+        let read = |name: &str| fs::read_to_string(supply.path().join(name)).unwrap_or_default();
+        read("type").trim() == "Battery" && read("status").trim() == "Discharging"
+    })
+}
+
+/// Background task that pings the local endpoint every `interval` so the server
+/// does not unload its model. Real requests count as pings, so a recently used
+/// endpoint is left alone.
+pub struct Keepalive {
+    last_activity: Arc<Mutex<Instant>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Keepalive {
+    /// Starts pinging. `paused` is checked before each ping, e.g. for battery power.
+    pub fn spawn<P, Q>(interval: Duration, ping: P, paused: Q) -> Self
+    where
+        P: Fn() + Send + 'static,
+        Q: Fn() -> bool + Send + 'static,
+    {
+        let last_activity = Arc::new(Mutex::new(Instant::now()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let last_activity = Arc::clone(&last_activity);
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    thread::sleep(TICK);
+                    let due = last_activity.lock().unwrap().elapsed() >= interval;
+                    if due && !paused() {
+                        ping();
+                        *last_activity.lock().unwrap() = Instant::now();
+                    }
+                }
+            })
+        };
+        Keepalive {
+            last_activity,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Starts the keepalive configured in `[network]`, or nothing when disabled
+    pub fn from_settings(local_whisper: &str, network: &NetworkSettings) -> Option<Self> {
+        if network.keepalive_interval_secs == 0 {
+            return None;
+        }
+        let url = local_whisper.to_string();
+        let on_battery = network.keepalive_on_battery;
+        Some(Keepalive::spawn(
+            Duration::from_secs(network.keepalive_interval_secs),
+            move || {
+                let alive = is_local_endpoint_available(&url);
+                debug!("Keepalive ping to {}: {}", url, if alive { "ok" } else { "unreachable" });
+            },
+            move || !on_battery && on_battery_power(),
+        ))
+    }
+
+    /// Records a real request to the endpoint, postponing the next ping
+    pub fn mark_used(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+}
+
+impl Drop for Keepalive {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn counting_keepalive(interval: Duration, paused: bool) -> (Keepalive, Arc<AtomicUsize>) {
+        let pings = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&pings);
+        let keepalive = Keepalive::spawn(
+            interval,
+            move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            },
+            move || paused,
+        );
+        (keepalive, pings)
+    }
+
+    #[test]
+    fn test_keepalive_fires_at_interval() {
+        let (keepalive, pings) = counting_keepalive(Duration::from_millis(50), false);
+        thread::sleep(Duration::from_millis(280));
+        drop(keepalive);
+
+        let count = pings.load(Ordering::SeqCst);
+        assert!((3..=5).contains(&count), "expected about 5 pings, got {}", count);
+    }
+
+    #[test]
+    fn test_recent_use_and_pause_suppress_pings() {
+        let (keepalive, pings) = counting_keepalive(Duration::from_millis(100), false);
+        for _ in 0..10 {
+            thread::sleep(Duration::from_millis(20));
+            keepalive.mark_used();
+        }
+        drop(keepalive);
+        assert_eq!(pings.load(Ordering::SeqCst), 0);
+
+        let (keepalive, pings) = counting_keepalive(Duration::from_millis(20), true);
+        thread::sleep(Duration::from_millis(100));
+        drop(keepalive);
+        assert_eq!(pings.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,42 @@
+pub mod analytics;
+pub mod api;
+pub mod audio;
+pub mod budget;
+pub mod calibrate;
+pub mod captions;
+pub mod chunks;
+pub mod clipboard;
+pub mod command_backend;
+pub mod config;
+pub mod consent;
+pub mod decode;
+pub mod device_check;
+pub mod diarize;
+pub mod diff;
+pub mod disk;
+pub mod error;
+pub mod history;
+pub mod hotkeys;
+pub mod http;
+pub mod idle;
+pub mod init;
+pub mod keepalive;
+pub mod llm;
+pub mod logging;
+pub mod migrate;
+pub mod notify;
+pub mod once;
+pub mod output;
+pub mod pipeline;
+pub mod queue;
+pub mod rebind;
+pub mod result;
+pub mod selection;
+pub mod session;
+pub mod streaming;
+pub mod subtitles;
+pub mod text;
+pub mod tray;
+pub mod vocabulary;
+pub mod webhook;
+pub mod window;
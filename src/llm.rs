@@ -0,0 +1,468 @@
+use anyhow::{Result, Context};
+use serde::Deserialize;
+
+use crate::api::{post_process_chat, post_process_text, ApiError};
+use crate::config::{ApiKeys, LLMSettings, LlmApiStyle, LlmProviderKind};
+use crate::http::{build_client, log_response_body, send_request, space_request};
+
+/// Model used with Ollama when `llm.model` is not set
+pub const DEFAULT_OLLAMA_MODEL: &str = "llama3";
+
+/// Model used with `llm.api_style = "chat"` when `llm.model` is not set
+pub const DEFAULT_CHAT_MODEL: &str = "gpt-4o-mini";
+
+/// Model used with Anthropic when `llm.model` is not set
+pub const DEFAULT_ANTHROPIC_MODEL: &str = "claude-3-5-haiku-latest";
+
+/// `anthropic-version` header the Messages request and response shapes follow
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Anthropic requires a reply length limit on every request
+const ANTHROPIC_MAX_TOKENS: u32 = 1024;
+
+/// Prompt placeholders, replaced when the prompt is rendered
+const TEXT_PLACEHOLDER: &str = "{{text}}";
+const LANG_PLACEHOLDER: &str = "{{lang}}";
+const DATE_PLACEHOLDER: &str = "{{date}}";
+
+/// Stands in for `{{lang}}` when no language was detected or configured
+const UNKNOWN_LANGUAGE: &str = "the original language";
+
+/// Backend that runs a prompt over a transcription and returns the result. An
+/// empty `prompt` sends `text` on its own, as for a rendered template.
+pub trait LlmProvider {
+    fn complete(&self, prompt: &str, text: &str) -> Result<String>;
+}
+
+/// `prompt` followed by `text`, the shape of single-string prompts
+pub fn join_prompt(prompt: &str, text: &str) -> String {
+    if prompt.is_empty() {
+        text.to_string()
+    } else {
+        format!("{} {}", prompt, text)
+    }
+}
+
+/// Replaces `{{lang}}` and `{{date}}` (today, as YYYY-MM-DD) in a prompt
+fn fill_placeholders(template: &str, lang: Option<&str>) -> String {
+    template
+        .replace(LANG_PLACEHOLDER, lang.unwrap_or(UNKNOWN_LANGUAGE))
+        .replace(DATE_PLACEHOLDER, &chrono::Local::now().format("%Y-%m-%d").to_string())
+}
+
+/// The full prompt for `text`: `{{text}}`, `{{lang}}` and `{{date}}` in
+/// `template` are filled in. A template without `{{text}}` is followed by
+/// the text, as prompts always were.
+pub fn render_prompt(template: &str, text: &str, lang: Option<&str>) -> String {
+    let prompt = fill_placeholders(template, lang);
+    // Filled last so placeholders spoken in the dictation stay as they are
+    if prompt.contains(TEXT_PLACEHOLDER) {
+        prompt.replace(TEXT_PLACEHOLDER, text)
+    } else {
+        join_prompt(&prompt, text)
+    }
+}
+
+/// Runs `template` over `text`. Templates that place the text themselves are
+/// sent as a single message; others keep the prompt and the text apart, e.g.
+/// as system and user message.
+pub fn complete_template(provider: &dyn LlmProvider, template: &str, text: &str, lang: Option<&str>) -> Result<String> {
+    if template.contains(TEXT_PLACEHOLDER) {
+        provider.complete("", &render_prompt(template, text, lang))
+    } else {
+        provider.complete(&fill_placeholders(template, lang), text)
+    }
+}
+
+/// OpenAI-style `/completions` endpoint (`{"choices": [{"text"}]}`)
+pub struct OpenAiCompletions {
+    pub url: String,
+    pub api_key: String,
+    pub max_tokens: u32,
+    pub temperature: f64,
+}
+
+impl LlmProvider for OpenAiCompletions {
+    fn complete(&self, prompt: &str, text: &str) -> Result<String> {
+        post_process_text(&self.url, &self.api_key, prompt, text, self.max_tokens, self.temperature)
+    }
+}
+
+/// OpenAI-style `/chat/completions` endpoint (`{"choices": [{"message": {"content"}}]}`)
+pub struct OpenAiChat {
+    pub url: String,
+    pub api_key: String,
+    pub model: String,
+    pub max_tokens: u32,
+    pub temperature: f64,
+}
+
+impl LlmProvider for OpenAiChat {
+    fn complete(&self, prompt: &str, text: &str) -> Result<String> {
+        post_process_chat(
+            &self.url,
+            &self.api_key,
+            &self.model,
+            prompt,
+            text,
+            self.max_tokens,
+            self.temperature,
+        )
+    }
+}
+
+/// Ollama's native `/api/generate` endpoint
+pub struct Ollama {
+    pub url: String,
+    pub model: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct OllamaChunk {
+    #[serde(default)]
+    response: String,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Joins the `response` fields of an Ollama reply. With `"stream": false` that is
+/// a single object; servers that stream anyway send one object per line.
+fn parse_ollama_body(body: &str) -> Result<String> {
+    let mut text = String::new();
+    for line in body.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let chunk: OllamaChunk = serde_json::from_str(line)
+            .context("Failed to parse Ollama response")?;
+        if let Some(error) = chunk.error {
+            return Err(anyhow::anyhow!("Ollama error: {}", error));
+        }
+        text.push_str(&chunk.response);
+    }
+    Ok(text.trim().to_string())
+}
+
+impl LlmProvider for Ollama {
+    fn complete(&self, prompt: &str, text: &str) -> Result<String> {
+        let payload = serde_json::json!({
+            "model": self.model,
+            "prompt": join_prompt(prompt, text),
+            "stream": false,
+        });
+
+        space_request(&self.url);
+        let client = build_client();
+        let request = client.post(&self.url).json(&payload);
+        let response = send_request(&client, request, "Ollama endpoint")?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::from_response("Ollama", response).into());
+        }
+        let body = response.text().context("Failed to read Ollama response")?;
+        log_response_body("Ollama", &body);
+        parse_ollama_body(&body)
+    }
+}
+
+/// Anthropic's Messages API (`/v1/messages`)
+pub struct Anthropic {
+    pub url: String,
+    pub api_key: String,
+    pub model: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct AnthropicContent {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct AnthropicResponse {
+    content: Vec<AnthropicContent>,
+}
+
+impl LlmProvider for Anthropic {
+    fn complete(&self, prompt: &str, text: &str) -> Result<String> {
+        if self.api_key.trim().is_empty() {
+            return Err(anyhow::anyhow!(
+                "No Anthropic API key found. Set api_keys.anthropic in config.toml or the ANTHROPIC_API_KEY environment variable."
+            ));
+        }
+        let mut payload = serde_json::json!({
+            "model": self.model,
+            "max_tokens": ANTHROPIC_MAX_TOKENS,
+            "messages": [
+                { "role": "user", "content": text },
+            ],
+        });
+        if !prompt.is_empty() {
+            payload["system"] = serde_json::json!(prompt);
+        }
+
+        space_request(&self.url);
+        let client = build_client();
+        let request = client
+            .post(&self.url)
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(&payload);
+        let response = send_request(&client, request, "Anthropic endpoint")?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::from_response("Anthropic", response).into());
+        }
+        let body = response.text().context("Failed to read Anthropic response")?;
+        log_response_body("Anthropic", &body);
+        let parsed: AnthropicResponse = serde_json::from_str(&body).context("Failed to parse Anthropic response")?;
+        parsed
+            .content
+            .into_iter()
+            .next()
+            .map(|content| content.text.trim().to_string())
+            .context("No content found in Anthropic response")
+    }
+}
+
+/// Builds the provider selected by `llm.provider` for the configured endpoint
+pub fn provider_from_settings(llm_url: &str, api_keys: &ApiKeys, llm: &LLMSettings) -> Box<dyn LlmProvider> {
+    let api_key = &api_keys.openai;
+    match llm.provider {
+        LlmProviderKind::OpenAi => match llm.api_style {
+            LlmApiStyle::Completions => Box::new(OpenAiCompletions {
+                url: llm_url.to_string(),
+                api_key: api_key.to_string(),
+                max_tokens: llm.max_tokens,
+                temperature: llm.temperature,
+            }),
+            LlmApiStyle::Chat => Box::new(OpenAiChat {
+                url: llm_url.to_string(),
+                api_key: api_key.to_string(),
+                model: llm.model.clone().unwrap_or_else(|| DEFAULT_CHAT_MODEL.to_string()),
+                max_tokens: llm.max_tokens,
+                temperature: llm.temperature,
+            }),
+        },
+        LlmProviderKind::Ollama => Box::new(Ollama {
+            url: llm_url.to_string(),
+            model: llm
+                .model
+                .clone()
+                .unwrap_or_else(|| DEFAULT_OLLAMA_MODEL.to_string()),
+        }),
+        LlmProviderKind::Anthropic => Box::new(Anthropic {
+            url: llm_url.to_string(),
+            api_key: api_keys.anthropic.clone(),
+            model: llm.model.clone().unwrap_or_else(|| DEFAULT_ANTHROPIC_MODEL.to_string()),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, Matcher};
+    use serde_json::json;
+
+    fn ollama(path: &str) -> Ollama {
+        Ollama {
+            url: format!("{}{}", &mockito::server_url(), path),
+            model: "llama3".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_ollama_generate_non_streaming() {
+        let _m = mock("POST", "/api/generate")
+            .match_body(Matcher::Json(json!({
+                "model": "llama3",
+                "prompt": "Clean up: um hello there",
+                "stream": false
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"model": "llama3", "response": " Hello there.", "done": true}"#)
+            .create();
+
+        let text = ollama("/api/generate").complete("Clean up:", "um hello there").unwrap();
+        assert_eq!(text, "Hello there.");
+    }
+
+    #[test]
+    fn test_ollama_generate_streamed_chunks() {
+        let _m = mock("POST", "/api/generate-stream")
+            .with_status(200)
+            .with_header("content-type", "application/x-ndjson")
+            .with_body(
+                "{\"response\": \"Hello\", \"done\": false}\n\
+                 {\"response\": \" there.\", \"done\": false}\n\
+                 {\"response\": \"\", \"done\": true}\n",
+            )
+            .create();
+
+        let text = ollama("/api/generate-stream").complete("Clean up:", "hello there").unwrap();
+        assert_eq!(text, "Hello there.");
+    }
+
+    #[test]
+    fn test_provider_from_settings_uses_api_style() {
+        let _m = mock("POST", "/style/chat/completions")
+            .match_body(Matcher::PartialJson(json!({ "model": "gpt-4o-mini" })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"choices": [{"message": {"role": "assistant", "content": "Chat reply."}}]}"#)
+            .create();
+        let _legacy = mock("POST", "/style/completions")
+            .match_body(Matcher::PartialJson(json!({ "prompt": "Clean up: hi" })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"choices": [{"text": "Legacy reply."}]}"#)
+            .create();
+
+        let mut llm: LLMSettings = toml::from_str(
+            "post_processing_prompt = \"Clean up:\"\nalways_post_process = false\napi_style = \"chat\"",
+        )
+        .unwrap();
+        let chat_url = format!("{}/style/chat/completions", &mockito::server_url());
+        let keys = ApiKeys {
+            openai: "key".to_string(),
+            ..ApiKeys::default()
+        };
+        let provider = provider_from_settings(&chat_url, &keys, &llm);
+        assert_eq!(provider.complete("Clean up:", "hi").unwrap(), "Chat reply.");
+
+        llm.api_style = LlmApiStyle::Completions;
+        let legacy_url = format!("{}/style/completions", &mockito::server_url());
+        let provider = provider_from_settings(&legacy_url, &keys, &llm);
+        assert_eq!(provider.complete("Clean up:", "hi").unwrap(), "Legacy reply.");
+    }
+
+    #[test]
+    fn test_render_prompt_fills_placeholders() {
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        assert_eq!(
+            render_prompt("Fix grammar in {{lang}}:\n{{text}}\n(as of {{date}})", "me and him goes", Some("en")),
+            format!("Fix grammar in en:\nme and him goes\n(as of {})", today)
+        );
+        assert_eq!(
+            render_prompt("Translate from {{lang}}: {{text}}", "hallo", None),
+            "Translate from the original language: hallo"
+        );
+        // Placeholders in the dictation itself are left alone
+        assert_eq!(render_prompt("{{text}} ({{lang}})", "say {{lang}}", Some("de")), "say {{lang}} (de)");
+    }
+
+    #[test]
+    fn test_render_prompt_without_text_placeholder_prepends() {
+        assert_eq!(render_prompt("Clean up:", "um hello", Some("en")), "Clean up: um hello");
+        assert_eq!(render_prompt("Clean up this {{lang}}:", "um hello", Some("en")), "Clean up this en: um hello");
+        assert_eq!(render_prompt("", "um hello", None), "um hello");
+    }
+
+    #[test]
+    fn test_chat_sends_rendered_template_as_one_message() {
+        let _m = mock("POST", "/template/chat/completions")
+            .match_body(Matcher::PartialJson(json!({
+                "messages": [{ "role": "user", "content": "Fix grammar in de:\nich gehen" }]
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"choices": [{"message": {"role": "assistant", "content": "Ich gehe."}}]}"#)
+            .create();
+
+        let llm: LLMSettings = toml::from_str(
+            "post_processing_prompt = \"Fix grammar in {{lang}}:\\n{{text}}\"\nalways_post_process = true\napi_style = \"chat\"",
+        )
+        .unwrap();
+        let url = format!("{}/template/chat/completions", &mockito::server_url());
+        let provider = provider_from_settings(&url, &ApiKeys::default(), &llm);
+        let output = crate::api::run_llm_passes(provider.as_ref(), &llm, "ich gehen", Some("de"), None, true).unwrap();
+        assert_eq!(output.text, "Ich gehe.");
+    }
+
+    #[test]
+    fn test_chat_payload_uses_configured_sampling() {
+        let _m = mock("POST", "/sampling/chat/completions")
+            .match_body(Matcher::Json(json!({
+                "model": "gpt-4o",
+                "messages": [
+                    { "role": "system", "content": "Clean up:" },
+                    { "role": "user", "content": "a long dictation" }
+                ],
+                "max_tokens": 2000,
+                "temperature": 0.2
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"choices": [{"message": {"role": "assistant", "content": "A long dictation."}}]}"#)
+            .create();
+
+        let llm: LLMSettings = toml::from_str(
+            "post_processing_prompt = \"Clean up:\"\nalways_post_process = false\napi_style = \"chat\"\n\
+             model = \"gpt-4o\"\nmax_tokens = 2000\ntemperature = 0.2",
+        )
+        .unwrap();
+        let url = format!("{}/sampling/chat/completions", &mockito::server_url());
+        let provider = provider_from_settings(&url, &ApiKeys::default(), &llm);
+        assert_eq!(provider.complete("Clean up:", "a long dictation").unwrap(), "A long dictation.");
+    }
+
+    #[test]
+    fn test_anthropic_messages_request() {
+        let _m = mock("POST", "/v1/messages")
+            .match_header("x-api-key", "anthropic_key")
+            .match_header("anthropic-version", "2023-06-01")
+            .match_header("content-type", "application/json")
+            .match_body(Matcher::Json(json!({
+                "model": "claude-3-5-haiku-latest",
+                "max_tokens": 1024,
+                "system": "Clean up:",
+                "messages": [{ "role": "user", "content": "um hello there" }]
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id": "msg_01", "type": "message", "role": "assistant",
+                    "content": [{"type": "text", "text": " Hello there. "}],
+                    "stop_reason": "end_turn"}"#,
+            )
+            .create();
+
+        let llm: LLMSettings = toml::from_str(
+            "post_processing_prompt = \"Clean up:\"\nalways_post_process = false\nprovider = \"anthropic\"",
+        )
+        .unwrap();
+        let keys = ApiKeys {
+            openai: "openai_key".to_string(),
+            anthropic: "anthropic_key".to_string(),
+        };
+        let url = format!("{}/v1/messages", &mockito::server_url());
+        let provider = provider_from_settings(&url, &keys, &llm);
+        assert_eq!(provider.complete("Clean up:", "um hello there").unwrap(), "Hello there.");
+    }
+
+    #[test]
+    fn test_anthropic_error_is_reported() {
+        let _m = mock("POST", "/v1/messages-overloaded")
+            .with_status(529)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#)
+            .create();
+
+        let provider = Anthropic {
+            url: format!("{}/v1/messages-overloaded", &mockito::server_url()),
+            api_key: "anthropic_key".to_string(),
+            model: DEFAULT_ANTHROPIC_MODEL.to_string(),
+        };
+        let err = provider.complete("Clean up:", "hi").unwrap_err();
+        assert!(err.to_string().starts_with("Anthropic API error 529"), "{}", err);
+    }
+
+    #[test]
+    fn test_ollama_error_field_is_reported() {
+        assert_eq!(
+            parse_ollama_body(r#"{"error": "model 'llama9' not found"}"#)
+                .unwrap_err()
+                .to_string(),
+            "Ollama error: model 'llama9' not found"
+        );
+    }
+}
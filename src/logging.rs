@@ -0,0 +1,92 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::{Log, Metadata, Record};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::{LogFormat, LogSettings};
+
+static JSON_LOGS: AtomicBool = AtomicBool::new(false);
+
+/// env_logger with `RUST_LOG` filtering as usual; lines are written as JSON
+/// instead once `log.format = "json"` is configured
+struct ScribeLogger {
+    text: env_logger::Logger,
+}
+
+impl Log for ScribeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.text.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !JSON_LOGS.load(Ordering::Relaxed) {
+            return self.text.log(record);
+        }
+        if self.text.matches(record) {
+            let mut stderr = std::io::stderr().lock();
+            let _ = writeln!(stderr, "{}", json_line(record, Utc::now()));
+        }
+    }
+
+    fn flush(&self) {
+        self.text.flush();
+    }
+}
+
+/// Installs the logger. Lines logged before the config is loaded are always
+/// text, since the format is not known yet.
+pub fn init_logging() {
+    let text = env_logger::Builder::from_default_env().build();
+    let max_level = text.filter();
+    if log::set_boxed_logger(Box::new(ScribeLogger { text })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Switches to the `[log]` format for everything logged afterwards
+pub fn configure_log_format(log: &LogSettings) {
+    JSON_LOGS.store(log.format == LogFormat::Json, Ordering::Relaxed);
+}
+
+/// One log record as a single-line JSON object
+pub fn json_line(record: &Record, ts: DateTime<Utc>) -> String {
+    serde_json::json!({
+        "ts": ts.to_rfc3339_opts(SecondsFormat::Millis, true),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "msg": record.args().to_string(),
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use log::Level;
+
+    #[test]
+    fn test_json_line_is_one_parseable_object() {
+        let ts = Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap();
+        let line = json_line(
+            &Record::builder()
+                .args(format_args!("Transcribed \"{}\"\nin {} ms", "hello", 840))
+                .level(Level::Warn)
+                .target("rusty_scribe::api")
+                .build(),
+            ts,
+        );
+
+        assert!(!line.contains('\n'));
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "ts": "2024-05-01T12:30:00.000Z",
+                "level": "WARN",
+                "target": "rusty_scribe::api",
+                "msg": "Transcribed \"hello\"\nin 840 ms",
+            })
+        );
+    }
+}
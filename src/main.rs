@@ -1,165 +1,575 @@
-mod clipboard;
-
-use clipboard::copy_to_clipboard;
-
-use anyhow::{anyhow, Result};
-use clap::Parser;
-use log::error;
-use log::info;
-use screenpipe_audio::create_whisper_channel;
-use screenpipe_audio::default_input_device;
-use screenpipe_audio::default_output_device;
-use screenpipe_audio::list_audio_devices;
-use screenpipe_audio::parse_audio_device;
-use screenpipe_audio::record_and_transcribe;
-use screenpipe_audio::AudioDevice;
-use screenpipe_audio::AudioTranscriptionEngine;
-use screenpipe_audio::VadEngineEnum;
-
+use anyhow::{Context, Result};
+use clap::{value_parser, Arg, ArgAction, Command};
+use hound::WavSpec;
+use log::{error, info, warn};
 use std::path::PathBuf;
-use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
-use std::time::Duration;
-
-#[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
-struct Args {
-    #[clap(
-        short,
-        long,
-        help = "Audio device name (can be specified multiple times)"
-    )]
-    audio_device: Vec<String>,
-
-    #[clap(long, help = "List available audio devices")]
-    list_audio_devices: bool,
-
-    #[clap(long, help = "Deepgram API key")]
-    deepgram_api_key: Option<String>,
-
-    #[clap(long, help = "Place the output into clipboard")]
-    copy_to_clipboard: bool,
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use rusty_scribe::analytics::print_stats;
+use rusty_scribe::audio::{
+    get_device_from_name, list_audio_devices, record_audio_until, record_with_empty_retry, CpalDevices,
+    DeviceSelector, TEMP_RECORDINGS,
+};
+use rusty_scribe::calibrate::calibrate;
+use rusty_scribe::clipboard::copy_to_clipboard;
+use rusty_scribe::captions::run_live_captions;
+use rusty_scribe::config::{config_path, load_config_from, Config, HotkeyMode};
+use rusty_scribe::device_check::{record_probe, SilentDeviceGuard};
+use rusty_scribe::http::{configure_http_logging, configure_request_spacing, configure_timeouts};
+use rusty_scribe::error::{notify_error, ScribeError};
+use rusty_scribe::history::{history_path, read_last_entries};
+use rusty_scribe::hotkeys::{start_hotkey_listener, HotkeyState};
+use rusty_scribe::idle::IdleTimer;
+use rusty_scribe::init::{init_path, write_default_config};
+use rusty_scribe::keepalive::Keepalive;
+use rusty_scribe::logging::{configure_log_format, init_logging};
+use rusty_scribe::migrate::migrate_config_file;
+use rusty_scribe::notify::{notify_desktop, DesktopNotifier, LifecycleEvent, LogNotifier, Notifier};
+use rusty_scribe::once::{run_file, run_once};
+use rusty_scribe::output::{auto_paste_enabled, fallback_output_mode, probe_simulate, select_output_mode};
+use rusty_scribe::pipeline::{deliver, process_recording, DryRunIo, FinishedRecording, SystemIo};
+use rusty_scribe::rebind::{rebind, REBINDABLE_ACTIONS};
+use rusty_scribe::session::{announce_recovered, SessionBuffer};
+use rusty_scribe::tray::start_tray;
+use rusty_scribe::vocabulary::load_vocabulary;
+
+/// How often the main loop samples the hotkey state
+const STATE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// How often the silent device check looks for a due probe
+const SILENT_CHECK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A capture running on its own thread until `stop` is raised
+struct ActiveRecording {
+    stop: Arc<AtomicBool>,
+    capture: JoinHandle<Result<(Vec<i16>, WavSpec)>>,
+    post_processing: bool,
+    cancelled: bool,
+}
+
+/// A stop signal raised once `after` has passed
+fn stop_after(after: Duration) -> Arc<AtomicBool> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let timer = Arc::clone(&stop);
+    thread::spawn(move || {
+        thread::sleep(after);
+        timer.store(true, Ordering::SeqCst);
+    });
+    stop
 }
 
-fn print_devices(devices: &[AudioDevice]) {
-    println!("Available audio devices:");
-    for (_, device) in devices.iter().enumerate() {
-        println!("  {}", device);
+/// Starts capturing from `audio.recording_device`, with "default" resolved
+/// by `devices` so a new OS default is picked up between recordings
+fn start_recording(config: &Config, devices: &mut DeviceSelector) -> ActiveRecording {
+    notify_desktop(&config.ui, &LifecycleEvent::RecordingStarted);
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut audio = config.audio.clone();
+    match devices.select(&audio.recording_device, &CpalDevices) {
+        Ok(device) => audio.recording_device = device,
+        // Opening the device reports the problem in full
+        Err(e) => warn!("Failed to resolve the recording device: {:?}", e),
     }
+    let profiles = config.device_profiles.clone();
+    let grace = Duration::from_millis(config.hotkeys.release_grace_ms);
+    let signal = Arc::clone(&stop);
 
-    #[cfg(target_os = "macos")]
-    println!("On macOS, it's not intuitive but output devices are your displays");
+    let capture = thread::spawn(move || {
+        let mut spec = None;
+        let mut signal = Some(signal);
+        let mut first_attempt = Duration::ZERO;
+        let samples = record_with_empty_retry(audio.retry_empty_recording, || {
+            // A retry records again for as long as the first attempt ran
+            let stop = signal.take().unwrap_or_else(|| stop_after(first_attempt));
+            let started = Instant::now();
+            let (tx, rx) = mpsc::channel();
+            let summary = record_audio_until(&audio.recording_device, &audio, &profiles, grace, stop, tx)?;
+            first_attempt = started.elapsed();
+            spec = Some(summary.spec);
+            // The stream owned the remaining senders, so this ends once it is dropped
+            Ok(rx.into_iter().collect())
+        })?;
+        Ok((samples, spec.expect("at least one capture ran")))
+    });
+
+    ActiveRecording {
+        stop,
+        capture,
+        post_processing: false,
+        cancelled: false,
+    }
 }
 
-// ! usage - cargo run --bin screenpipe-audio -- --audio-device "Display 1 (output)"
+/// Runs the `audio.silent_device_check` probes on their own thread: one at
+/// startup, then every `silent_device_check_interval_mins`, skipped while a
+/// recording holds the device
+fn start_silent_device_check(config: &Config, state: Arc<Mutex<HotkeyState>>) {
+    if !config.audio.silent_device_check {
+        return;
+    }
+    let device = config.audio.recording_device.clone();
+    let interval_mins = config.audio.silent_device_check_interval_mins;
+    let notify = config.ui.error_notifications;
+    thread::spawn(move || {
+        let notifier: &dyn Notifier = if notify { &DesktopNotifier } else { &LogNotifier };
+        let mut guard = SilentDeviceGuard::new(interval_mins);
+        loop {
+            let now = Instant::now();
+            if guard.is_due(now) && !state.lock().unwrap().is_recording {
+                match record_probe(&device) {
+                    Ok(samples) => {
+                        guard.check(&samples, now, notifier);
+                    }
+                    Err(e) => {
+                        warn!("Silent device check could not record: {:?}", e);
+                        return;
+                    }
+                }
+                if interval_mins == 0 {
+                    return;
+                }
+            }
+            thread::sleep(SILENT_CHECK_POLL_INTERVAL);
+        }
+    });
+}
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    use env_logger::Builder;
-    use log::LevelFilter;
+/// Stops the capture and processes it on a blocking task, adding the
+/// delivered text to the session if one is kept
+fn finish_recording(
+    recording: ActiveRecording,
+    config: &Config,
+    state: &Arc<Mutex<HotkeyState>>,
+    session: &Option<Arc<Mutex<SessionBuffer>>>,
+    is_post_processing: bool,
+) {
+    recording.stop.store(true, Ordering::SeqCst);
+    let (forced, post_processing_enabled, target_window, selection) = {
+        let mut state = state.lock().unwrap();
+        state.pending_transcriptions += 1;
+        (
+            state.take_endpoint_override(),
+            state.post_processing_enabled,
+            state.target_window,
+            state.selection_context.take(),
+        )
+    };
+    let config = config.clone();
+    let modifier_held = recording.post_processing || is_post_processing;
+    let cancelled = recording.cancelled;
+    let state = Arc::clone(state);
+    let session = session.clone();
+    tokio::task::spawn_blocking(move || {
+        let result = match recording.capture.join() {
+            Ok(capture) => capture.map_err(|e| ScribeError::Capture(e).into()).and_then(|(samples, spec)| {
+                let recording = FinishedRecording {
+                    samples,
+                    spec,
+                    modifier_held,
+                    post_processing_enabled,
+                    forced,
+                    cancelled,
+                    target_window,
+                    selection,
+                };
+                if config.app.dry_run {
+                    let prompt = &config.llm.post_processing_prompt;
+                    let mut target = target_window;
+                    let mut io = DryRunIo::new(prompt, |text: &str| deliver(&config, text, &mut target));
+                    process_recording(&config, &recording, &mut io)
+                } else {
+                    process_recording(&config, &recording, &mut SystemIo::new(&config, target_window))
+                }
+            }),
+            Err(_) => Err(anyhow::anyhow!("Recording thread panicked")),
+        };
+        match result {
+            Ok(None) => {}
+            Ok(Some(result)) => {
+                if let Some(session) = &session {
+                    session.lock().unwrap().append(&result.text);
+                }
+                notify_desktop(
+                    &config.ui,
+                    &LifecycleEvent::Delivered {
+                        text: &result.text,
+                        mode: config.output.mode,
+                    },
+                )
+            }
+            Err(e) => {
+                error!("Failed to process recording: {:?}", e);
+                match e.downcast_ref::<ScribeError>() {
+                    Some(err) => notify_error(err, config.ui.error_notifications, &DesktopNotifier),
+                    None => notify_desktop(&config.ui, &LifecycleEvent::Failed(&e)),
+                }
+            }
+        }
+        state.lock().unwrap().pending_transcriptions -= 1;
+    });
+}
 
-    Builder::new()
-        .filter(None, LevelFilter::Debug)
-        .filter_module("tokenizers", LevelFilter::Error)
-        .init();
+fn print_history(config: &Config, count: usize) -> Result<()> {
+    let path = history_path(&config.history)?;
+    if !config.history.enabled {
+        eprintln!("history.enabled is off; new transcriptions are not being recorded");
+    }
+    for entry in read_last_entries(&path, count)? {
+        let endpoint = if entry.used_local { "local" } else { "hosted" };
+        let cleaned = if entry.post_processed { ", post-processed" } else { "" };
+        println!(
+            "{} ({:.1}s, {} {}{}) {}",
+            entry.timestamp, entry.duration_secs, endpoint, entry.model, cleaned, entry.text
+        );
+    }
+    Ok(())
+}
 
-    let args = Args::parse();
+/// The continuous-dictation session, kept only with `[session]` autosave and
+/// never in memory-only mode. The text of one that did not end cleanly is
+/// handed back to the user and carried on.
+fn start_session(config: &Config, notifier: &dyn Notifier) -> Option<SessionBuffer> {
+    let settings = &config.session;
+    if !settings.autosave_enabled() {
+        return None;
+    }
+    if config.privacy.memory_only {
+        warn!("privacy.memory_only keeps dictated text off disk; session autosave is off");
+        return None;
+    }
+    let session = SessionBuffer::restore(settings).unwrap_or_else(|e| {
+        warn!("Starting a new session: {:?}", e);
+        SessionBuffer::new(settings)
+    });
+    announce_recovered(&session, notifier, copy_to_clipboard);
+    Some(session)
+}
 
-    let devices = list_audio_devices().await?;
+/// `doctor`: whether this session supports synthetic input, and the output
+/// the daemon would use because of it
+fn print_diagnostics(config: &Config) {
+    let simulate_available = probe_simulate();
+    println!(
+        "Synthetic keyboard input: {}",
+        if simulate_available { "available" } else { "unavailable" }
+    );
+    let requested = config.output.mode;
+    match fallback_output_mode(requested, simulate_available, config.output.fallback_to_clipboard) {
+        Ok(None) => println!("Output mode: {:?}", requested),
+        Ok(Some(mode)) => println!("Output mode: {:?} (falls back from {:?})", mode, requested),
+        Err(e) => println!("Output mode: {:?} cannot be used: {}", requested, e),
+    }
+    if config.clipboard.auto_paste {
+        let state = if simulate_available { "on" } else { "off (needs synthetic input)" };
+        println!("clipboard.auto_paste: {}", state);
+    }
+}
+
+/// `--json` of the subcommands that print a transcription
+fn json_arg() -> Arg {
+    Arg::new("json")
+        .long("json")
+        .action(ArgAction::SetTrue)
+        .help("Print the result as JSON, with the raw text and the endpoint and model used")
+}
+
+fn cli() -> Command {
+    Command::new("rusty-scribe")
+        .about("Push-to-talk dictation through Whisper")
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("PATH")
+                .global(true)
+                .value_parser(value_parser!(PathBuf))
+                .help("Config file (default: $RUSTY_SCRIBE_CONFIG, the platform config dir, then ./config.toml)"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_name("NAME")
+                .global(true)
+                .help("Apply [profiles.NAME] over the [llm] and [audio] settings (default: default_profile)"),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Record and deliver without calling Whisper or the LLM (app.dry_run)"),
+        )
+        .subcommand(
+            Command::new("calibrate")
+                .about("Check the recording device levels")
+                .arg(
+                    Arg::new("echo-test")
+                        .long("echo-test")
+                        .action(ArgAction::SetTrue)
+                        .help("Play a test tone and warn if the microphone picks up the speakers"),
+                ),
+        )
+        .subcommand(
+            Command::new("init")
+                .about("Write a commented default config file")
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .help("Overwrite an existing config file"),
+                ),
+        )
+        .subcommand(Command::new("devices").about("List input devices with their default formats"))
+        .subcommand(Command::new("doctor").about("Check whether output can simulate key presses in this session"))
+        .subcommand(Command::new("captions").about("Show live captions of the recording device until stopped"))
+        .subcommand(
+            Command::new("once")
+                .about("Record one dictation and print the transcription to stdout")
+                .arg(
+                    Arg::new("duration")
+                        .long("duration")
+                        .value_name("SECS")
+                        .value_parser(value_parser!(f64))
+                        .help("Stop after this many seconds (default: when audio.silence_timeout_ms of silence follows speech)"),
+                )
+                .arg(
+                    Arg::new("srt")
+                        .long("srt")
+                        .value_name("FILE")
+                        .value_parser(value_parser!(PathBuf))
+                        .help("Also write subtitles with word timings to FILE (skips post-processing)"),
+                )
+                .arg(json_arg()),
+        )
+        .subcommand(
+            Command::new("file")
+                .about("Transcribe an audio file (WAV, MP3, FLAC, Ogg or M4A) and print the text to stdout")
+                .arg(
+                    Arg::new("path")
+                        .value_name("FILE")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(json_arg()),
+        )
+        .subcommand(
+            Command::new("history").about("Print the most recent transcriptions").arg(
+                Arg::new("tail")
+                    .long("tail")
+                    .value_name("N")
+                    .default_value("10")
+                    .value_parser(value_parser!(usize))
+                    .help("Number of entries to print"),
+            ),
+        )
+        .subcommand(Command::new("stats").about("Print per-day totals from the analytics database"))
+        .subcommand(
+            Command::new("rebind")
+                .about("Press a new key chord for a hotkey and save it to the config file")
+                .arg(
+                    Arg::new("action")
+                        .value_name("ACTION")
+                        .required(true)
+                        .value_parser(REBINDABLE_ACTIONS)
+                        .help("Entry of [hotkeys] to rebind"),
+                ),
+        )
+        .subcommand(
+            Command::new("migrate-config").about("Upgrade the config file to the current format, keeping a backup"),
+        )
+}
 
-    if args.list_audio_devices {
-        print_devices(&devices);
+#[tokio::main]
+async fn main() -> Result<()> {
+    init_logging();
+    let matches = cli().get_matches();
+    let explicit_config = matches.get_one::<PathBuf>("config").cloned();
+    if let Some(("init", args)) = matches.subcommand() {
+        let path = init_path(explicit_config);
+        write_default_config(&path, args.get_flag("force"))?;
+        println!("Wrote {}", path.display());
+        return Ok(());
+    }
+    if let Some(("devices", _)) = matches.subcommand() {
+        return list_audio_devices();
+    }
+    if let Some(("migrate-config", _)) = matches.subcommand() {
+        let path = config_path(explicit_config);
+        match migrate_config_file(&path)? {
+            Some(backup) => println!("{} upgraded; the original was saved as {}", path.display(), backup.display()),
+            None => println!("{} is already up to date", path.display()),
+        }
         return Ok(());
     }
+    if let Some(("rebind", args)) = matches.subcommand() {
+        return rebind(&config_path(explicit_config), args.get_one::<String>("action").expect("is required"));
+    }
 
-    let deepgram_api_key = args.deepgram_api_key;
+    let mut config = load_config_from(explicit_config, matches.get_one::<String>("profile").map(String::as_str))?;
+    configure_log_format(&config.log);
+    if matches.get_flag("dry-run") {
+        config.app.dry_run = true;
+    }
+    if config.app.dry_run {
+        info!("Dry run: recordings are not sent to Whisper or the LLM");
+    }
+    if let Some(("calibrate", args)) = matches.subcommand() {
+        return calibrate(&config, args.get_flag("echo-test"));
+    }
+    if let Some(("doctor", _)) = matches.subcommand() {
+        print_diagnostics(&config);
+        return Ok(());
+    }
+    if let Some(("history", args)) = matches.subcommand() {
+        return print_history(&config, *args.get_one::<usize>("tail").expect("has a default"));
+    }
+    if let Some(("stats", _)) = matches.subcommand() {
+        let db_path = config
+            .analytics
+            .db_path
+            .as_deref()
+            .context("analytics.db_path is not set, so no stats are being recorded")?;
+        return print_stats(db_path);
+    }
+    configure_timeouts(&config.timeouts);
+    configure_http_logging(&config.debug);
+    configure_request_spacing(&config.network, &config.endpoints.local_whisper);
+    load_vocabulary(&config.transcription).context("transcription.vocabulary_file could not be loaded")?;
+    if let Some(("file", args)) = matches.subcommand() {
+        return run_file(&config, args.get_one::<PathBuf>("path").expect("is required"), args.get_flag("json"));
+    }
+    // Fail now rather than on the first hotkey press
+    get_device_from_name(&config.audio.recording_device, config.audio.fallback_to_default)
+        .context("audio.recording_device does not name an input device")?;
+    if let Some(("captions", _)) = matches.subcommand() {
+        return run_live_captions(&config);
+    }
+    if let Some(("once", args)) = matches.subcommand() {
+        let duration = args.get_one::<f64>("duration").map(|secs| Duration::from_secs_f64(*secs));
+        let srt = args.get_one::<PathBuf>("srt").map(PathBuf::as_path);
+        return run_once(&config, duration, srt, args.get_flag("json"));
+    }
+    // Paste and type need synthetic input, which e.g. Wayland sessions lack
+    let simulate_available = probe_simulate();
+    let notifier: &dyn Notifier = if config.ui.error_notifications { &DesktopNotifier } else { &LogNotifier };
+    config.output.mode = select_output_mode(&config.output, simulate_available, notifier)?;
+    config.clipboard.auto_paste = auto_paste_enabled(config.clipboard.auto_paste, simulate_available);
+    let state = Arc::new(Mutex::new(HotkeyState::for_hotkeys(&config.hotkeys)));
 
-    let devices = if args.audio_device.is_empty() {
-        vec![default_input_device()?, default_output_device().await?]
-    } else {
-        args.audio_device
-            .iter()
-            .map(|d| parse_audio_device(d))
-            .collect::<Result<Vec<_>>>()?
-    };
+    // Runs until the process exits
+    let _listener = start_hotkey_listener(&config.hotkeys, Arc::clone(&state))?;
+    match config.hotkeys.mode {
+        HotkeyMode::Hold => info!("Ready. Hold {} to record.", config.hotkeys.recording),
+        HotkeyMode::Toggle => info!("Ready. Press {} to start and stop recording.", config.hotkeys.recording),
+        HotkeyMode::Hybrid => info!(
+            "Ready. Tap {} to start and stop recording, or hold it to record while held.",
+            config.hotkeys.recording
+        ),
+    }
 
-    if devices.is_empty() {
-        return Err(anyhow!("No audio input devices found"));
-    }
-
-    // delete .mp4 files (output*.mp4)
-    std::fs::remove_file("output_0.mp4").unwrap_or_default();
-    std::fs::remove_file("output_1.mp4").unwrap_or_default();
-
-    let chunk_duration = Duration::from_secs(5);
-    let output_path = PathBuf::from("output.mp4");
-    let (whisper_sender, mut whisper_receiver, _) = create_whisper_channel(
-        Arc::new(AudioTranscriptionEngine::WhisperTiny),
-        VadEngineEnum::WebRtc, // Or VadEngineEnum::WebRtc, hardcoded for now
-        deepgram_api_key,
-        &output_path,
-    )
-    .await?;
-    // Spawn threads for each device
-    let recording_threads: Vec<_> = devices
-        .into_iter()
-        .enumerate()
-        .map(|(_, device)| {
-            let device = Arc::new(device);
-            let whisper_sender = whisper_sender.clone();
-            let device_control = Arc::new(AtomicBool::new(true));
-            let device_clone = Arc::clone(&device);
-
-            tokio::spawn(async move {
-                let device_control_clone = Arc::clone(&device_control);
-                let device_clone_2 = Arc::clone(&device_clone);
-
-                record_and_transcribe(
-                    device_clone_2,
-                    chunk_duration,
-                    whisper_sender,
-                    device_control_clone,
-                )
-            })
-        })
-        .collect();
-    let mut consecutive_timeouts = 0;
-    let max_consecutive_timeouts = 3; // Adjust this value as needed
-
-    // Main loop to receive and print transcriptions
-    let mut transcribed_text = String::new();
-    loop {
-        match whisper_receiver.try_recv() {
-            Ok(result) => {
-                info!("Transcription: {:?}", result);
-                if let Some(text) = result.transcription {
-                    transcribed_text.push_str(&text);
-                    transcribed_text.push(' ');
-                }
-                consecutive_timeouts = 0; // Reset the counter on successful receive
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let signal = Arc::clone(&shutdown);
+    ctrlc::set_handler(move || signal.store(true, Ordering::SeqCst)).context("Failed to install Ctrl+C handler")?;
+    if config.ui.tray {
+        start_tray(Arc::clone(&state), Arc::clone(&shutdown))?;
+    }
+    start_silent_device_check(&config, Arc::clone(&state));
+    let keepalive = Keepalive::from_settings(&config.endpoints.local_whisper, &config.network);
+    let session = start_session(&config, notifier).map(|session| Arc::new(Mutex::new(session)));
+
+    let mut active: Option<ActiveRecording> = None;
+    // Set when silence ended a recording while the hotkey was still held
+    let mut awaiting_release = false;
+    // Last seen value of the toggle_post_processing switch, to notify changes
+    let mut sticky_post_processing = false;
+    let mut idle = IdleTimer::new(config.general.idle_exit_secs);
+    let mut devices = DeviceSelector::new(config.audio.follow_default_device);
+    while !shutdown.load(Ordering::SeqCst) {
+        let (is_recording, is_post_processing, is_cancelled, post_processing_enabled, pending) = {
+            let mut state = state.lock().unwrap();
+            state.refresh_recording(Instant::now());
+            let cancelled = state.take_cancel();
+            (
+                state.is_recording,
+                state.is_post_processing,
+                cancelled,
+                state.post_processing_enabled,
+                state.pending_transcriptions,
+            )
+        };
+
+        if post_processing_enabled != sticky_post_processing {
+            sticky_post_processing = post_processing_enabled;
+            notify_desktop(&config.ui, &LifecycleEvent::PostProcessingToggled(post_processing_enabled));
+        }
+
+        if is_cancelled {
+            if let Some(mut recording) = active.take() {
+                recording.cancelled = true;
+                // A held combo must be released before the next recording
+                awaiting_release = is_recording;
+                finish_recording(recording, &config, &state, &session, is_post_processing);
             }
-            Err(_) => {
-                consecutive_timeouts += 1;
-                if consecutive_timeouts >= max_consecutive_timeouts {
-                    info!("No transcriptions received for a while, stopping...");
-                    break;
-                }
-                continue;
+        }
+
+        match (is_recording, active.take()) {
+            (true, None) if !awaiting_release => active = Some(start_recording(&config, &mut devices)),
+            (true, Some(mut recording)) if !recording.stop.load(Ordering::SeqCst) => {
+                // The modifier counts if it was held at any point during the recording
+                recording.post_processing |= is_post_processing;
+                active = Some(recording);
+            }
+            (true, Some(recording)) => {
+                // Silence or audio.max_recording_secs ended the recording: switch a
+                // toggle off, or wait for a held combo to be released
+                awaiting_release = {
+                    let mut state = state.lock().unwrap();
+                    state.stop_recording();
+                    state.is_recording
+                };
+                finish_recording(recording, &config, &state, &session, is_post_processing);
             }
+            (false, Some(recording)) => finish_recording(recording, &config, &state, &session, is_post_processing),
+            (false, None) => awaiting_release = false,
+            (true, None) => {}
         }
-    }
-    if args.copy_to_clipboard  {
-        info!("Copying to clipboard: {:?}", transcribed_text);
-        if let Err(e) = copy_to_clipboard(&transcribed_text) {
-            error!("Failed to copy to clipboard: {:?}", e);
+
+        if let Some(session) = &session {
+            session.lock().unwrap().tick();
         }
-    }
 
-    // Wait for all recording threads to finish
-    for (i, thread) in recording_threads.into_iter().enumerate() {
-        let file_path = thread.await.unwrap().await;
-        println!("Recording {} complete: {:?}", i, file_path);
+        // A transcription in flight keeps the local model warm without pings
+        if pending > 0 {
+            if let Some(keepalive) = &keepalive {
+                keepalive.mark_used();
+            }
+        }
+
+        // Recordings and transcriptions still in flight count as activity
+        if active.is_some() || pending > 0 {
+            idle.touch();
+        } else if idle.is_expired() {
+            info!("Idle for {} s (general.idle_exit_secs)", config.general.idle_exit_secs);
+            break;
+        }
+
+        tokio::time::sleep(STATE_POLL_INTERVAL).await;
     }
 
-    Ok(())
-}
\ No newline at end of file
+    info!("Shutting down");
+    if let Some(recording) = active {
+        // Joining drops the stream; the unfinished recording is discarded
+        recording.stop.store(true, Ordering::SeqCst);
+        let _ = recording.capture.join();
+    }
+    if let Some(Err(e)) = session.map(|session| session.lock().unwrap().end()) {
+        warn!("Failed to end the session: {:?}", e);
+    }
+    let removed = TEMP_RECORDINGS.remove_all();
+    if removed > 0 {
+        info!("Removed {} temporary recording(s) that were still waiting for upload", removed);
+    }
+    // Exiting directly abandons transcriptions still running on blocking tasks,
+    // whose recordings were removed above, instead of waiting for them
+    std::process::exit(0)
+}
@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Schema version written by this release. Files without `config_version`
+/// predate versioning and count as version 1.
+pub const CURRENT_CONFIG_VERSION: u32 = 2;
+
+/// API key placeholder from the example config; keeping it would shadow
+/// `OPENAI_API_KEY` and the keyring
+const PLACEHOLDER_API_KEY: &str = "your_openai_api_key_here";
+
+/// Edits the lines of a config file to reach the next version
+type Migration = fn(&mut [String]);
+
+/// Upgrade steps, each taking a config from the version before it
+const MIGRATIONS: [(u32, Migration); 1] = [(2, clear_placeholder_api_key)];
+
+/// `config_version` of a config file
+pub fn config_version(content: &str) -> Result<u32> {
+    let value: toml::Value = toml::from_str(content).context("Error parsing config file")?;
+    match value.get("config_version") {
+        None => Ok(1),
+        Some(version) => version
+            .as_integer()
+            .and_then(|v| u32::try_from(v).ok())
+            .context("config_version must be a positive integer"),
+    }
+}
+
+fn is_section_header(line: &str) -> bool {
+    line.trim_start().starts_with('[')
+}
+
+fn is_key_line(line: &str, key: &str) -> bool {
+    line.trim_start()
+        .strip_prefix(key)
+        .is_some_and(|rest| rest.trim_start().starts_with('='))
+}
+
+fn clear_placeholder_api_key(lines: &mut [String]) {
+    let Some(header) = lines.iter().position(|l| l.trim() == "[api_keys]") else {
+        return;
+    };
+    for line in lines[header + 1..].iter_mut().take_while(|l| !is_section_header(l)) {
+        if is_key_line(line, "openai") && line.contains(PLACEHOLDER_API_KEY) {
+            *line = "openai = \"\"".to_string();
+        }
+    }
+}
+
+/// Sets the top-level `config_version`, which has to come before any table
+fn set_version(lines: &mut Vec<String>, version: u32) {
+    let entry = format!("config_version = {}", version);
+    let top_level = lines.iter().position(|l| is_section_header(l)).unwrap_or(lines.len());
+    match (0..top_level).find(|&i| is_key_line(&lines[i], "config_version")) {
+        Some(i) => lines[i] = entry,
+        None => lines.insert(0, entry),
+    }
+}
+
+/// Upgrades config file contents to `CURRENT_CONFIG_VERSION`, editing line by
+/// line so comments and formatting survive. Returns `None` when the config is
+/// already current.
+pub fn migrate_config_content(content: &str) -> Result<Option<String>> {
+    let version = config_version(content)?;
+    if version > CURRENT_CONFIG_VERSION {
+        return Err(anyhow::anyhow!(
+            "config_version {} is newer than this release supports ({})",
+            version,
+            CURRENT_CONFIG_VERSION
+        ));
+    }
+    if version == CURRENT_CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    for (target, migrate) in MIGRATIONS.iter().filter(|(target, _)| *target > version) {
+        migrate(&mut lines);
+        info!("Migrated config to version {}", target);
+    }
+    set_version(&mut lines, CURRENT_CONFIG_VERSION);
+
+    let mut updated = lines.join("\n");
+    updated.push('\n');
+    Ok(Some(updated))
+}
+
+/// `rusty-scribe migrate-config`: upgrades the file in place after copying the
+/// original next to it. Returns the backup path, or `None` if nothing changed.
+pub fn migrate_config_file(path: &Path) -> Result<Option<PathBuf>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Unable to read {}", path.display()))?;
+    let Some(updated) = migrate_config_content(&content)? else {
+        return Ok(None);
+    };
+
+    let backup = PathBuf::from(format!("{}.v{}.bak", path.display(), config_version(&content)?));
+    fs::copy(path, &backup).with_context(|| format!("Unable to back up {} to {}", path.display(), backup.display()))?;
+    fs::write(path, updated).with_context(|| format!("Unable to write {}", path.display()))?;
+    Ok(Some(backup))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use tempfile::TempDir;
+
+    const OLD_CONFIG: &str = "\
+# rusty-scribe settings
+
+[endpoints]
+local_whisper = \"http://localhost:5000/transcribe\"
+hosted_whisper = \"https://api.openai.com/v1/audio/transcriptions\"
+llm_endpoint = \"https://api.openai.com/v1/engines/davinci/completions\"
+
+[hotkeys]
+recording = \"Shift+Space\" # push to talk
+post_processing_modifier = \"Control\"
+
+[audio]
+recording_device = \"USB Microphone\"
+silence_timeout_ms = 800
+
+[llm]
+post_processing_prompt = \"Please clean up and format the following text:\"
+always_post_process = true
+
+[api_keys]
+openai = \"your_openai_api_key_here\"
+";
+
+    #[test]
+    fn test_old_config_migrates_to_equivalent_current_config() {
+        assert_eq!(config_version(OLD_CONFIG).unwrap(), 1);
+        let upgraded = migrate_config_content(OLD_CONFIG).unwrap().expect("old config needs migrating");
+
+        assert!(upgraded.starts_with("config_version = 2\n# rusty-scribe settings"));
+        assert!(upgraded.contains("recording = \"Shift+Space\" # push to talk"));
+        assert_eq!(config_version(&upgraded).unwrap(), CURRENT_CONFIG_VERSION);
+
+        let old: Config = toml::from_str(OLD_CONFIG).unwrap();
+        let new: Config = toml::from_str(&upgraded).unwrap();
+        assert_eq!(new.api_keys.openai, "");
+        assert_eq!(
+            new,
+            Config {
+                config_version: Some(CURRENT_CONFIG_VERSION),
+                api_keys: Default::default(),
+                ..old
+            }
+        );
+
+        assert_eq!(migrate_config_content(&upgraded).unwrap(), None);
+    }
+
+    #[test]
+    fn test_migrate_config_file_keeps_backup() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, OLD_CONFIG).unwrap();
+
+        let backup = migrate_config_file(&path).unwrap().expect("backup written");
+        assert_eq!(backup, dir.path().join("config.toml.v1.bak"));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), OLD_CONFIG);
+        assert_eq!(config_version(&fs::read_to_string(&path).unwrap()).unwrap(), 2);
+
+        assert_eq!(migrate_config_file(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn test_newer_config_is_rejected() {
+        assert!(migrate_config_content("config_version = 99\n").is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use log::warn;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::api::is_rate_limited;
+use crate::config::{NetworkSettings, OutputMode, UiSettings};
+
+/// Destination for user-facing notifications
+pub trait Notifier {
+    fn notify(&self, title: &str, body: &str);
+}
+
+/// Fallback notifier that only writes to the log
+pub struct LogNotifier;
+
+impl Notifier for LogNotifier {
+    fn notify(&self, title: &str, body: &str) {
+        warn!("{}: {}", title, body);
+    }
+}
+
+/// OS notifications through notify-rust. Failures, e.g. without a
+/// notification daemon, are logged and otherwise ignored.
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, title: &str, body: &str) {
+        let shown = notify_rust::Notification::new()
+            .appname("rusty-scribe")
+            .summary(title)
+            .body(body)
+            .show();
+        if let Err(e) = shown {
+            warn!("Failed to show notification '{}': {}", title, e);
+        }
+    }
+}
+
+/// Characters of the transcription shown in the delivery notification
+const PREVIEW_CHARS: usize = 40;
+
+/// Stages of a dictation reported with `ui.notifications`
+#[derive(Debug)]
+pub enum LifecycleEvent<'a> {
+    RecordingStarted,
+    Transcribing,
+    Delivered { text: &'a str, mode: OutputMode },
+    Failed(&'a anyhow::Error),
+    /// `hotkeys.toggle_post_processing` flipped the sticky switch
+    PostProcessingToggled(bool),
+}
+
+/// First `max_chars` characters of `text` on one line, with an ellipsis if cut
+fn preview(text: &str, max_chars: usize) -> String {
+    let flat = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match flat.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", flat[..end].trim_end()),
+        None => flat,
+    }
+}
+
+impl LifecycleEvent<'_> {
+    /// Notification title and body for this event
+    pub fn notification(&self) -> (&'static str, String) {
+        match self {
+            LifecycleEvent::RecordingStarted => ("Recording started", String::new()),
+            LifecycleEvent::Transcribing => ("Transcribing…", String::new()),
+            LifecycleEvent::Delivered { text, mode } => {
+                let title = match mode {
+                    OutputMode::Clipboard => "Copied to clipboard",
+                    OutputMode::Paste => "Pasted",
+                    OutputMode::Type => "Typed",
+                };
+                (title, preview(text, PREVIEW_CHARS))
+            }
+            LifecycleEvent::Failed(err) => ("Dictation failed", preview(&err.to_string(), 2 * PREVIEW_CHARS)),
+            LifecycleEvent::PostProcessingToggled(true) => ("Post-processing on", String::new()),
+            LifecycleEvent::PostProcessingToggled(false) => ("Post-processing off", String::new()),
+        }
+    }
+}
+
+/// Sends the notification for `event` when enabled; a no-op otherwise
+pub fn notify_lifecycle(event: &LifecycleEvent, enabled: bool, notifier: &dyn Notifier) {
+    if enabled {
+        let (title, body) = event.notification();
+        notifier.notify(title, &body);
+    }
+}
+
+/// `notify_lifecycle` through the desktop, gated by `ui.notifications`
+pub fn notify_desktop(ui: &UiSettings, event: &LifecycleEvent) {
+    notify_lifecycle(event, ui.notifications, &DesktopNotifier);
+}
+
+pub const RATE_LIMIT_TITLE: &str = "Rate limited";
+pub const RATE_LIMIT_BODY: &str =
+    "The hosted endpoint keeps rejecting requests (429). Back off for a while or switch to the local endpoint.";
+
+/// Turns repeated 429 failures into at most one notification per cooldown window
+pub struct RateLimitNotifier {
+    cooldown: Duration,
+    last_notified: Option<Instant>,
+}
+
+impl RateLimitNotifier {
+    pub fn new(cooldown: Duration) -> Self {
+        RateLimitNotifier {
+            cooldown,
+            last_notified: None,
+        }
+    }
+
+    /// Inspects a failed request (after retries) and notifies if it was rate-limited.
+    /// Returns whether a notification was sent.
+    pub fn observe(&mut self, err: &anyhow::Error, notifier: &dyn Notifier) -> bool {
+        self.observe_at(err, Instant::now(), notifier)
+    }
+
+    fn observe_at(&mut self, err: &anyhow::Error, now: Instant, notifier: &dyn Notifier) -> bool {
+        if !is_rate_limited(err) {
+            return false;
+        }
+        if let Some(last) = self.last_notified {
+            if now.duration_since(last) < self.cooldown {
+                return false;
+            }
+        }
+        self.last_notified = Some(now);
+        notifier.notify(RATE_LIMIT_TITLE, RATE_LIMIT_BODY);
+        true
+    }
+}
+
+/// Rate-limit state of this process, shared by every recording
+static RATE_LIMITS: Mutex<Option<RateLimitNotifier>> = Mutex::new(None);
+
+/// Passes a failed transcription or LLM request to the process-wide
+/// `RateLimitNotifier`, with `network.rate_limit_notify_interval_mins` as
+/// its cooldown. The notification goes to the desktop when
+/// `ui.notifications` is on and to the log otherwise.
+pub fn observe_rate_limit(network: &NetworkSettings, ui: &UiSettings, err: &anyhow::Error) {
+    let notifier: &dyn Notifier = if ui.notifications { &DesktopNotifier } else { &LogNotifier };
+    let cooldown = Duration::from_secs(network.rate_limit_notify_interval_mins * 60);
+    RATE_LIMITS
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| RateLimitNotifier::new(cooldown))
+        .observe(err, notifier);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ApiError;
+    use reqwest::StatusCode;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct RecordingNotifier {
+        sent: RefCell<Vec<String>>,
+    }
+
+    impl Notifier for RecordingNotifier {
+        fn notify(&self, title: &str, _body: &str) {
+            self.sent.borrow_mut().push(title.to_string());
+        }
+    }
+
+    fn api_error(status: StatusCode) -> anyhow::Error {
+        ApiError {
+            service: "Whisper",
+            status,
+            body: String::new(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_repeated_rate_limits_notify_once_per_window() {
+        let notifier = RecordingNotifier::default();
+        let mut limiter = RateLimitNotifier::new(Duration::from_secs(600));
+        let start = Instant::now();
+        let err = api_error(StatusCode::TOO_MANY_REQUESTS);
+
+        assert!(limiter.observe_at(&err, start, &notifier));
+        assert!(!limiter.observe_at(&err, start + Duration::from_secs(30), &notifier));
+        assert!(!limiter.observe_at(&err, start + Duration::from_secs(599), &notifier));
+        assert_eq!(notifier.sent.borrow().len(), 1);
+
+        assert!(limiter.observe_at(&err, start + Duration::from_secs(601), &notifier));
+        assert_eq!(notifier.sent.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_delivery_notification_previews_text() {
+        let text = "This dictation is quite a bit longer than forty characters.";
+        let event = LifecycleEvent::Delivered {
+            text,
+            mode: OutputMode::Clipboard,
+        };
+        assert_eq!(
+            event.notification(),
+            ("Copied to clipboard", "This dictation is quite a bit longer tha…".to_string())
+        );
+
+        let event = LifecycleEvent::Delivered {
+            text: "Short\nnote.",
+            mode: OutputMode::Type,
+        };
+        assert_eq!(event.notification(), ("Typed", "Short note.".to_string()));
+    }
+
+    #[test]
+    fn test_lifecycle_notifications_respect_setting() {
+        let notifier = RecordingNotifier::default();
+        let err = anyhow::anyhow!("Whisper API error 500");
+
+        notify_lifecycle(&LifecycleEvent::RecordingStarted, false, &notifier);
+        notify_lifecycle(&LifecycleEvent::Failed(&err), false, &notifier);
+        assert!(notifier.sent.borrow().is_empty());
+
+        notify_lifecycle(&LifecycleEvent::RecordingStarted, true, &notifier);
+        notify_lifecycle(&LifecycleEvent::Transcribing, true, &notifier);
+        notify_lifecycle(&LifecycleEvent::Failed(&err), true, &notifier);
+        assert_eq!(
+            *notifier.sent.borrow(),
+            vec!["Recording started", "Transcribing…", "Dictation failed"]
+        );
+    }
+
+    #[test]
+    fn test_other_errors_do_not_notify() {
+        let notifier = RecordingNotifier::default();
+        let mut limiter = RateLimitNotifier::new(Duration::from_secs(600));
+
+        assert!(!limiter.observe(&api_error(StatusCode::INTERNAL_SERVER_ERROR), &notifier));
+        assert!(!limiter.observe(&anyhow::anyhow!("timed out"), &notifier));
+        assert!(notifier.sent.borrow().is_empty());
+    }
+}
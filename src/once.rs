@@ -0,0 +1,217 @@
+use anyhow::{Context, Result};
+use log::info;
+use hound::{SampleFormat, WavSpec};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use crate::api::{is_local_endpoint_available, transcribe_audio_bytes_timed, EndpointKind, LlmOutput, Transcription};
+use crate::audio::{
+    encode_wav_to_memory, prepare_for_endpoint, record_audio_until, CapturedAudio, MIN_CAPTURED_SAMPLES,
+};
+use crate::config::{AudioSettings, Config};
+use crate::decode::{decode_audio_file, DecodedAudio};
+use crate::pipeline::{
+    prepare_audio, process_recording, DryRunIo, FinishedRecording, PostProcessRequest, RecordingIo, SystemIo,
+};
+use crate::subtitles::{cues, to_srt};
+use crate::window::TargetWindow;
+
+/// Wraps another `RecordingIo`, printing the final text to stdout instead of
+/// delivering it. With `json` nothing is printed on delivery; the caller
+/// prints the whole result once the pipeline is done.
+struct StdoutIo<I> {
+    inner: I,
+    json: bool,
+}
+
+fn print_line(line: &str) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{}", line).context("Failed to write to stdout")?;
+    stdout.flush().context("Failed to write to stdout")
+}
+
+impl<I: RecordingIo> RecordingIo for StdoutIo<I> {
+    fn transcribe(&mut self, audio: &CapturedAudio, forced: Option<EndpointKind>) -> Result<Transcription> {
+        self.inner.transcribe(audio, forced)
+    }
+
+    fn post_process(&mut self, request: &PostProcessRequest) -> Result<LlmOutput> {
+        self.inner.post_process(request)
+    }
+
+    fn select_endpoint(&mut self, forced: Option<EndpointKind>) -> EndpointKind {
+        self.inner.select_endpoint(forced)
+    }
+
+    fn deliver(&mut self, text: &str) -> Result<()> {
+        if self.json {
+            return Ok(());
+        }
+        print_line(text)
+    }
+}
+
+/// Without `--duration`, only silence detection can end the recording
+fn check_stop_condition(duration: Option<Duration>, audio: &AudioSettings) -> Result<()> {
+    if duration.is_none() && audio.silence_timeout_ms == 0 {
+        return Err(anyhow::anyhow!(
+            "Nothing would end the recording: pass --duration or set audio.silence_timeout_ms"
+        ));
+    }
+    Ok(())
+}
+
+/// Transcribes with timings, writes them to `path` as SRT and prints the
+/// text. Post-processing is skipped since it would no longer match the timings.
+fn write_subtitles(config: &Config, recording: &FinishedRecording, path: &Path) -> Result<()> {
+    let (samples, spec) = prepare_audio(config, recording);
+    let clip_secs = samples.len() as f64 / spec.channels.max(1) as f64 / spec.sample_rate as f64;
+    let (endpoint, url) = if is_local_endpoint_available(&config.endpoints.local_whisper) {
+        (EndpointKind::Local, &config.endpoints.local_whisper)
+    } else {
+        (EndpointKind::Hosted, &config.endpoints.hosted_whisper)
+    };
+    let (samples, spec) =
+        prepare_for_endpoint(&samples, spec, endpoint, config.transcription.resample_for_hosted_only);
+    let wav = encode_wav_to_memory(&samples, spec).context("Failed to encode the recording")?;
+    let timed = transcribe_audio_bytes_timed(url, &config.api_keys.openai, wav, &config.audio.timestamp_granularities)?;
+
+    fs::write(path, to_srt(&cues(&timed, clip_secs)))
+        .with_context(|| format!("Failed to write subtitles to {}", path.display()))?;
+    info!("Subtitles written to {}", path.display());
+    println!("{}", timed.text.trim());
+    Ok(())
+}
+
+/// `rusty-scribe once`: records one dictation until `duration` has passed or
+/// trailing silence ends it, then prints the transcription, post-processed
+/// as configured, to stdout, or with `json` the whole `TranscriptionResult`
+/// as JSON. With `srt`, subtitles of the raw transcription are written there
+/// as well. Logs stay on stderr.
+pub fn run_once(config: &Config, duration: Option<Duration>, srt: Option<&Path>, json: bool) -> Result<()> {
+    check_stop_condition(duration, &config.audio)?;
+    if srt.is_some() && config.app.dry_run {
+        return Err(anyhow::anyhow!("--srt needs real word timings and cannot be combined with --dry-run"));
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    if let Some(duration) = duration {
+        let timer = Arc::clone(&stop);
+        std::thread::spawn(move || {
+            std::thread::sleep(duration);
+            timer.store(true, Ordering::SeqCst);
+        });
+    }
+    let (tx, rx) = mpsc::channel();
+    let audio = &config.audio;
+    let summary = record_audio_until(&audio.recording_device, audio, &config.device_profiles, Duration::ZERO, stop, tx)?;
+    let samples: Vec<i16> = rx.into_iter().collect();
+    if samples.len() < MIN_CAPTURED_SAMPLES {
+        return Err(anyhow::anyhow!("No audio was captured from the recording device"));
+    }
+
+    let recording = FinishedRecording {
+        samples,
+        spec: summary.spec,
+        modifier_held: false,
+        post_processing_enabled: false,
+        forced: None,
+        cancelled: false,
+        target_window: TargetWindow::Unset,
+        selection: None,
+    };
+    if let Some(path) = srt {
+        return write_subtitles(config, &recording, path);
+    }
+    print_transcription(config, &recording, json)
+}
+
+/// Runs `recording` through the pipeline, printing the result to stdout
+fn print_transcription(config: &Config, recording: &FinishedRecording, json: bool) -> Result<()> {
+    let result = if config.app.dry_run {
+        let prompt = &config.llm.post_processing_prompt;
+        // StdoutIo delivers, so the inner sink is never called
+        let mut io = StdoutIo {
+            inner: DryRunIo::new(prompt, |_: &str| Ok(())),
+            json,
+        };
+        process_recording(config, recording, &mut io)?
+    } else {
+        // Nothing is typed into other windows, so no placeholder either
+        let mut config = config.clone();
+        config.output.placeholder_while_processing = false;
+        let mut io = StdoutIo {
+            inner: SystemIo::new(&config, TargetWindow::Unset),
+            json,
+        };
+        process_recording(&config, recording, &mut io)?
+    };
+    match result {
+        Some(result) if json => print_line(&result.to_json()?),
+        _ => Ok(()),
+    }
+}
+
+/// A decoded file as if it had just been recorded
+fn file_recording(decoded: DecodedAudio) -> FinishedRecording {
+    FinishedRecording {
+        samples: decoded.samples,
+        spec: WavSpec {
+            channels: decoded.channels,
+            sample_rate: decoded.sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        },
+        modifier_held: false,
+        post_processing_enabled: false,
+        forced: None,
+        cancelled: false,
+        target_window: TargetWindow::Unset,
+        selection: None,
+    }
+}
+
+/// `rusty-scribe file <PATH>`: decodes a WAV, MP3, FLAC, Ogg or M4A file and
+/// prints its transcription, post-processed as configured, like `once`
+pub fn run_file(config: &Config, path: &Path, json: bool) -> Result<()> {
+    let recording = file_recording(decode_audio_file(path)?);
+    if recording.samples.len() < MIN_CAPTURED_SAMPLES {
+        return Err(anyhow::anyhow!("{} contains no audio", path.display()));
+    }
+    print_transcription(config, &recording, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_once_needs_a_way_to_stop() {
+        let audio: AudioSettings = toml::from_str("recording_device = \"default\"").unwrap();
+        assert!(check_stop_condition(None, &audio).is_err());
+        assert!(check_stop_condition(Some(Duration::from_secs(5)), &audio).is_ok());
+
+        let audio = AudioSettings {
+            silence_timeout_ms: 800,
+            ..audio
+        };
+        assert!(check_stop_condition(None, &audio).is_ok());
+    }
+
+    #[test]
+    fn test_file_recording_keeps_the_decoded_format() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ramp.flac");
+        let decoded = decode_audio_file(&fixture).unwrap();
+        let (sample_rate, channels, len) = (decoded.sample_rate, decoded.channels, decoded.samples.len());
+
+        let recording = file_recording(decoded);
+        assert_eq!(recording.spec.sample_rate, sample_rate);
+        assert_eq!(recording.spec.channels, channels);
+        assert_eq!(recording.samples.len(), len);
+        assert!(!recording.cancelled);
+    }
+}
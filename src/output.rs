@@ -0,0 +1,839 @@
+use anyhow::{Result, Context};
+use log::{error, info, warn};
+use rdev::{simulate, EventType, Key};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::clipboard::{auto_paste, copy_to_clipboard};
+use crate::config::{LineEnding, OutputMode, OutputSettings, SegmentOutput};
+use crate::notify::Notifier;
+use crate::text::strip_tags;
+use crate::window::WindowHelper;
+
+const BRACKETED_PASTE_START: &str = "\x1b[200~";
+const BRACKETED_PASTE_END: &str = "\x1b[201~";
+
+/// Placeholder in `output.insert_command` arguments replaced by the text
+const TEXT_PLACEHOLDER: &str = "{{text}}";
+
+/// Checks whether `rdev::simulate` can deliver synthetic input in this session.
+/// Releasing a key that is not held is a no-op for the focused application.
+pub fn probe_simulate() -> bool {
+    if cfg!(target_os = "linux") && is_wayland_session() {
+        // XTest events only reach XWayland clients, so treat it as unsupported
+        return false;
+    }
+    simulate(&EventType::KeyRelease(Key::ShiftLeft)).is_ok()
+}
+
+fn is_wayland_session() -> bool {
+    std::env::var("XDG_SESSION_TYPE")
+        .map(|session| session.eq_ignore_ascii_case("wayland"))
+        .unwrap_or(false)
+}
+
+/// Decides the effective output mode given whether synthetic input works.
+/// Returns `Ok(None)` when the requested mode can be used as-is.
+pub fn fallback_output_mode(
+    requested: OutputMode,
+    simulate_available: bool,
+    fallback_to_clipboard: bool,
+) -> Result<Option<OutputMode>> {
+    if requested == OutputMode::Clipboard || simulate_available {
+        return Ok(None);
+    }
+    if fallback_to_clipboard {
+        Ok(Some(OutputMode::Clipboard))
+    } else {
+        Err(anyhow::anyhow!(
+            "Output mode {:?} requires synthetic keyboard input, which is not supported in this session",
+            requested
+        ))
+    }
+}
+
+/// Resolves the output mode once at startup. A degraded mode is reported
+/// through `notifier` as well as the log, since on Wayland the log is
+/// usually out of sight.
+pub fn select_output_mode(
+    settings: &OutputSettings,
+    simulate_available: bool,
+    notifier: &dyn Notifier,
+) -> Result<OutputMode> {
+    match fallback_output_mode(settings.mode, simulate_available, settings.fallback_to_clipboard)? {
+        Some(mode) => {
+            warn!(
+                "Synthetic keyboard input is unavailable (e.g. Wayland); output mode {:?} falls back to {:?}",
+                settings.mode, mode
+            );
+            notifier.notify(
+                "Output falls back to the clipboard",
+                "Synthetic keyboard input is unavailable in this session, so transcriptions are copied to the \
+                 clipboard for you to paste.",
+            );
+            Ok(mode)
+        }
+        None => Ok(settings.mode),
+    }
+}
+
+/// Whether `clipboard.auto_paste` can stay on. Its paste keystroke is
+/// simulated, so without synthetic input it would fail on every delivery.
+pub fn auto_paste_enabled(requested: bool, simulate_available: bool) -> bool {
+    if requested && !simulate_available {
+        warn!("Synthetic keyboard input is unavailable; clipboard.auto_paste is turned off");
+        return false;
+    }
+    requested
+}
+
+/// Whether a window class belongs to a terminal emulator per `output.terminal_window_classes`
+pub fn is_terminal_class(window_class: &str, terminal_classes: &[String]) -> bool {
+    let class = window_class.to_lowercase();
+    terminal_classes
+        .iter()
+        .any(|pattern| !pattern.is_empty() && class.contains(&pattern.to_lowercase()))
+}
+
+/// Wraps text in bracketed-paste markers so a terminal inserts it literally
+/// instead of executing each line. Any end marker inside the text is removed
+/// so dictated content cannot break out of the paste.
+pub fn wrap_bracketed_paste(text: &str) -> String {
+    format!(
+        "{}{}{}",
+        BRACKETED_PASTE_START,
+        text.replace(BRACKETED_PASTE_END, ""),
+        BRACKETED_PASTE_END
+    )
+}
+
+/// Applies terminal-specific wrapping when enabled and the focused window is a terminal
+pub fn prepare_for_focused_window(text: &str, settings: &OutputSettings, windows: &dyn WindowHelper) -> String {
+    if !settings.bracketed_paste {
+        return text.to_string();
+    }
+    let is_terminal = windows
+        .focused_window()
+        .and_then(|id| windows.window_class(id))
+        .map(|class| is_terminal_class(&class, &settings.terminal_window_classes))
+        .unwrap_or(false);
+    if is_terminal {
+        wrap_bracketed_paste(text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Rewrites every line break (LF, CRLF or lone CR) to the configured ending
+pub fn convert_line_endings(text: &str, line_ending: LineEnding) -> String {
+    let normalized = text.replace("\r\n", "\n").replace('\r', "\n");
+    let crlf = match line_ending {
+        LineEnding::Lf => false,
+        LineEnding::Crlf => true,
+        LineEnding::Platform => cfg!(windows),
+    };
+    if crlf {
+        normalized.replace('\n', "\r\n")
+    } else {
+        normalized
+    }
+}
+
+/// Output stage applied to the final text before it reaches the clipboard or
+/// is typed: tag stripping, line ending conversion, then terminal wrapping
+pub fn prepare_output(text: &str, settings: &OutputSettings, windows: &dyn WindowHelper) -> String {
+    let text = strip_tags(text, &settings.strip_tags);
+    let text = convert_line_endings(&text, settings.line_ending);
+    prepare_for_focused_window(&text, settings, windows)
+}
+
+/// Runs `output.insert_command` with the text, killing it after `timeout`
+pub fn run_insert_command(command: &[String], text: &str, timeout: Duration) -> Result<()> {
+    let (program, args) = command
+        .split_first()
+        .context("output.insert_command is empty")?;
+    let use_placeholder = args.iter().any(|arg| arg.contains(TEXT_PLACEHOLDER));
+    let args: Vec<String> = args.iter().map(|arg| arg.replace(TEXT_PLACEHOLDER, text)).collect();
+
+    let mut child = Command::new(program)
+        .args(&args)
+        .stdin(if use_placeholder { Stdio::null() } else { Stdio::piped() })
+        .spawn()
+        .with_context(|| format!("Failed to start insert command '{}'", program))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .context("Failed to write text to insert command")?;
+    }
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait().context("Failed to wait for insert command")? {
+            return if status.success() {
+                info!("Text inserted via '{}'", program);
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!("Insert command '{}' exited with {}", program, status))
+            };
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow::anyhow!(
+                "Insert command '{}' timed out after {} ms",
+                program,
+                timeout.as_millis()
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Inserts via the configured command, falling back to `fallback` (the clipboard)
+/// when no command is set or it fails
+pub fn insert_text<F>(settings: &OutputSettings, text: &str, fallback: F) -> Result<()>
+where
+    F: FnOnce(&str) -> Result<()>,
+{
+    if let Some(command) = &settings.insert_command {
+        let timeout = Duration::from_millis(settings.insert_command_timeout_ms);
+        match run_insert_command(command, text, timeout) {
+            Ok(()) => return Ok(()),
+            Err(e) => error!("Insert command failed, falling back to clipboard: {:?}", e),
+        }
+    }
+    fallback(text)
+}
+
+/// A single synthetic input action for the focused window
+#[derive(Debug, Clone, PartialEq)]
+pub enum Keystroke {
+    Type(String),
+    Backspace,
+}
+
+/// Sends keystrokes to whichever window has focus
+pub trait Keyboard {
+    fn send(&mut self, keystroke: &Keystroke) -> Result<()>;
+}
+
+/// Keyboard backed by the same synthetic key events as `type_text`
+pub struct SimulatedKeyboard {
+    /// Pause after each typed character (`output.typing_delay_ms`)
+    pub delay: Duration,
+}
+
+impl Keyboard for SimulatedKeyboard {
+    fn send(&mut self, keystroke: &Keystroke) -> Result<()> {
+        match keystroke {
+            Keystroke::Type(text) => type_text(text, self.delay),
+            Keystroke::Backspace => type_char(Key::Backspace, false, &mut simulate_event),
+        }
+    }
+}
+
+/// Keystrokes that erase a typed placeholder and type `text` in its place.
+/// One backspace per character of the placeholder.
+pub fn replacement_keystrokes(placeholder: &str, text: &str) -> Vec<Keystroke> {
+    let mut keystrokes = vec![Keystroke::Backspace; placeholder.chars().count()];
+    if !text.is_empty() {
+        keystrokes.push(Keystroke::Type(text.to_string()));
+    }
+    keystrokes
+}
+
+/// Placeholder typed while a transcription is in flight
+/// (`output.placeholder_while_processing`)
+#[derive(Debug)]
+pub struct Placeholder {
+    typed: String,
+}
+
+impl Placeholder {
+    pub fn show(keyboard: &mut dyn Keyboard, placeholder: &str) -> Result<Self> {
+        keyboard.send(&Keystroke::Type(placeholder.to_string()))?;
+        Ok(Placeholder {
+            typed: placeholder.to_string(),
+        })
+    }
+
+    /// Replaces the placeholder with the final text; an empty text just removes it
+    pub fn replace(self, keyboard: &mut dyn Keyboard, text: &str) -> Result<()> {
+        for keystroke in replacement_keystrokes(&self.typed, text) {
+            keyboard.send(&keystroke)?;
+        }
+        Ok(())
+    }
+
+    /// Backspaces over the placeholder
+    pub fn erase(self, keyboard: &mut dyn Keyboard) -> Result<()> {
+        self.replace(keyboard, "")
+    }
+}
+
+/// Key and Shift state that produce `c` on a US layout, or `None` when no
+/// single key does (accented letters, emoji, other scripts)
+pub fn key_for_char(c: char) -> Option<(Key, bool)> {
+    const LETTERS: [Key; 26] = [
+        Key::KeyA, Key::KeyB, Key::KeyC, Key::KeyD, Key::KeyE, Key::KeyF, Key::KeyG, Key::KeyH, Key::KeyI,
+        Key::KeyJ, Key::KeyK, Key::KeyL, Key::KeyM, Key::KeyN, Key::KeyO, Key::KeyP, Key::KeyQ, Key::KeyR,
+        Key::KeyS, Key::KeyT, Key::KeyU, Key::KeyV, Key::KeyW, Key::KeyX, Key::KeyY, Key::KeyZ,
+    ];
+    const DIGITS: [Key; 10] = [
+        Key::Num0, Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5, Key::Num6, Key::Num7, Key::Num8,
+        Key::Num9,
+    ];
+    const SHIFTED_DIGITS: &str = ")!@#$%^&*(";
+
+    if c.is_ascii_lowercase() {
+        return Some((LETTERS[(c as u8 - b'a') as usize], false));
+    }
+    if c.is_ascii_uppercase() {
+        return Some((LETTERS[(c as u8 - b'A') as usize], true));
+    }
+    if c.is_ascii_digit() {
+        return Some((DIGITS[(c as u8 - b'0') as usize], false));
+    }
+    if let Some(digit) = SHIFTED_DIGITS.find(c) {
+        return Some((DIGITS[digit], true));
+    }
+    let key = match c {
+        ' ' => (Key::Space, false),
+        '\n' => (Key::Return, false),
+        '\t' => (Key::Tab, false),
+        '-' => (Key::Minus, false),
+        '_' => (Key::Minus, true),
+        '=' => (Key::Equal, false),
+        '+' => (Key::Equal, true),
+        '[' => (Key::LeftBracket, false),
+        '{' => (Key::LeftBracket, true),
+        ']' => (Key::RightBracket, false),
+        '}' => (Key::RightBracket, true),
+        ';' => (Key::SemiColon, false),
+        ':' => (Key::SemiColon, true),
+        '\'' => (Key::Quote, false),
+        '"' => (Key::Quote, true),
+        '`' => (Key::BackQuote, false),
+        '~' => (Key::BackQuote, true),
+        '\\' => (Key::BackSlash, false),
+        '|' => (Key::BackSlash, true),
+        ',' => (Key::Comma, false),
+        '<' => (Key::Comma, true),
+        '.' => (Key::Dot, false),
+        '>' => (Key::Dot, true),
+        '/' => (Key::Slash, false),
+        '?' => (Key::Slash, true),
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Part of a text in type mode: characters with a key of their own, or a run
+/// that has to go through the clipboard instead
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypingRun {
+    Keys(String),
+    Paste(String),
+}
+
+/// Splits `text` into alternating typed and pasted runs
+pub fn typing_runs(text: &str) -> Vec<TypingRun> {
+    let mut runs: Vec<TypingRun> = Vec::new();
+    for c in text.chars() {
+        let typeable = key_for_char(c).is_some();
+        match runs.last_mut() {
+            Some(TypingRun::Keys(run)) if typeable => run.push(c),
+            Some(TypingRun::Paste(run)) if !typeable => run.push(c),
+            _ if typeable => runs.push(TypingRun::Keys(c.to_string())),
+            _ => runs.push(TypingRun::Paste(c.to_string())),
+        }
+    }
+    runs
+}
+
+/// Presses and releases `key` through `send`, holding Shift if asked. Shift is
+/// released again even if the key fails.
+fn simulate_event(event: &EventType) -> Result<()> {
+    simulate(event).map_err(|e| anyhow::anyhow!("Failed to simulate {:?}: {:?}", event, e))
+}
+
+fn type_char<F>(key: Key, shift: bool, send: &mut F) -> Result<()>
+where
+    F: FnMut(&EventType) -> Result<()>,
+{
+    if shift {
+        send(&EventType::KeyPress(Key::ShiftLeft))?;
+    }
+    let typed = send(&EventType::KeyPress(key)).and_then(|()| send(&EventType::KeyRelease(key)));
+    if shift {
+        let released = send(&EventType::KeyRelease(Key::ShiftLeft));
+        return typed.and(released);
+    }
+    typed
+}
+
+/// `type_text` with injectable key events and paste, waiting `delay` after
+/// each typed character
+pub fn type_text_with<S, P>(text: &str, delay: Duration, mut send: S, mut paste: P) -> Result<()>
+where
+    S: FnMut(&EventType) -> Result<()>,
+    P: FnMut(&str) -> Result<()>,
+{
+    for run in typing_runs(text) {
+        match run {
+            TypingRun::Keys(run) => {
+                for c in run.chars() {
+                    let (key, shift) = key_for_char(c).expect("run holds typeable characters only");
+                    type_char(key, shift, &mut send)?;
+                    std::thread::sleep(delay);
+                }
+            }
+            TypingRun::Paste(run) => paste(&run).with_context(|| {
+                format!(
+                    "{:?} has no key on a US layout and cannot be typed with synthetic key events; pasting it through the clipboard failed",
+                    run
+                )
+            })?,
+        }
+    }
+    Ok(())
+}
+
+/// Types `text` into the focused window one key event at a time, for fields
+/// that block paste (`output.mode = "type"`). Characters without a key of
+/// their own on a US layout are pasted through the clipboard instead, so those
+/// runs still fail in fields that refuse paste.
+pub fn type_text(text: &str, delay: Duration) -> Result<()> {
+    type_text_with(
+        text,
+        delay,
+        simulate_event,
+        |run| {
+            copy_to_clipboard(run)?;
+            auto_paste()
+        },
+    )
+}
+
+/// Paste presses this soon after a segment was staged are taken for the
+/// delivery's own simulated paste and do not advance the queue
+const OWN_PASTE_GRACE: Duration = Duration::from_millis(150);
+
+/// Time the target app gets to read a pasted segment before the next one
+/// replaces it on the clipboard
+const SEGMENT_PASTE_SETTLE: Duration = Duration::from_millis(200);
+
+/// Segments of the last sequential dictation still to be pasted
+static PENDING_SEGMENTS: Mutex<SegmentQueue> = Mutex::new(SegmentQueue::new());
+
+/// Segments still waiting to be delivered, one per paste in sequential mode
+#[derive(Debug, Default)]
+pub struct SegmentQueue {
+    pending: VecDeque<String>,
+    /// When the segment now on the clipboard was put there
+    staged_at: Option<Instant>,
+}
+
+impl SegmentQueue {
+    pub const fn new() -> Self {
+        SegmentQueue {
+            pending: VecDeque::new(),
+            staged_at: None,
+        }
+    }
+
+    /// Turns the segments into the text to deliver now. Tabbed output joins them
+    /// with Tabs; sequential output returns the first and keeps the rest queued.
+    pub fn deliver(&mut self, segments: Vec<String>, mode: SegmentOutput) -> Option<String> {
+        match mode {
+            SegmentOutput::Tabbed => {
+                self.pending.clear();
+                Some(segments.join("\t"))
+            }
+            SegmentOutput::Sequential => {
+                self.pending = segments.into();
+                self.next_segment()
+            }
+        }
+    }
+
+    /// Next segment for the clipboard after the previous one was pasted
+    pub fn next_segment(&mut self) -> Option<String> {
+        self.pending.pop_front()
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether a paste pressed at `pressed_at` used up the staged segment
+    pub fn advances_on_paste(&self, pressed_at: Instant) -> bool {
+        self.staged_at.is_some_and(|staged| pressed_at >= staged + OWN_PASTE_GRACE)
+    }
+
+    fn stage_next(&mut self) {
+        self.staged_at = None;
+        if let Some(segment) = self.next_segment() {
+            match copy_to_clipboard(&segment) {
+                Ok(()) => {
+                    info!("Next segment is on the clipboard ({} more queued)", self.remaining());
+                    self.staged_at = Some(Instant::now());
+                }
+                Err(e) => warn!("Failed to put the next segment on the clipboard: {:?}", e),
+            }
+        }
+    }
+}
+
+/// Replaces the segments waiting from an earlier dictation with `segments`
+/// and puts the first of them on the clipboard for the next paste
+pub fn stage_segments(segments: SegmentQueue) {
+    let mut pending = PENDING_SEGMENTS.lock().unwrap();
+    *pending = segments;
+    pending.stage_next();
+}
+
+/// Called for every paste keystroke: once the target app has read the
+/// clipboard, the next waiting segment replaces it
+pub fn advance_segments(pressed_at: Instant) {
+    std::thread::sleep(SEGMENT_PASTE_SETTLE);
+    let mut pending = PENDING_SEGMENTS.lock().unwrap();
+    if pending.advances_on_paste(pressed_at) {
+        pending.stage_next();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::window::WindowId;
+
+    struct FocusedClass(Option<&'static str>);
+
+    #[derive(Default)]
+    struct CountingNotifier {
+        sent: std::cell::Cell<usize>,
+    }
+
+    impl Notifier for CountingNotifier {
+        fn notify(&self, _title: &str, _body: &str) {
+            self.sent.set(self.sent.get() + 1);
+        }
+    }
+
+    impl WindowHelper for FocusedClass {
+        fn focused_window(&self) -> Option<WindowId> {
+            Some(1)
+        }
+
+        fn window_exists(&self, _id: WindowId) -> bool {
+            true
+        }
+
+        fn activate(&self, _id: WindowId) -> Result<()> {
+            Ok(())
+        }
+
+        fn window_class(&self, _id: WindowId) -> Option<String> {
+            self.0.map(str::to_string)
+        }
+
+        fn is_fullscreen(&self, _id: WindowId) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_clipboard_mode_never_falls_back() {
+        assert_eq!(fallback_output_mode(OutputMode::Clipboard, false, false).unwrap(), None);
+    }
+
+    #[test]
+    fn test_simulate_available_keeps_requested_mode() {
+        assert_eq!(fallback_output_mode(OutputMode::Type, true, true).unwrap(), None);
+        assert_eq!(fallback_output_mode(OutputMode::Paste, true, false).unwrap(), None);
+    }
+
+    #[test]
+    fn test_simulate_unavailable_falls_back_to_clipboard() {
+        assert_eq!(
+            fallback_output_mode(OutputMode::Paste, false, true).unwrap(),
+            Some(OutputMode::Clipboard)
+        );
+
+        let settings = OutputSettings {
+            mode: OutputMode::Type,
+            ..OutputSettings::default()
+        };
+        let notifier = CountingNotifier::default();
+        assert_eq!(select_output_mode(&settings, false, &notifier).unwrap(), OutputMode::Clipboard);
+        assert_eq!(notifier.sent.get(), 1);
+        assert_eq!(select_output_mode(&settings, true, &notifier).unwrap(), OutputMode::Type);
+        assert_eq!(notifier.sent.get(), 1);
+
+        assert!(!auto_paste_enabled(true, false));
+        assert!(auto_paste_enabled(true, true));
+        assert!(!auto_paste_enabled(false, true));
+    }
+
+    #[test]
+    fn test_simulate_unavailable_without_fallback_errors() {
+        let result = fallback_output_mode(OutputMode::Type, false, false);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_bracketed_paste_applied_for_terminal_only() {
+        let settings = OutputSettings {
+            bracketed_paste: true,
+            ..OutputSettings::default()
+        };
+        let text = "git status\ngit diff";
+
+        assert_eq!(
+            prepare_for_focused_window(text, &settings, &FocusedClass(Some("gnome-terminal-server"))),
+            "\x1b[200~git status\ngit diff\x1b[201~"
+        );
+        assert_eq!(prepare_for_focused_window(text, &settings, &FocusedClass(Some("firefox"))), text);
+        assert_eq!(prepare_for_focused_window(text, &settings, &FocusedClass(None)), text);
+    }
+
+    #[test]
+    fn test_bracketed_paste_disabled_leaves_text() {
+        let settings = OutputSettings::default();
+        let text = "echo hi\n";
+        assert_eq!(prepare_for_focused_window(text, &settings, &FocusedClass(Some("kitty"))), text);
+    }
+
+    #[test]
+    fn test_wrap_bracketed_paste_strips_embedded_end_marker() {
+        assert_eq!(
+            wrap_bracketed_paste("a\x1b[201~rm -rf ~\n"),
+            "\x1b[200~arm -rf ~\n\x1b[201~"
+        );
+    }
+
+    #[test]
+    fn test_convert_line_endings_modes() {
+        let mixed = "first\nsecond\r\nthird\rfourth";
+
+        assert_eq!(convert_line_endings(mixed, LineEnding::Lf), "first\nsecond\nthird\nfourth");
+        assert_eq!(
+            convert_line_endings(mixed, LineEnding::Crlf),
+            "first\r\nsecond\r\nthird\r\nfourth"
+        );
+        let expected = if cfg!(windows) {
+            "first\r\nsecond\r\nthird\r\nfourth"
+        } else {
+            "first\nsecond\nthird\nfourth"
+        };
+        assert_eq!(convert_line_endings(mixed, LineEnding::Platform), expected);
+    }
+
+    #[test]
+    fn test_prepare_output_applies_line_ending_before_wrapping() {
+        let settings = OutputSettings {
+            bracketed_paste: true,
+            strip_tags: vec!["speak".to_string()],
+            line_ending: LineEnding::Crlf,
+            ..OutputSettings::default()
+        };
+        assert_eq!(
+            prepare_output("<speak>a\nb</speak>", &settings, &FocusedClass(Some("kitty"))),
+            "\x1b[200~a\r\nb\x1b[201~"
+        );
+    }
+
+    #[cfg(unix)]
+    fn sh(script: &str, extra: &[&str]) -> Vec<String> {
+        let mut command = vec!["sh".to_string(), "-c".to_string(), script.to_string()];
+        command.extend(extra.iter().map(|a| a.to_string()));
+        command
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_insert_command_receives_text() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let received = dir.path().join("received.txt");
+        let received_arg = received.to_str().unwrap();
+
+        // Text on stdin
+        let settings = OutputSettings {
+            insert_command: Some(sh("cat > \"$0\"", &[received_arg])),
+            ..OutputSettings::default()
+        };
+        insert_text(&settings, "hello\nworld", |_| panic!("should not fall back")).unwrap();
+        assert_eq!(std::fs::read_to_string(&received).unwrap(), "hello\nworld");
+
+        // Text substituted for the placeholder
+        let settings = OutputSettings {
+            insert_command: Some(sh("printf %s \"$1\" > \"$0\"", &[received_arg, "{{text}}"])),
+            ..OutputSettings::default()
+        };
+        insert_text(&settings, "it's done", |_| panic!("should not fall back")).unwrap();
+        assert_eq!(std::fs::read_to_string(&received).unwrap(), "it's done");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_insert_command_failure_falls_back_to_clipboard() {
+        let fallback_text = std::cell::RefCell::new(None);
+        let record = |text: &str| {
+            *fallback_text.borrow_mut() = Some(text.to_string());
+            Ok(())
+        };
+
+        let failing = OutputSettings {
+            insert_command: Some(sh("exit 3", &[])),
+            ..OutputSettings::default()
+        };
+        insert_text(&failing, "one", record).unwrap();
+        assert_eq!(fallback_text.borrow_mut().take(), Some("one".to_string()));
+
+        let hanging = OutputSettings {
+            insert_command: Some(sh("sleep 5", &[])),
+            insert_command_timeout_ms: 100,
+            ..OutputSettings::default()
+        };
+        let start = Instant::now();
+        insert_text(&hanging, "two", record).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(fallback_text.borrow_mut().take(), Some("two".to_string()));
+    }
+
+    #[derive(Default)]
+    struct RecordingKeyboard {
+        sent: Vec<Keystroke>,
+    }
+
+    impl Keyboard for RecordingKeyboard {
+        fn send(&mut self, keystroke: &Keystroke) -> Result<()> {
+            self.sent.push(keystroke.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_placeholder_is_backspaced_then_replaced() {
+        let mut keyboard = RecordingKeyboard::default();
+        let placeholder = Placeholder::show(&mut keyboard, "[…]").unwrap();
+        placeholder.replace(&mut keyboard, "Hello there.").unwrap();
+
+        assert_eq!(
+            keyboard.sent,
+            vec![
+                Keystroke::Type("[…]".to_string()),
+                Keystroke::Backspace,
+                Keystroke::Backspace,
+                Keystroke::Backspace,
+                Keystroke::Type("Hello there.".to_string()),
+            ]
+        );
+        assert_eq!(replacement_keystrokes("ab", ""), vec![Keystroke::Backspace; 2]);
+    }
+
+    #[test]
+    fn test_typing_runs_split_unmapped_characters() {
+        assert_eq!(
+            typing_runs("Café au lait: 3€!"),
+            vec![
+                TypingRun::Keys("Caf".to_string()),
+                TypingRun::Paste("é".to_string()),
+                TypingRun::Keys(" au lait: 3".to_string()),
+                TypingRun::Paste("€".to_string()),
+                TypingRun::Keys("!".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_text_sends_keys_and_pastes_the_rest() {
+        let mut events = Vec::new();
+        let mut pasted = Vec::new();
+        type_text_with(
+            "A-é",
+            Duration::ZERO,
+            |event| {
+                events.push(*event);
+                Ok(())
+            },
+            |run| {
+                pasted.push(run.to_string());
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                EventType::KeyPress(Key::ShiftLeft),
+                EventType::KeyPress(Key::KeyA),
+                EventType::KeyRelease(Key::KeyA),
+                EventType::KeyRelease(Key::ShiftLeft),
+                EventType::KeyPress(Key::Minus),
+                EventType::KeyRelease(Key::Minus),
+            ]
+        );
+        assert_eq!(pasted, vec!["é"]);
+    }
+
+    #[test]
+    fn test_type_text_releases_shift_when_a_key_fails() {
+        let mut events = Vec::new();
+        let result = type_text_with(
+            "?",
+            Duration::ZERO,
+            |event| {
+                events.push(*event);
+                match event {
+                    EventType::KeyPress(Key::Slash) => Err(anyhow::anyhow!("simulate failed")),
+                    _ => Ok(()),
+                }
+            },
+            |_| Ok(()),
+        );
+        assert!(result.is_err());
+        assert_eq!(events.last(), Some(&EventType::KeyRelease(Key::ShiftLeft)));
+    }
+
+    fn segments() -> Vec<String> {
+        vec!["Jane Doe".to_string(), "jane@example.com".to_string(), "555 1234".to_string()]
+    }
+
+    #[test]
+    fn test_tabbed_segment_output() {
+        let mut queue = SegmentQueue::default();
+        assert_eq!(
+            queue.deliver(segments(), SegmentOutput::Tabbed),
+            Some("Jane Doe\tjane@example.com\t555 1234".to_string())
+        );
+        assert_eq!(queue.remaining(), 0);
+    }
+
+    #[test]
+    fn test_sequential_segment_output() {
+        let mut queue = SegmentQueue::default();
+        assert_eq!(queue.deliver(segments(), SegmentOutput::Sequential), Some("Jane Doe".to_string()));
+        assert_eq!(queue.next_segment(), Some("jane@example.com".to_string()));
+        assert_eq!(queue.next_segment(), Some("555 1234".to_string()));
+        assert_eq!(queue.next_segment(), None);
+    }
+
+    #[test]
+    fn test_own_paste_does_not_advance_segments() {
+        let staged = Instant::now();
+        let queue = SegmentQueue {
+            pending: segments().into(),
+            staged_at: Some(staged),
+        };
+        assert!(!queue.advances_on_paste(staged + Duration::from_millis(20)));
+        assert!(queue.advances_on_paste(staged + Duration::from_secs(2)));
+        assert!(!SegmentQueue::default().advances_on_paste(staged));
+    }
+}
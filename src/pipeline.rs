@@ -0,0 +1,1844 @@
+use anyhow::{bail, Context, Result};
+use hound::{WavReader, WavSpec};
+use log::{info, warn};
+use std::io::Cursor;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::analytics::{count_words, record_analytics, RecordingStats};
+use crate::api::{
+    is_local_endpoint_available, run_llm_passes, transcribe_audio_bytes_timed, transcribe_bytes_with_override,
+    transcribe_with_override, translate_audio, translate_audio_bytes, EndpointKind, LlmOutput, TimedTranscription,
+    Transcription, RECORDING_FILE_NAME, WHISPER_MODEL,
+};
+use crate::audio::{
+    convert_for_upload, normalize_samples, prepare_for_endpoint, store_recording, AudioPipeline, CapturedAudio,
+    NORMALIZE_TARGET_DBFS, TEMP_RECORDINGS,
+};
+use crate::clipboard::{auto_paste, copy_to_clipboard_with_retries};
+use crate::budget::{BudgetedProvider, SessionBudgets};
+use crate::chunks::{split_into_chunks, stitch, transcribe_chunks_parallel};
+use crate::command_backend::transcribe_command_or_http;
+use crate::config::{Config, OutputMode, OutputSettings, ResponseFormat, TimestampGranularity};
+use crate::consent::{confirm_endpoint, is_declined, prompt_terminal, ConsentStore, Declined};
+use crate::diarize::diarized_text;
+use crate::diff::report_diff;
+use crate::disk::{free_disk_mb, plan_storage, StoragePlan};
+use crate::error::{notify_error, ScribeError};
+use crate::history::{record_history, HistoryEntry};
+use crate::llm::provider_from_settings;
+use crate::notify::{notify_desktop, observe_rate_limit, DesktopNotifier, LifecycleEvent, LogNotifier, Notifier};
+use crate::output::{
+    insert_text, prepare_output, stage_segments, type_text, Placeholder, SegmentQueue, SimulatedKeyboard,
+};
+use crate::queue::TranscriptionQueue;
+use crate::result::TranscriptionResult;
+use crate::selection::prompt_with_selection;
+use crate::text::{
+    correct_homophones, discard_hallucination, group_digits, instruction_regex, split_instruction, split_segments,
+    Preamble,
+};
+use crate::vocabulary::vocabulary_prompt_for;
+use crate::webhook::emit_to_webhook;
+use crate::window::{SystemWindowHelper, TargetWindow, WindowHelper};
+
+/// `model` reported for dry-run transcriptions
+pub const DRY_RUN_MODEL: &str = "dry-run";
+
+/// A capture that ended, with what was decided while it ran
+#[derive(Debug, Clone)]
+pub struct FinishedRecording {
+    pub samples: Vec<i16>,
+    pub spec: WavSpec,
+    /// The post-processing modifier was held at some point during the recording
+    pub modifier_held: bool,
+    /// The sticky `hotkeys.toggle_post_processing` switch was on
+    pub post_processing_enabled: bool,
+    /// Endpoint forced by a hotkey for this recording
+    pub forced: Option<EndpointKind>,
+    /// Escape was pressed while recording; the audio is discarded untranscribed
+    pub cancelled: bool,
+    /// Window chosen with `hotkeys.set_target_window` when the recording ended
+    pub target_window: TargetWindow,
+    /// Text captured with `hotkeys.capture_selection`, given to the LLM as context
+    pub selection: Option<String>,
+}
+
+/// What the LLM passes are asked to do with a transcription
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostProcessRequest<'a> {
+    /// The transcription, without any spoken instruction
+    pub text: &'a str,
+    /// Run the cleanup pass; otherwise only an optional summary is made
+    pub clean_up: bool,
+    /// Instruction spoken before the dictation, used instead of the cleanup prompt
+    pub instruction: Option<&'a str>,
+    /// Selected text the dictation refers to, prepended to the prompt
+    pub selection: Option<&'a str>,
+    /// Language Whisper detected, which picks the cleanup prompt
+    pub language: Option<&'a str>,
+}
+
+/// The side effects of processing a recording, so the pipeline can run
+/// against stubs
+pub trait RecordingIo {
+    /// Uploads the prepared audio and returns the raw transcription
+    fn transcribe(&mut self, audio: &CapturedAudio, forced: Option<EndpointKind>) -> Result<Transcription>;
+    /// Runs the LLM passes over `request.text`: the cleanup pass when asked
+    /// for and the `llm.summarize` pass when configured
+    fn post_process(&mut self, request: &PostProcessRequest) -> Result<LlmOutput>;
+    /// Hands the final text to the user
+    fn deliver(&mut self, text: &str) -> Result<()>;
+    /// Shows that the new recording is being transcribed, once its kept
+    /// predecessors are delivered. By default nothing is shown.
+    fn show_placeholder(&mut self) {}
+    /// The endpoint a transcription not forced elsewhere would go to. By
+    /// default the hosted one.
+    fn select_endpoint(&mut self, forced: Option<EndpointKind>) -> EndpointKind {
+        forced.unwrap_or(EndpointKind::Hosted)
+    }
+    /// Holds on to a recording whose transcription failed. By default it is
+    /// dropped.
+    fn keep_for_retry(&mut self, _audio: CapturedAudio) {}
+    /// Recordings kept by `keep_for_retry`, oldest first, to try again
+    fn take_retries(&mut self) -> Vec<CapturedAudio> {
+        Vec::new()
+    }
+    /// Transcribes the chunks of a long recording (`transcription.chunk_secs`)
+    /// as one transcription. By default they go through `transcribe` in turn.
+    fn transcribe_chunks(&mut self, chunks: &[CapturedAudio], forced: Option<EndpointKind>) -> Result<Transcription> {
+        transcribe_in_turn(self, chunks, forced)
+    }
+    /// Holds the segments of a sequential dictation left after the first was
+    /// delivered, for the user's next pastes. By default they are dropped.
+    fn queue_segments(&mut self, _segments: SegmentQueue) {}
+}
+
+/// Runs `audio.preprocessing` and normalization over the recording and
+/// converts it to the upload format. With `transcription.resample_for_hosted_only`
+/// the native format is kept for `prepare_for_endpoint` to decide on.
+pub fn prepare_audio(config: &Config, recording: &FinishedRecording) -> (Vec<i16>, WavSpec) {
+    let (mut samples, spec) =
+        AudioPipeline::new(config.audio.preprocessing.clone()).apply(recording.samples.clone(), recording.spec);
+    if config.audio.normalize {
+        normalize_samples(&mut samples, NORMALIZE_TARGET_DBFS);
+    }
+    // With resample_for_hosted_only the local endpoint gets the native format
+    if config.transcription.resample_for_hosted_only {
+        (samples, spec)
+    } else {
+        convert_for_upload(&samples, spec, config.audio.target_sample_rate)
+    }
+}
+
+/// Recordings are uploaded straight from memory unless a transcription
+/// command or diarization needs a file to read, which `privacy.memory_only`
+/// rules out as well
+pub fn keeps_audio_in_memory(config: &Config) -> bool {
+    config.privacy.memory_only || (config.transcription.command.is_none() && !config.transcription.diarize)
+}
+
+/// Transcribes a finished recording, post-processes it if requested and
+/// delivers the result, then hands it to the webhook if one is configured.
+/// Before anything is written to disk, `general.min_free_disk_mb` is checked
+/// and the recording kept in memory or refused as `low_disk_action` says.
+/// Recordings kept after an earlier failed transcription are retried and
+/// delivered first; a recording that fails now is kept for the next one.
+/// A failed LLM pass falls back to the raw transcription with a notification.
+/// Rate-limited failures of either are reported through `observe_rate_limit`.
+/// Transcription and clipboard failures come back as `ScribeError`s so the
+/// caller can show the matching advice.
+/// Returns what was delivered, or `None` for a cancelled recording, which is
+/// never written to disk or uploaded, for a recording whose upload the user
+/// declined, and for a transcription discarded as one of
+/// `transcription.hallucination_phrases`.
+pub fn process_recording(
+    config: &Config,
+    recording: &FinishedRecording,
+    io: &mut dyn RecordingIo,
+) -> Result<Option<TranscriptionResult>> {
+    if recording.cancelled {
+        info!("Recording cancelled, discarding {} samples", recording.samples.len());
+        return Ok(None);
+    }
+    let started = Instant::now();
+    let (samples, spec) = prepare_audio(config, recording);
+    let temp_dir = std::env::temp_dir();
+    let plan = if !keeps_audio_in_memory(config) {
+        let notifier: &dyn Notifier = if config.ui.error_notifications { &DesktopNotifier } else { &LogNotifier };
+        plan_storage(free_disk_mb(&temp_dir), &config.general, notifier)?
+    } else {
+        StoragePlan::Disk
+    };
+    let in_memory = keeps_audio_in_memory(config) || plan == StoragePlan::Memory;
+    retry_kept_recordings(config, recording, io);
+    io.show_placeholder();
+
+    let store = |samples: &[i16], spec| store_recording(samples, spec, in_memory, &temp_dir);
+    let transcribed = if config.transcription.resample_for_hosted_only {
+        transcribe_per_endpoint(config, recording.forced, &samples, spec, &store, io)
+    } else {
+        transcribe_samples_or_keep(config, &samples, spec, &store, recording.forced, io)
+    };
+    let transcription = match transcribed {
+        Ok(transcription) => transcription,
+        Err(e) if is_declined(&e) => {
+            info!("{}, dropping the recording", e);
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    };
+    let frames = recording.samples.len() / recording.spec.channels.max(1) as usize;
+    let duration_secs = frames as f64 / recording.spec.sample_rate as f64;
+    finish_transcription(config, recording, transcription, duration_secs, started, io)
+}
+
+/// `transcription.resample_for_hosted_only`: picks the endpoint first, then
+/// transcribes the audio in its format, downsampled only for the hosted one,
+/// in chunks when the result is long. When the local endpoint was picked
+/// rather than forced and fails, the recording falls back to hosted with the
+/// downsampled audio.
+fn transcribe_per_endpoint(
+    config: &Config,
+    forced: Option<EndpointKind>,
+    samples: &[i16],
+    spec: WavSpec,
+    store: &dyn Fn(&[i16], WavSpec) -> Result<CapturedAudio>,
+    io: &mut dyn RecordingIo,
+) -> Result<Transcription> {
+    let mut endpoint = io.select_endpoint(forced);
+    if endpoint == EndpointKind::Local && forced.is_none() {
+        let (local, local_spec) = prepare_for_endpoint(samples, spec, EndpointKind::Local, true);
+        let transcribed = match long_recording_chunks(config, &local, local_spec) {
+            Some(chunks) => transcribe_stored_chunks(&chunks, local_spec, store, Some(EndpointKind::Local), io),
+            None => {
+                let audio = store(&local, local_spec)?;
+                let transcribed = io.transcribe(&audio, Some(EndpointKind::Local));
+                if let CapturedAudio::File(path) = &audio {
+                    TEMP_RECORDINGS.remove(path);
+                }
+                transcribed
+            }
+        };
+        match transcribed {
+            Ok(transcription) => return Ok(transcription),
+            Err(e) => warn!("Local transcription failed, falling back to hosted: {:?}", e),
+        }
+        endpoint = EndpointKind::Hosted;
+    }
+    let (samples, spec) = prepare_for_endpoint(samples, spec, endpoint, true);
+    transcribe_samples_or_keep(config, &samples, spec, store, Some(endpoint), io)
+}
+
+/// Transcribes the recording in one request, or in chunks when it is long,
+/// keeping it for retry if that fails
+fn transcribe_samples_or_keep(
+    config: &Config,
+    samples: &[i16],
+    spec: WavSpec,
+    store: &dyn Fn(&[i16], WavSpec) -> Result<CapturedAudio>,
+    forced: Option<EndpointKind>,
+    io: &mut dyn RecordingIo,
+) -> Result<Transcription> {
+    match long_recording_chunks(config, samples, spec) {
+        Some(chunks) => transcribe_in_chunks(config, &chunks, (samples, spec), store, forced, io),
+        None => transcribe_or_keep(config, store(samples, spec)?, forced, io),
+    }
+}
+
+/// The chunks of a recording longer than `transcription.chunk_secs`, unless
+/// the transcription needs the whole recording in one request
+fn long_recording_chunks(config: &Config, samples: &[i16], spec: WavSpec) -> Option<Vec<Vec<i16>>> {
+    let transcription = &config.transcription;
+    let whole_recording = config.audio.translate
+        || config.output.word_timestamps_json
+        || transcription.command.is_some()
+        || transcription.diarize;
+    if transcription.chunk_secs == 0 || whole_recording {
+        return None;
+    }
+    let chunks = split_into_chunks(samples, spec.channels, spec.sample_rate, transcription.chunk_secs);
+    (chunks.len() > 1).then_some(chunks)
+}
+
+/// Transcribes the chunks of a long recording and stitches them in order.
+/// Chunk files are removed either way; if any chunk fails, the whole
+/// `recording` is handed to `keep_for_retry`.
+fn transcribe_in_chunks(
+    config: &Config,
+    chunks: &[Vec<i16>],
+    recording: (&[i16], WavSpec),
+    store: &dyn Fn(&[i16], WavSpec) -> Result<CapturedAudio>,
+    forced: Option<EndpointKind>,
+    io: &mut dyn RecordingIo,
+) -> Result<Transcription> {
+    let (samples, spec) = recording;
+    transcribe_stored_chunks(chunks, spec, store, forced, io).or_else(|e| {
+        if is_declined(&e) {
+            return Err(e);
+        }
+        observe_rate_limit(&config.network, &config.ui, &e);
+        io.keep_for_retry(store(samples, spec)?);
+        Err(ScribeError::Transcribe(e).into())
+    })
+}
+
+/// Stores and transcribes the chunks of a long recording, removing the chunk
+/// files either way
+fn transcribe_stored_chunks(
+    chunks: &[Vec<i16>],
+    spec: WavSpec,
+    store: &dyn Fn(&[i16], WavSpec) -> Result<CapturedAudio>,
+    forced: Option<EndpointKind>,
+    io: &mut dyn RecordingIo,
+) -> Result<Transcription> {
+    let audio = chunks.iter().map(|chunk| store(chunk, spec)).collect::<Result<Vec<_>>>()?;
+    info!("Transcribing the recording in {} chunks", audio.len());
+    let transcribed = io.transcribe_chunks(&audio, forced);
+    for chunk in &audio {
+        if let CapturedAudio::File(path) = chunk {
+            TEMP_RECORDINGS.remove(path);
+        }
+    }
+    transcribed
+}
+
+/// `RecordingIo::transcribe_chunks` one chunk after another
+fn transcribe_in_turn<I: RecordingIo + ?Sized>(
+    io: &mut I,
+    chunks: &[CapturedAudio],
+    forced: Option<EndpointKind>,
+) -> Result<Transcription> {
+    let parts = chunks
+        .iter()
+        .map(|chunk| io.transcribe(chunk, forced))
+        .collect::<Result<Vec<_>>>()?;
+    stitch_transcriptions(parts)
+}
+
+/// One transcription from those of consecutive chunks, reported with the
+/// endpoint, model and language of the first
+fn stitch_transcriptions(parts: Vec<Transcription>) -> Result<Transcription> {
+    let texts: Vec<String> = parts.iter().map(|part| part.text.clone()).collect();
+    let first = parts.into_iter().next().context("No chunks were transcribed")?;
+    Ok(Transcription {
+        text: stitch(&texts),
+        ..first
+    })
+}
+
+/// Transcribes `audio`, handing it to `keep_for_retry` if that fails for any
+/// reason but a declined confirmation
+fn transcribe_or_keep(
+    config: &Config,
+    audio: CapturedAudio,
+    forced: Option<EndpointKind>,
+    io: &mut dyn RecordingIo,
+) -> Result<Transcription> {
+    match io.transcribe(&audio, forced) {
+        Err(e) if !is_declined(&e) => {
+            observe_rate_limit(&config.network, &config.ui, &e);
+            io.keep_for_retry(audio);
+            Err(ScribeError::Transcribe(e).into())
+        }
+        transcribed => {
+            if let CapturedAudio::File(path) = &audio {
+                TEMP_RECORDINGS.remove(path);
+            }
+            transcribed
+        }
+    }
+}
+
+/// Transcribes and delivers the recordings kept after failed transcriptions.
+/// Their own modifier, selection and forced endpoint were not kept, and those
+/// of `recording` are meant for it alone, so only the sticky post-processing
+/// switch carries over. The first one that fails again is kept along with the
+/// rest, as the endpoint is most likely still down.
+fn retry_kept_recordings(config: &Config, recording: &FinishedRecording, io: &mut dyn RecordingIo) {
+    let retried = FinishedRecording {
+        samples: Vec::new(),
+        spec: recording.spec,
+        modifier_held: false,
+        post_processing_enabled: recording.post_processing_enabled,
+        forced: None,
+        cancelled: false,
+        target_window: recording.target_window,
+        selection: None,
+    };
+    let mut kept = io.take_retries().into_iter();
+    while let Some(audio) = kept.next() {
+        info!("Retrying a recording kept after a failed transcription");
+        let (duration_secs, started) = (wav_duration_secs(&audio), Instant::now());
+        let delivered = transcribe_or_keep(config, audio, None, io).and_then(|transcription| {
+            finish_transcription(config, &retried, transcription, duration_secs, started, io)
+        });
+        match delivered {
+            Err(e) if is_declined(&e) => info!("{}, dropping the kept recording", e),
+            Err(e) => {
+                warn!("Retrying a kept recording failed: {:?}", e);
+                for audio in kept.by_ref() {
+                    io.keep_for_retry(audio);
+                }
+            }
+            Ok(_) => {}
+        }
+    }
+}
+
+/// Length of a stored recording, from its WAV header
+fn wav_duration_secs(audio: &CapturedAudio) -> f64 {
+    let header = match audio {
+        CapturedAudio::File(path) => WavReader::open(path).map(|reader| (reader.duration(), reader.spec())),
+        CapturedAudio::Memory(wav) => WavReader::new(Cursor::new(wav)).map(|reader| (reader.duration(), reader.spec())),
+    };
+    header
+        .map(|(frames, spec)| frames as f64 / spec.sample_rate as f64)
+        .unwrap_or(0.0)
+}
+
+/// Post-processes and delivers a transcription, then reports it to the
+/// webhook, the history and the analytics, with the latency counted from
+/// `started`. `None` when it was discarded as a hallucination.
+fn finish_transcription(
+    config: &Config,
+    recording: &FinishedRecording,
+    transcription: Transcription,
+    duration_secs: f64,
+    started: Instant,
+    io: &mut dyn RecordingIo,
+) -> Result<Option<TranscriptionResult>> {
+    if discard_hallucination(&transcription.text, &config.transcription.hallucination_phrases).is_none() {
+        return Ok(None);
+    }
+    let corrected = correct_homophones(&transcription.text, &config.transcription.homophones)?;
+    let preamble = match &config.transcription.instruction_prefix_regex {
+        Some(pattern) => split_instruction(&corrected, &instruction_regex(pattern)?),
+        None => Preamble {
+            instruction: None,
+            text: corrected,
+        },
+    };
+    if let Some(instruction) = &preamble.instruction {
+        info!("Spoken instruction: {}", instruction);
+    }
+
+    // A spoken instruction or a captured selection asks for post-processing by itself
+    let requested = recording.post_processing_enabled
+        || recording.modifier_held
+        || preamble.instruction.is_some()
+        || recording.selection.is_some();
+    // Cleaned-up text would no longer match the word timings
+    let clean_up = config.llm.should_post_process(requested, transcription.language.as_deref())
+        && !config.output.word_timestamps_json;
+    let request = PostProcessRequest {
+        text: &preamble.text,
+        clean_up,
+        instruction: preamble.instruction.as_deref(),
+        selection: recording.selection.as_deref(),
+        language: transcription.language.as_deref(),
+    };
+    let output = io.post_process(&request).unwrap_or_else(|e| {
+        if is_declined(&e) {
+            info!("{}, using the raw transcription", e);
+        } else {
+            observe_rate_limit(&config.network, &config.ui, &e);
+            warn!("Post-processing failed, using the raw transcription: {:?}", e);
+            notify_error(&ScribeError::PostProcess(e), config.ui.error_notifications, &DesktopNotifier);
+        }
+        LlmOutput {
+            text: preamble.text.clone(),
+            post_processed: false,
+            summary: None,
+        }
+    });
+    let diff = if output.post_processed {
+        let notifier: &dyn Notifier = if config.ui.error_notifications { &DesktopNotifier } else { &LogNotifier };
+        report_diff(&preamble.text, &output.text, &config.llm, notifier)
+    } else {
+        None
+    };
+    // After the LLM, which would otherwise regroup the digits its own way
+    let text = group_digits(&output.text, &config.transcription.digit_grouping);
+    let post_processed = output.post_processed;
+    let mut segments = SegmentQueue::default();
+    let delivered = match config.transcription.segment_delimiter.as_deref() {
+        Some(delimiter) => segments
+            .deliver(split_segments(&text, delimiter), config.output.segment_output)
+            .unwrap_or_default(),
+        None => text.clone(),
+    };
+    io.deliver(&delivered).map_err(|e| match config.output.mode {
+        OutputMode::Type => e,
+        OutputMode::Clipboard | OutputMode::Paste => ScribeError::Clipboard(e).into(),
+    })?;
+    if segments.remaining() > 0 {
+        io.queue_segments(segments);
+    }
+    let mut result = TranscriptionResult::new(&transcription, &text, post_processed, transcription.language.as_deref());
+    if let Some(summary) = output.summary {
+        info!("Summary: {}", summary);
+        result.summary = Some(summary);
+    }
+    result.diff = diff.clone();
+    emit_to_webhook(config.webhook.as_ref(), &result);
+
+    record_analytics(
+        &config.analytics,
+        &RecordingStats {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            duration_secs,
+            word_count: count_words(&text),
+            language: result.language.clone(),
+            endpoint: result.endpoint_used.to_string(),
+            latency_ms: started.elapsed().as_millis() as u64,
+        },
+    );
+    record_history(
+        &config.history,
+        &HistoryEntry {
+            timestamp: result.timestamp.clone(),
+            duration_secs,
+            model: result.model_used.clone(),
+            used_local: result.endpoint_used == EndpointKind::Local,
+            post_processed,
+            text,
+            diff,
+        },
+    );
+    Ok(Some(result))
+}
+
+/// Where `deliver` ends up, so its decisions can be tested without a desktop
+pub trait OutputBackend {
+    fn type_text(&mut self, text: &str, delay: Duration) -> Result<()>;
+    fn copy(&mut self, text: &str) -> Result<()>;
+    fn paste(&mut self) -> Result<()>;
+}
+
+/// The real keyboard and clipboard. Copies are retried as
+/// `output.clipboard_retries` asks, for clipboard managers that briefly hold
+/// the clipboard.
+pub struct SystemOutput<'a> {
+    pub settings: &'a OutputSettings,
+}
+
+impl OutputBackend for SystemOutput<'_> {
+    fn type_text(&mut self, text: &str, delay: Duration) -> Result<()> {
+        type_text(text, delay)
+    }
+
+    fn copy(&mut self, text: &str) -> Result<()> {
+        copy_to_clipboard_with_retries(
+            text,
+            self.settings.clipboard_retries,
+            Duration::from_millis(self.settings.clipboard_retry_delay_ms),
+        )
+    }
+
+    fn paste(&mut self) -> Result<()> {
+        auto_paste()
+    }
+}
+
+/// Hands the final text to the user as `output.mode` asks: typed key by key,
+/// or copied to the clipboard and, in paste mode or with `clipboard.auto_paste`,
+/// pasted into the focused window. A window set with
+/// `hotkeys.set_target_window` is activated first, and the text goes through
+/// `prepare_output` for the window that ends up focused. `output.insert_command`
+/// takes over when set, with the clipboard as its fallback.
+pub fn deliver(config: &Config, text: &str, target: &mut TargetWindow) -> Result<()> {
+    let mut output = SystemOutput {
+        settings: &config.output,
+    };
+    deliver_with(config, text, target, &SystemWindowHelper, &mut output)
+}
+
+/// `deliver` with injectable windows and output
+pub fn deliver_with(
+    config: &Config,
+    text: &str,
+    target: &mut TargetWindow,
+    windows: &dyn WindowHelper,
+    output: &mut dyn OutputBackend,
+) -> Result<()> {
+    target.activate_for_output(windows);
+    let text = prepare_output(text, &config.output, windows);
+    if config.output.insert_command.is_some() {
+        return insert_text(&config.output, &text, |text| copy_and_paste(config, text, output));
+    }
+    match config.output.mode {
+        OutputMode::Type => {
+            output.type_text(&text, Duration::from_millis(config.output.typing_delay_ms))?;
+            info!("Transcription typed into the focused window");
+            Ok(())
+        }
+        OutputMode::Clipboard | OutputMode::Paste => copy_and_paste(config, &text, output),
+    }
+}
+
+/// Copies `text`, pasting it in paste mode or with `clipboard.auto_paste`
+fn copy_and_paste(config: &Config, text: &str, output: &mut dyn OutputBackend) -> Result<()> {
+    output.copy(text)?;
+    info!("Transcription copied to clipboard");
+    if config.output.mode == OutputMode::Paste || config.clipboard.auto_paste {
+        output.paste()?;
+    }
+    Ok(())
+}
+
+/// Canned stand-in for the endpoints and LLM (`app.dry_run`): the
+/// transcription describes the recording and post-processing echoes the
+/// prompt. Delivery goes to `deliver`, normally the real output.
+pub struct DryRunIo<'a, D: FnMut(&str) -> Result<()>> {
+    prompt: &'a str,
+    deliver: D,
+}
+
+impl<'a, D: FnMut(&str) -> Result<()>> DryRunIo<'a, D> {
+    pub fn new(prompt: &'a str, deliver: D) -> Self {
+        DryRunIo { prompt, deliver }
+    }
+}
+
+impl<D: FnMut(&str) -> Result<()>> RecordingIo for DryRunIo<'_, D> {
+    fn transcribe(&mut self, audio: &CapturedAudio, _forced: Option<EndpointKind>) -> Result<Transcription> {
+        let text = match audio {
+            CapturedAudio::File(path) => {
+                let samples = WavReader::open(path).context("Failed to read the recording")?.len();
+                format!("Dry run: {} samples in {}", samples, path.display())
+            }
+            CapturedAudio::Memory(wav) => {
+                let samples = WavReader::new(Cursor::new(wav)).context("Failed to read the recording")?.len();
+                format!("Dry run: {} samples in memory", samples)
+            }
+        };
+        info!("{}", text);
+        Ok(Transcription {
+            text,
+            endpoint_used: EndpointKind::Local,
+            model_used: DRY_RUN_MODEL.to_string(),
+            language: None,
+        })
+    }
+
+    fn post_process(&mut self, request: &PostProcessRequest) -> Result<LlmOutput> {
+        let text = if request.clean_up {
+            format!("{} {}", request.instruction.unwrap_or(self.prompt), request.text)
+        } else {
+            request.text.to_string()
+        };
+        Ok(LlmOutput {
+            text,
+            post_processed: request.clean_up,
+            summary: None,
+        })
+    }
+
+    fn deliver(&mut self, text: &str) -> Result<()> {
+        (self.deliver)(text)
+    }
+}
+
+/// Recordings whose transcription failed, shared by every `SystemIo` of the process
+static RETRY_QUEUE: Mutex<Option<TranscriptionQueue>> = Mutex::new(None);
+
+/// `max_calls_per_session` budgets, counted from the first recording until restart
+static SESSION_BUDGETS: OnceLock<SessionBudgets> = OnceLock::new();
+
+/// The real endpoints, LLM and output. Starting a transcription sends the
+/// "Transcribing" notification. With `output.placeholder_while_processing` in
+/// type mode, a placeholder is typed for the new recording and erased before
+/// its text is delivered, or when the recording fails. Text goes to `target` when one is set.
+/// With `output.word_timestamps_json`, the word timings are delivered as JSON
+/// in place of the text. Failed recordings wait in a process-wide
+/// `TranscriptionQueue`, within `general.max_memory_mb`. With `privacy.confirm_before_hosted`, nothing is sent
+/// to a hosted endpoint or the LLM before the user has accepted it. Whisper and
+/// LLM calls stop once their `max_calls_per_session` is used up. The chunks
+/// of a long recording are sent to one endpoint, `max_parallel_chunks` at a time.
+pub struct SystemIo<'a> {
+    config: &'a Config,
+    placeholder: Option<Placeholder>,
+    target: TargetWindow,
+    word_timings: Option<TimedTranscription>,
+    consent: Option<ConsentStore>,
+}
+
+impl<'a> SystemIo<'a> {
+    pub fn new(config: &'a Config, target: TargetWindow) -> Self {
+        SystemIo {
+            config,
+            placeholder: None,
+            target,
+            word_timings: None,
+            consent: None,
+        }
+    }
+
+    /// Fails unless data may be sent to `url`, prompting on the terminal the
+    /// first time an endpoint is seen
+    fn confirm(&mut self, url: &str) -> Result<()> {
+        let privacy = &self.config.privacy;
+        if !privacy.confirm_before_hosted {
+            return Ok(());
+        }
+        let store = match &mut self.consent {
+            Some(store) => store,
+            None => self.consent.insert(ConsentStore::load(&privacy.consent_file)?),
+        };
+        if confirm_endpoint(url, privacy, store, prompt_terminal)? {
+            Ok(())
+        } else {
+            Err(Declined {
+                endpoint: url.to_string(),
+            }
+            .into())
+        }
+    }
+
+    /// Runs `transcribe` against the forced endpoint, or the local one when it
+    /// answers, falling back to hosted once that is confirmed
+    fn with_consent<F>(&mut self, forced: Option<EndpointKind>, transcribe: F) -> Result<Transcription>
+    where
+        F: Fn(Option<EndpointKind>) -> Result<Transcription>,
+    {
+        let endpoints = &self.config.endpoints;
+        match forced {
+            Some(EndpointKind::Local) => return transcribe(forced),
+            Some(EndpointKind::Hosted) => {}
+            None if is_local_endpoint_available(&endpoints.local_whisper) => {
+                match transcribe(Some(EndpointKind::Local)) {
+                    Ok(transcription) => return Ok(transcription),
+                    Err(e) => warn!("Local transcription failed, falling back to hosted: {:?}", e),
+                }
+            }
+            None => {}
+        }
+        self.confirm(&endpoints.hosted_whisper)?;
+        transcribe(Some(EndpointKind::Hosted))
+    }
+
+    /// Reserves `calls` Whisper requests from the session budget and sends the
+    /// "Transcribing" notification
+    fn begin_transcription(&mut self, calls: usize) -> Result<()> {
+        for _ in 0..calls {
+            if !self.budgets().transcription.try_acquire(self.notifier()) {
+                bail!("Whisper call limit for this session reached");
+            }
+        }
+        notify_desktop(&self.config.ui, &LifecycleEvent::Transcribing);
+        Ok(())
+    }
+
+    fn keyboard(&self) -> SimulatedKeyboard {
+        SimulatedKeyboard {
+            delay: Duration::from_millis(self.config.output.typing_delay_ms),
+        }
+    }
+
+    fn budgets(&self) -> &'static SessionBudgets {
+        SESSION_BUDGETS.get_or_init(|| {
+            SessionBudgets::new(self.config.llm.max_calls_per_session, self.config.transcription.max_calls_per_session)
+        })
+    }
+
+    fn notifier(&self) -> &'static dyn Notifier {
+        if self.config.ui.error_notifications { &DesktopNotifier } else { &LogNotifier }
+    }
+
+    /// What `deliver` hands over for `text`
+    fn output_text(&mut self, text: &str) -> Result<String> {
+        match self.word_timings.take() {
+            Some(timed) => timed.to_json(),
+            None => Ok(text.to_string()),
+        }
+    }
+}
+
+/// Word-timed transcription for `output.word_timestamps_json`, from the forced
+/// endpoint or else the local one when it answers. Timed requests have no
+/// fallback after a failed local request.
+fn transcribe_words(
+    config: &Config,
+    audio: &CapturedAudio,
+    forced: Option<EndpointKind>,
+) -> Result<(Transcription, TimedTranscription)> {
+    let endpoints = &config.endpoints;
+    let endpoint_used = match forced {
+        Some(endpoint) => endpoint,
+        None if is_local_endpoint_available(&endpoints.local_whisper) => EndpointKind::Local,
+        None => EndpointKind::Hosted,
+    };
+    let url = match endpoint_used {
+        EndpointKind::Local => &endpoints.local_whisper,
+        EndpointKind::Hosted => &endpoints.hosted_whisper,
+    };
+    let wav = match audio {
+        CapturedAudio::File(path) => std::fs::read(path).context("Failed to read the recording")?,
+        CapturedAudio::Memory(wav) => wav.clone(),
+    };
+    let timed = transcribe_audio_bytes_timed(url, &config.api_keys.openai, wav, &[TimestampGranularity::Word])?;
+    info!("Transcribed with word timings via {} endpoint ({})", endpoint_used, WHISPER_MODEL);
+    let transcription = Transcription {
+        text: timed.text.clone(),
+        endpoint_used,
+        model_used: WHISPER_MODEL.to_string(),
+        language: None,
+    };
+    Ok((transcription, timed))
+}
+
+/// `audio.response_format`, upgraded to `verbose_json` when only that reports
+/// the language the LLM settings depend on
+fn response_format(config: &Config) -> ResponseFormat {
+    match config.audio.response_format {
+        ResponseFormat::Json if config.llm.needs_language() => ResponseFormat::VerboseJson,
+        format => format,
+    }
+}
+
+/// English text of the recording from the translation route. There is only
+/// the one endpoint, so endpoint overrides and fallback do not apply.
+fn translate(url: &str, api_key: &str, audio: &CapturedAudio) -> Result<Transcription> {
+    let text = match audio {
+        CapturedAudio::File(path) => {
+            translate_audio(url, api_key, path.to_str().context("Recording path is not valid UTF-8")?)?
+        }
+        CapturedAudio::Memory(wav) => translate_audio_bytes(url, api_key, wav.clone(), RECORDING_FILE_NAME)?,
+    };
+    info!("Translated via {} ({})", url, WHISPER_MODEL);
+    Ok(Transcription {
+        text,
+        endpoint_used: EndpointKind::Hosted,
+        model_used: WHISPER_MODEL.to_string(),
+        language: None,
+    })
+}
+
+impl RecordingIo for SystemIo<'_> {
+    fn transcribe(&mut self, audio: &CapturedAudio, forced: Option<EndpointKind>) -> Result<Transcription> {
+        let config = self.config;
+        self.begin_transcription(1)?;
+        let output = &config.output;
+        let api_key = &config.api_keys.openai;
+        if config.audio.translate {
+            self.confirm(&config.endpoints.translation)?;
+            return translate(&config.endpoints.translation, api_key, audio);
+        }
+        if output.word_timestamps_json {
+            let endpoint = self.select_endpoint(forced);
+            if endpoint == EndpointKind::Hosted {
+                self.confirm(&config.endpoints.hosted_whisper)?;
+            }
+            let (transcription, timed) = transcribe_words(config, audio, Some(endpoint))?;
+            self.word_timings = Some(timed);
+            return Ok(transcription);
+        }
+        let prompt = vocabulary_prompt_for(&config.transcription);
+        let prompt = prompt.as_deref();
+        let format = response_format(config);
+        match audio {
+            CapturedAudio::File(path) => {
+                let path = path.to_str().context("Recording path is not valid UTF-8")?;
+                let http =
+                    |endpoint| transcribe_with_override(&config.endpoints, api_key, path, prompt, endpoint, format);
+                let transcription =
+                    transcribe_command_or_http(&config.transcription, path, || self.with_consent(forced, http))?;
+                if !config.transcription.diarize {
+                    return Ok(transcription);
+                }
+                let url = config
+                    .transcription
+                    .diarization_endpoint
+                    .as_deref()
+                    .unwrap_or(&config.endpoints.hosted_whisper);
+                self.confirm(url)?;
+                Ok(Transcription {
+                    text: diarized_text(url, api_key, path, &transcription.text),
+                    ..transcription
+                })
+            }
+            CapturedAudio::Memory(wav) => self.with_consent(forced, |endpoint| {
+                transcribe_bytes_with_override(
+                    &config.endpoints,
+                    api_key,
+                    wav,
+                    RECORDING_FILE_NAME,
+                    prompt,
+                    endpoint,
+                    format,
+                )
+            }),
+        }
+    }
+
+    fn transcribe_chunks(&mut self, chunks: &[CapturedAudio], forced: Option<EndpointKind>) -> Result<Transcription> {
+        let config = self.config;
+        self.begin_transcription(chunks.len())?;
+        let endpoint = self.select_endpoint(forced);
+        if endpoint == EndpointKind::Hosted {
+            self.confirm(&config.endpoints.hosted_whisper)?;
+        }
+        let (endpoints, api_key) = (&config.endpoints, &config.api_keys.openai);
+        let prompt = vocabulary_prompt_for(&config.transcription);
+        let prompt = prompt.as_deref();
+        let format = response_format(config);
+        let forced = Some(endpoint);
+        let transcribe = |_, audio: &CapturedAudio| match audio {
+            CapturedAudio::File(path) => {
+                let path = path.to_str().context("Recording path is not valid UTF-8")?;
+                transcribe_with_override(endpoints, api_key, path, prompt, forced, format)
+            }
+            CapturedAudio::Memory(wav) => {
+                transcribe_bytes_with_override(endpoints, api_key, wav, RECORDING_FILE_NAME, prompt, forced, format)
+            }
+        };
+        let parts = transcribe_chunks_parallel(chunks, config.transcription.max_parallel_chunks, transcribe)?;
+        info!("Transcribed {} chunks via {} endpoint", chunks.len(), endpoint);
+        stitch_transcriptions(parts)
+    }
+
+    fn post_process(&mut self, request: &PostProcessRequest) -> Result<LlmOutput> {
+        let config = self.config;
+        if request.clean_up || config.llm.summarize {
+            self.confirm(&config.endpoints.llm_endpoint)?;
+        }
+        let provider = provider_from_settings(&config.endpoints.llm_endpoint, &config.api_keys, &config.llm);
+        let provider = BudgetedProvider {
+            inner: provider.as_ref(),
+            budget: &self.budgets().llm,
+            notifier: self.notifier(),
+        };
+        let with_selection = request.selection.map(|selection| {
+            let prompt = request.instruction.unwrap_or_else(|| config.llm.prompt_for(request.language));
+            prompt_with_selection(prompt, Some(selection))
+        });
+        run_llm_passes(
+            &provider,
+            &config.llm,
+            request.text,
+            request.language,
+            with_selection.as_deref().or(request.instruction),
+            request.clean_up,
+        )
+    }
+
+    fn deliver(&mut self, text: &str) -> Result<()> {
+        let text = self.output_text(text)?;
+        if let Some(placeholder) = self.placeholder.take() {
+            placeholder.erase(&mut self.keyboard())?;
+        }
+        deliver(self.config, &text, &mut self.target)
+    }
+
+    fn show_placeholder(&mut self) {
+        let output = &self.config.output;
+        if !output.placeholder_while_processing || output.mode != OutputMode::Type || self.placeholder.is_some() {
+            return;
+        }
+        // The placeholder must land where the text will
+        self.target.activate_for_output(&SystemWindowHelper);
+        match Placeholder::show(&mut self.keyboard(), &output.placeholder_text) {
+            Ok(placeholder) => self.placeholder = Some(placeholder),
+            Err(e) => warn!("Failed to show the placeholder: {:?}", e),
+        }
+    }
+
+    fn select_endpoint(&mut self, forced: Option<EndpointKind>) -> EndpointKind {
+        match forced {
+            Some(endpoint) => endpoint,
+            None if is_local_endpoint_available(&self.config.endpoints.local_whisper) => EndpointKind::Local,
+            None => EndpointKind::Hosted,
+        }
+    }
+
+    fn keep_for_retry(&mut self, audio: CapturedAudio) {
+        let general = &self.config.general;
+        let mut queue = RETRY_QUEUE.lock().unwrap();
+        let queue = queue.get_or_insert_with(|| {
+            TranscriptionQueue::new(general.max_memory_mb, self.config.privacy.memory_only, std::env::temp_dir())
+        });
+        match queue.push(audio) {
+            Ok(()) => info!("Kept the recording to retry with the next one ({} waiting)", queue.len()),
+            Err(e) => warn!("Dropping the recording: {:#}", e),
+        }
+    }
+
+    fn take_retries(&mut self) -> Vec<CapturedAudio> {
+        let mut queue = RETRY_QUEUE.lock().unwrap();
+        let Some(queue) = queue.as_mut() else {
+            return Vec::new();
+        };
+        std::iter::from_fn(|| queue.pop()).collect()
+    }
+
+    fn queue_segments(&mut self, segments: SegmentQueue) {
+        stage_segments(segments);
+    }
+}
+
+impl Drop for SystemIo<'_> {
+    fn drop(&mut self) {
+        if let Some(placeholder) = self.placeholder.take() {
+            if let Err(e) = placeholder.erase(&mut self.keyboard()) {
+                warn!("Failed to remove the placeholder: {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::SampleFormat;
+
+    /// Records what the pipeline asked for and answers with canned results
+    struct StubIo {
+        transcription: Option<String>,
+        language: Option<String>,
+        post_processed: Option<String>,
+        summary: Option<String>,
+        transcribe_calls: usize,
+        clean_up_requests: Vec<bool>,
+        /// Text and instruction of each post-processing request
+        llm_inputs: Vec<(String, Option<String>)>,
+        selections: Vec<Option<String>>,
+        delivered: Vec<String>,
+        /// Segments left for later pastes
+        queued_segments: Vec<String>,
+        /// Recordings kept for retry; `None` drops them like the default
+        retries: Option<TranscriptionQueue>,
+        /// Answer of `select_endpoint` when nothing is forced
+        available: EndpointKind,
+        /// Sample rate of each uploaded WAV
+        upload_rates: Vec<u32>,
+        /// Answer "no" when asked to upload
+        declined: bool,
+        /// Number of texts delivered when each placeholder was shown
+        placeholders: Vec<usize>,
+    }
+
+    impl StubIo {
+        fn new(transcription: Option<&str>, post_processed: Option<&str>) -> Self {
+            StubIo {
+                transcription: transcription.map(String::from),
+                language: None,
+                post_processed: post_processed.map(String::from),
+                summary: None,
+                transcribe_calls: 0,
+                clean_up_requests: Vec::new(),
+                llm_inputs: Vec::new(),
+                selections: Vec::new(),
+                delivered: Vec::new(),
+                queued_segments: Vec::new(),
+                retries: None,
+                available: EndpointKind::Local,
+                upload_rates: Vec::new(),
+                declined: false,
+                placeholders: Vec::new(),
+            }
+        }
+    }
+
+    impl RecordingIo for StubIo {
+        fn transcribe(&mut self, audio: &CapturedAudio, _forced: Option<EndpointKind>) -> Result<Transcription> {
+            let CapturedAudio::Memory(wav) = audio else {
+                panic!("expected an in-memory recording");
+            };
+            self.upload_rates.push(WavReader::new(Cursor::new(wav)).unwrap().spec().sample_rate);
+            self.transcribe_calls += 1;
+            if self.declined {
+                return Err(Declined {
+                    endpoint: "https://api.openai.com/v1/audio/transcriptions".to_string(),
+                }
+                .into());
+            }
+            Ok(Transcription {
+                text: self.transcription.clone().context("endpoint unreachable")?,
+                endpoint_used: EndpointKind::Local,
+                model_used: WHISPER_MODEL.to_string(),
+                language: self.language.clone(),
+            })
+        }
+
+        fn post_process(&mut self, request: &PostProcessRequest) -> Result<LlmOutput> {
+            self.clean_up_requests.push(request.clean_up);
+            self.llm_inputs
+                .push((request.text.to_string(), request.instruction.map(String::from)));
+            self.selections.push(request.selection.map(String::from));
+            let text = match (&self.post_processed, request.clean_up) {
+                (Some(text), true) => text.clone(),
+                (Some(_), false) => request.text.to_string(),
+                (None, _) => return Err(anyhow::anyhow!("LLM unavailable")),
+            };
+            Ok(LlmOutput {
+                text,
+                post_processed: request.clean_up,
+                summary: self.summary.clone(),
+            })
+        }
+
+        fn deliver(&mut self, text: &str) -> Result<()> {
+            self.delivered.push(text.to_string());
+            Ok(())
+        }
+
+        fn show_placeholder(&mut self) {
+            self.placeholders.push(self.delivered.len());
+        }
+
+        fn select_endpoint(&mut self, forced: Option<EndpointKind>) -> EndpointKind {
+            forced.unwrap_or(self.available)
+        }
+
+        fn keep_for_retry(&mut self, audio: CapturedAudio) {
+            if let Some(queue) = &mut self.retries {
+                queue.push(audio).unwrap();
+            }
+        }
+
+        fn take_retries(&mut self) -> Vec<CapturedAudio> {
+            match &mut self.retries {
+                Some(queue) => std::iter::from_fn(|| queue.pop()).collect(),
+                None => Vec::new(),
+            }
+        }
+
+        fn queue_segments(&mut self, mut segments: SegmentQueue) {
+            self.queued_segments.extend(std::iter::from_fn(|| segments.next_segment()));
+        }
+    }
+
+    /// Records what `deliver_with` sent where
+    #[derive(Default)]
+    struct RecordingOutput {
+        typed: Vec<String>,
+        copied: Vec<String>,
+        pastes: usize,
+    }
+
+    impl OutputBackend for RecordingOutput {
+        fn type_text(&mut self, text: &str, _delay: Duration) -> Result<()> {
+            self.typed.push(text.to_string());
+            Ok(())
+        }
+
+        fn copy(&mut self, text: &str) -> Result<()> {
+            self.copied.push(text.to_string());
+            Ok(())
+        }
+
+        fn paste(&mut self) -> Result<()> {
+            self.pastes += 1;
+            Ok(())
+        }
+    }
+
+    /// A focused window of the given class
+    struct FocusedClass(&'static str);
+
+    impl WindowHelper for FocusedClass {
+        fn focused_window(&self) -> Option<crate::window::WindowId> {
+            Some(1)
+        }
+
+        fn window_exists(&self, _id: crate::window::WindowId) -> bool {
+            true
+        }
+
+        fn activate(&self, _id: crate::window::WindowId) -> Result<()> {
+            Ok(())
+        }
+
+        fn window_class(&self, _id: crate::window::WindowId) -> Option<String> {
+            Some(self.0.to_string())
+        }
+
+        fn is_fullscreen(&self, _id: crate::window::WindowId) -> bool {
+            false
+        }
+    }
+
+    /// What `deliver_with` copies for `text` with `window` focused
+    fn copied(config: &Config, text: &str, window: &'static str) -> Vec<String> {
+        let mut output = RecordingOutput::default();
+        deliver_with(config, text, &mut TargetWindow::Unset, &FocusedClass(window), &mut output).unwrap();
+        output.copied
+    }
+
+    fn config() -> Config {
+        let mut config: Config = toml::from_str(crate::init::CONFIG_TEMPLATE).unwrap();
+        // Keep failing LLM stubs from reaching the desktop
+        config.ui.error_notifications = false;
+        config
+    }
+
+    fn recording(modifier_held: bool) -> FinishedRecording {
+        FinishedRecording {
+            samples: [0, 1000, -1000, 0].repeat(400),
+            spec: WavSpec {
+                channels: 1,
+                sample_rate: 16000,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            },
+            modifier_held,
+            post_processing_enabled: false,
+            forced: None,
+            cancelled: false,
+            target_window: TargetWindow::Unset,
+            selection: None,
+        }
+    }
+
+    #[test]
+    fn test_audio_stays_in_memory_unless_a_file_is_needed() {
+        let mut config = config();
+        assert!(keeps_audio_in_memory(&config));
+
+        config.transcription.command = Some(vec!["whisper-cli".to_string(), "{{audio}}".to_string()]);
+        assert!(!keeps_audio_in_memory(&config));
+        config.privacy.memory_only = true;
+        assert!(keeps_audio_in_memory(&config));
+    }
+
+    #[test]
+    fn test_modifier_selects_cleanup() {
+        let config = config();
+
+        let mut io = StubIo::new(Some("um hello there"), Some("Hello there."));
+        let text = process_recording(&config, &recording(true), &mut io).unwrap().map(|result| result.text);
+        assert_eq!(text.as_deref(), Some("Hello there."));
+        assert_eq!(io.clean_up_requests, vec![true]);
+        assert_eq!(io.delivered, vec!["Hello there."]);
+
+        let mut io = StubIo::new(Some("um hello there"), Some("Hello there."));
+        let text = process_recording(&config, &recording(false), &mut io).unwrap().map(|result| result.text);
+        assert_eq!(text.as_deref(), Some("um hello there"));
+        assert_eq!(io.clean_up_requests, vec![false]);
+    }
+
+    #[test]
+    fn test_any_post_processing_source_enables_cleanup() {
+        // (sticky toggle, modifier held, always_post_process) -> clean up
+        let cases = [
+            ((false, false, false), false),
+            ((true, false, false), true),
+            ((false, true, false), true),
+            ((false, false, true), true),
+            ((true, true, false), true),
+            ((true, false, true), true),
+        ];
+        for ((sticky, modifier, always), expected) in cases {
+            let mut config = config();
+            config.llm.always_post_process = always;
+            let recording = FinishedRecording {
+                post_processing_enabled: sticky,
+                ..recording(modifier)
+            };
+
+            let mut io = StubIo::new(Some("um hello there"), Some("Hello there."));
+            process_recording(&config, &recording, &mut io).unwrap();
+            assert_eq!(
+                io.clean_up_requests,
+                vec![expected],
+                "sticky={} modifier={} always={}",
+                sticky,
+                modifier,
+                always
+            );
+        }
+    }
+
+    #[test]
+    fn test_dry_run_describes_recording_and_echoes_prompt() {
+        let config = config();
+        let prompt = &config.llm.post_processing_prompt;
+        let mut delivered = Vec::new();
+        let mut io = DryRunIo::new(prompt, |text: &str| {
+            delivered.push(text.to_string());
+            Ok(())
+        });
+
+        let text = process_recording(&config, &recording(true), &mut io).unwrap().unwrap().text;
+        assert_eq!(text, format!("{} Dry run: 1600 samples in memory", prompt));
+        assert_eq!(delivered, vec![text]);
+    }
+
+    #[test]
+    fn test_failed_post_processing_delivers_raw_text() {
+        let mut config = config();
+        config.llm.always_post_process = true;
+
+        let mut io = StubIo::new(Some("um hello there"), None);
+        let text = process_recording(&config, &recording(false), &mut io).unwrap().map(|result| result.text);
+        assert_eq!(text.as_deref(), Some("um hello there"));
+        assert_eq!(io.delivered, vec!["um hello there"]);
+    }
+
+    #[test]
+    fn test_detected_language_can_skip_cleanup() {
+        let mut config = config();
+        config.llm.skip_post_processing_languages = vec!["de".to_string()];
+        let mut io = StubIo::new(Some("Hallo."), Some("Cleaned."));
+        io.language = Some("de".to_string());
+
+        let text = process_recording(&config, &recording(true), &mut io).unwrap().map(|result| result.text);
+
+        assert_eq!(text.as_deref(), Some("Hallo."));
+        assert_eq!(io.clean_up_requests, vec![false]);
+    }
+
+    #[test]
+    fn test_spoken_instruction_is_split_off_before_post_processing() {
+        let mut config = config();
+        config.transcription.instruction_prefix_regex = Some(r"(?i)as an? ([^:]+):".to_string());
+
+        let mut io = StubIo::new(Some("As a tweet: shipping today"), Some("Shipping today!"));
+        let text = process_recording(&config, &recording(false), &mut io).unwrap().map(|result| result.text);
+        assert_eq!(text.as_deref(), Some("Shipping today!"));
+        assert_eq!(io.clean_up_requests, vec![true]);
+        assert_eq!(io.llm_inputs, vec![("shipping today".to_string(), Some("tweet".to_string()))]);
+
+        let mut io = StubIo::new(Some("shipping today"), Some("unused"));
+        let text = process_recording(&config, &recording(false), &mut io).unwrap().map(|result| result.text);
+        assert_eq!(text.as_deref(), Some("shipping today"));
+        assert_eq!(io.llm_inputs, vec![("shipping today".to_string(), None)]);
+    }
+
+    #[test]
+    fn test_failed_transcription_delivers_nothing() {
+        let mut io = StubIo::new(None, Some("unused"));
+        let err = process_recording(&config(), &recording(true), &mut io).unwrap_err();
+        assert_eq!(err.to_string(), "Transcription error: endpoint unreachable");
+        assert!(matches!(err.downcast_ref::<ScribeError>(), Some(ScribeError::Transcribe(_))));
+        assert!(io.clean_up_requests.is_empty());
+        assert!(io.delivered.is_empty());
+    }
+
+    #[test]
+    fn test_delivered_text_is_recorded_in_history() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let mut config = config();
+        config.history.enabled = true;
+        config.history.path = Some(path.to_str().unwrap().to_string());
+
+        let mut io = StubIo::new(Some("um hello there"), Some("Hello there."));
+        process_recording(&config, &recording(true), &mut io).unwrap();
+
+        let entries = crate::history::read_last_entries(&path, 5).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].text, "Hello there.");
+        assert_eq!(entries[0].duration_secs, 0.1);
+        assert_eq!(entries[0].model, WHISPER_MODEL);
+        assert!(entries[0].used_local);
+        assert!(entries[0].post_processed);
+    }
+
+    #[test]
+    fn test_delivered_result_is_posted_to_the_webhook() {
+        let hook = mockito::mock("POST", "/pipeline-hook")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "text": "Hello there.",
+                "raw_text": "um hello there",
+                "post_processed": true,
+                "endpoint_used": "local",
+            })))
+            .with_status(200)
+            .create();
+        let mut config = config();
+        config.webhook = Some(crate::config::WebhookSettings {
+            url: format!("{}/pipeline-hook", mockito::server_url()),
+            auth_header: None,
+        });
+
+        let mut io = StubIo::new(Some("um hello there"), Some("Hello there."));
+        process_recording(&config, &recording(true), &mut io).unwrap();
+
+        hook.assert();
+    }
+
+    #[test]
+    fn test_result_records_the_endpoint_and_model_used() {
+        let mut io = StubIo::new(Some("um hello there"), Some("Hello there."));
+        let result = process_recording(&config(), &recording(true), &mut io).unwrap().unwrap();
+
+        assert_eq!(result.text, "Hello there.");
+        assert_eq!(result.raw_text, "um hello there");
+        assert_eq!(result.endpoint_used, EndpointKind::Local);
+        assert_eq!(result.model_used, WHISPER_MODEL);
+        let json: serde_json::Value = serde_json::from_str(&result.to_json().unwrap()).unwrap();
+        assert_eq!(json["endpoint_used"], "local");
+        assert_eq!(json["model_used"], WHISPER_MODEL);
+    }
+
+    #[test]
+    fn test_summary_is_attached_to_the_result() {
+        let mut io = StubIo::new(Some("um the meeting went well"), Some("The meeting went well."));
+        io.summary = Some("Good meeting.".to_string());
+
+        let result = process_recording(&config(), &recording(true), &mut io).unwrap().unwrap();
+        assert_eq!(io.delivered, vec!["The meeting went well."]);
+        assert_eq!(result.text, "The meeting went well.");
+        assert_eq!(result.summary.as_deref(), Some("Good meeting."));
+    }
+
+    #[test]
+    fn test_word_timestamps_json_delivers_the_timings() {
+        let _m = mockito::mock("POST", "/pipeline-words")
+            .match_body(mockito::Matcher::Regex("timestamp_granularities".to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "Hello there.", "words": [{"word": "Hello", "start": 0.0, "end": 0.4}, {"word": "there.", "start": 0.5, "end": 0.9}]}"#)
+            .create();
+        let mut config = config();
+        config.output.word_timestamps_json = true;
+        config.endpoints.hosted_whisper = format!("{}/pipeline-words", mockito::server_url());
+
+        let mut io = SystemIo::new(&config, TargetWindow::Unset);
+        let wav = crate::audio::encode_wav_to_memory(&recording(false).samples, recording(false).spec).unwrap();
+        let transcription = io
+            .transcribe(&CapturedAudio::Memory(wav), Some(EndpointKind::Hosted))
+            .unwrap();
+        assert_eq!(transcription.text, "Hello there.");
+        assert_eq!(transcription.endpoint_used, EndpointKind::Hosted);
+
+        let delivered: serde_json::Value = serde_json::from_str(&io.output_text("Hello there.").unwrap()).unwrap();
+        assert_eq!(delivered["word_level"], true);
+        assert_eq!(delivered["words"][1]["word"], "there.");
+        assert_eq!(io.output_text("next").unwrap(), "next");
+    }
+
+    #[test]
+    fn test_accepted_endpoint_is_used_without_prompting() {
+        let _m = mockito::mock("POST", "/pipeline-consent")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "Hello there."}"#)
+            .expect(1)
+            .create();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config();
+        config.endpoints.hosted_whisper = format!("{}/pipeline-consent", mockito::server_url());
+        config.privacy.confirm_before_hosted = true;
+        config.privacy.consent_file = dir.path().join("consent.json").to_string_lossy().into_owned();
+        ConsentStore::load(&config.privacy.consent_file)
+            .unwrap()
+            .accept(&config.endpoints.hosted_whisper)
+            .unwrap();
+
+        let mut io = SystemIo::new(&config, TargetWindow::Unset);
+        let wav = crate::audio::encode_wav_to_memory(&recording(false).samples, recording(false).spec).unwrap();
+        let transcription = io
+            .transcribe(&CapturedAudio::Memory(wav), Some(EndpointKind::Hosted))
+            .unwrap();
+        assert_eq!(transcription.text, "Hello there.");
+        _m.assert();
+    }
+
+    #[test]
+    fn test_raw_transcription_skips_the_llm_confirmation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config();
+        config.privacy.confirm_before_hosted = true;
+        config.privacy.consent_file = dir.path().join("consent.json").to_string_lossy().into_owned();
+        let request = PostProcessRequest {
+            text: "Hello there.",
+            clean_up: false,
+            instruction: None,
+            selection: None,
+            language: None,
+        };
+
+        let output = SystemIo::new(&config, TargetWindow::Unset).post_process(&request).unwrap();
+        assert_eq!(output.text, "Hello there.");
+        assert!(!output.post_processed);
+    }
+
+    #[test]
+    fn test_detected_language_picks_the_cleanup_prompt() {
+        let _m = mockito::mock("POST", "/pipeline-language")
+            .match_body(mockito::Matcher::Regex("Bereinige den Text:".to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"choices": [{ "text": "Hallo Welt." }]}"#)
+            .expect(2)
+            .create();
+        let mut config = config();
+        config.endpoints.llm_endpoint = format!("{}/pipeline-language", mockito::server_url());
+        config.llm.prompts_by_language.insert("de".to_string(), "Bereinige den Text:".to_string());
+
+        let mut io = SystemIo::new(&config, TargetWindow::Unset);
+        let mut request = PostProcessRequest {
+            text: "hallo welt",
+            clean_up: true,
+            instruction: None,
+            selection: None,
+            language: Some("de"),
+        };
+        assert_eq!(io.post_process(&request).unwrap().text, "Hallo Welt.");
+        request.selection = Some("Liebe Grüße");
+        assert_eq!(io.post_process(&request).unwrap().text, "Hallo Welt.");
+        _m.assert();
+    }
+
+    #[test]
+    fn test_vocabulary_is_sent_as_the_prompt() {
+        let _m = mockito::mock("POST", "/pipeline-vocabulary")
+            .match_body(mockito::Matcher::Regex("name=\"prompt\"\r\n\r\nGrafana, Loki\r\n".to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "Loki is up."}"#)
+            .create();
+        let dir = tempfile::tempdir().unwrap();
+        let vocabulary = dir.path().join("vocabulary.txt");
+        std::fs::write(&vocabulary, "Grafana\nLoki\n").unwrap();
+        let mut config = config();
+        config.endpoints.hosted_whisper = format!("{}/pipeline-vocabulary", mockito::server_url());
+        config.transcription.vocabulary_file = Some(vocabulary.to_string_lossy().into_owned());
+
+        let mut io = SystemIo::new(&config, TargetWindow::Unset);
+        let wav = crate::audio::encode_wav_to_memory(&recording(false).samples, recording(false).spec).unwrap();
+        let transcription = io
+            .transcribe(&CapturedAudio::Memory(wav), Some(EndpointKind::Hosted))
+            .unwrap();
+        assert_eq!(transcription.text, "Loki is up.");
+    }
+
+    #[test]
+    fn test_word_timestamps_json_skips_cleanup() {
+        let mut config = config();
+        config.output.word_timestamps_json = true;
+
+        let mut io = StubIo::new(Some("um hello there"), Some("Hello there."));
+        process_recording(&config, &recording(true), &mut io).unwrap();
+        assert_eq!(io.clean_up_requests, vec![false]);
+    }
+
+    #[test]
+    fn test_delivery_uses_bracketed_paste_in_terminals_only() {
+        let mut config = config();
+        config.output.bracketed_paste = true;
+
+        assert_eq!(copied(&config, "ls\nrm -r", "kitty"), vec!["\x1b[200~ls\nrm -r\x1b[201~"]);
+        assert_eq!(copied(&config, "ls\nrm -r", "firefox"), vec!["ls\nrm -r"]);
+    }
+
+    #[test]
+    fn test_delivery_strips_configured_tags() {
+        let mut config = config();
+        config.output.mode = OutputMode::Type;
+        config.output.strip_tags = vec!["speak".to_string()];
+        let mut output = RecordingOutput::default();
+
+        deliver_with(
+            &config,
+            "<speak>Hello</speak> world",
+            &mut TargetWindow::Unset,
+            &FocusedClass("firefox"),
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(output.typed, vec!["Hello world"]);
+        assert!(output.copied.is_empty());
+    }
+
+    #[test]
+    fn test_delivery_converts_line_endings() {
+        let mut config = config();
+        config.output.line_ending = crate::config::LineEnding::Crlf;
+
+        assert_eq!(copied(&config, "first\nsecond", "firefox"), vec!["first\r\nsecond"]);
+    }
+
+    #[test]
+    fn test_failed_recording_is_retried_with_the_next_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut io = StubIo::new(None, None);
+        io.retries = Some(TranscriptionQueue::new(1, false, dir.path()));
+
+        let err = process_recording(&config(), &recording(false), &mut io).unwrap_err();
+        assert!(matches!(err.downcast_ref::<ScribeError>(), Some(ScribeError::Transcribe(_))));
+        assert_eq!(io.retries.as_ref().unwrap().len(), 1);
+
+        io.transcription = Some("Back online.".to_string());
+        let result = process_recording(&config(), &recording(false), &mut io).unwrap().unwrap();
+        assert_eq!(result.text, "Back online.");
+        assert_eq!(io.transcribe_calls, 3);
+        assert_eq!(io.delivered, vec!["Back online.", "Back online."]);
+        assert!(io.retries.as_ref().unwrap().is_empty());
+        // Only the new recordings get a placeholder, after the kept one is delivered
+        assert_eq!(io.placeholders, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_retried_recording_does_not_take_the_next_ones_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut io = StubIo::new(None, Some("Cleaned up."));
+        io.retries = Some(TranscriptionQueue::new(1, false, dir.path()));
+        process_recording(&config(), &recording(false), &mut io).unwrap_err();
+
+        io.transcription = Some("Back online.".to_string());
+        let recording = FinishedRecording {
+            selection: Some("Quarterly report".to_string()),
+            ..recording(true)
+        };
+        process_recording(&config(), &recording, &mut io).unwrap();
+        assert_eq!(io.clean_up_requests, vec![false, true]);
+        assert_eq!(io.selections, vec![None, Some("Quarterly report".to_string())]);
+        assert_eq!(io.delivered, vec!["Back online.", "Cleaned up."]);
+    }
+
+    #[test]
+    fn test_declined_upload_is_dropped_quietly() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut io = StubIo::new(Some("Hello."), None);
+        io.retries = Some(TranscriptionQueue::new(1, false, dir.path()));
+        io.declined = true;
+
+        assert!(process_recording(&config(), &recording(false), &mut io).unwrap().is_none());
+        assert!(io.retries.as_ref().unwrap().is_empty());
+        assert!(io.delivered.is_empty());
+    }
+
+    #[test]
+    fn test_delivery_uses_the_insert_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let received = dir.path().join("received.txt");
+        let mut config = config();
+        config.output.mode = OutputMode::Type;
+        config.output.insert_command = Some(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "cat > \"$0\"".to_string(),
+            received.to_string_lossy().into_owned(),
+        ]);
+        let mut output = RecordingOutput::default();
+
+        deliver_with(&config, "Hello", &mut TargetWindow::Unset, &FocusedClass("emacs"), &mut output).unwrap();
+        assert_eq!(std::fs::read_to_string(&received).unwrap(), "Hello");
+        assert!(output.typed.is_empty() && output.copied.is_empty());
+
+        config.output.insert_command = Some(vec!["sh".to_string(), "-c".to_string(), "exit 3".to_string()]);
+        deliver_with(&config, "Hello", &mut TargetWindow::Unset, &FocusedClass("emacs"), &mut output).unwrap();
+        assert_eq!(output.copied, vec!["Hello"]);
+        assert!(output.typed.is_empty());
+    }
+
+    #[test]
+    fn test_delivered_digits_are_grouped() {
+        let mut config = config();
+        config.transcription.digit_grouping = vec!["###-###-####".to_string()];
+        let mut io = StubIo::new(Some("Call 555 123 4567 at 9."), None);
+
+        let result = process_recording(&config, &recording(false), &mut io).unwrap().unwrap();
+        assert_eq!(io.delivered, vec!["Call 555-123-4567 at 9."]);
+        assert_eq!(result.text, "Call 555-123-4567 at 9.");
+    }
+
+    #[test]
+    fn test_low_disk_keeps_the_recording_in_memory_or_refuses_it() {
+        let mut config = config();
+        // Would otherwise be written to a temporary WAV for the command
+        config.transcription.command = Some(vec!["whisper-cli".to_string()]);
+        config.general.min_free_disk_mb = u64::MAX;
+
+        config.general.low_disk_action = crate::config::LowDiskAction::MemoryOnly;
+        let mut io = StubIo::new(Some("Still here."), None);
+        process_recording(&config, &recording(false), &mut io).unwrap();
+        assert_eq!(io.delivered, vec!["Still here."]);
+
+        config.general.low_disk_action = crate::config::LowDiskAction::Abort;
+        let mut io = StubIo::new(Some("Still here."), None);
+        let err = process_recording(&config, &recording(false), &mut io).unwrap_err();
+        assert!(err.to_string().starts_with("Not enough free disk space"));
+        assert_eq!(io.transcribe_calls, 0);
+    }
+
+    #[test]
+    fn test_captured_selection_is_passed_to_post_processing() {
+        let recording = FinishedRecording {
+            selection: Some("The quarterly numbers".to_string()),
+            ..recording(false)
+        };
+        let mut io = StubIo::new(Some("continue this paragraph"), Some("The quarterly numbers look good."));
+
+        process_recording(&config(), &recording, &mut io).unwrap();
+        assert_eq!(io.clean_up_requests, vec![true]);
+        assert_eq!(io.selections, vec![Some("The quarterly numbers".to_string())]);
+        assert_eq!(io.delivered, vec!["The quarterly numbers look good."]);
+    }
+
+    #[test]
+    fn test_hallucinated_transcription_is_discarded() {
+        let mut io = StubIo::new(Some("Thanks for watching!"), Some("Thanks for watching!"));
+        assert_eq!(process_recording(&config(), &recording(true), &mut io).unwrap(), None);
+        assert!(io.clean_up_requests.is_empty());
+        assert!(io.delivered.is_empty());
+
+        let mut io = StubIo::new(Some("Thanks for watching! The demo starts now."), None);
+        process_recording(&config(), &recording(false), &mut io).unwrap().unwrap();
+        assert_eq!(io.delivered, vec!["Thanks for watching! The demo starts now."]);
+    }
+
+    #[test]
+    fn test_each_recording_is_added_to_the_analytics() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("analytics.db");
+        let mut config = config();
+        config.analytics.db_path = Some(db_path.to_string_lossy().into_owned());
+        let mut io = StubIo::new(Some("hello there world"), None);
+
+        process_recording(&config, &recording(false), &mut io).unwrap();
+        let totals = crate::analytics::AnalyticsStore::open(&db_path).unwrap().daily_totals().unwrap();
+        assert_eq!(totals.len(), 1);
+        assert_eq!(totals[0].recordings, 1);
+        assert_eq!(totals[0].words, 3);
+        assert!((totals[0].audio_secs - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_homophones_are_corrected_before_post_processing() {
+        let mut config = config();
+        config.transcription.homophones = vec![crate::config::HomophoneRule {
+            from: "rust".to_string(),
+            to: "Rust".to_string(),
+            before: None,
+            after: Some(r"\s+crate".to_string()),
+        }];
+        let mut io = StubIo::new(Some("this rust crate has no rust"), Some("This Rust crate has no rust."));
+
+        let result = process_recording(&config, &recording(true), &mut io).unwrap().unwrap();
+        assert_eq!(io.llm_inputs[0].0, "this Rust crate has no rust");
+        assert_eq!(result.raw_text, "this rust crate has no rust");
+    }
+
+    #[test]
+    fn test_post_processing_changes_are_recorded() {
+        let mut config = config();
+        config.llm.record_diff = true;
+        let mut io = StubIo::new(Some("um send the report"), Some("Send the report"));
+
+        let result = process_recording(&config, &recording(true), &mut io).unwrap().unwrap();
+        assert_eq!(
+            result.diff.unwrap().changes,
+            vec![crate::diff::WordChange::Changed {
+                from: "um send".to_string(),
+                to: "Send".to_string(),
+            }]
+        );
+
+        let result = process_recording(&config, &recording(false), &mut io).unwrap().unwrap();
+        assert_eq!(result.diff, None);
+    }
+
+    #[test]
+    fn test_dictation_is_split_into_segments() {
+        let mut config = config();
+        config.transcription.segment_delimiter = Some("next field".to_string());
+        let dictation = "Jane Doe, next field, jane@example.com. Next field 555 1234";
+
+        let mut io = StubIo::new(Some(dictation), None);
+        process_recording(&config, &recording(false), &mut io).unwrap();
+        assert_eq!(io.delivered, vec!["Jane Doe\tjane@example.com\t555 1234"]);
+        assert!(io.queued_segments.is_empty());
+
+        config.output.segment_output = crate::config::SegmentOutput::Sequential;
+        let mut io = StubIo::new(Some(dictation), None);
+        process_recording(&config, &recording(false), &mut io).unwrap();
+        assert_eq!(io.delivered, vec!["Jane Doe"]);
+        assert_eq!(io.queued_segments, vec!["jane@example.com", "555 1234"]);
+    }
+
+    #[test]
+    fn test_long_recording_is_transcribed_in_chunks() {
+        let mut config = config();
+        config.transcription.chunk_secs = 1;
+        let recording = FinishedRecording {
+            samples: vec![0; 40000],
+            ..recording(false)
+        };
+
+        let mut io = StubIo::new(Some("hello"), None);
+        let result = process_recording(&config, &recording, &mut io).unwrap().unwrap();
+        assert_eq!(io.transcribe_calls, 3);
+        assert_eq!(result.text, "hello hello hello");
+
+        config.transcription.chunk_secs = 0;
+        let mut io = StubIo::new(Some("hello"), None);
+        process_recording(&config, &recording, &mut io).unwrap();
+        assert_eq!(io.transcribe_calls, 1);
+    }
+
+    #[test]
+    fn test_only_hosted_uploads_are_resampled() {
+        let mut config = config();
+        config.transcription.resample_for_hosted_only = true;
+        let recording = FinishedRecording {
+            samples: vec![0; 4800],
+            spec: WavSpec {
+                sample_rate: 48000,
+                ..recording(false).spec
+            },
+            ..recording(false)
+        };
+
+        let mut io = StubIo::new(Some("hello"), None);
+        process_recording(&config, &recording, &mut io).unwrap();
+        assert_eq!(io.upload_rates, vec![48000]);
+
+        let mut io = StubIo::new(Some("hello"), None);
+        io.available = EndpointKind::Hosted;
+        process_recording(&config, &recording, &mut io).unwrap();
+        assert_eq!(io.upload_rates, vec![16000]);
+
+        let forced = FinishedRecording {
+            forced: Some(EndpointKind::Hosted),
+            ..recording
+        };
+        let mut io = StubIo::new(Some("hello"), None);
+        process_recording(&config, &forced, &mut io).unwrap();
+        assert_eq!(io.upload_rates, vec![16000]);
+    }
+
+    #[test]
+    fn test_per_endpoint_recordings_are_still_chunked() {
+        let mut config = config();
+        config.transcription.resample_for_hosted_only = true;
+        config.transcription.chunk_secs = 1;
+        let recording = FinishedRecording {
+            samples: vec![0; 120000],
+            spec: WavSpec {
+                sample_rate: 48000,
+                ..recording(false).spec
+            },
+            ..recording(false)
+        };
+
+        let mut io = StubIo::new(Some("hello"), None);
+        let result = process_recording(&config, &recording, &mut io).unwrap().unwrap();
+        assert_eq!(io.upload_rates, vec![48000; 3]);
+        assert_eq!(result.text, "hello hello hello");
+
+        let mut io = StubIo::new(Some("hello"), None);
+        io.available = EndpointKind::Hosted;
+        process_recording(&config, &recording, &mut io).unwrap();
+        assert_eq!(io.upload_rates, vec![16000; 3]);
+    }
+
+    #[test]
+    fn test_cancelled_recording_is_never_uploaded() {
+        let recording = FinishedRecording {
+            cancelled: true,
+            ..recording(true)
+        };
+
+        let mut io = StubIo::new(Some("um hello there"), Some("Hello there."));
+        assert_eq!(process_recording(&config(), &recording, &mut io).unwrap(), None);
+        assert_eq!(io.transcribe_calls, 0);
+        assert!(io.clean_up_requests.is_empty());
+        assert!(io.delivered.is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::{Result, Context};
+use log::{info, warn};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::audio::CapturedAudio;
+
+/// Recordings waiting to be transcribed, kept within `general.max_memory_mb`.
+/// Once the in-memory audio would exceed the budget, new recordings are spilled
+/// to `spill_dir`, or refused when `privacy.memory_only` forbids touching disk.
+pub struct TranscriptionQueue {
+    items: VecDeque<CapturedAudio>,
+    budget_bytes: Option<usize>,
+    memory_only: bool,
+    spill_dir: PathBuf,
+    spilled: usize,
+}
+
+impl TranscriptionQueue {
+    pub fn new(max_memory_mb: u64, memory_only: bool, spill_dir: impl Into<PathBuf>) -> Self {
+        TranscriptionQueue {
+            items: VecDeque::new(),
+            budget_bytes: (max_memory_mb > 0).then(|| max_memory_mb as usize * 1024 * 1024),
+            memory_only,
+            spill_dir: spill_dir.into(),
+            spilled: 0,
+        }
+    }
+
+    /// Bytes of audio currently held in memory
+    pub fn memory_used(&self) -> usize {
+        self.items
+            .iter()
+            .map(|item| match item {
+                CapturedAudio::Memory(wav) => wav.len(),
+                CapturedAudio::File(_) => 0,
+            })
+            .sum()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Queues a recording, spilling or refusing it if it would exceed the budget
+    pub fn push(&mut self, audio: CapturedAudio) -> Result<()> {
+        let wav = match audio {
+            CapturedAudio::Memory(wav) => wav,
+            file => {
+                self.items.push_back(file);
+                return Ok(());
+            }
+        };
+
+        let over_budget = self
+            .budget_bytes
+            .map(|budget| self.memory_used() + wav.len() > budget)
+            .unwrap_or(false);
+        if !over_budget {
+            self.items.push_back(CapturedAudio::Memory(wav));
+            return Ok(());
+        }
+
+        if self.memory_only {
+            warn!("Memory budget reached; refusing new recording until the queue drains");
+            return Err(anyhow::anyhow!(
+                "general.max_memory_mb reached and privacy.memory_only forbids spilling to disk; wait for pending transcriptions to finish"
+            ));
+        }
+
+        self.spilled += 1;
+        let path = self.spill_dir.join(format!("rusty_scribe_spill_{}.wav", self.spilled));
+        fs::write(&path, &wav)
+            .with_context(|| format!("Failed to spill recording to {}", path.display()))?;
+        info!("Memory budget reached; spilled recording to {}", path.display());
+        self.items.push_back(CapturedAudio::File(path));
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<CapturedAudio> {
+        self.items.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const MB: usize = 1024 * 1024;
+
+    #[test]
+    fn test_exceeding_budget_spills_to_disk() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let mut queue = TranscriptionQueue::new(1, false, dir.path());
+
+        queue.push(CapturedAudio::Memory(vec![0; MB / 2])).unwrap();
+        queue.push(CapturedAudio::Memory(vec![1; MB / 2 + 1])).unwrap();
+
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.memory_used(), MB / 2);
+        assert!(matches!(queue.pop(), Some(CapturedAudio::Memory(_))));
+        match queue.pop() {
+            Some(CapturedAudio::File(path)) => assert_eq!(fs::read(path).unwrap().len(), MB / 2 + 1),
+            other => panic!("expected a spilled file, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_exceeding_budget_in_memory_only_mode_blocks() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let mut queue = TranscriptionQueue::new(1, true, dir.path());
+
+        queue.push(CapturedAudio::Memory(vec![0; MB])).unwrap();
+        assert!(queue.push(CapturedAudio::Memory(vec![0; 1])).is_err());
+
+        assert_eq!(queue.len(), 1);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        // Draining the queue makes room again
+        queue.pop();
+        assert!(queue.push(CapturedAudio::Memory(vec![0; 1])).is_ok());
+    }
+
+    #[test]
+    fn test_unlimited_budget_never_spills() {
+        let dir = tempdir().expect("Failed to create temp dir");
+        let mut queue = TranscriptionQueue::new(0, false, dir.path());
+        queue.push(CapturedAudio::Memory(vec![0; 2 * MB])).unwrap();
+        assert_eq!(queue.memory_used(), 2 * MB);
+    }
+}
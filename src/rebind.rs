@@ -0,0 +1,120 @@
+use anyhow::{Result, Context};
+use log::info;
+use std::fs;
+use std::path::Path;
+
+use crate::hotkeys::{capture_chord, keys_to_hotkey_string};
+
+/// `[hotkeys]` entries that `rusty-scribe rebind <action>` accepts
+pub const REBINDABLE_ACTIONS: [&str; 8] = [
+    "recording",
+    "post_processing_modifier",
+    "set_target_window",
+    "capture_selection",
+    "force_hosted",
+    "force_local",
+    "clear_clipboard",
+    "toggle_post_processing",
+];
+
+fn is_section_header(line: &str) -> bool {
+    line.trim_start().starts_with('[')
+}
+
+fn is_key_line(line: &str, key: &str) -> bool {
+    line.trim_start()
+        .strip_prefix(key)
+        .is_some_and(|rest| rest.trim_start().starts_with('='))
+}
+
+/// Sets `action = "hotkey"` in the `[hotkeys]` table of a config file, editing
+/// only that line so comments and formatting elsewhere survive
+pub fn set_hotkey_in_config(content: &str, action: &str, hotkey: &str) -> Result<String> {
+    if !REBINDABLE_ACTIONS.contains(&action) {
+        return Err(anyhow::anyhow!(
+            "Unknown hotkey action '{}'. Expected one of: {}",
+            action,
+            REBINDABLE_ACTIONS.join(", ")
+        ));
+    }
+    let entry = format!("{} = \"{}\"", action, hotkey);
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    match lines.iter().position(|l| l.trim() == "[hotkeys]") {
+        Some(header) => {
+            let end = lines[header + 1..]
+                .iter()
+                .position(|l| is_section_header(l))
+                .map_or(lines.len(), |i| header + 1 + i);
+            match (header + 1..end).find(|&i| is_key_line(&lines[i], action)) {
+                Some(i) => lines[i] = entry,
+                None => lines.insert(header + 1, entry),
+            }
+        }
+        None => {
+            lines.push(String::new());
+            lines.push("[hotkeys]".to_string());
+            lines.push(entry);
+        }
+    }
+
+    let mut updated = lines.join("\n");
+    updated.push('\n');
+    Ok(updated)
+}
+
+/// Interactive `rebind` command: waits for a chord and writes it to the config
+pub fn rebind(config_path: &Path, action: &str) -> Result<()> {
+    let content = fs::read_to_string(config_path)
+        .with_context(|| format!("Unable to read {}", config_path.display()))?;
+    // Validate the action before waiting on the keyboard
+    set_hotkey_in_config(&content, action, "")?;
+
+    println!("Press the new hotkey for '{}'...", action);
+    let hotkey = keys_to_hotkey_string(&capture_chord()?);
+    let updated = set_hotkey_in_config(&content, action, &hotkey)?;
+    fs::write(config_path, updated).with_context(|| format!("Unable to write {}", config_path.display()))?;
+
+    info!("Bound {} to {}", action, hotkey);
+    println!("{} is now {}", action, hotkey);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_hotkey_replaces_only_target_line() {
+        let content = "\
+# my config
+[hotkeys]
+recording = \"Shift+Space\" # push to talk
+post_processing_modifier = \"Control\"
+
+[audio]
+recording = \"not a hotkey\"
+";
+        let updated = set_hotkey_in_config(content, "recording", "Control+Alt+Space").unwrap();
+        assert_eq!(
+            updated,
+            "\
+# my config
+[hotkeys]
+recording = \"Control+Alt+Space\"
+post_processing_modifier = \"Control\"
+
+[audio]
+recording = \"not a hotkey\"
+"
+        );
+    }
+
+    #[test]
+    fn test_set_hotkey_inserts_missing_entry() {
+        let updated = set_hotkey_in_config("[hotkeys]\nrecording = \"Space\"\n", "force_local", "Alt+Enter").unwrap();
+        assert_eq!(updated, "[hotkeys]\nforce_local = \"Alt+Enter\"\nrecording = \"Space\"\n");
+
+        assert!(set_hotkey_in_config("", "teleport", "Space").is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::api::{EndpointKind, Transcription};
+use crate::diff::WordDiff;
+
+/// Outcome of a single recording, shared by every output sink
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TranscriptionResult {
+    /// RFC 3339 time at which the transcription finished
+    pub timestamp: String,
+    /// Final text after any post-processing
+    pub text: String,
+    /// Text exactly as returned by Whisper
+    pub raw_text: String,
+    pub post_processed: bool,
+    pub language: Option<String>,
+    /// Whisper endpoint that produced the text, after any fallback
+    pub endpoint_used: EndpointKind,
+    pub model_used: String,
+    /// Output of the optional `llm.summarize` pass
+    pub summary: Option<String>,
+    /// What post-processing changed, when `llm.record_diff` is on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<WordDiff>,
+}
+
+impl TranscriptionResult {
+    pub fn new(
+        transcription: &Transcription,
+        text: &str,
+        post_processed: bool,
+        language: Option<&str>,
+    ) -> Self {
+        TranscriptionResult {
+            timestamp: Utc::now().to_rfc3339(),
+            text: text.to_string(),
+            raw_text: transcription.text.clone(),
+            post_processed,
+            language: language.map(str::to_string),
+            endpoint_used: transcription.endpoint_used,
+            model_used: transcription.model_used.clone(),
+            summary: None,
+            diff: None,
+        }
+    }
+
+    /// The result as one line of JSON, as printed by `once --json`
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).context("Failed to serialize the transcription result")
+    }
+}
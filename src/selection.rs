@@ -0,0 +1,156 @@
+use anyhow::Result;
+use clipboard::ClipboardProvider;
+use log::{info, warn};
+use rdev::{simulate, EventType, Key};
+use std::time::Duration;
+
+/// Time for the focused app to answer the simulated copy before the clipboard is read
+pub const COPY_SETTLE: Duration = Duration::from_millis(150);
+
+/// Presses the platform copy shortcut in the focused window
+pub fn simulate_copy() -> Result<()> {
+    let modifier = if cfg!(target_os = "macos") { Key::MetaLeft } else { Key::ControlLeft };
+    for event in [
+        EventType::KeyPress(modifier),
+        EventType::KeyPress(Key::KeyC),
+        EventType::KeyRelease(Key::KeyC),
+        EventType::KeyRelease(modifier),
+    ] {
+        simulate(&event).map_err(|e| anyhow::anyhow!("Failed to simulate copy: {:?}", e))?;
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    Ok(())
+}
+
+/// Copies the current selection via `copy` and returns it, restoring whatever
+/// was on the clipboard before. The clipboard is cleared first so that an empty
+/// selection (the copy changes nothing) yields `None` rather than stale contents.
+pub fn capture_selection<C, F>(clipboard: &mut C, copy: F, settle: Duration) -> Result<Option<String>>
+where
+    C: ClipboardProvider,
+    F: FnOnce() -> Result<()>,
+{
+    let original = clipboard.get_contents().ok();
+    clipboard
+        .set_contents(String::new())
+        .map_err(|e| anyhow::anyhow!("Failed to clear clipboard: {}", e))?;
+
+    let copied = copy().map(|()| {
+        std::thread::sleep(settle);
+        clipboard.get_contents().unwrap_or_default()
+    });
+
+    if let Some(original) = original {
+        if let Err(e) = clipboard.set_contents(original) {
+            warn!("Failed to restore clipboard after capturing selection: {}", e);
+        }
+    }
+
+    let selection = copied?;
+    if selection.trim().is_empty() {
+        info!("No text selected; dictating without selection context");
+        Ok(None)
+    } else {
+        info!("Captured {} characters of selected text as context", selection.len());
+        Ok(Some(selection))
+    }
+}
+
+/// Prefixes the post-processing prompt with the captured selection, e.g. so that
+/// "continue this paragraph" knows which paragraph is meant
+pub fn prompt_with_selection(prompt: &str, selection: Option<&str>) -> String {
+    match selection.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(selection) => format!(
+            "Context (text selected by the user):\n\"\"\"\n{}\n\"\"\"\n\n{}",
+            selection, prompt
+        ),
+        None => prompt.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::error::Error;
+    use std::rc::Rc;
+
+    /// Clipboard whose contents are shared with the fake "copy" so the test can
+    /// play the part of the focused app writing its selection
+    struct FakeClipboard {
+        contents: Rc<RefCell<String>>,
+    }
+
+    impl ClipboardProvider for FakeClipboard {
+        fn new() -> std::result::Result<Self, Box<dyn Error>> {
+            Ok(FakeClipboard {
+                contents: Rc::new(RefCell::new(String::new())),
+            })
+        }
+
+        fn get_contents(&mut self) -> std::result::Result<String, Box<dyn Error>> {
+            Ok(self.contents.borrow().clone())
+        }
+
+        fn set_contents(&mut self, contents: String) -> std::result::Result<(), Box<dyn Error>> {
+            *self.contents.borrow_mut() = contents;
+            Ok(())
+        }
+    }
+
+    /// Captures with a clipboard holding `previous` while the app has `selected` highlighted.
+    /// Returns the captured selection and the clipboard contents afterwards.
+    fn capture(previous: &str, selected: &str) -> (Option<String>, String) {
+        let contents = Rc::new(RefCell::new(previous.to_string()));
+        let mut clipboard = FakeClipboard {
+            contents: Rc::clone(&contents),
+        };
+        let app_clipboard = Rc::clone(&contents);
+        let selected = selected.to_string();
+
+        let captured = capture_selection(
+            &mut clipboard,
+            move || {
+                if !selected.is_empty() {
+                    *app_clipboard.borrow_mut() = selected;
+                }
+                Ok(())
+            },
+            Duration::ZERO,
+        )
+        .unwrap();
+        let after = contents.borrow().clone();
+        (captured, after)
+    }
+
+    #[test]
+    fn test_capture_selection_restores_clipboard() {
+        let (captured, after) = capture("previous clipboard", "The quick brown fox");
+
+        assert_eq!(captured, Some("The quick brown fox".to_string()));
+        assert_eq!(after, "previous clipboard");
+    }
+
+    #[test]
+    fn test_empty_selection_is_none() {
+        let (captured, after) = capture("previous clipboard", "");
+
+        assert_eq!(captured, None);
+        assert_eq!(after, "previous clipboard");
+    }
+
+    #[test]
+    fn test_prompt_with_selection_assembles_context() {
+        let prompt = prompt_with_selection(
+            "Please clean up and format the following text:",
+            Some("  It was a dark and stormy night.\n"),
+        );
+        assert_eq!(
+            prompt,
+            "Context (text selected by the user):\n\"\"\"\nIt was a dark and stormy night.\n\"\"\"\n\nPlease clean up and format the following text:"
+        );
+
+        assert_eq!(prompt_with_selection("Clean up:", None), "Clean up:");
+        assert_eq!(prompt_with_selection("Clean up:", Some("  \n")), "Clean up:");
+    }
+}
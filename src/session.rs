@@ -0,0 +1,231 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::config::SessionSettings;
+use crate::notify::Notifier;
+
+/// Text accumulated over a dictation session, periodically saved to
+/// `session.recovery_file` so a crash loses at most the last few seconds.
+/// The file is removed when the session ends cleanly; if it is still there on
+/// the next start, `restore` picks the text back up.
+pub struct SessionBuffer {
+    text: String,
+    recovery_path: PathBuf,
+    autosave_interval: Option<Duration>,
+    autosave_chars: usize,
+    last_saved: Instant,
+    saved_len: usize,
+}
+
+impl SessionBuffer {
+    pub fn new(settings: &SessionSettings) -> Self {
+        SessionBuffer {
+            text: String::new(),
+            recovery_path: PathBuf::from(&settings.recovery_file),
+            autosave_interval: (settings.autosave_secs > 0).then(|| Duration::from_secs(settings.autosave_secs)),
+            autosave_chars: settings.autosave_chars,
+            last_saved: Instant::now(),
+            saved_len: 0,
+        }
+    }
+
+    /// Starts a session, resuming the text of one that did not end cleanly
+    pub fn restore(settings: &SessionSettings) -> Result<Self> {
+        let mut buffer = SessionBuffer::new(settings);
+        if buffer.recovery_path.exists() {
+            buffer.text = fs::read_to_string(&buffer.recovery_path).with_context(|| {
+                format!("Failed to read session recovery file at {}", buffer.recovery_path.display())
+            })?;
+            buffer.saved_len = buffer.text.len();
+            info!(
+                "Restored {} characters from an unfinished session ({})",
+                buffer.text.chars().count(),
+                buffer.recovery_path.display()
+            );
+        }
+        Ok(buffer)
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Adds a transcription to the session, saving if the throttle allows
+    pub fn append(&mut self, text: &str) {
+        self.append_at(text, Instant::now());
+    }
+
+    fn append_at(&mut self, text: &str, now: Instant) {
+        if !self.text.is_empty() && !text.is_empty() {
+            self.text.push(' ');
+        }
+        self.text.push_str(text);
+        self.autosave_at(now);
+    }
+
+    /// Saves when the interval has elapsed or enough text was added. Failures
+    /// are logged only; dictation carries on without the safety net.
+    fn autosave_at(&mut self, now: Instant) {
+        if self.text.len() == self.saved_len {
+            return;
+        }
+        let interval_due = self
+            .autosave_interval
+            .is_some_and(|interval| now.duration_since(self.last_saved) >= interval);
+        let size_due = self.autosave_chars > 0 && self.text.len() - self.saved_len >= self.autosave_chars;
+        if !(interval_due || size_due) {
+            return;
+        }
+        match self.save() {
+            Ok(()) => {
+                self.last_saved = now;
+                self.saved_len = self.text.len();
+            }
+            Err(e) => warn!("Failed to auto-save session: {:?}", e),
+        }
+    }
+
+    /// Checks the interval without new text, e.g. from a timer
+    pub fn tick(&mut self) {
+        self.autosave_at(Instant::now());
+    }
+
+    /// Writes to a temporary file first so a crash mid-write cannot leave a
+    /// truncated recovery file behind
+    fn save(&self) -> Result<()> {
+        if let Some(dir) = self.recovery_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create session directory {}", dir.display()))?;
+        }
+        let temp = self.recovery_path.with_extension("tmp");
+        fs::write(&temp, &self.text)
+            .with_context(|| format!("Failed to write session recovery file at {}", temp.display()))?;
+        fs::rename(&temp, &self.recovery_path).with_context(|| {
+            format!("Failed to move session recovery file to {}", self.recovery_path.display())
+        })
+    }
+
+    /// Ends the session cleanly, removing the recovery file, and returns its text
+    pub fn end(&mut self) -> Result<String> {
+        if self.recovery_path.exists() {
+            fs::remove_file(&self.recovery_path).with_context(|| {
+                format!("Failed to remove session recovery file at {}", self.recovery_path.display())
+            })?;
+        }
+        self.saved_len = 0;
+        Ok(std::mem::take(&mut self.text))
+    }
+}
+
+/// Hands the text of an unfinished session back to the user: it is logged,
+/// copied with `copy` and announced through `notifier`. It also stays in the
+/// session until that ends cleanly.
+pub fn announce_recovered<F>(session: &SessionBuffer, notifier: &dyn Notifier, copy: F)
+where
+    F: FnOnce(&str) -> Result<()>,
+{
+    let text = session.text();
+    if text.is_empty() {
+        return;
+    }
+    info!("Recovered session text: {}", text);
+    let chars = text.chars().count();
+    let body = match copy(text) {
+        Ok(()) => format!("{} characters from an unfinished session were copied to the clipboard", chars),
+        Err(e) => {
+            warn!("Failed to copy the recovered session text: {:?}", e);
+            format!("{} characters from an unfinished session are in the log", chars)
+        }
+    };
+    notifier.notify("Session recovered", &body);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use tempfile::TempDir;
+
+    fn settings(dir: &TempDir, autosave_secs: u64, autosave_chars: usize) -> SessionSettings {
+        SessionSettings {
+            autosave_secs,
+            autosave_chars,
+            recovery_file: dir.path().join("rusty-scribe").join("session.txt").to_str().unwrap().to_string(),
+        }
+    }
+
+    #[test]
+    fn test_autosave_on_interval_and_restore_after_crash() {
+        let dir = TempDir::new().unwrap();
+        let settings = settings(&dir, 30, 0);
+        let recovery = PathBuf::from(&settings.recovery_file);
+
+        let mut session = SessionBuffer::new(&settings);
+        let start = session.last_saved;
+        session.append_at("First thought.", start + Duration::from_secs(5));
+        assert!(!recovery.exists(), "saved before the interval elapsed");
+
+        session.append_at("Second thought.", start + Duration::from_secs(31));
+        assert_eq!(fs::read_to_string(&recovery).unwrap(), "First thought. Second thought.");
+
+        session.append_at("Lost in the crash.", start + Duration::from_secs(40));
+        drop(session);
+
+        let mut restored = SessionBuffer::restore(&settings).unwrap();
+        assert_eq!(restored.text(), "First thought. Second thought.");
+        assert_eq!(restored.end().unwrap(), "First thought. Second thought.");
+        assert!(!recovery.exists(), "clean end keeps the recovery file");
+        assert_eq!(SessionBuffer::restore(&settings).unwrap().text(), "");
+    }
+
+    #[derive(Default)]
+    struct RecordingNotifier {
+        sent: RefCell<Vec<String>>,
+    }
+
+    impl Notifier for RecordingNotifier {
+        fn notify(&self, _title: &str, body: &str) {
+            self.sent.borrow_mut().push(body.to_string());
+        }
+    }
+
+    #[test]
+    fn test_recovered_text_is_copied_and_announced() {
+        let dir = TempDir::new().unwrap();
+        let settings = settings(&dir, 0, 1);
+        SessionBuffer::new(&settings).append("Unsaved draft.");
+
+        let restored = SessionBuffer::restore(&settings).unwrap();
+        let notifier = RecordingNotifier::default();
+        let copied = RefCell::new(String::new());
+        announce_recovered(&restored, &notifier, |text| {
+            copied.replace(text.to_string());
+            Ok(())
+        });
+        assert_eq!(*copied.borrow(), "Unsaved draft.");
+        assert_eq!(
+            *notifier.sent.borrow(),
+            vec!["14 characters from an unfinished session were copied to the clipboard"]
+        );
+
+        // A clean start has nothing to announce
+        let notifier = RecordingNotifier::default();
+        announce_recovered(&SessionBuffer::new(&settings), &notifier, |_| Ok(()));
+        assert!(notifier.sent.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_autosave_after_enough_characters() {
+        let dir = TempDir::new().unwrap();
+        let settings = settings(&dir, 0, 10);
+
+        let mut session = SessionBuffer::new(&settings);
+        session.append("short");
+        assert!(!PathBuf::from(&settings.recovery_file).exists());
+        session.append("and longer");
+        assert_eq!(fs::read_to_string(&settings.recovery_file).unwrap(), "short and longer");
+    }
+}
@@ -0,0 +1,210 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::io::{BufRead, Read};
+use std::str;
+
+/// Reassembles UTF-8 text from byte frames that may split a multi-byte
+/// character. Complete characters are returned as soon as they arrive;
+/// a trailing incomplete sequence is held until the next frame.
+#[derive(Debug, Default)]
+pub struct Utf8Reassembler {
+    pending: Vec<u8>,
+}
+
+impl Utf8Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a frame and returns all text that is now complete. Bytes that can
+    /// never form valid UTF-8 are replaced with U+FFFD.
+    pub fn push(&mut self, frame: &[u8]) -> String {
+        self.pending.extend_from_slice(frame);
+
+        let mut text = String::new();
+        let mut rest: &[u8] = &self.pending;
+        loop {
+            match str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    rest = &[];
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    text.push_str(str::from_utf8(valid).expect("prefix was validated"));
+                    match e.error_len() {
+                        Some(bad) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after[bad..];
+                        }
+                        // Incomplete sequence at the end: wait for the next frame
+                        None => {
+                            rest = after;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.pending = rest.to_vec();
+        text
+    }
+
+    /// Flushes any leftover bytes at end of stream
+    pub fn finish(&mut self) -> String {
+        let leftover = std::mem::take(&mut self.pending);
+        String::from_utf8_lossy(&leftover).into_owned()
+    }
+}
+
+/// Accumulates a streamed transcript and reports the growing partial to a
+/// callback, only ever with whole characters
+pub struct PartialTranscript<F: FnMut(&str)> {
+    decoder: Utf8Reassembler,
+    text: String,
+    on_partial: F,
+}
+
+impl<F: FnMut(&str)> PartialTranscript<F> {
+    pub fn new(on_partial: F) -> Self {
+        PartialTranscript {
+            decoder: Utf8Reassembler::new(),
+            text: String::new(),
+            on_partial,
+        }
+    }
+
+    pub fn on_frame(&mut self, frame: &[u8]) {
+        let complete = self.decoder.push(frame);
+        if !complete.is_empty() {
+            self.text.push_str(&complete);
+            (self.on_partial)(&self.text);
+        }
+    }
+
+    /// Ends the stream and returns the full transcript
+    pub fn finish(mut self) -> String {
+        let rest = self.decoder.finish();
+        if !rest.is_empty() {
+            self.text.push_str(&rest);
+            (self.on_partial)(&self.text);
+        }
+        self.text
+    }
+}
+
+/// Reads a transcription streamed as chunked plain text, calling `on_chunk`
+/// with each piece as it arrives. Chunks may end inside a multi-byte
+/// character; `on_chunk` only ever sees whole characters.
+pub fn read_text_stream<R: Read>(mut reader: R, mut on_chunk: impl FnMut(&str)) -> Result<String> {
+    let mut emitted = 0;
+    let mut transcript = PartialTranscript::new(|partial: &str| {
+        on_chunk(&partial[emitted..]);
+        emitted = partial.len();
+    });
+    let mut frame = [0; 4096];
+    loop {
+        let read = reader.read(&mut frame).context("Failed to read transcription stream")?;
+        if read == 0 {
+            break;
+        }
+        transcript.on_frame(&frame[..read]);
+    }
+    Ok(transcript.finish())
+}
+
+/// Event of a streamed transcription (`stream=true`), sent as the `data:` of
+/// a Server-Sent Event
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type")]
+enum TranscriptEvent {
+    #[serde(rename = "transcript.text.delta")]
+    Delta { delta: String },
+    #[serde(rename = "transcript.text.done")]
+    Done { text: String },
+    #[serde(other)]
+    Other,
+}
+
+/// Reads a Server-Sent Events transcription stream, calling `on_delta` with
+/// each piece of text as it arrives. Returns the text of the final `done`
+/// event, or the deltas joined if the stream ends without one.
+pub fn read_transcript_events<R: BufRead>(reader: R, mut on_delta: impl FnMut(&str)) -> Result<String> {
+    let mut assembled = String::new();
+    let mut data = String::new();
+    // A trailing empty line dispatches an event still pending when the stream ends
+    for line in reader.lines().chain(std::iter::once(Ok(String::new()))) {
+        let line = line.context("Failed to read transcription stream")?;
+        if let Some(value) = line.strip_prefix("data:") {
+            if !data.is_empty() {
+                data.push('\n');
+            }
+            data.push_str(value.strip_prefix(' ').unwrap_or(value));
+            continue;
+        }
+        // Other fields (event:, id:, comments) carry nothing we use
+        if !line.is_empty() || data.is_empty() {
+            continue;
+        }
+        let payload = std::mem::take(&mut data);
+        if payload == "[DONE]" {
+            break;
+        }
+        let event: TranscriptEvent = serde_json::from_str(&payload)
+            .with_context(|| format!("Failed to parse transcription event: {}", payload))?;
+        match event {
+            TranscriptEvent::Delta { delta } => {
+                on_delta(&delta);
+                assembled.push_str(&delta);
+            }
+            TranscriptEvent::Done { text } => return Ok(text),
+            TranscriptEvent::Other => {}
+        }
+    }
+    Ok(assembled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multibyte_character_split_across_frames() {
+        let bytes = "Grüße 👋".as_bytes();
+        // "ü" is two bytes; split inside it, and inside the four-byte emoji
+        let ue = bytes.iter().position(|&b| b == 0xC3).unwrap();
+        let wave = bytes.iter().position(|&b| b == 0xF0).unwrap();
+        let frames = [&bytes[..ue + 1], &bytes[ue + 1..wave + 2], &bytes[wave + 2..]];
+
+        let mut partials = Vec::new();
+        let mut transcript = PartialTranscript::new(|partial: &str| partials.push(partial.to_string()));
+        for frame in frames {
+            transcript.on_frame(frame);
+        }
+        let text = transcript.finish();
+
+        assert_eq!(text, "Grüße 👋");
+        assert_eq!(partials, vec!["Gr", "Grüße ", "Grüße 👋"]);
+    }
+
+    #[test]
+    fn test_text_stream_reports_whole_characters() {
+        // Each read returns one frame; the "é" is split between them
+        let frames = (&b"caf\xC3"[..]).chain(&b"\xA9 ok"[..]);
+
+        let mut chunks = Vec::new();
+        let text = read_text_stream(frames, |chunk| chunks.push(chunk.to_string())).unwrap();
+
+        assert_eq!(chunks, vec!["caf", "é ok"]);
+        assert_eq!(text, "café ok");
+    }
+
+    #[test]
+    fn test_invalid_bytes_are_replaced_not_buffered() {
+        let mut decoder = Utf8Reassembler::new();
+        assert_eq!(decoder.push(b"ok \xFF then"), "ok \u{FFFD} then");
+        assert_eq!(decoder.push(b" \xE2\x82"), " ");
+        assert_eq!(decoder.finish(), "\u{FFFD}");
+    }
+}
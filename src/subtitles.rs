@@ -0,0 +1,175 @@
+use crate::api::{TimedTranscription, Word};
+
+/// Longest subtitle line built from word timings, a common broadcast limit
+const MAX_CUE_CHARS: usize = 42;
+
+/// A pause this long between words starts a new subtitle
+const CUE_GAP_SECS: f64 = 1.0;
+
+/// One subtitle: text shown from `start` to `end`, in seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+fn ends_sentence(word: &str) -> bool {
+    word.ends_with(['.', '!', '?'])
+}
+
+/// Groups timed words into subtitles, breaking at sentence ends, at long
+/// pauses and before a line would exceed `MAX_CUE_CHARS`
+fn cues_from_words(words: &[Word]) -> Vec<Cue> {
+    let mut cues: Vec<Cue> = Vec::new();
+    let mut open = false;
+    for word in words {
+        let text = word.word.trim();
+        if text.is_empty() {
+            continue;
+        }
+        match cues.last_mut() {
+            Some(cue)
+                if open
+                    && word.start - cue.end < CUE_GAP_SECS
+                    && cue.text.chars().count() + 1 + text.chars().count() <= MAX_CUE_CHARS =>
+            {
+                cue.text.push(' ');
+                cue.text.push_str(text);
+                cue.end = word.end;
+            }
+            _ => cues.push(Cue {
+                start: word.start,
+                end: word.end,
+                text: text.to_string(),
+            }),
+        }
+        open = !ends_sentence(text);
+    }
+    cues
+}
+
+/// Subtitles for a transcription of a clip `clip_secs` long. Word timings
+/// are grouped into lines; segment timings are used as they are. Without any
+/// timings the whole text becomes one subtitle spanning the clip.
+pub fn cues(timed: &TimedTranscription, clip_secs: f64) -> Vec<Cue> {
+    if timed.words.is_empty() {
+        let text = timed.text.trim();
+        if text.is_empty() {
+            return Vec::new();
+        }
+        return vec![Cue {
+            start: 0.0,
+            end: clip_secs,
+            text: text.to_string(),
+        }];
+    }
+    if timed.word_level {
+        return cues_from_words(&timed.words);
+    }
+    timed
+        .words
+        .iter()
+        .filter(|segment| !segment.word.trim().is_empty())
+        .map(|segment| Cue {
+            start: segment.start,
+            end: segment.end,
+            text: segment.word.trim().to_string(),
+        })
+        .collect()
+}
+
+/// `HH:MM:SS,mmm`, as SRT writes times
+fn srt_time(secs: f64) -> String {
+    let millis = (secs.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Formats subtitles as an SRT file: numbered blocks separated by blank lines
+pub fn to_srt(cues: &[Cue]) -> String {
+    cues.iter()
+        .enumerate()
+        .map(|(i, cue)| format!("{}\n{} --> {}\n{}\n", i + 1, srt_time(cue.start), srt_time(cue.end), cue.text))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(word: &str, start: f64, end: f64) -> Word {
+        Word {
+            word: word.to_string(),
+            start,
+            end,
+        }
+    }
+
+    fn timed(words: Vec<Word>, word_level: bool) -> TimedTranscription {
+        TimedTranscription {
+            text: words.iter().map(|w| w.word.as_str()).collect::<Vec<_>>().join(" "),
+            word_level,
+            words,
+        }
+    }
+
+    #[test]
+    fn test_words_grouped_into_numbered_srt_blocks() {
+        let words = vec![
+            word("Hello", 0.0, 0.4),
+            word("there.", 0.5, 0.9),
+            word("This", 1.2, 1.4),
+            word("is", 1.45, 1.6),
+            word("a", 1.65, 1.7),
+            word("test", 1.75, 2.1),
+            // A long pause starts a new line even mid-sentence
+            word("of", 3.5, 3.6),
+            word("subtitles", 3.65, 4.25),
+        ];
+
+        let srt = to_srt(&cues(&timed(words, true), 5.0));
+        assert_eq!(
+            srt,
+            "1\n00:00:00,000 --> 00:00:00,900\nHello there.\n\n\
+             2\n00:00:01,200 --> 00:00:02,100\nThis is a test\n\n\
+             3\n00:00:03,500 --> 00:00:04,250\nof subtitles\n"
+        );
+    }
+
+    #[test]
+    fn test_long_lines_are_split() {
+        let words: Vec<Word> = (0..12).map(|i| word("subtitle", i as f64 * 0.5, i as f64 * 0.5 + 0.4)).collect();
+        let cues = cues(&timed(words, true), 6.0);
+        assert!(cues.iter().all(|cue| cue.text.chars().count() <= MAX_CUE_CHARS));
+        assert_eq!(cues.len(), 3);
+        assert_eq!(cues[1].start, 2.0);
+    }
+
+    #[test]
+    fn test_segment_timings_are_kept_whole() {
+        let segments = vec![word("First segment.", 0.0, 2.0), word("Second one", 2.5, 4.0)];
+        let cues = cues(&timed(segments, false), 4.0);
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[1].text, "Second one");
+    }
+
+    #[test]
+    fn test_no_timings_gives_one_block_spanning_the_clip() {
+        let timed = TimedTranscription {
+            text: " Hello world ".to_string(),
+            word_level: false,
+            words: Vec::new(),
+        };
+        assert_eq!(
+            to_srt(&cues(&timed, 3723.5)),
+            "1\n00:00:00,000 --> 01:02:03,500\nHello world\n"
+        );
+    }
+}
@@ -0,0 +1,399 @@
+use anyhow::{Result, Context};
+use log::warn;
+use regex::Regex;
+
+use crate::config::HomophoneRule;
+
+/// Transcription split into a spoken instruction preamble and the remaining text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preamble {
+    pub instruction: Option<String>,
+    pub text: String,
+}
+
+/// Compiles `transcription.instruction_prefix_regex`, anchoring it to the start of the text
+pub fn instruction_regex(pattern: &str) -> Result<Regex> {
+    Regex::new(&format!("^(?:{})", pattern))
+        .with_context(|| format!("Invalid transcription.instruction_prefix_regex '{}'", pattern))
+}
+
+/// Detects a spoken instruction like "as a tweet:" at the start of the text and
+/// separates it from the dictation. The first capture group is used as the
+/// instruction when present, otherwise the whole match minus trailing punctuation.
+pub fn split_instruction(text: &str, pattern: &Regex) -> Preamble {
+    let trimmed = text.trim_start();
+    let captures = match pattern.captures(trimmed) {
+        Some(captures) => captures,
+        None => {
+            return Preamble {
+                instruction: None,
+                text: text.to_string(),
+            }
+        }
+    };
+
+    let whole = captures.get(0).expect("capture group 0 always exists");
+    let instruction = captures
+        .get(1)
+        .unwrap_or(whole)
+        .as_str()
+        .trim()
+        .trim_end_matches([':', ',', '.', ';'])
+        .trim()
+        .to_string();
+
+    Preamble {
+        instruction: if instruction.is_empty() { None } else { Some(instruction) },
+        text: trimmed[whole.end()..].trim_start().to_string(),
+    }
+}
+
+/// Removes opening, closing and self-closing forms of the listed tags (matched
+/// case-insensitively), keeping their inner text. Anything that is not one of
+/// those tags, such as `x < y` or `<other>`, is left untouched.
+pub fn strip_tags(text: &str, tags: &[String]) -> String {
+    let names: Vec<String> = tags
+        .iter()
+        .map(|tag| tag.trim())
+        .filter(|tag| !tag.is_empty())
+        .map(regex::escape)
+        .collect();
+    if names.is_empty() {
+        return text.to_string();
+    }
+
+    let pattern = format!(r"(?i)</?(?:{})(?:\s[^<>]*)?/?>", names.join("|"));
+    let tag_regex = Regex::new(&pattern).expect("escaped tag names always form a valid regex");
+    tag_regex.replace_all(text, "").trim().to_string()
+}
+
+/// Regroups digit sequences (digits separated by spaces, dashes, dots or
+/// parentheses) whose digit count matches one of the `#` patterns. Sequences
+/// touching letters, currency signs or decimal separators are left alone so
+/// prices, years and version numbers keep their form.
+pub fn group_digits(text: &str, patterns: &[String]) -> String {
+    if patterns.is_empty() {
+        return text.to_string();
+    }
+    let sequence = Regex::new(r"\(?\d(?:[\d .\-]|\) ?)*\d").expect("digit sequence regex is valid");
+
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for found in sequence.find_iter(text) {
+        let before = text[..found.start()].chars().next_back();
+        let mut after = text[found.end()..].chars();
+        let next = after.next();
+        let next_next = after.next();
+
+        let glued_before = before.is_some_and(|c| c.is_alphanumeric() || "$€£¥.,".contains(c));
+        let glued_after = next.is_some_and(|c| c.is_alphanumeric() || c == '%');
+        let decimal_after = matches!(next, Some('.') | Some(','))
+            && next_next.is_some_and(|c| c.is_ascii_digit());
+        // "555.123.4567" is a phone number, "3.141592653" is not
+        let decimal_inside = found.as_str().contains('.')
+            && found
+                .as_str()
+                .split('.')
+                .any(|group| !(2..=4).contains(&group.trim().len()));
+        if glued_before || glued_after || decimal_after || decimal_inside {
+            continue;
+        }
+
+        let digits: Vec<char> = found.as_str().chars().filter(char::is_ascii_digit).collect();
+        let pattern = patterns
+            .iter()
+            .find(|p| p.chars().filter(|&c| c == '#').count() == digits.len());
+        if let Some(pattern) = pattern {
+            let mut digits = digits.into_iter();
+            let formatted: String = pattern
+                .chars()
+                .map(|c| if c == '#' { digits.next().unwrap_or(c) } else { c })
+                .collect();
+            result.push_str(&text[last..found.start()]);
+            result.push_str(&formatted);
+            last = found.end();
+        }
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+/// Lowercases and reduces text to space-separated words, keeping the dots of
+/// things like "amara.org"
+fn normalize_words(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !(c.is_alphanumeric() || c == '.' || c == '\''))
+        .map(|word| word.trim_matches(['.', '\'']))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether the transcription consists only of blocklisted phrases (possibly
+/// repeated), which is what Whisper produces for near-silent audio. A phrase
+/// inside longer, real text does not count.
+pub fn is_hallucination(text: &str, phrases: &[String]) -> bool {
+    let mut remaining = format!(" {} ", normalize_words(text));
+    if remaining.trim().is_empty() {
+        return false;
+    }
+
+    // Longest phrases first so "thank you for watching" wins over "thank you"
+    let mut phrases: Vec<String> = phrases
+        .iter()
+        .map(|p| normalize_words(p))
+        .filter(|p| !p.is_empty())
+        .collect();
+    phrases.sort_by_key(|p| std::cmp::Reverse(p.len()));
+
+    for phrase in &phrases {
+        remaining = remaining.replace(&format!(" {} ", phrase), " ");
+        // Back-to-back repeats share a space, so run once more to catch every other one
+        remaining = remaining.replace(&format!(" {} ", phrase), " ");
+    }
+    remaining.trim().is_empty()
+}
+
+/// Returns the text unless it is a hallucination, which is dropped with a warning
+pub fn discard_hallucination(text: &str, phrases: &[String]) -> Option<String> {
+    if is_hallucination(text, phrases) {
+        warn!("Discarding transcription that looks like a Whisper hallucination: {:?}", text);
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
+
+/// Applies `[[transcription.homophone]]` rules in order. Each occurrence of a
+/// rule's word is replaced only when its context guards (if any) match.
+pub fn correct_homophones(text: &str, rules: &[HomophoneRule]) -> Result<String> {
+    let mut text = text.to_string();
+    for rule in rules {
+        let word = Regex::new(&format!(r"(?i)\b{}\b", regex::escape(&rule.from)))
+            .expect("escaped word always forms a valid regex");
+        let before = rule
+            .before
+            .as_deref()
+            .map(|pattern| Regex::new(&format!("(?:{})$", pattern)))
+            .transpose()
+            .with_context(|| format!("Invalid 'before' guard for homophone '{}'", rule.from))?;
+        let after = rule
+            .after
+            .as_deref()
+            .map(|pattern| Regex::new(&format!("^(?:{})", pattern)))
+            .transpose()
+            .with_context(|| format!("Invalid 'after' guard for homophone '{}'", rule.from))?;
+
+        let mut corrected = String::with_capacity(text.len());
+        let mut last = 0;
+        for found in word.find_iter(&text) {
+            let guarded = before.as_ref().is_none_or(|re| re.is_match(&text[..found.start()]))
+                && after.as_ref().is_none_or(|re| re.is_match(&text[found.end()..]));
+            if guarded {
+                corrected.push_str(&text[last..found.start()]);
+                corrected.push_str(&rule.to);
+                last = found.end();
+            }
+        }
+        corrected.push_str(&text[last..]);
+        text = corrected;
+    }
+    Ok(text)
+}
+
+/// Splits a dictation on a spoken delimiter phrase such as "next field",
+/// matched case-insensitively as whole words. Punctuation Whisper puts around
+/// the phrase is dropped and empty segments are skipped.
+pub fn split_segments(text: &str, delimiter: &str) -> Vec<String> {
+    let words: Vec<String> = delimiter.split_whitespace().map(regex::escape).collect();
+    if words.is_empty() {
+        return vec![text.trim().to_string()];
+    }
+    let pattern = format!(r"(?i)[\s,.;:…-]*\b{}\b[\s,.;:…-]*", words.join(r"[\s,]+"));
+    let delimiter = Regex::new(&pattern).expect("escaped delimiter always forms a valid regex");
+
+    delimiter
+        .split(text)
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_instruction_with_preamble() {
+        let pattern = instruction_regex(r"(?i)^as an? ([^:]+):").unwrap();
+        let preamble = split_instruction("As a tweet: shipping the new release today", &pattern);
+
+        assert_eq!(preamble.instruction, Some("tweet".to_string()));
+        assert_eq!(preamble.text, "shipping the new release today");
+    }
+
+    #[test]
+    fn test_split_instruction_uses_whole_match_without_group() {
+        let pattern = instruction_regex(r"(?i)format as email[:,.]").unwrap();
+        let preamble = split_instruction("  Format as email, hi Bob, see you Monday", &pattern);
+
+        assert_eq!(preamble.instruction, Some("Format as email".to_string()));
+        assert_eq!(preamble.text, "hi Bob, see you Monday");
+    }
+
+    #[test]
+    fn test_split_instruction_without_preamble() {
+        let pattern = instruction_regex(r"(?i)^as an? ([^:]+):").unwrap();
+        let text = "Meeting notes: we agreed to ship as a tweet: later";
+        let preamble = split_instruction(text, &pattern);
+
+        assert_eq!(preamble.instruction, None);
+        assert_eq!(preamble.text, text);
+    }
+
+    #[test]
+    fn test_instruction_regex_rejects_invalid_pattern() {
+        assert!(instruction_regex("as a (tweet").is_err());
+    }
+
+    fn tags(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_strip_tags_removes_listed_markup() {
+        let text = r#"<speak><prosody rate="slow">Hello there.</prosody> <break time="1s"/>Bye</speak>"#;
+        assert_eq!(
+            strip_tags(text, &tags(&["speak", "prosody", "break"])),
+            "Hello there. Bye"
+        );
+        assert_eq!(strip_tags("<SPEAK>Hi</Speak>", &tags(&["speak"])), "Hi");
+    }
+
+    #[test]
+    fn test_strip_tags_leaves_plain_angle_brackets_and_other_tags() {
+        let text = "if x < y and y > z then <b>bold</b> <speaker>";
+        assert_eq!(strip_tags(text, &tags(&["speak", "b"])), "if x < y and y > z then bold <speaker>");
+        assert_eq!(strip_tags(text, &[]), text);
+    }
+
+    #[test]
+    fn test_group_digits_formats_phone_numbers() {
+        let patterns = tags(&["###-###-####"]);
+
+        assert_eq!(group_digits("Call me at 555 123 4567.", &patterns), "Call me at 555-123-4567.");
+        assert_eq!(group_digits("Call (555) 1234567 now", &patterns), "Call 555-123-4567 now");
+        assert_eq!(group_digits("5551234567", &patterns), "555-123-4567");
+        assert_eq!(group_digits("555.123.4567", &patterns), "555-123-4567");
+    }
+
+    #[test]
+    fn test_group_digits_leaves_other_numbers_alone() {
+        let patterns = tags(&["###-###-####", "#### #### #### ####"]);
+
+        for text in [
+            "It cost $1299 in 2024.",
+            "Order 12345 shipped",
+            "Version 1.2.3 is out",
+            "Pi is 3.141592653 roughly",
+            "Dial 55512345678 instead",
+            "Growth was 1234567890% this year",
+            "ID A5551234567",
+        ] {
+            assert_eq!(group_digits(text, &patterns), text);
+        }
+        assert_eq!(
+            group_digits("Card 4111 1111-1111 1111 expires", &patterns),
+            "Card 4111 1111 1111 1111 expires"
+        );
+    }
+
+    fn default_phrases() -> Vec<String> {
+        crate::config::TranscriptionSettings::default().hallucination_phrases
+    }
+
+    #[test]
+    fn test_hallucination_is_discarded() {
+        let phrases = default_phrases();
+
+        for text in [
+            "Thanks for watching!",
+            " Thank you for watching. ",
+            "Subtitles by the Amara.org community",
+            "Thank you. Thank you. Thank you.",
+            "you",
+        ] {
+            assert_eq!(discard_hallucination(text, &phrases), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_embedded_phrase_is_kept() {
+        let phrases = default_phrases();
+
+        for text in [
+            "Thanks for watching the kids yesterday, I owe you one.",
+            "Thank you for the quick review.",
+            "Could you send the report?",
+        ] {
+            assert_eq!(discard_hallucination(text, &phrases), Some(text.to_string()));
+        }
+        assert!(!is_hallucination("", &phrases));
+    }
+
+    fn rule(from: &str, to: &str, before: Option<&str>, after: Option<&str>) -> HomophoneRule {
+        HomophoneRule {
+            from: from.to_string(),
+            to: to.to_string(),
+            before: before.map(str::to_string),
+            after: after.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_correct_homophones_straight_replacement() {
+        let rules = vec![rule("rust", "Rust", None, None), rule("kubectl", "kubectl", None, None)];
+
+        assert_eq!(
+            correct_homophones("I wrote it in rust, then RUST again; trust me", &rules).unwrap(),
+            "I wrote it in Rust, then Rust again; trust me"
+        );
+    }
+
+    #[test]
+    fn test_correct_homophones_with_context_guards() {
+        let rules = vec![
+            rule("their", "there", None, Some(r"\s+(is|are|was|were)\b")),
+            rule("rust", "Rust", Some(r"\b(in|learn|write)\s+"), None),
+        ];
+
+        assert_eq!(
+            correct_homophones("Their is a bug and their code has rust on it", &rules).unwrap(),
+            "there is a bug and their code has rust on it"
+        );
+        assert_eq!(
+            correct_homophones("I want to learn rust", &rules).unwrap(),
+            "I want to learn Rust"
+        );
+    }
+
+    #[test]
+    fn test_correct_homophones_rejects_invalid_guard() {
+        let rules = vec![rule("their", "there", None, Some("(unclosed"))];
+        assert!(correct_homophones("their", &rules).is_err());
+    }
+
+    #[test]
+    fn test_split_segments_on_delimiter() {
+        assert_eq!(
+            split_segments("Jane Doe. Next field, jane@example.com next field 555 1234", "next field"),
+            vec!["Jane Doe", "jane@example.com", "555 1234"]
+        );
+        assert_eq!(
+            split_segments("Next field. Only one... next  field", "next field"),
+            vec!["Only one"]
+        );
+        assert_eq!(split_segments("the nextfield is fine", "next field"), vec!["the nextfield is fine"]);
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::Result;
+use log::info;
+#[cfg(not(feature = "tray-icon"))]
+use log::warn;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::hotkeys::HotkeyState;
+
+/// How often the tray picks up menu clicks and state changes
+#[cfg_attr(not(feature = "tray-icon"), allow(dead_code))]
+const TRAY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Side length of the generated tray icons, in pixels
+const ICON_SIZE: u32 = 32;
+
+/// What the tray icon shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrayStatus {
+    Idle,
+    Recording,
+    Processing,
+}
+
+impl TrayStatus {
+    /// Recording wins over transcriptions still running from earlier recordings
+    pub fn of(state: &HotkeyState) -> Self {
+        if state.is_recording {
+            TrayStatus::Recording
+        } else if state.pending_transcriptions > 0 {
+            TrayStatus::Processing
+        } else {
+            TrayStatus::Idle
+        }
+    }
+
+    pub fn tooltip(self) -> &'static str {
+        match self {
+            TrayStatus::Idle => "rusty-scribe: idle",
+            TrayStatus::Recording => "rusty-scribe: recording",
+            TrayStatus::Processing => "rusty-scribe: transcribing",
+        }
+    }
+
+    fn color(self) -> [u8; 3] {
+        match self {
+            TrayStatus::Idle => [0x80, 0x80, 0x80],
+            TrayStatus::Recording => [0xd9, 0x30, 0x25],
+            TrayStatus::Processing => [0xf2, 0xa9, 0x00],
+        }
+    }
+}
+
+/// A filled circle in the status color, as `ICON_SIZE`² RGBA pixels
+pub fn icon_rgba(status: TrayStatus) -> Vec<u8> {
+    let [r, g, b] = status.color();
+    let center = (ICON_SIZE as f32 - 1.0) / 2.0;
+    let radius = ICON_SIZE as f32 / 2.0 - 1.0;
+    let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let distance = (x as f32 - center).hypot(y as f32 - center);
+            let alpha = if distance <= radius { 0xff } else { 0 };
+            rgba.extend_from_slice(&[r, g, b, alpha]);
+        }
+    }
+    rgba
+}
+
+/// Entries of the tray menu
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrayAction {
+    ToggleRecording,
+    TogglePostProcessing,
+    Quit,
+}
+
+/// Applies a menu click to the state the hotkeys share. Recording is switched
+/// like a toggle-mode press, so key events and the main loop's refresh keep
+/// it, and Quit raises the same flag as Ctrl+C.
+pub fn apply_action(action: TrayAction, state: &Mutex<HotkeyState>, shutdown: &AtomicBool) {
+    match action {
+        TrayAction::ToggleRecording => {
+            let recording = state.lock().unwrap().toggle_recording(Instant::now());
+            info!("Recording {} from the tray", if recording { "started" } else { "stopped" });
+        }
+        TrayAction::TogglePostProcessing => {
+            state.lock().unwrap().toggle_post_processing();
+        }
+        TrayAction::Quit => {
+            info!("Quit requested from the tray");
+            shutdown.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Shows the tray icon on its own thread (`ui.tray`). Builds without the
+/// `tray-icon` feature only log that they run headless.
+pub fn start_tray(state: Arc<Mutex<HotkeyState>>, shutdown: Arc<AtomicBool>) -> Result<()> {
+    #[cfg(feature = "tray-icon")]
+    {
+        system_tray::spawn(state, shutdown)
+    }
+    #[cfg(not(feature = "tray-icon"))]
+    {
+        let _ = (state, shutdown);
+        warn!("ui.tray is set, but this build has no tray support (enable the tray-icon feature); running headless");
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tray-icon")]
+mod system_tray {
+    use anyhow::{Context, Result};
+    use gtk::glib;
+    use log::error;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tray_icon::menu::{Menu, MenuEvent, MenuItem};
+    use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+    use super::{apply_action, icon_rgba, TrayAction, TrayStatus, ICON_SIZE, TRAY_POLL_INTERVAL};
+    use crate::hotkeys::HotkeyState;
+
+    pub fn spawn(state: Arc<Mutex<HotkeyState>>, shutdown: Arc<AtomicBool>) -> Result<()> {
+        std::thread::Builder::new()
+            .name("tray".to_string())
+            .spawn(move || {
+                if let Err(e) = run(state, shutdown) {
+                    error!("Tray icon stopped: {:?}", e);
+                }
+            })
+            .context("Failed to start the tray thread")?;
+        Ok(())
+    }
+
+    fn icon(status: TrayStatus) -> Result<Icon> {
+        Icon::from_rgba(icon_rgba(status), ICON_SIZE, ICON_SIZE).context("Failed to build the tray icon")
+    }
+
+    fn show_status(tray: &TrayIcon, status: TrayStatus) -> Result<()> {
+        tray.set_icon(Some(icon(status)?))?;
+        tray.set_tooltip(Some(status.tooltip()))?;
+        Ok(())
+    }
+
+    /// The tray needs a GTK main loop on the thread that created it; a timer
+    /// on that loop forwards menu clicks and swaps the icon
+    fn run(state: Arc<Mutex<HotkeyState>>, shutdown: Arc<AtomicBool>) -> Result<()> {
+        gtk::init().context("Failed to initialize GTK for the tray icon")?;
+
+        let toggle_recording = MenuItem::new("Start/stop recording", true, None);
+        let toggle_post_processing = MenuItem::new("Toggle post-processing", true, None);
+        let quit = MenuItem::new("Quit", true, None);
+        let menu = Menu::new();
+        menu.append_items(&[&toggle_recording, &toggle_post_processing, &quit])
+            .context("Failed to build the tray menu")?;
+        let actions = [
+            (toggle_recording.id().clone(), TrayAction::ToggleRecording),
+            (toggle_post_processing.id().clone(), TrayAction::TogglePostProcessing),
+            (quit.id().clone(), TrayAction::Quit),
+        ];
+
+        let mut status = TrayStatus::Idle;
+        let tray = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip(status.tooltip())
+            .with_icon(icon(status)?)
+            .build()
+            .context("Failed to create the tray icon")?;
+
+        glib::timeout_add_local(TRAY_POLL_INTERVAL, move || {
+            while let Ok(event) = MenuEvent::receiver().try_recv() {
+                if let Some((_, action)) = actions.iter().find(|(id, _)| *id == event.id) {
+                    apply_action(*action, &state, &shutdown);
+                }
+            }
+            if shutdown.load(Ordering::SeqCst) {
+                gtk::main_quit();
+                return glib::ControlFlow::Break;
+            }
+
+            let current = TrayStatus::of(&state.lock().unwrap());
+            if current != status {
+                status = current;
+                if let Err(e) = show_status(&tray, status) {
+                    error!("Failed to update the tray icon: {:?}", e);
+                }
+            }
+            glib::ControlFlow::Continue
+        });
+        gtk::main();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_follows_shared_state() {
+        let mut state = HotkeyState::new();
+        assert_eq!(TrayStatus::of(&state), TrayStatus::Idle);
+
+        state.pending_transcriptions = 1;
+        assert_eq!(TrayStatus::of(&state), TrayStatus::Processing);
+        state.is_recording = true;
+        assert_eq!(TrayStatus::of(&state), TrayStatus::Recording);
+
+        let pixels = icon_rgba(TrayStatus::Recording);
+        assert_eq!(pixels.len(), (ICON_SIZE * ICON_SIZE * 4) as usize);
+        // Transparent corner, opaque red center
+        assert_eq!(pixels[3], 0);
+        let center = ((ICON_SIZE / 2 * ICON_SIZE + ICON_SIZE / 2) * 4) as usize;
+        assert_eq!(&pixels[center..center + 4], &[0xd9, 0x30, 0x25, 0xff]);
+    }
+
+    #[test]
+    fn test_menu_actions_update_shared_state() {
+        let state = Mutex::new(HotkeyState::new());
+        let shutdown = AtomicBool::new(false);
+
+        apply_action(TrayAction::ToggleRecording, &state, &shutdown);
+        assert!(state.lock().unwrap().is_recording);
+        apply_action(TrayAction::ToggleRecording, &state, &shutdown);
+        assert!(!state.lock().unwrap().is_recording);
+
+        apply_action(TrayAction::TogglePostProcessing, &state, &shutdown);
+        assert!(state.lock().unwrap().post_processing_enabled);
+
+        apply_action(TrayAction::Quit, &state, &shutdown);
+        assert!(shutdown.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_tray_start_survives_key_events_and_refreshes() {
+        use crate::config::HotkeyMode;
+        let shutdown = AtomicBool::new(false);
+        for mode in [HotkeyMode::Hybrid, HotkeyMode::Hold, HotkeyMode::Toggle] {
+            let state = Mutex::new(HotkeyState {
+                mode,
+                ..HotkeyState::new()
+            });
+            apply_action(TrayAction::ToggleRecording, &state, &shutdown);
+            {
+                let mut state = state.lock().unwrap();
+                // An unrelated key event, then the main loop's poll
+                state.on_recording_combo(false, Instant::now());
+                state.refresh_recording(Instant::now());
+                assert!(state.is_recording, "{:?} mode dropped the tray's recording", mode);
+            }
+            apply_action(TrayAction::ToggleRecording, &state, &shutdown);
+            state.lock().unwrap().refresh_recording(Instant::now());
+            assert!(!state.lock().unwrap().is_recording);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use anyhow::{Result, Context};
+use log::{info, warn};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::config::TranscriptionSettings;
+
+/// Whisper only looks at the last 224 tokens of its prompt
+pub const WHISPER_PROMPT_MAX_TOKENS: usize = 224;
+
+/// Rough token estimate for English text (about four characters per token)
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Parses a vocabulary file: one term per line, blank lines and `#` comments ignored
+pub fn parse_vocabulary(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Joins terms into a Whisper prompt that fits in `max_tokens`. Terms are
+/// appended to the file over time, so the most recent ones (at the end) are kept
+/// when the list is too long.
+pub fn vocabulary_prompt(terms: &[String], max_tokens: usize) -> Option<String> {
+    let mut kept: Vec<&str> = Vec::new();
+    let mut used = 0;
+    for term in terms.iter().rev() {
+        let cost = estimate_tokens(term) + if kept.is_empty() { 0 } else { 1 };
+        if used + cost > max_tokens {
+            break;
+        }
+        used += cost;
+        kept.push(term);
+    }
+    if kept.is_empty() {
+        return None;
+    }
+    kept.reverse();
+    Some(kept.join(", "))
+}
+
+/// `transcription.vocabulary_file`, re-read whenever its modification time changes
+pub struct VocabularyFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    prompt: Option<String>,
+}
+
+impl VocabularyFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        VocabularyFile {
+            path: path.into(),
+            modified: None,
+            prompt: None,
+        }
+    }
+
+    /// Returns the prompt for the next request, reloading the file if it changed
+    pub fn prompt(&mut self) -> Result<Option<String>> {
+        let modified = fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .with_context(|| format!("Failed to read vocabulary file at {}", self.path.display()))?;
+
+        if self.modified != Some(modified) {
+            let content = fs::read_to_string(&self.path)
+                .with_context(|| format!("Failed to read vocabulary file at {}", self.path.display()))?;
+            let terms = parse_vocabulary(&content);
+            info!("Loaded {} vocabulary terms from {}", terms.len(), self.path.display());
+            self.prompt = vocabulary_prompt(&terms, WHISPER_PROMPT_MAX_TOKENS);
+            self.modified = Some(modified);
+        }
+        Ok(self.prompt.clone())
+    }
+}
+
+/// The vocabulary file of this process, kept so reloads only happen on change
+static VOCABULARY: Mutex<Option<VocabularyFile>> = Mutex::new(None);
+
+/// Loads `transcription.vocabulary_file`, or re-reads it if it changed since
+/// the last call. `None` when no file is configured.
+pub fn load_vocabulary(settings: &TranscriptionSettings) -> Result<Option<String>> {
+    let Some(path) = &settings.vocabulary_file else {
+        return Ok(None);
+    };
+    let mut vocabulary = VOCABULARY.lock().unwrap();
+    let file = match vocabulary.as_mut() {
+        Some(file) if file.path.as_os_str() == path.as_str() => file,
+        _ => vocabulary.insert(VocabularyFile::new(path)),
+    };
+    file.prompt()
+}
+
+/// The Whisper prompt for the next request. A file that cannot be read is
+/// logged and the request goes out without a prompt.
+pub fn vocabulary_prompt_for(settings: &TranscriptionSettings) -> Option<String> {
+    load_vocabulary(settings).unwrap_or_else(|e| {
+        warn!("Sending the recording without its vocabulary: {:#}", e);
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::transcribe_audio_with_prompt;
+    use mockito::{mock, Matcher};
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_vocabulary_prompt_keeps_most_recent_terms() {
+        let terms = parse_vocabulary("# team names\nKubernetes\n\nGrafana\nPrometheus\n");
+        assert_eq!(terms, vec!["Kubernetes", "Grafana", "Prometheus"]);
+
+        assert_eq!(
+            vocabulary_prompt(&terms, WHISPER_PROMPT_MAX_TOKENS),
+            Some("Kubernetes, Grafana, Prometheus".to_string())
+        );
+        // "Prometheus" costs 3 tokens, ", Grafana" 3 more; "Kubernetes" no longer fits
+        assert_eq!(vocabulary_prompt(&terms, 6), Some("Grafana, Prometheus".to_string()));
+        assert_eq!(vocabulary_prompt(&terms, 0), None);
+    }
+
+    #[test]
+    fn test_vocabulary_file_contents_sent_as_prompt() {
+        let mut vocabulary = NamedTempFile::new().expect("Failed to create temp file");
+        writeln!(vocabulary, "Valorekhov\nrusty-scribe").expect("Failed to write vocabulary");
+        let prompt = VocabularyFile::new(vocabulary.path()).prompt().unwrap();
+
+        let _m = mock("POST", "/vocab/transcriptions")
+            .match_body(Matcher::Regex(
+                "name=\"prompt\"\r\n\r\nValorekhov, rusty-scribe\r\n".to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"text": "Valorekhov maintains rusty-scribe."}"#)
+            .create();
+
+        let mut audio = NamedTempFile::new().expect("Failed to create temp file");
+        write!(audio, "dummy audio data").expect("Failed to write to temp file");
+        let url = format!("{}/vocab/transcriptions", &mockito::server_url());
+
+        let text = transcribe_audio_with_prompt(
+            &url,
+            "test_api_key",
+            audio.path().to_str().unwrap(),
+            prompt.as_deref(),
+        )
+        .expect("Transcription with vocabulary prompt failed");
+        assert_eq!(text, "Valorekhov maintains rusty-scribe.");
+    }
+
+    #[test]
+    fn test_vocabulary_file_reloads_on_change() {
+        let mut vocabulary = NamedTempFile::new().expect("Failed to create temp file");
+        writeln!(vocabulary, "Grafana").expect("Failed to write vocabulary");
+        let mut file = VocabularyFile::new(vocabulary.path());
+        assert_eq!(file.prompt().unwrap(), Some("Grafana".to_string()));
+
+        fs::write(vocabulary.path(), "Grafana\nLoki\n").expect("Failed to rewrite vocabulary");
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        vocabulary.as_file().set_modified(later).expect("Failed to bump mtime");
+        assert_eq!(file.prompt().unwrap(), Some("Grafana, Loki".to_string()));
+    }
+}
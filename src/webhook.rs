@@ -0,0 +1,106 @@
+use anyhow::Result;
+use log::{error, info};
+use reqwest::header::AUTHORIZATION;
+
+use crate::config::WebhookSettings;
+use crate::http::{build_client, send_request};
+use crate::result::TranscriptionResult;
+
+/// POSTs the transcription result as JSON to the configured webhook
+pub fn send_to_webhook(settings: &WebhookSettings, result: &TranscriptionResult) -> Result<()> {
+    let client = build_client();
+
+    let mut request = client.post(&settings.url).json(result);
+    if let Some(auth) = &settings.auth_header {
+        request = request.header(AUTHORIZATION, auth);
+    }
+
+    let response = send_request(&client, request, "webhook")?;
+
+    if response.status().is_success() {
+        info!("Transcription delivered to webhook.");
+        Ok(())
+    } else {
+        let status = response.status();
+        let text = response.text().unwrap_or_default();
+        Err(anyhow::anyhow!("Webhook error {}: {}", status, text))
+    }
+}
+
+/// Delivers to the webhook if one is configured. Failures are logged only, so
+/// they never prevent the other outputs from running.
+pub fn emit_to_webhook(settings: Option<&WebhookSettings>, result: &TranscriptionResult) {
+    if let Some(settings) = settings {
+        if let Err(e) = send_to_webhook(settings, result) {
+            error!("Failed to deliver transcription to webhook: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::EndpointKind;
+    use mockito::{mock, Matcher};
+    use serde_json::json;
+
+    fn sample_result() -> TranscriptionResult {
+        TranscriptionResult {
+            timestamp: "2024-09-01T12:00:00+00:00".to_string(),
+            text: "Cleaned up text.".to_string(),
+            raw_text: "cleaned up text".to_string(),
+            post_processed: true,
+            language: Some("en".to_string()),
+            endpoint_used: EndpointKind::Hosted,
+            model_used: "whisper-1".to_string(),
+            summary: None,
+            diff: None,
+        }
+    }
+
+    #[test]
+    fn test_send_to_webhook_posts_result_json() {
+        let _m = mock("POST", "/hook")
+            .match_header("authorization", "Bearer hook_token")
+            .match_header("content-type", "application/json")
+            .match_body(Matcher::Json(json!({
+                "timestamp": "2024-09-01T12:00:00+00:00",
+                "text": "Cleaned up text.",
+                "raw_text": "cleaned up text",
+                "post_processed": true,
+                "language": "en",
+                "endpoint_used": "hosted",
+                "model_used": "whisper-1",
+                "summary": null
+            })))
+            .with_status(200)
+            .create();
+
+        let settings = WebhookSettings {
+            url: format!("{}/hook", &mockito::server_url()),
+            auth_header: Some("Bearer hook_token".to_string()),
+        };
+
+        let result = send_to_webhook(&settings, &sample_result());
+        assert!(result.is_ok(), "Webhook delivery failed: {:?}", result.err());
+    }
+
+    #[test]
+    fn test_send_to_webhook_failure() {
+        let _m = mock("POST", "/hook-down")
+            .with_status(503)
+            .with_body("unavailable")
+            .create();
+
+        let settings = WebhookSettings {
+            url: format!("{}/hook-down", &mockito::server_url()),
+            auth_header: None,
+        };
+
+        let result = send_to_webhook(&settings, &sample_result());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Webhook error 503 Service Unavailable: unavailable"
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use anyhow::Result;
+use log::{info, warn};
+#[cfg(target_os = "linux")]
+use anyhow::Context;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+
+/// Identifier of a top-level window as reported by the platform
+pub type WindowId = u64;
+
+/// Platform window operations needed to direct output at a specific window
+pub trait WindowHelper {
+    fn focused_window(&self) -> Option<WindowId>;
+    fn window_exists(&self, id: WindowId) -> bool;
+    fn activate(&self, id: WindowId) -> Result<()>;
+    /// Window class (e.g. "kitty", "gnome-terminal-server") used by app-specific rules
+    fn window_class(&self, id: WindowId) -> Option<String>;
+    fn is_fullscreen(&self, id: WindowId) -> bool;
+}
+
+/// Window helper backed by `xdotool` on Linux. Other platforms report no
+/// focused window, so targeting silently degrades to current-focus output.
+pub struct SystemWindowHelper;
+
+#[cfg(target_os = "linux")]
+impl WindowHelper for SystemWindowHelper {
+    fn focused_window(&self) -> Option<WindowId> {
+        let output = Command::new("xdotool").arg("getactivewindow").output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    }
+
+    fn window_exists(&self, id: WindowId) -> bool {
+        Command::new("xdotool")
+            .args(["getwindowname", &id.to_string()])
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    }
+
+    fn activate(&self, id: WindowId) -> Result<()> {
+        let status = Command::new("xdotool")
+            .args(["windowactivate", "--sync", &id.to_string()])
+            .status()
+            .context("Failed to run xdotool")?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("xdotool could not activate window {}", id))
+        }
+    }
+
+    fn window_class(&self, id: WindowId) -> Option<String> {
+        let output = Command::new("xdotool")
+            .args(["getwindowclassname", &id.to_string()])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn is_fullscreen(&self, id: WindowId) -> bool {
+        Command::new("xprop")
+            .args(["-id", &id.to_string(), "_NET_WM_STATE"])
+            .output()
+            .map(|output| {
+                output.status.success()
+                    && String::from_utf8_lossy(&output.stdout).contains("_NET_WM_STATE_FULLSCREEN")
+            })
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl WindowHelper for SystemWindowHelper {
+    fn focused_window(&self) -> Option<WindowId> {
+        None
+    }
+
+    fn window_exists(&self, _id: WindowId) -> bool {
+        false
+    }
+
+    fn activate(&self, _id: WindowId) -> Result<()> {
+        Err(anyhow::anyhow!("Window targeting is not supported on this platform"))
+    }
+
+    fn window_class(&self, _id: WindowId) -> Option<String> {
+        None
+    }
+
+    fn is_fullscreen(&self, _id: WindowId) -> bool {
+        false
+    }
+}
+
+/// Where the next output should be inserted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputTarget {
+    Window(WindowId),
+    CurrentFocus,
+}
+
+/// Tracks the window chosen with the "set target" hotkey
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TargetWindow {
+    #[default]
+    Unset,
+    Set(WindowId),
+}
+
+impl TargetWindow {
+    /// Stores the currently focused window as the output target
+    pub fn capture(&mut self, helper: &dyn WindowHelper) -> Option<WindowId> {
+        match helper.focused_window() {
+            Some(id) => {
+                info!("Output target window set to {}", id);
+                *self = TargetWindow::Set(id);
+                Some(id)
+            }
+            None => {
+                warn!("Could not determine the focused window; output target unchanged");
+                None
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = TargetWindow::Unset;
+    }
+
+    /// Activates the target window ahead of inserting text. A target that no
+    /// longer exists is forgotten and output falls back to the focused window.
+    pub fn activate_for_output(&mut self, helper: &dyn WindowHelper) -> OutputTarget {
+        let id = match *self {
+            TargetWindow::Unset => return OutputTarget::CurrentFocus,
+            TargetWindow::Set(id) => id,
+        };
+
+        if !helper.window_exists(id) {
+            warn!("Target window {} no longer exists; using the focused window", id);
+            self.clear();
+            return OutputTarget::CurrentFocus;
+        }
+
+        match helper.activate(id) {
+            Ok(()) => OutputTarget::Window(id),
+            Err(e) => {
+                warn!("Failed to activate target window {}: {:?}", id, e);
+                OutputTarget::CurrentFocus
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    struct FakeWindows {
+        focused: Option<WindowId>,
+        existing: Vec<WindowId>,
+        activated: RefCell<Vec<WindowId>>,
+    }
+
+    impl WindowHelper for FakeWindows {
+        fn focused_window(&self) -> Option<WindowId> {
+            self.focused
+        }
+
+        fn window_exists(&self, id: WindowId) -> bool {
+            self.existing.contains(&id)
+        }
+
+        fn activate(&self, id: WindowId) -> Result<()> {
+            self.activated.borrow_mut().push(id);
+            Ok(())
+        }
+
+        fn window_class(&self, _id: WindowId) -> Option<String> {
+            None
+        }
+
+        fn is_fullscreen(&self, _id: WindowId) -> bool {
+            false
+        }
+    }
+
+    fn fake(focused: Option<WindowId>, existing: &[WindowId]) -> FakeWindows {
+        FakeWindows {
+            focused,
+            existing: existing.to_vec(),
+            activated: RefCell::new(vec![]),
+        }
+    }
+
+    #[test]
+    fn test_capture_stores_focused_window() {
+        let helper = fake(Some(42), &[42]);
+        let mut target = TargetWindow::default();
+
+        assert_eq!(target.capture(&helper), Some(42));
+        assert_eq!(target, TargetWindow::Set(42));
+    }
+
+    #[test]
+    fn test_capture_without_focus_keeps_previous_target() {
+        let helper = fake(None, &[]);
+        let mut target = TargetWindow::Set(7);
+
+        assert_eq!(target.capture(&helper), None);
+        assert_eq!(target, TargetWindow::Set(7));
+    }
+
+    #[test]
+    fn test_unset_target_uses_current_focus() {
+        let helper = fake(Some(1), &[1]);
+        let mut target = TargetWindow::Unset;
+
+        assert_eq!(target.activate_for_output(&helper), OutputTarget::CurrentFocus);
+        assert!(helper.activated.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_existing_target_is_activated() {
+        let helper = fake(Some(1), &[1, 42]);
+        let mut target = TargetWindow::Set(42);
+
+        assert_eq!(target.activate_for_output(&helper), OutputTarget::Window(42));
+        assert_eq!(*helper.activated.borrow(), vec![42]);
+        assert_eq!(target, TargetWindow::Set(42));
+    }
+
+    #[test]
+    fn test_vanished_target_falls_back_and_is_cleared() {
+        let helper = fake(Some(1), &[1]);
+        let mut target = TargetWindow::Set(42);
+
+        assert_eq!(target.activate_for_output(&helper), OutputTarget::CurrentFocus);
+        assert_eq!(target, TargetWindow::Unset);
+        assert!(helper.activated.borrow().is_empty());
+    }
+}
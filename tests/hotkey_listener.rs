@@ -0,0 +1,27 @@
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use rusty_scribe::config::Hotkeys;
+use rusty_scribe::hotkeys::{start_hotkey_listener, HotkeyState};
+
+// The listener is a plain function returning its thread, so main can start it
+// without a runtime or an `.await`
+type StartListener = fn(&Hotkeys, Arc<Mutex<HotkeyState>>) -> anyhow::Result<JoinHandle<()>>;
+const _: StartListener = start_hotkey_listener;
+
+fn hotkeys(recording: &str) -> Hotkeys {
+    toml::from_str(&format!(
+        "recording = \"{}\"\npost_processing_modifier = \"Control\"",
+        recording
+    ))
+    .unwrap()
+}
+
+#[test]
+fn invalid_hotkeys_fail_before_a_listener_thread_starts() {
+    let state = Arc::new(Mutex::new(HotkeyState::new()));
+
+    let err = start_hotkey_listener(&hotkeys("Control+Foo"), Arc::clone(&state)).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid hotkeys.recording");
+    assert_eq!(*state.lock().unwrap(), HotkeyState::new());
+}